@@ -1,53 +1,313 @@
-// Gemini API client for smart replies
-use serde::Deserialize;
+// AI providers for smart replies, summaries and rewrites
+//
+// Every backend implements `AiProvider::generate`; the task methods build
+// prompts and parse results identically for all of them.
+
+pub mod openai;
+
+use crate::auth::AiCredentials;
+use crate::models::Thread;
+use serde::{Deserialize, Serialize};
 use serde_json::json;
+use std::future::Future;
 
-const API_ENDPOINT: &str = "https://generativelanguage.googleapis.com/v1beta/models/gemini-1.5-flash:generateContent";
+pub use openai::OpenAiClient;
 
-pub struct GeminiClient {
-    client: reqwest::Client,
-    api_key: String,
+const API_BASE: &str = "https://generativelanguage.googleapis.com/v1beta/models";
+const DEFAULT_GEMINI_MODEL: &str = "gemini-1.5-flash";
+
+/// Prompt language when detection isn't confident
+pub const DEFAULT_LANGUAGE: &str = "English";
+
+/// Detect the language a conversation is written in, by English name
+/// ("French"). Short or mixed text falls back to English.
+pub fn detect_language(text: &str) -> &'static str {
+    match whatlang::detect(text) {
+        Some(info) if info.is_reliable() => info.lang().eng_name(),
+        _ => DEFAULT_LANGUAGE,
+    }
 }
 
-#[derive(Debug, Deserialize)]
-struct GenerationResponse {
-    candidates: Option<Vec<Candidate>>,
+/// Per-request AI overrides from settings; unset fields keep the provider's defaults
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct AiSettings {
+    #[serde(default)]
+    pub model: Option<String>,
 }
 
-#[derive(Debug, Deserialize)]
-struct Candidate {
-    content: Option<Content>,
+/// Check a model name is safe to put in a request path ("gemini-1.5-pro")
+fn validate_model(model: &str) -> Result<(), String> {
+    if model.is_empty() {
+        return Err("AI model name is empty".to_string());
+    }
+    if !model.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '.' | '_')) {
+        return Err(format!("Invalid AI model name: {}", model));
+    }
+    Ok(())
 }
 
-#[derive(Debug, Deserialize)]
-struct Content {
-    parts: Option<Vec<Part>>,
+/// Build a client for AI requests (a hung request would otherwise block forever)
+fn http_client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(30))
+        .build()
+        .unwrap_or_else(|_| reqwest::Client::new())
 }
 
-#[derive(Debug, Deserialize)]
-struct Part {
-    text: Option<String>,
+pub trait AiProvider: Sync {
+    /// Short provider name ("gemini", "openai")
+    fn name(&self) -> &'static str;
+
+    /// Complete a single prompt, returning the raw model text
+    fn generate(&self, prompt: &str, max_tokens: u32) -> impl Future<Output = Result<String, String>> + Send;
+
+    /// Three ready-to-send replies, written in `language` ("French")
+    fn suggest_replies(
+        &self,
+        email_context: &str,
+        user_email: &str,
+        language: &str,
+    ) -> impl Future<Output = Result<Vec<String>, String>> + Send {
+        async move {
+            let text = self.generate(&suggest_replies_prompt(email_context, user_email, language), 512).await?;
+            parse_json_list(&text)
+        }
+    }
+
+    fn summarize(&self, email_context: &str) -> impl Future<Output = Result<String, String>> + Send {
+        async move {
+            let prompt = format!(
+                "Summarize this email thread in 2-3 sentences. Mention any open questions or action items.\n\nEmail Thread:\n{}",
+                email_context
+            );
+            Ok(self.generate(&prompt, 256).await?.trim().to_string())
+        }
+    }
+
+    /// A 2-3 sentence summary plus the thread's open action items
+    fn summarize_thread(&self, thread_context: &str) -> impl Future<Output = Result<ThreadSummary, String>> + Send {
+        async move {
+            let text = self.generate(&summarize_thread_prompt(thread_context), 512).await?;
+            parse_thread_summary(&text)
+        }
+    }
+
+    /// Write one complete email body from a short instruction ("decline
+    /// politely, suggest next Tuesday"), replying to `context` when given
+    fn compose_draft(
+        &self,
+        instruction: &str,
+        context: Option<&str>,
+        user_email: &str,
+        tone: Option<Tone>,
+    ) -> impl Future<Output = Result<String, String>> + Send {
+        async move {
+            let prompt = compose_draft_prompt(instruction, context, user_email, tone);
+            let text = self.generate(&prompt, 1024).await?;
+            let body = strip_code_fence(&text).to_string();
+            if body.is_empty() {
+                return Err("AI returned an empty draft".to_string());
+            }
+            Ok(body)
+        }
+    }
+
+    fn rewrite(&self, text: &str, instruction: &str) -> impl Future<Output = Result<String, String>> + Send {
+        async move {
+            let prompt = format!(
+                "Rewrite the following email text. {}\nReturn ONLY the rewritten text, no explanation.\n\nText:\n{}",
+                instruction, text
+            );
+            Ok(self.generate(&prompt, 1024).await?.trim().to_string())
+        }
+    }
+
+    /// Group a week's threads (see `digest_context`) into topics
+    fn weekly_digest(&self, context: &DigestContext) -> impl Future<Output = Result<WeeklyDigest, String>> + Send {
+        async move {
+            let text = self.generate(&weekly_digest_prompt(&context.text), 1024).await?;
+            parse_digest(&text, &context.thread_ids)
+        }
+    }
 }
 
-impl GeminiClient {
-    pub fn new(api_key: String) -> Self {
-        // Without a timeout a hung Gemini request blocks suggest_replies forever
-        let client = reqwest::Client::builder()
-            .timeout(std::time::Duration::from_secs(30))
-            .build()
-            .unwrap_or_else(|_| reqwest::Client::new());
-        Self { client, api_key }
+/// Most threads fed into a weekly digest
+pub const MAX_DIGEST_THREADS: usize = 40;
+/// Longest snippet kept per thread
+const MAX_DIGEST_SNIPPET_CHARS: usize = 200;
+/// Overall cap on the digest prompt's thread list
+const MAX_DIGEST_CONTEXT_CHARS: usize = 12_000;
+
+/// The thread list sent to the model, and the ids it may cite
+pub struct DigestContext {
+    pub text: String,
+    pub thread_ids: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DigestTopic {
+    pub topic: String,
+    pub summary: String,
+    /// Threads to open from this topic
+    #[serde(default)]
+    pub thread_ids: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WeeklyDigest {
+    pub topics: Vec<DigestTopic>,
+}
+
+/// Tone requested for a composed draft
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Tone {
+    Formal,
+    Casual,
+    Friendly,
+}
+
+impl Tone {
+    fn guideline(self) -> &'static str {
+        match self {
+            Tone::Formal => "Use a formal, professional tone.",
+            Tone::Casual => "Use a relaxed, casual tone.",
+            Tone::Friendly => "Use a warm, friendly tone.",
+        }
+    }
+}
+
+fn compose_draft_prompt(instruction: &str, context: Option<&str>, user_email: &str, tone: Option<Tone>) -> String {
+    let tone = tone
+        .map(Tone::guideline)
+        .unwrap_or("Match the tone of the conversation, or a neutral professional tone if there is none.");
+    let thread = context
+        .map(|c| format!("\nYou are replying to this email thread:\n{}\n", c))
+        .unwrap_or_default();
+    format!(
+        r#"You are writing an email on behalf of {user_email}.
+{thread}
+Write a complete email body following this instruction: {instruction}
+
+Guidelines:
+- {tone}
+- Write in the first person, ready to send as-is
+- Include a greeting and a sign-off, but no subject line
+- Don't invent facts, dates or commitments beyond the instruction and the thread
+
+Return ONLY the email body as plain text. No markdown, no explanation."#,
+        user_email = user_email,
+        thread = thread,
+        instruction = instruction.trim(),
+        tone = tone
+    )
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ThreadSummary {
+    pub summary: String,
+    #[serde(default)]
+    pub action_items: Vec<String>,
+}
+
+fn summarize_thread_prompt(thread_context: &str) -> String {
+    format!(
+        r#"Summarize this email thread in 2-3 sentences for someone deciding how to respond.
+Then list the open action items (who needs to do what), if any.
+
+Email Thread:
+{context}
+
+Return ONLY raw JSON, no markdown, in this shape:
+{{"summary": "...", "action_items": ["...", "..."]}}"#,
+        context = thread_context
+    )
+}
+
+fn parse_thread_summary(text: &str) -> Result<ThreadSummary, String> {
+    let clean_text = strip_code_fence(text);
+    let mut summary = serde_json::from_str::<ThreadSummary>(clean_text)
+        .map_err(|e| format!("Failed to parse summary: {} (Text: {})", e, clean_text))?;
+    summary.summary = summary.summary.trim().to_string();
+    summary.action_items.retain(|item| !item.trim().is_empty());
+    Ok(summary)
+}
+
+/// Models often wrap JSON in a markdown code fence despite being told not to
+fn strip_code_fence(text: &str) -> &str {
+    text.trim()
+        .trim_start_matches("```json")
+        .trim_start_matches("```")
+        .trim_end_matches("```")
+        .trim()
+}
+
+fn truncate_chars(text: &str, max: usize) -> String {
+    match text.char_indices().nth(max) {
+        Some((end, _)) => format!("{}...", &text[..end]),
+        None => text.to_string(),
     }
+}
+
+/// One line per thread (id, sender, subject, snippet), stopping at the
+/// thread and size caps so the prompt stays small
+pub fn digest_context(threads: &[Thread]) -> DigestContext {
+    let mut text = String::new();
+    let mut thread_ids = Vec::new();
+    for thread in threads.iter().take(MAX_DIGEST_THREADS) {
+        let line = format!(
+            "[{}] From: {} | Subject: {} | {}\n",
+            thread.gmail_thread_id,
+            thread.participants.first().map(String::as_str).unwrap_or("unknown"),
+            truncate_chars(thread.subject.trim(), MAX_DIGEST_SNIPPET_CHARS),
+            truncate_chars(thread.snippet.trim(), MAX_DIGEST_SNIPPET_CHARS),
+        );
+        if text.len() + line.len() > MAX_DIGEST_CONTEXT_CHARS {
+            break;
+        }
+        text.push_str(&line);
+        thread_ids.push(thread.gmail_thread_id.clone());
+    }
+    DigestContext { text, thread_ids }
+}
 
-    pub async fn suggest_replies(&self, email_context: &str, user_email: &str) -> Result<Vec<String>, String> {
-        let url = format!("{}?key={}", API_ENDPOINT, self.api_key);
+fn weekly_digest_prompt(threads: &str) -> String {
+    format!(
+        r#"You are an email assistant writing a "catch up on the week" digest.
 
-        let prompt = format!(
-            r#"You are an email assistant for {user_email}.
+Group the threads below into 3-6 topics. For each topic write a one or two sentence
+summary mentioning decisions, deadlines and anything awaiting a reply.
+
+Threads (the id is in brackets):
+{threads}
+
+Return ONLY raw JSON, no markdown, in this shape:
+{{"topics": [{{"topic": "Short title", "summary": "...", "thread_ids": ["id1", "id2"]}}]}}"#,
+        threads = threads
+    )
+}
+
+/// Parse the model's digest, dropping thread ids it wasn't given
+fn parse_digest(text: &str, known_thread_ids: &[String]) -> Result<WeeklyDigest, String> {
+    let clean_text = strip_code_fence(text);
+
+    let mut digest = serde_json::from_str::<WeeklyDigest>(clean_text)
+        .map_err(|e| format!("Failed to parse digest: {} (Text: {})", e, clean_text))?;
+    for topic in &mut digest.topics {
+        topic.thread_ids.retain(|id| known_thread_ids.contains(id));
+    }
+    digest.topics.retain(|t| !t.topic.trim().is_empty());
+    Ok(digest)
+}
+
+fn suggest_replies_prompt(email_context: &str, user_email: &str, language: &str) -> String {
+    format!(
+        r#"You are an email assistant for {user_email}.
 
 Analyze this email thread and generate 3 contextually appropriate reply suggestions.
 
 Guidelines:
+- Reply in {language}, the language of the conversation
 - Match the tone of the conversation (formal for business, casual for personal)
 - If it's a scheduling request: suggest accepting, declining, or proposing alternatives
 - If it's a question: provide a substantive answer or acknowledge you'll look into it
@@ -63,9 +323,145 @@ Email Thread:
 
 Return ONLY a raw JSON array of 3 strings. No markdown, no explanation.
 Example format: ["Reply 1", "Reply 2", "Reply 3"]"#,
-            user_email = user_email,
-            context = email_context
-        );
+        user_email = user_email,
+        context = email_context,
+        language = language
+    )
+}
+
+/// Most suggestions taken from a reply that isn't JSON
+const MAX_FALLBACK_SUGGESTIONS: usize = 3;
+
+/// Drop a leading `1.`/`2)` or `-`/`*` list marker. A marker must be followed
+/// by whitespace, so text that merely starts with a number ("2.30 works") stays.
+fn strip_list_marker(line: &str) -> &str {
+    let digits = line.len() - line.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    let marker = if digits > 0 {
+        line[digits..].strip_prefix(['.', ')'])
+    } else {
+        line.strip_prefix(['-', '*'])
+    };
+    match marker {
+        Some(rest) if rest.starts_with(char::is_whitespace) => rest.trim_start(),
+        _ => line,
+    }
+}
+
+/// Parse the model's suggestion list. Models sometimes add prose around the
+/// array, so fall back to the outermost `[...]`, then to one suggestion per line.
+fn parse_json_list(text: &str) -> Result<Vec<String>, String> {
+    let clean_text = strip_code_fence(text);
+
+    if let Ok(list) = serde_json::from_str::<Vec<String>>(clean_text) {
+        return Ok(list);
+    }
+
+    if let (Some(start), Some(end)) = (clean_text.find('['), clean_text.rfind(']')) {
+        if start < end {
+            if let Ok(list) = serde_json::from_str::<Vec<String>>(&clean_text[start..=end]) {
+                return Ok(list);
+            }
+        }
+    }
+
+    let lines: Vec<String> = clean_text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .enumerate()
+        // A lead-in such as "Here are some replies:"
+        .filter(|(i, line)| !(*i == 0 && line.ends_with(':')))
+        .map(|(_, line)| {
+            strip_list_marker(line)
+                .trim_matches('"')
+                .trim_end_matches(',')
+                .trim_end_matches('"')
+                .trim()
+                .to_string()
+        })
+        .filter(|line| !line.is_empty() && !matches!(line.as_str(), "[" | "]"))
+        .take(MAX_FALLBACK_SUGGESTIONS)
+        .collect();
+
+    if lines.is_empty() {
+        return Err(format!("Failed to parse JSON suggestions (Text: {})", clean_text));
+    }
+    Ok(lines)
+}
+
+pub struct GeminiClient {
+    client: reqwest::Client,
+    api_key: String,
+    model: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct GenerationResponse {
+    candidates: Option<Vec<Candidate>>,
+    #[serde(rename = "promptFeedback")]
+    prompt_feedback: Option<PromptFeedback>,
+    #[serde(rename = "usageMetadata")]
+    usage_metadata: Option<UsageMetadata>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PromptFeedback {
+    #[serde(rename = "blockReason")]
+    block_reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct UsageMetadata {
+    #[serde(rename = "totalTokenCount")]
+    total_token_count: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Candidate {
+    content: Option<Content>,
+    #[serde(rename = "finishReason")]
+    finish_reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Content {
+    parts: Option<Vec<Part>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Part {
+    text: Option<String>,
+}
+
+impl GeminiClient {
+    /// `model` defaults to gemini-1.5-flash (also when blank, as settings
+    /// store it); users can opt into e.g. gemini-1.5-pro
+    pub fn new(api_key: String, model: Option<String>) -> Result<Self, String> {
+        let model = model
+            .map(|m| m.trim().to_string())
+            .filter(|m| !m.is_empty())
+            .unwrap_or_else(|| DEFAULT_GEMINI_MODEL.to_string());
+        validate_model(&model)?;
+
+        Ok(Self {
+            client: http_client(),
+            api_key,
+            model,
+        })
+    }
+
+    fn endpoint(&self) -> String {
+        format!("{}/{}:generateContent", API_BASE, self.model)
+    }
+}
+
+impl AiProvider for GeminiClient {
+    fn name(&self) -> &'static str {
+        "gemini"
+    }
+
+    async fn generate(&self, prompt: &str, max_tokens: u32) -> Result<String, String> {
+        let url = format!("{}?key={}", self.endpoint(), self.api_key);
 
         let body = json!({
             "contents": [{
@@ -74,7 +470,7 @@ Example format: ["Reply 1", "Reply 2", "Reply 3"]"#,
             }],
             "generationConfig": {
                 "temperature": 0.4,
-                "maxOutputTokens": 512,
+                "maxOutputTokens": max_tokens,
             }
         });
 
@@ -95,31 +491,327 @@ Example format: ["Reply 1", "Reply 2", "Reply 3"]"#,
             .await
             .map_err(|e| format!("Failed to parse response: {}", e))?;
 
-        if let Some(candidates) = response.candidates {
-            if let Some(first) = candidates.first() {
-                if let Some(content) = &first.content {
-                    if let Some(parts) = &content.parts {
-                        if let Some(first_part) = parts.first() {
-                            if let Some(text) = &first_part.text {
-                                return self.parse_json_list(text);
-                            }
-                        }
-                    }
-                }
-            }
+        response_text(response)
+    }
+}
+
+/// Finish reasons meaning the output was withheld by a filter
+const BLOCKED_FINISH_REASONS: &[&str] = &["SAFETY", "RECITATION", "BLOCKLIST", "PROHIBITED_CONTENT", "SPII"];
+
+/// The first candidate's text, or why there is none. Logs token usage.
+fn response_text(response: GenerationResponse) -> Result<String, String> {
+    if let Some(total) = response.usage_metadata.as_ref().and_then(|u| u.total_token_count) {
+        tracing::info!("Gemini request used {} tokens", total);
+    }
+
+    if let Some(reason) = response.prompt_feedback.and_then(|f| f.block_reason) {
+        return Err(format!("Response blocked by safety filter ({})", reason));
+    }
+
+    let candidate = response.candidates.and_then(|candidates| candidates.into_iter().next());
+    let finish_reason = candidate.as_ref().and_then(|c| c.finish_reason.clone());
+    let text = candidate
+        .and_then(|candidate| candidate.content)
+        .and_then(|content| content.parts)
+        .and_then(|parts| parts.into_iter().next())
+        .and_then(|part| part.text);
+
+    match (text, finish_reason) {
+        (Some(text), _) => Ok(text),
+        (None, Some(reason)) if BLOCKED_FINISH_REASONS.contains(&reason.as_str()) => {
+            Err(format!("Response blocked by safety filter ({})", reason))
+        }
+        _ => Err("No valid response content from AI".to_string()),
+    }
+}
+
+/// The AI backend chosen in settings
+pub enum AiBackend {
+    Gemini(GeminiClient),
+    OpenAi(OpenAiClient),
+}
+
+impl AiBackend {
+    /// Build the backend described by stored credentials
+    pub fn from_credentials(credentials: &AiCredentials) -> Result<Self, String> {
+        match credentials.provider.as_str() {
+            "gemini" => Ok(Self::Gemini(GeminiClient::new(
+                credentials.api_key.clone(),
+                credentials.model.clone(),
+            )?)),
+            "openai" => Ok(Self::OpenAi(OpenAiClient::new(
+                credentials.api_key.clone(),
+                credentials.base_url.clone(),
+                credentials.model.clone(),
+            ))),
+            other => Err(format!("Unknown AI provider: {}", other)),
+        }
+    }
+}
+
+impl AiProvider for AiBackend {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Gemini(client) => client.name(),
+            Self::OpenAi(client) => client.name(),
+        }
+    }
+
+    async fn generate(&self, prompt: &str, max_tokens: u32) -> Result<String, String> {
+        match self {
+            Self::Gemini(client) => client.generate(prompt, max_tokens).await,
+            Self::OpenAi(client) => client.generate(prompt, max_tokens).await,
         }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
 
-        Err("No valid response content from AI".to_string())
+    /// Records prompts instead of calling a model
+    struct EchoProvider {
+        prompts: Mutex<Vec<String>>,
+        reply: String,
     }
 
-    fn parse_json_list(&self, text: &str) -> Result<Vec<String>, String> {
-        let clean_text = text.trim()
-            .trim_start_matches("```json")
-            .trim_start_matches("```")
-            .trim_end_matches("```")
-            .trim();
+    impl AiProvider for EchoProvider {
+        fn name(&self) -> &'static str {
+            "echo"
+        }
+
+        async fn generate(&self, prompt: &str, _max_tokens: u32) -> Result<String, String> {
+            self.prompts.lock().unwrap().push(prompt.to_string());
+            Ok(self.reply.clone())
+        }
+    }
+
+    #[tokio::test]
+    async fn task_methods_go_through_generate() {
+        let provider = EchoProvider {
+            prompts: Mutex::new(Vec::new()),
+            reply: "```json\n[\"Sounds good\", \"Thanks!\", \"Will do\"]\n```".to_string(),
+        };
+
+        let replies = provider.suggest_replies("Can we meet Friday?", "me@example.com", DEFAULT_LANGUAGE).await.unwrap();
+        assert_eq!(replies, vec!["Sounds good", "Thanks!", "Will do"]);
+
+        let prompts = provider.prompts.lock().unwrap();
+        assert_eq!(prompts.len(), 1);
+        assert!(prompts[0].contains("Can we meet Friday?"));
+        assert!(prompts[0].contains("me@example.com"));
+        assert!(prompts[0].contains("Reply in English"));
+    }
+
+    #[test]
+    fn french_thread_gets_a_french_reply_instruction() {
+        let body = "Bonjour Marie, est-ce que nous pouvons nous retrouver vendredi après-midi \
+                    pour parler du budget de l'année prochaine ? Merci beaucoup et à bientôt.";
+        let language = detect_language(body);
+        assert_eq!(language, "French");
+        assert!(suggest_replies_prompt(body, "me@example.com", language).contains("Reply in French"));
+
+        assert_eq!(detect_language("ok"), DEFAULT_LANGUAGE);
+    }
+
+    #[tokio::test]
+    async fn thread_summary_is_parsed_from_fenced_json() {
+        let provider = EchoProvider {
+            prompts: Mutex::new(Vec::new()),
+            reply: "```json\n{\"summary\": \" Ann needs the Q3 numbers. \", \"action_items\": [\"Send Q3 numbers by Friday\", \" \"]}\n```".to_string(),
+        };
+
+        let summary = provider.summarize_thread("Subject: Q3\n\nFrom: Ann\nCan you send the numbers?").await.unwrap();
+        assert_eq!(summary.summary, "Ann needs the Q3 numbers.");
+        assert_eq!(summary.action_items, vec!["Send Q3 numbers by Friday"]);
+        assert!(provider.prompts.lock().unwrap()[0].contains("Can you send the numbers?"));
+
+        assert!(parse_thread_summary("no json here").is_err());
+    }
+
+    #[tokio::test]
+    async fn composed_draft_is_one_unwrapped_body() {
+        let provider = EchoProvider {
+            prompts: Mutex::new(Vec::new()),
+            reply: "```\nHi Ann,\n\nThanks for the invite, but I can't make it. Could we try next Tuesday?\n\nBest,\nMe\n```".to_string(),
+        };
+
+        let draft = provider
+            .compose_draft("decline politely, suggest next Tuesday", Some("From: Ann\nLunch Friday?"), "me@example.com", Some(Tone::Friendly))
+            .await
+            .unwrap();
+        assert!(draft.starts_with("Hi Ann,"));
+        assert!(draft.ends_with("Me"));
+
+        let prompt = &provider.prompts.lock().unwrap()[0];
+        assert!(prompt.contains("decline politely, suggest next Tuesday"));
+        assert!(prompt.contains("Lunch Friday?"));
+        assert!(prompt.contains(Tone::Friendly.guideline()));
+    }
+
+    #[test]
+    fn suggestion_list_is_found_in_messy_output() {
+        let expected = vec!["Sounds good", "Thanks!", "Will do"];
+
+        assert_eq!(parse_json_list(r#"["Sounds good", "Thanks!", "Will do"]"#).unwrap(), expected);
+        assert_eq!(
+            parse_json_list("```json\n[\"Sounds good\", \"Thanks!\", \"Will do\"]\n```").unwrap(),
+            expected
+        );
+        assert_eq!(
+            parse_json_list("Here are three replies:\n[\"Sounds good\", \"Thanks!\", \"Will do\"]\nLet me know if you need more.")
+                .unwrap(),
+            expected
+        );
+
+        // No array at all: one suggestion per line
+        assert_eq!(
+            parse_json_list("1. Sounds good\n2. Thanks!\n3. Will do\n4. Extra").unwrap(),
+            expected
+        );
+        assert!(parse_json_list("  \n ").is_err());
+    }
+
+    #[test]
+    fn fallback_keeps_leading_numbers_that_are_not_list_markers() {
+        assert_eq!(
+            parse_json_list("10am works for me\n2) Let's do 3pm\n- 5 minutes late, sorry").unwrap(),
+            vec!["10am works for me", "Let's do 3pm", "5 minutes late, sorry"]
+        );
+        assert_eq!(
+            parse_json_list("2.30 works for me\n1.5 hours should be enough").unwrap(),
+            vec!["2.30 works for me", "1.5 hours should be enough"]
+        );
+    }
+
+    #[test]
+    fn fallback_skips_lead_in_lines() {
+        assert_eq!(
+            parse_json_list("Here are some replies you could send:\n1. Sounds good\n2. Thanks!").unwrap(),
+            vec!["Sounds good", "Thanks!"]
+        );
+        // Only a first line is a lead-in
+        assert_eq!(
+            parse_json_list("Sounds good\nHere's the agenda:\nThanks!").unwrap(),
+            vec!["Sounds good", "Here's the agenda:", "Thanks!"]
+        );
+    }
+
+    fn thread(id: &str, subject: &str, snippet: &str) -> Thread {
+        Thread {
+            gmail_thread_id: id.to_string(),
+            account_id: "acc".to_string(),
+            subject: subject.to_string(),
+            snippet: snippet.to_string(),
+            last_message_date: chrono::Utc::now(),
+            unread_count: 1,
+            labels: Vec::new(),
+            participants: vec!["ann@example.com".to_string()],
+            has_attachment: false,
+            attachments: Vec::new(),
+            calendar_event: None,
+            category: "Primary".to_string(),
+        }
+    }
+
+    #[test]
+    fn digest_context_is_capped() {
+        let long_snippet = "word ".repeat(200);
+        let threads: Vec<Thread> = (0..100).map(|i| thread(&format!("t{}", i), "Budget", &long_snippet)).collect();
+
+        let context = digest_context(&threads);
+        assert!(context.text.len() <= MAX_DIGEST_CONTEXT_CHARS);
+        assert!(context.thread_ids.len() <= MAX_DIGEST_THREADS);
+        assert_eq!(context.text.lines().count(), context.thread_ids.len());
+        // Snippets are truncated, not dropped
+        assert!(context.text.lines().all(|line| line.ends_with("...") && line.len() < 400));
+
+        let few = digest_context(&threads[..2]);
+        assert_eq!(few.thread_ids, vec!["t0", "t1"]);
+    }
+
+    #[tokio::test]
+    async fn digest_json_is_parsed_and_unknown_ids_dropped() {
+        let provider = EchoProvider {
+            prompts: Mutex::new(Vec::new()),
+            reply: r#"```json
+{"topics": [
+  {"topic": "Budget", "summary": "Finance wants Q3 numbers by Friday.", "thread_ids": ["t1", "made-up"]},
+  {"topic": "Hiring", "summary": "Two candidates await feedback."}
+]}
+```"#
+                .to_string(),
+        };
+        let context = digest_context(&[thread("t1", "Q3 budget", "Numbers by Friday"), thread("t2", "Candidates", "")]);
+
+        let digest = provider.weekly_digest(&context).await.unwrap();
+        assert_eq!(digest.topics.len(), 2);
+        assert_eq!(digest.topics[0].thread_ids, vec!["t1"]);
+        assert!(digest.topics[1].thread_ids.is_empty());
+        assert!(provider.prompts.lock().unwrap()[0].contains("[t1] From: ann@example.com | Subject: Q3 budget"));
+
+        assert!(parse_digest("not json", &[]).is_err());
+    }
+
+    #[test]
+    fn blocked_prompt_reports_safety_filter() {
+        let blocked: GenerationResponse = serde_json::from_str(
+            r#"{"promptFeedback": {"blockReason": "SAFETY"}, "usageMetadata": {"promptTokenCount": 120, "totalTokenCount": 120}}"#,
+        )
+        .unwrap();
+        assert_eq!(response_text(blocked).unwrap_err(), "Response blocked by safety filter (SAFETY)");
+
+        let stopped: GenerationResponse =
+            serde_json::from_str(r#"{"candidates": [{"finishReason": "SAFETY"}]}"#).unwrap();
+        assert!(response_text(stopped).unwrap_err().contains("safety filter"));
+
+        let ok: GenerationResponse = serde_json::from_str(
+            r#"{"candidates": [{"content": {"parts": [{"text": "Hi"}]}, "finishReason": "STOP"}]}"#,
+        )
+        .unwrap();
+        assert_eq!(response_text(ok).unwrap(), "Hi");
+    }
+
+    #[test]
+    fn gemini_model_is_configurable() {
+        let default = GeminiClient::new("key".to_string(), None).unwrap();
+        assert_eq!(default.endpoint(), format!("{}/gemini-1.5-flash:generateContent", API_BASE));
+
+        let pro = GeminiClient::new("key".to_string(), Some(" gemini-1.5-pro ".to_string())).unwrap();
+        assert_eq!(pro.endpoint(), format!("{}/gemini-1.5-pro:generateContent", API_BASE));
+
+        let blank = GeminiClient::new("key".to_string(), Some(" ".to_string())).unwrap();
+        assert_eq!(blank.endpoint(), default.endpoint());
+        assert!(GeminiClient::new("key".to_string(), Some("../admin?x=".to_string())).is_err());
+    }
+
+    #[test]
+    fn backend_dispatches_to_configured_provider() {
+        let openai = AiBackend::from_credentials(&AiCredentials {
+            provider: "openai".to_string(),
+            api_key: "sk-test".to_string(),
+            base_url: Some("https://llm.example.com/v1/".to_string()),
+            model: Some("gpt-4o-mini".to_string()),
+        })
+        .unwrap();
+        assert_eq!(openai.name(), "openai");
+
+        let gemini = AiBackend::from_credentials(&AiCredentials {
+            provider: "gemini".to_string(),
+            api_key: "AIza-test".to_string(),
+            base_url: None,
+            model: None,
+        })
+        .unwrap();
+        assert_eq!(gemini.name(), "gemini");
 
-        serde_json::from_str::<Vec<String>>(clean_text)
-            .map_err(|e| format!("Failed to parse JSON suggestions: {} (Text: {})", e, clean_text))
+        assert!(AiBackend::from_credentials(&AiCredentials {
+            provider: "carrier-pigeon".to_string(),
+            api_key: String::new(),
+            base_url: None,
+            model: None,
+        })
+        .is_err());
     }
 }
@@ -0,0 +1,119 @@
+// OpenAI-compatible chat completions client (OpenAI, Azure-style proxies,
+// local servers exposing /v1/chat/completions)
+
+use super::{http_client, AiProvider};
+use serde::Deserialize;
+use serde_json::json;
+
+const DEFAULT_BASE_URL: &str = "https://api.openai.com/v1";
+const DEFAULT_MODEL: &str = "gpt-4o-mini";
+
+pub struct OpenAiClient {
+    client: reqwest::Client,
+    api_key: String,
+    base_url: String,
+    model: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatResponse {
+    choices: Vec<Choice>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Choice {
+    message: ChatMessage,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatMessage {
+    content: Option<String>,
+}
+
+impl OpenAiClient {
+    /// `base_url` is the API root serving `/chat/completions`; blank values
+    /// fall back to OpenAI's API and gpt-4o-mini
+    pub fn new(api_key: String, base_url: Option<String>, model: Option<String>) -> Self {
+        let base_url = base_url
+            .map(|url| url.trim().trim_end_matches('/').to_string())
+            .filter(|url| !url.is_empty())
+            .unwrap_or_else(|| DEFAULT_BASE_URL.to_string());
+        let model = model
+            .map(|m| m.trim().to_string())
+            .filter(|m| !m.is_empty())
+            .unwrap_or_else(|| DEFAULT_MODEL.to_string());
+
+        Self {
+            client: http_client(),
+            api_key,
+            base_url,
+            model,
+        }
+    }
+
+    fn endpoint(&self) -> String {
+        format!("{}/chat/completions", self.base_url)
+    }
+}
+
+impl AiProvider for OpenAiClient {
+    fn name(&self) -> &'static str {
+        "openai"
+    }
+
+    async fn generate(&self, prompt: &str, max_tokens: u32) -> Result<String, String> {
+        let url = self.endpoint();
+
+        let body = json!({
+            "model": self.model,
+            "messages": [{ "role": "user", "content": prompt }],
+            "temperature": 0.4,
+            "max_tokens": max_tokens,
+        });
+
+        let resp = self.client
+            .post(&url)
+            .bearer_auth(&self.api_key)
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| format!("Request failed: {}", e))?;
+
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let text = resp.text().await.unwrap_or_default();
+            return Err(format!("AI API error {}: {}", status, text));
+        }
+
+        let response: ChatResponse = resp.json()
+            .await
+            .map_err(|e| format!("Failed to parse response: {}", e))?;
+
+        response
+            .choices
+            .into_iter()
+            .next()
+            .and_then(|choice| choice.message.content)
+            .ok_or_else(|| "No valid response content from AI".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn endpoint_and_model_fall_back_to_openai() {
+        let default = OpenAiClient::new("sk-test".to_string(), Some("  ".to_string()), None);
+        assert_eq!(default.endpoint(), "https://api.openai.com/v1/chat/completions");
+        assert_eq!(default.model, DEFAULT_MODEL);
+
+        let local = OpenAiClient::new(
+            "sk-test".to_string(),
+            Some("http://localhost:11434/v1/".to_string()),
+            Some("llama3".to_string()),
+        );
+        assert_eq!(local.endpoint(), "http://localhost:11434/v1/chat/completions");
+        assert_eq!(local.model, "llama3");
+    }
+}
@@ -7,7 +7,14 @@ use std::sync::Arc;
 use std::thread;
 use std::time::Duration;
 
-const CALLBACK_PORT: u16 = 8420;
+/// Loopback ports tried in order for the OAuth redirect. Google accepts any
+/// port on a loopback redirect for desktop clients, so the first free one works.
+pub const CALLBACK_PORTS: std::ops::RangeInclusive<u16> = 8420..=8430;
+
+/// Redirect URI for a callback server listening on `port`
+pub fn redirect_uri(port: u16) -> String {
+    format!("http://localhost:{}/callback", port)
+}
 
 /// OAuth callback result with code and state
 pub struct CallbackResult {
@@ -19,19 +26,42 @@ pub struct CallbackResult {
 /// browser so the redirect can never race the bind.
 pub struct CallbackServer {
     listener: TcpListener,
+    port: u16,
 }
 
 impl CallbackServer {
+    /// Bind the first free port in `CALLBACK_PORTS`
     pub fn bind() -> Result<Self, String> {
-        let listener = TcpListener::bind(format!("127.0.0.1:{}", CALLBACK_PORT))
-            .map_err(|e| format!("Failed to bind to port {}: {}", CALLBACK_PORT, e))?;
+        let mut last_err = None;
+        for port in CALLBACK_PORTS {
+            match TcpListener::bind(("127.0.0.1", port)) {
+                Ok(listener) => {
+                    // Non-blocking accept so the wait loop can poll the cancel flag
+                    listener
+                        .set_nonblocking(true)
+                        .map_err(|e| format!("Failed to set non-blocking: {}", e))?;
+                    if port != *CALLBACK_PORTS.start() {
+                        tracing::info!("OAuth callback port {} busy, using {}", CALLBACK_PORTS.start(), port);
+                    }
+                    return Ok(Self { listener, port });
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::AddrInUse => last_err = Some(e),
+                Err(e) => return Err(format!("Failed to bind to port {}: {}", port, e)),
+            }
+        }
 
-        // Non-blocking accept so the wait loop can poll the cancel flag
-        listener
-            .set_nonblocking(true)
-            .map_err(|e| format!("Failed to set non-blocking: {}", e))?;
+        tracing::warn!("No free OAuth callback port: {:?}", last_err);
+        Err(format!(
+            "Ports {}-{} are all in use by other apps. Close the app using port {} and try signing in again.",
+            CALLBACK_PORTS.start(),
+            CALLBACK_PORTS.end(),
+            CALLBACK_PORTS.start()
+        ))
+    }
 
-        Ok(Self { listener })
+    /// Redirect URI to send in the auth request so the browser comes back here
+    pub fn redirect_uri(&self) -> String {
+        redirect_uri(self.port)
     }
 
     /// Block until the OAuth redirect arrives, the timeout elapses, or the
@@ -0,0 +1,122 @@
+// Refresh-token health per account
+//
+// `get_access_token` records every refresh attempt here so accounts that
+// silently stop syncing can be diagnosed: the last successful refresh, the
+// last error, and a status derived from whichever happened more recently.
+
+use super::AuthError;
+use serde::Serialize;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthStatus {
+    Ok,
+    /// The refresh token was revoked or expired; the user must log in again
+    TokenExpired,
+    /// Google couldn't be reached; retrying later may succeed
+    NetworkError,
+    /// No refresh attempted since launch
+    Unknown,
+}
+
+impl HealthStatus {
+    /// Classify a failed refresh
+    pub fn from_auth_error(error: &AuthError) -> Self {
+        match error {
+            AuthError::Http(_) => HealthStatus::NetworkError,
+            AuthError::ReauthRequired(_) => HealthStatus::TokenExpired,
+            // Other token endpoint errors (5xx, rate limits) are usually transient
+            AuthError::TokenRefresh(_) => HealthStatus::NetworkError,
+            // No stored refresh token at all
+            AuthError::NoCredentials | AuthError::Keyring(_) | AuthError::OAuth2(_) => HealthStatus::TokenExpired,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AccountHealth {
+    pub account_id: String,
+    pub status: HealthStatus,
+    /// Unix ms of the last successful token refresh
+    pub last_successful_refresh: Option<i64>,
+    pub last_refresh_error: Option<String>,
+    /// Unix ms of the last failed refresh
+    pub last_error_at: Option<i64>,
+}
+
+#[derive(Debug, Default)]
+struct RefreshRecord {
+    last_success: Option<i64>,
+    last_error: Option<(HealthStatus, String, i64)>,
+}
+
+#[derive(Debug, Default)]
+pub struct TokenHealth {
+    records: HashMap<String, RefreshRecord>,
+}
+
+impl TokenHealth {
+    pub fn record_success(&mut self, account_id: &str, now: i64) {
+        self.records.entry(account_id.to_string()).or_default().last_success = Some(now);
+    }
+
+    pub fn record_failure(&mut self, account_id: &str, error: &AuthError, now: i64) {
+        let status = HealthStatus::from_auth_error(error);
+        self.records.entry(account_id.to_string()).or_default().last_error = Some((status, error.to_string(), now));
+    }
+
+    pub fn remove(&mut self, account_id: &str) {
+        self.records.remove(account_id);
+    }
+
+    pub fn health(&self, account_id: &str) -> AccountHealth {
+        let record = self.records.get(account_id);
+        let last_success = record.and_then(|r| r.last_success);
+        let last_error = record.and_then(|r| r.last_error.clone());
+
+        let status = match (&last_error, last_success) {
+            (Some((status, _, at)), Some(success)) if *at > success => *status,
+            (Some((status, _, _)), None) => *status,
+            (_, Some(_)) => HealthStatus::Ok,
+            (None, None) => HealthStatus::Unknown,
+        };
+
+        AccountHealth {
+            account_id: account_id.to_string(),
+            status,
+            last_successful_refresh: last_success,
+            last_refresh_error: last_error.as_ref().map(|(_, message, _)| message.clone()),
+            last_error_at: last_error.map(|(_, _, at)| at),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn refresh_failure_records_error_and_time() {
+        let mut health = TokenHealth::default();
+        assert_eq!(health.health("acc").status, HealthStatus::Unknown);
+
+        health.record_success("acc", 1_000);
+        assert_eq!(health.health("acc").status, HealthStatus::Ok);
+
+        let revoked = AuthError::ReauthRequired(r#"{"error": "invalid_grant"}"#.to_string());
+        health.record_failure("acc", &revoked, 2_000);
+
+        let status = health.health("acc");
+        assert_eq!(status.status, HealthStatus::TokenExpired);
+        assert_eq!(status.last_successful_refresh, Some(1_000));
+        assert_eq!(status.last_error_at, Some(2_000));
+        assert!(status.last_refresh_error.unwrap().contains("invalid_grant"));
+
+        // A later success clears the status but keeps the error for diagnostics
+        health.record_success("acc", 3_000);
+        let status = health.health("acc");
+        assert_eq!(status.status, HealthStatus::Ok);
+        assert_eq!(status.last_error_at, Some(2_000));
+    }
+}
@@ -1,10 +1,13 @@
 // Authentication module
 
 pub mod callback;
+pub mod health;
 pub mod oauth2;
 
-pub use callback::{CallbackResult, CallbackServer};
+pub use callback::{redirect_uri, CallbackResult, CallbackServer, CALLBACK_PORTS};
+pub use health::{AccountHealth, TokenHealth};
 pub use oauth2::{
-    delete_refresh_token, get_oauth_credentials, get_refresh_token,
-    store_oauth_credentials, store_refresh_token, AuthError, GmailAuth,
+    delete_ai_credentials, delete_refresh_token, get_ai_credentials, get_oauth_credentials,
+    get_refresh_token, store_ai_credentials, store_oauth_credentials, store_refresh_token,
+    AiCredentials, AuthError, GmailAuth, ScopeSet, OPTIONAL_SCOPES, REAUTH_ERROR_PREFIX,
 };
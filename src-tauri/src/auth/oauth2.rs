@@ -7,8 +7,70 @@ use tokio::sync::Mutex;
 
 const GOOGLE_AUTH_URL: &str = "https://accounts.google.com/o/oauth2/v2/auth";
 const GOOGLE_TOKEN_URL: &str = "https://oauth2.googleapis.com/token";
-const SCOPES: &str = "https://mail.google.com/ https://www.googleapis.com/auth/calendar.readonly https://www.googleapis.com/auth/calendar.events https://www.googleapis.com/auth/contacts.readonly email profile";
-const REDIRECT_URI: &str = "http://localhost:8420/callback";
+const GOOGLE_REVOKE_URL: &str = "https://oauth2.googleapis.com/revoke";
+/// Revoking is best-effort, so don't let a slow endpoint hold up account removal
+const REVOKE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Scopes every sign-in requests; mail access is the app's core
+const REQUIRED_SCOPES: &[&str] = &["https://mail.google.com/", "email", "profile"];
+
+/// Scopes the user can opt out of. Features needing a missing one fail with
+/// a "scope not granted" error instead of the whole sign-in.
+pub const OPTIONAL_SCOPES: &[&str] = &[
+    FILTERS_SCOPE,
+    CALENDAR_READ_SCOPE,
+    CALENDAR_EVENTS_SCOPE,
+    CONTACTS_SCOPE,
+    STORAGE_SCOPE,
+];
+
+const FILTERS_SCOPE: &str = "https://www.googleapis.com/auth/gmail.settings.basic";
+const CALENDAR_READ_SCOPE: &str = "https://www.googleapis.com/auth/calendar.readonly";
+const CALENDAR_EVENTS_SCOPE: &str = "https://www.googleapis.com/auth/calendar.events";
+const CONTACTS_SCOPE: &str = "https://www.googleapis.com/auth/contacts.readonly";
+/// Enough to read the account's storage quota from Drive's `about`
+const STORAGE_SCOPE: &str = "https://www.googleapis.com/auth/drive.metadata.readonly";
+
+/// Scopes to request at sign-in, as chosen in settings. Defaults to
+/// every optional scope; mail access is always included.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScopeSet {
+    optional: Vec<&'static str>,
+}
+
+impl Default for ScopeSet {
+    fn default() -> Self {
+        Self {
+            optional: OPTIONAL_SCOPES.to_vec(),
+        }
+    }
+}
+
+impl ScopeSet {
+    /// The optional scopes named in `selected`. Unknown scopes are rejected.
+    pub fn from_selected(selected: &[String]) -> Result<Self, String> {
+        if let Some(unknown) = selected.iter().find(|s| !OPTIONAL_SCOPES.contains(&s.as_str())) {
+            return Err(format!("Unsupported scope: {}", unknown));
+        }
+        Ok(Self {
+            optional: OPTIONAL_SCOPES
+                .iter()
+                .copied()
+                .filter(|s| selected.iter().any(|x| x == s))
+                .collect(),
+        })
+    }
+
+    /// Space-separated scope string for the auth URL
+    pub fn scope_string(&self) -> String {
+        REQUIRED_SCOPES
+            .iter()
+            .chain(&self.optional)
+            .copied()
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
 
 #[derive(Error, Debug)]
 pub enum AuthError {
@@ -16,6 +78,9 @@ pub enum AuthError {
     OAuth2(String),
     #[error("Token refresh failed: {0}")]
     TokenRefresh(String),
+    /// The refresh token was revoked or expired (`invalid_grant`)
+    #[error("Sign-in expired, please reconnect the account: {0}")]
+    ReauthRequired(String),
     #[error("Keyring error: {0}")]
     Keyring(String),
     #[error("No credentials configured")]
@@ -24,6 +89,17 @@ pub enum AuthError {
     Http(#[from] reqwest::Error),
 }
 
+/// Prefix on command errors that mean the user has to sign in again
+pub const REAUTH_ERROR_PREFIX: &str = "REAUTH:";
+
+impl AuthError {
+    /// True when only a new sign-in can fix this: the refresh token was
+    /// revoked or expired, or none is stored
+    pub fn needs_reauth(&self) -> bool {
+        matches!(self, AuthError::ReauthRequired(_) | AuthError::Keyring(_))
+    }
+}
+
 #[derive(Debug, Deserialize)]
 struct TokenResponse {
     access_token: String,
@@ -40,6 +116,8 @@ pub struct GmailAuth {
 struct PendingAuth {
     verifier: String,
     state: String,
+    /// Must be sent again with the code exchange
+    redirect_uri: String,
 }
 
 impl GmailAuth {
@@ -52,13 +130,12 @@ impl GmailAuth {
     }
 
     fn generate_pkce() -> (String, String) {
-        // Verifier: 64 URL-safe hex chars (RFC 7636 requires 43-128) built
-        // from two v4 UUIDs, which use a cryptographic RNG
-        let verifier = format!(
-            "{}{}",
-            uuid::Uuid::new_v4().simple(),
-            uuid::Uuid::new_v4().simple()
-        );
+        // Verifier: 32 bytes from the OS CSPRNG, base64url-encoded to the
+        // 43 characters RFC 7636 recommends (it allows 43-128)
+        use rand::RngCore;
+        let mut bytes = [0u8; 32];
+        rand::rngs::OsRng.fill_bytes(&mut bytes);
+        let verifier = base64_url_encode(&bytes);
 
         // Generate challenge (SHA256 of verifier, base64url encoded)
         use sha2::{Digest, Sha256};
@@ -70,7 +147,9 @@ impl GmailAuth {
         (verifier, challenge)
     }
 
-    pub async fn start_auth_flow(&self) -> Result<(String, String), AuthError> {
+    /// Start a flow whose redirect lands on `redirect_uri` (see `callback::redirect_uri`),
+    /// asking for `scopes`. Returns (auth_url, state).
+    pub async fn start_auth_flow(&self, redirect_uri: &str, scopes: &ScopeSet) -> Result<(String, String), AuthError> {
         let (verifier, challenge) = Self::generate_pkce();
         let state: String = format!("{:016x}", rand::random::<u64>());
 
@@ -78,8 +157,8 @@ impl GmailAuth {
             "{}?client_id={}&redirect_uri={}&response_type=code&scope={}&access_type=offline&prompt=consent&code_challenge={}&code_challenge_method=S256&state={}",
             GOOGLE_AUTH_URL,
             urlencoding::encode(&self.client_id),
-            urlencoding::encode(REDIRECT_URI),
-            urlencoding::encode(SCOPES),
+            urlencoding::encode(redirect_uri),
+            urlencoding::encode(&scopes.scope_string()),
             urlencoding::encode(&challenge),
             urlencoding::encode(&state)
         );
@@ -87,6 +166,7 @@ impl GmailAuth {
         *self.pending_auth.lock().await = Some(PendingAuth {
             verifier,
             state: state.clone(),
+            redirect_uri: redirect_uri.to_string(),
         });
 
         Ok((auth_url, state))
@@ -97,7 +177,7 @@ impl GmailAuth {
     pub async fn exchange_code(&self, code: String, received_state: Option<&str>) -> Result<(String, String, Option<u64>), AuthError> {
         // Verify state BEFORE consuming the pending flow, so a stray callback
         // with a bad state doesn't destroy a still-valid flow
-        let verifier = {
+        let (verifier, redirect_uri) = {
             let mut guard = self.pending_auth.lock().await;
             let pending = guard
                 .as_ref()
@@ -109,7 +189,7 @@ impl GmailAuth {
                 }
             }
 
-            guard.take().map(|p| p.verifier).unwrap_or_default()
+            guard.take().map(|p| (p.verifier, p.redirect_uri)).unwrap_or_default()
         };
 
         tracing::info!("Exchanging code for tokens...");
@@ -124,7 +204,7 @@ impl GmailAuth {
                 ("code", &code),
                 ("code_verifier", &verifier),
                 ("grant_type", "authorization_code"),
-                ("redirect_uri", REDIRECT_URI),
+                ("redirect_uri", &redirect_uri),
             ])
             .send()
             .await?;
@@ -151,8 +231,12 @@ impl GmailAuth {
         Ok((token_resp.access_token, refresh_token, token_resp.expires_in))
     }
 
-    /// Refresh the access token. Returns (access_token, expires_in_secs).
-    pub async fn refresh_access_token(&self, refresh_token: &str) -> Result<(String, Option<u64>), AuthError> {
+    /// Refresh the access token. Returns (access_token, expires_in_secs, rotated_refresh_token);
+    /// the last is set only when Google issues a replacement refresh token.
+    pub async fn refresh_access_token(
+        &self,
+        refresh_token: &str,
+    ) -> Result<(String, Option<u64>, Option<String>), AuthError> {
         let client = reqwest::Client::new();
         let resp = client
             .post(GOOGLE_TOKEN_URL)
@@ -167,11 +251,34 @@ impl GmailAuth {
 
         if !resp.status().is_success() {
             let error_text = resp.text().await.unwrap_or_default();
+            if error_text.contains("invalid_grant") {
+                return Err(AuthError::ReauthRequired(error_text));
+            }
             return Err(AuthError::TokenRefresh(format!("Token refresh failed: {}", error_text)));
         }
 
         let token_resp: TokenResponse = resp.json().await?;
-        Ok((token_resp.access_token, token_resp.expires_in))
+        let rotated = token_resp.refresh_token.filter(|t| !t.is_empty() && t != refresh_token);
+        Ok((token_resp.access_token, token_resp.expires_in, rotated))
+    }
+
+    /// Revoke a token with Google. Revoking a refresh token also invalidates
+    /// the access tokens issued from it. Needs no client credentials, so
+    /// callers don't have to hold the auth lock.
+    pub async fn revoke_token(token: &str) -> Result<(), AuthError> {
+        let resp = reqwest::Client::new()
+            .post(GOOGLE_REVOKE_URL)
+            .timeout(REVOKE_TIMEOUT)
+            .form(&[("token", token)])
+            .send()
+            .await?;
+
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let error_text = resp.text().await.unwrap_or_default();
+            return Err(AuthError::OAuth2(format!("Token revoke failed ({}): {}", status, error_text)));
+        }
+        Ok(())
     }
 }
 
@@ -193,6 +300,10 @@ fn get_credentials_file_path(app_data_dir: &Path) -> PathBuf {
     app_data_dir.join("tokens").join("oauth_credentials.json")
 }
 
+fn get_ai_credentials_file_path(app_data_dir: &Path) -> PathBuf {
+    app_data_dir.join("tokens").join("ai_credentials.json")
+}
+
 pub fn store_refresh_token(account_id: &str, token: &str, app_data_dir: &Path) -> Result<(), AuthError> {
     tracing::info!("Storing refresh token for account: {}", account_id);
 
@@ -360,3 +471,105 @@ pub fn delete_oauth_credentials(app_data_dir: &Path) -> Result<(), AuthError> {
 
     Ok(())
 }
+
+/// User-supplied AI backend: provider name plus its API key and endpoint overrides
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct AiCredentials {
+    pub provider: String,
+    pub api_key: String,
+    #[serde(default)]
+    pub base_url: Option<String>,
+    #[serde(default)]
+    pub model: Option<String>,
+}
+
+pub fn store_ai_credentials(credentials: &AiCredentials, app_data_dir: &Path) -> Result<(), AuthError> {
+    tracing::info!("Storing AI provider credentials ({})", credentials.provider);
+
+    let json = serde_json::to_string(credentials)
+        .map_err(|e| AuthError::Keyring(format!("Failed to serialize AI credentials: {}", e)))?;
+
+    let keychain_ok = if let Ok(entry) = keyring::Entry::new(KEYRING_SERVICE, "ai:credentials") {
+        entry.set_password(&json).is_ok()
+    } else {
+        false
+    };
+
+    if !keychain_ok {
+        tracing::warn!("Keychain storage failed for AI credentials");
+    }
+
+    // Always also write to file as backup
+    let path = get_ai_credentials_file_path(app_data_dir);
+    if let Some(parent) = path.parent() {
+        let _ = std::fs::create_dir_all(parent);
+    }
+    std::fs::write(&path, &json)
+        .map_err(|e| AuthError::Keyring(format!("Failed to store AI credentials: {}", e)))?;
+
+    Ok(())
+}
+
+pub fn get_ai_credentials(app_data_dir: &Path) -> Result<AiCredentials, AuthError> {
+    if let Ok(entry) = keyring::Entry::new(KEYRING_SERVICE, "ai:credentials") {
+        if let Ok(json) = entry.get_password() {
+            if let Ok(creds) = serde_json::from_str(&json) {
+                return Ok(creds);
+            }
+        }
+    }
+
+    let path = get_ai_credentials_file_path(app_data_dir);
+    if let Ok(json) = std::fs::read_to_string(&path) {
+        if let Ok(creds) = serde_json::from_str::<AiCredentials>(&json) {
+            return Ok(creds);
+        }
+    }
+
+    Err(AuthError::NoCredentials)
+}
+
+pub fn delete_ai_credentials(app_data_dir: &Path) -> Result<(), AuthError> {
+    if let Ok(entry) = keyring::Entry::new(KEYRING_SERVICE, "ai:credentials") {
+        let _ = entry.delete_credential();
+    }
+
+    let path = get_ai_credentials_file_path(app_data_dir);
+    let _ = std::fs::remove_file(path);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pkce_verifier_is_43_unreserved_chars() {
+        let (verifier, challenge) = GmailAuth::generate_pkce();
+        assert_eq!(verifier.len(), 43);
+        assert!(verifier.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'));
+        // SHA-256 digest, base64url without padding
+        assert_eq!(challenge.len(), 43);
+
+        let (other, _) = GmailAuth::generate_pkce();
+        assert_ne!(verifier, other);
+    }
+
+    #[tokio::test]
+    async fn auth_url_requests_only_selected_scopes() {
+        let auth = GmailAuth::new("client".to_string(), "secret".to_string());
+        let calendar = "https://www.googleapis.com/auth/calendar.readonly".to_string();
+        let scopes = ScopeSet::from_selected(std::slice::from_ref(&calendar)).unwrap();
+        let (url, _) = auth.start_auth_flow("http://localhost:8420/callback", &scopes).await.unwrap();
+
+        assert!(url.contains(&*urlencoding::encode("https://mail.google.com/ email profile")));
+        assert!(url.contains(&*urlencoding::encode(&calendar)));
+        assert!(!url.contains("contacts"));
+        assert!(!url.contains("calendar.events"));
+
+        let all: Vec<String> = OPTIONAL_SCOPES.iter().map(|s| s.to_string()).collect();
+        assert_eq!(ScopeSet::from_selected(&all).unwrap(), ScopeSet::default());
+        assert!(ScopeSet::from_selected(&["https://www.googleapis.com/auth/drive".to_string()]).is_err());
+    }
+}
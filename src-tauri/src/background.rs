@@ -0,0 +1,289 @@
+// Background jobs that run for the lifetime of the app
+//
+// Follow-up reminders are checked on a fixed interval: a reply since the
+// reminder was set cancels it, otherwise it fires once its time comes.
+// Snoozed threads go back to the inbox once their wake time has passed.
+// Every job skips its work while battery saver has background activity paused.
+
+use crate::commands::{get_access_token, get_app_data_dir, with_db, AppState, SNOOZE_LABEL_NAME};
+use crate::gmail::{reply_received_since, FullThread, GmailClient};
+use crate::models::FollowupReminder;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tauri::{Emitter, Manager};
+
+/// How often pending follow-ups are checked against their threads
+const FOLLOWUP_CHECK_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+/// Event emitted to the frontend when a follow-up comes due
+pub const FOLLOWUP_DUE_EVENT: &str = "followup-due";
+
+/// How often snoozed threads are checked for a wake time that has passed
+const SNOOZE_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Event emitted with the account id when snoozed threads return to the inbox
+pub const SNOOZE_WOKE_EVENT: &str = "snoozed-threads-woke";
+
+/// Event emitted when background activity is paused or resumed; the payload is the new state
+pub const BACKGROUND_PAUSED_EVENT: &str = "background-paused";
+
+/// `app_settings` key persisting the paused state
+pub const BACKGROUND_PAUSED_SETTING: &str = "background_paused";
+
+/// Run one tick of a background job, or nothing while paused
+pub async fn run_unless_paused<F, Fut>(paused: &AtomicBool, job: F) -> Option<Fut::Output>
+where
+    F: FnOnce() -> Fut,
+    Fut: Future,
+{
+    if paused.load(Ordering::SeqCst) {
+        return None;
+    }
+    Some(job().await)
+}
+
+#[derive(Debug, PartialEq)]
+pub enum FollowupCheck {
+    /// Someone replied after the reminder was set; drop it silently
+    Replied,
+    /// No reply and the window has passed; notify the user
+    Due,
+    /// No reply yet, but still inside the window
+    Waiting,
+}
+
+pub fn check_followup(reminder: &FollowupReminder, thread: &FullThread, account_email: &str, now: i64) -> FollowupCheck {
+    if reply_received_since(thread, account_email, reminder.created_at) {
+        FollowupCheck::Replied
+    } else if now >= reminder.remind_at {
+        FollowupCheck::Due
+    } else {
+        FollowupCheck::Waiting
+    }
+}
+
+pub async fn run_followup_checker(app: tauri::AppHandle) {
+    let paused = app.state::<AppState>().background_paused.clone();
+    loop {
+        tokio::time::sleep(FOLLOWUP_CHECK_INTERVAL).await;
+        if let Some(Err(e)) = run_unless_paused(&paused, || check_followups(&app)).await {
+            tracing::warn!("Follow-up check failed: {}", e);
+        }
+    }
+}
+
+async fn check_followups(app: &tauri::AppHandle) -> Result<(), String> {
+    let state = app.state::<AppState>();
+    let app_data_dir = get_app_data_dir(app)?;
+
+    let (reminders, accounts) = with_db(&state, |db| {
+        let reminders = db.get_followups(None).map_err(|e| e.to_string())?;
+        let accounts = db.get_accounts().map_err(|e| e.to_string())?;
+        Ok((reminders, accounts))
+    })?;
+
+    for reminder in reminders {
+        let Some(account) = accounts.iter().find(|a| a.id == reminder.account_id) else {
+            continue;
+        };
+
+        let thread = match get_access_token(&state, &reminder.account_id, &app_data_dir).await {
+            Ok(token) => GmailClient::new(token).get_thread(&reminder.thread_id).await,
+            Err(e) => Err(e),
+        };
+        let thread = match thread {
+            Ok(thread) => thread,
+            Err(e) => {
+                tracing::warn!("Follow-up check for thread {} failed: {}", reminder.thread_id, e);
+                continue;
+            }
+        };
+
+        let now = chrono::Utc::now().timestamp_millis();
+        match check_followup(&reminder, &thread, &account.email, now) {
+            FollowupCheck::Waiting => continue,
+            FollowupCheck::Due => {
+                let _ = app.emit(FOLLOWUP_DUE_EVENT, &reminder);
+            }
+            FollowupCheck::Replied => {}
+        }
+
+        with_db(&state, |db| {
+            db.delete_followup(&reminder.account_id, &reminder.thread_id)
+                .map_err(|e| e.to_string())
+        })?;
+    }
+
+    Ok(())
+}
+
+/// Threads whose wake time is at or before `now`
+pub fn due_snoozes(snoozed: &[(String, i64)], now: i64) -> Vec<String> {
+    snoozed
+        .iter()
+        .filter(|(_, wake_at)| *wake_at <= now)
+        .map(|(thread_id, _)| thread_id.clone())
+        .collect()
+}
+
+pub async fn run_snooze_waker(app: tauri::AppHandle) {
+    let paused = app.state::<AppState>().background_paused.clone();
+    loop {
+        tokio::time::sleep(SNOOZE_CHECK_INTERVAL).await;
+        if let Some(Err(e)) = run_unless_paused(&paused, || wake_snoozed_threads(&app)).await {
+            tracing::warn!("Snooze wake-up failed: {}", e);
+        }
+    }
+}
+
+/// Re-inbox every snoozed thread whose time has come, drop the snooze label,
+/// and forget the snoozes that were applied. Failed threads are retried on
+/// the next tick.
+async fn wake_snoozed_threads(app: &tauri::AppHandle) -> Result<(), String> {
+    let state = app.state::<AppState>();
+    let app_data_dir = get_app_data_dir(app)?;
+    let accounts = with_db(&state, |db| db.get_accounts().map_err(|e| e.to_string()))?;
+    let now = chrono::Utc::now().timestamp_millis();
+
+    for account in accounts {
+        let snoozed = with_db(&state, |db| db.get_snoozed_threads(&account.id).map_err(|e| e.to_string()))?;
+        let due = due_snoozes(&snoozed, now);
+        if due.is_empty() {
+            continue;
+        }
+
+        let gmail = match get_access_token(&state, &account.id, &app_data_dir).await {
+            Ok(token) => GmailClient::new(token),
+            Err(e) => {
+                tracing::warn!("Can't wake snoozed threads for {}: {}", account.email, e);
+                continue;
+            }
+        };
+
+        let snooze_label = gmail
+            .list_labels()
+            .await
+            .ok()
+            .and_then(|labels| labels.into_iter().find(|l| l.name.eq_ignore_ascii_case(SNOOZE_LABEL_NAME)));
+        let remove: Vec<String> = snooze_label.map(|l| l.id).into_iter().collect();
+
+        let outcomes = gmail.modify_threads_batch(&due, &["INBOX".to_string()], &remove).await;
+        let woke: Vec<String> = due
+            .into_iter()
+            .filter(|id| match outcomes.get(id) {
+                Some(outcome) if outcome.ok => true,
+                outcome => {
+                    let error = outcome.and_then(|o| o.error.as_deref()).unwrap_or("no response");
+                    tracing::warn!("Failed to wake snoozed thread {}: {}", id, error);
+                    false
+                }
+            })
+            .collect();
+        if woke.is_empty() {
+            continue;
+        }
+
+        with_db(&state, |db| {
+            db.delete_snoozed_threads(&account.id, &woke)
+                .map_err(|e| e.to_string())
+        })?;
+        let _ = app.emit(SNOOZE_WOKE_EVENT, &account.id);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gmail::{FullMessage, Header, MessagePayload};
+
+    fn message(id: &str, from: &str, date_ms: i64) -> FullMessage {
+        FullMessage {
+            id: id.to_string(),
+            thread_id: "t1".to_string(),
+            label_ids: None,
+            snippet: None,
+            internal_date: Some(date_ms.to_string()),
+            payload: Some(MessagePayload {
+                headers: Some(vec![Header {
+                    name: "From".to_string(),
+                    value: from.to_string(),
+                }]),
+                body: None,
+                parts: None,
+                mime_type: None,
+            }),
+        }
+    }
+
+    fn reminder(created_at: i64, remind_at: i64) -> FollowupReminder {
+        FollowupReminder {
+            account_id: "acc".to_string(),
+            thread_id: "t1".to_string(),
+            remind_at,
+            created_at,
+            recipient: "bob@example.com".to_string(),
+            subject: "Proposal".to_string(),
+        }
+    }
+
+    #[test]
+    fn reply_before_window_cancels_reminder() {
+        let day = 86_400_000;
+        let reminder = reminder(1_000, 1_000 + 3 * day);
+        let sent = message("m1", "Me <me@example.com>", 900);
+        let mut thread = FullThread {
+            id: "t1".to_string(),
+            history_id: None,
+            messages: vec![sent],
+        };
+
+        assert_eq!(check_followup(&reminder, &thread, "me@example.com", 1_000 + day), FollowupCheck::Waiting);
+
+        thread.messages.push(message("m2", "Bob <bob@example.com>", 1_000 + day));
+        assert_eq!(check_followup(&reminder, &thread, "me@example.com", 1_000 + 2 * day), FollowupCheck::Replied);
+    }
+
+    #[test]
+    fn unanswered_reminder_fires_after_window() {
+        let reminder = reminder(1_000, 5_000);
+        let thread = FullThread {
+            id: "t1".to_string(),
+            history_id: None,
+            // The user's own follow-up doesn't count as a reply
+            messages: vec![message("m1", "me@example.com", 900), message("m2", "ME@example.com", 2_000)],
+        };
+
+        assert_eq!(check_followup(&reminder, &thread, "me@example.com", 5_000), FollowupCheck::Due);
+    }
+
+    #[test]
+    fn only_snoozes_past_their_wake_time_are_due() {
+        let snoozed = vec![
+            ("t1".to_string(), 1_000),
+            ("t2".to_string(), 2_000),
+            ("t3".to_string(), 3_000),
+        ];
+        assert_eq!(due_snoozes(&snoozed, 2_000), vec!["t1".to_string(), "t2".to_string()]);
+        assert!(due_snoozes(&snoozed, 999).is_empty());
+    }
+
+    #[tokio::test]
+    async fn paused_poll_performs_no_fetches() {
+        let paused = AtomicBool::new(true);
+        let fetches = std::sync::atomic::AtomicUsize::new(0);
+        let poll = || async {
+            fetches.fetch_add(1, Ordering::SeqCst);
+        };
+
+        assert_eq!(run_unless_paused(&paused, poll).await, None);
+        assert_eq!(run_unless_paused(&paused, poll).await, None);
+        assert_eq!(fetches.load(Ordering::SeqCst), 0);
+
+        paused.store(false, Ordering::SeqCst);
+        assert_eq!(run_unless_paused(&paused, poll).await, Some(()));
+        assert_eq!(fetches.load(Ordering::SeqCst), 1);
+    }
+}
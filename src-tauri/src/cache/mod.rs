@@ -1,5 +1,7 @@
 // Local cache module
 
+pub mod prefetch;
 pub mod sqlite;
 
+pub use prefetch::PrefetchBuffer;
 pub use sqlite::CacheDb;
@@ -0,0 +1,96 @@
+// In-memory buffer of prefetched card pages
+//
+// Each card holds at most one page: the one that follows the card's current
+// `next_page_token`. "Load more" takes it out of the buffer instead of
+// hitting the network.
+
+use std::collections::HashMap;
+
+/// Pages older than this are refetched rather than served from the buffer
+const MAX_PREFETCH_AGE_SECS: i64 = 10 * 60;
+
+struct PrefetchedPage<T> {
+    page_token: String,
+    page: T,
+    fetched_at: i64,
+}
+
+pub struct PrefetchBuffer<T> {
+    pages: HashMap<String, PrefetchedPage<T>>,
+}
+
+impl<T> Default for PrefetchBuffer<T> {
+    fn default() -> Self {
+        Self { pages: HashMap::new() }
+    }
+}
+
+impl<T> PrefetchBuffer<T> {
+    /// Whether a fresh page for `page_token` is already buffered for the card
+    pub fn has(&self, card_id: &str, page_token: &str, now: i64) -> bool {
+        self.pages
+            .get(card_id)
+            .is_some_and(|p| p.page_token == page_token && now - p.fetched_at <= MAX_PREFETCH_AGE_SECS)
+    }
+
+    /// Buffer the page that `page_token` resolves to, replacing any older one
+    pub fn store(&mut self, card_id: &str, page_token: &str, page: T, now: i64) {
+        self.pages.insert(
+            card_id.to_string(),
+            PrefetchedPage {
+                page_token: page_token.to_string(),
+                page,
+                fetched_at: now,
+            },
+        );
+    }
+
+    /// Remove and return the buffered page if it matches `page_token` and is fresh.
+    /// A mismatched or stale entry is dropped, since the card has moved on.
+    pub fn take(&mut self, card_id: &str, page_token: &str, now: i64) -> Option<T> {
+        let entry = self.pages.remove(card_id)?;
+        if entry.page_token == page_token && now - entry.fetched_at <= MAX_PREFETCH_AGE_SECS {
+            Some(entry.page)
+        } else {
+            None
+        }
+    }
+
+    pub fn clear(&mut self, card_id: &str) {
+        self.pages.remove(card_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buffered_page_is_returned_once() {
+        let mut buffer = PrefetchBuffer::default();
+        buffer.store("card", "token-2", vec!["t21", "t22"], 1_000);
+
+        assert!(buffer.has("card", "token-2", 1_000));
+        assert_eq!(buffer.take("card", "token-2", 1_010), Some(vec!["t21", "t22"]));
+        // Taking consumes the page, so the next load goes to the network
+        assert_eq!(buffer.take("card", "token-2", 1_010), None);
+    }
+
+    #[test]
+    fn mismatched_token_is_discarded() {
+        let mut buffer = PrefetchBuffer::default();
+        buffer.store("card", "token-2", 2, 1_000);
+
+        assert_eq!(buffer.take("card", "token-3", 1_000), None);
+        assert!(!buffer.has("card", "token-2", 1_000));
+    }
+
+    #[test]
+    fn stale_pages_are_not_served() {
+        let mut buffer = PrefetchBuffer::default();
+        buffer.store("card", "token-2", 2, 1_000);
+
+        assert!(!buffer.has("card", "token-2", 1_000 + MAX_PREFETCH_AGE_SECS + 1));
+        assert_eq!(buffer.take("card", "token-2", 1_000 + MAX_PREFETCH_AGE_SECS + 1), None);
+    }
+}
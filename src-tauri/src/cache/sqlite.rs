@@ -1,11 +1,16 @@
 // SQLite cache for offline access
 
-use crate::models::{Account, Card, Thread};
+use crate::models::{Account, Card, FollowupReminder, Message, SentLogEntry, Thread};
+use crate::people::Contact;
+use std::collections::HashMap;
 use rusqlite::{params, Connection};
 use std::path::Path;
 use std::sync::{Arc, Mutex};
 use thiserror::Error;
 
+/// Sent messages kept per account in the local sent log
+pub const MAX_SENT_LOG: i64 = 200;
+
 #[derive(Error, Debug)]
 pub enum CacheError {
     #[error("Database error: {0}")]
@@ -100,6 +105,64 @@ impl CacheDb {
                 cached_at INTEGER NOT NULL,
                 PRIMARY KEY (card_id)
             );
+
+            -- Snoozed threads: wake time per thread, in Unix milliseconds
+            CREATE TABLE IF NOT EXISTS snoozed_threads (
+                account_id TEXT NOT NULL,
+                thread_id TEXT NOT NULL,
+                wake_at INTEGER NOT NULL,
+                snoozed_at INTEGER NOT NULL,
+                PRIMARY KEY (account_id, thread_id)
+            );
+
+            -- Follow-up reminders on sent threads, times in Unix milliseconds
+            CREATE TABLE IF NOT EXISTS followup_reminders (
+                account_id TEXT NOT NULL,
+                thread_id TEXT NOT NULL,
+                remind_at INTEGER NOT NULL,
+                created_at INTEGER NOT NULL,
+                recipient TEXT NOT NULL,
+                subject TEXT NOT NULL,
+                PRIMARY KEY (account_id, thread_id)
+            );
+
+            -- Messages sent from the app, newest kept per account (see MAX_SENT_LOG)
+            CREATE TABLE IF NOT EXISTS sent_log (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                account_id TEXT NOT NULL,
+                thread_id TEXT,
+                recipient TEXT NOT NULL,
+                subject TEXT NOT NULL,
+                sent_at INTEGER NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS idx_sent_log_account ON sent_log(account_id, sent_at);
+
+            -- Contacts for resolving participant names and photos, one row per email
+            CREATE TABLE IF NOT EXISTS contacts (
+                account_id TEXT NOT NULL,
+                email TEXT NOT NULL,
+                resource_name TEXT NOT NULL,
+                display_name TEXT,
+                photo_url TEXT,
+                cached_at INTEGER NOT NULL,
+                PRIMARY KEY (account_id, email)
+            );
+
+            -- Downloaded attachments, stored as decoded bytes
+            CREATE TABLE IF NOT EXISTS attachment_cache (
+                account_id TEXT NOT NULL,
+                message_id TEXT NOT NULL,
+                attachment_id TEXT NOT NULL,
+                data BLOB NOT NULL,
+                cached_at INTEGER NOT NULL,
+                PRIMARY KEY (account_id, message_id, attachment_id)
+            );
+
+            -- App-wide settings that must survive a restart
+            CREATE TABLE IF NOT EXISTS app_settings (
+                key TEXT PRIMARY KEY,
+                value TEXT NOT NULL
+            );
             "#,
         )?;
         Ok(())
@@ -116,6 +179,13 @@ impl CacheDb {
         let _ = conn.execute("ALTER TABLE cards ADD COLUMN card_type TEXT NOT NULL DEFAULT 'email'", []);
         // Add signature column to accounts
         let _ = conn.execute("ALTER TABLE accounts ADD COLUMN signature TEXT", []);
+        // Add nickname and color columns to accounts
+        let _ = conn.execute("ALTER TABLE accounts ADD COLUMN nickname TEXT", []);
+        let _ = conn.execute("ALTER TABLE accounts ADD COLUMN color TEXT", []);
+        // Add account, subject and labels to cached messages
+        let _ = conn.execute("ALTER TABLE messages ADD COLUMN account_id TEXT", []);
+        let _ = conn.execute("ALTER TABLE messages ADD COLUMN subject TEXT", []);
+        let _ = conn.execute("ALTER TABLE messages ADD COLUMN label_ids TEXT", []);
         Ok(())
     }
 
@@ -123,14 +193,16 @@ impl CacheDb {
 
     pub fn get_accounts(&self) -> Result<Vec<Account>, CacheError> {
         let conn = self.conn.lock().map_err(|_| CacheError::Lock)?;
-        let mut stmt = conn.prepare("SELECT id, email, picture, signature, refresh_token_ref FROM accounts ORDER BY email")?;
+        let mut stmt = conn.prepare("SELECT id, email, picture, signature, nickname, color, refresh_token_ref FROM accounts ORDER BY email")?;
         let rows = stmt.query_map([], |row| {
             Ok(Account {
                 id: row.get(0)?,
                 email: row.get(1)?,
                 picture: row.get(2)?,
                 signature: row.get(3)?,
-                refresh_token_ref: row.get(4)?,
+                nickname: row.get(4)?,
+                color: row.get(5)?,
+                refresh_token_ref: row.get(6)?,
             })
         })?;
         rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
@@ -139,7 +211,7 @@ impl CacheDb {
     pub fn get_account_by_email(&self, email: &str) -> Result<Option<Account>, CacheError> {
         let conn = self.conn.lock().map_err(|_| CacheError::Lock)?;
         let mut stmt = conn.prepare(
-            "SELECT id, email, picture, signature, refresh_token_ref FROM accounts WHERE email = ?1",
+            "SELECT id, email, picture, signature, nickname, color, refresh_token_ref FROM accounts WHERE email = ?1 COLLATE NOCASE",
         )?;
         let result = stmt.query_row(params![email], |row| {
             Ok(Account {
@@ -147,7 +219,9 @@ impl CacheDb {
                 email: row.get(1)?,
                 picture: row.get(2)?,
                 signature: row.get(3)?,
-                refresh_token_ref: row.get(4)?,
+                nickname: row.get(4)?,
+                color: row.get(5)?,
+                refresh_token_ref: row.get(6)?,
             })
         });
         match result {
@@ -160,8 +234,44 @@ impl CacheDb {
     pub fn insert_account(&self, account: &Account) -> Result<(), CacheError> {
         let conn = self.conn.lock().map_err(|_| CacheError::Lock)?;
         conn.execute(
-            "INSERT OR REPLACE INTO accounts (id, email, picture, signature, refresh_token_ref) VALUES (?1, ?2, ?3, ?4, ?5)",
-            params![account.id, account.email, account.picture, account.signature, account.refresh_token_ref],
+            "INSERT OR REPLACE INTO accounts (id, email, picture, signature, nickname, color, refresh_token_ref) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                account.id,
+                account.email,
+                account.picture,
+                account.signature,
+                account.nickname,
+                account.color,
+                account.refresh_token_ref
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Return the account for `email`, creating it on first sign-in. Re-auth of
+    /// a known email keeps its id (and so its cards), refreshing only the picture.
+    pub fn upsert_account_by_email(&self, email: &str, picture: Option<String>) -> Result<Account, CacheError> {
+        let account = match self.get_account_by_email(email)? {
+            Some(mut account) => {
+                account.picture = picture;
+                account
+            }
+            None => Account::new(email.to_string(), picture),
+        };
+        self.insert_account(&account)?;
+        Ok(account)
+    }
+
+    pub fn update_account_appearance(
+        &self,
+        account_id: &str,
+        nickname: Option<&str>,
+        color: Option<&str>,
+    ) -> Result<(), CacheError> {
+        let conn = self.conn.lock().map_err(|_| CacheError::Lock)?;
+        conn.execute(
+            "UPDATE accounts SET nickname = ?1, color = ?2 WHERE id = ?3",
+            params![nickname, color, account_id],
         )?;
         Ok(())
     }
@@ -189,12 +299,64 @@ impl CacheDb {
         )?;
         tx.execute("DELETE FROM cards WHERE account_id = ?1", params![id])?;
         tx.execute("DELETE FROM threads WHERE account_id = ?1", params![id])?;
+        tx.execute("DELETE FROM messages WHERE account_id = ?1", params![id])?;
         tx.execute("DELETE FROM sync_state WHERE account_id = ?1", params![id])?;
+        tx.execute("DELETE FROM snoozed_threads WHERE account_id = ?1", params![id])?;
+        tx.execute("DELETE FROM followup_reminders WHERE account_id = ?1", params![id])?;
+        tx.execute("DELETE FROM sent_log WHERE account_id = ?1", params![id])?;
+        tx.execute("DELETE FROM contacts WHERE account_id = ?1", params![id])?;
+        tx.execute("DELETE FROM attachment_cache WHERE account_id = ?1", params![id])?;
         tx.execute("DELETE FROM accounts WHERE id = ?1", params![id])?;
         tx.commit()?;
         Ok(())
     }
 
+    /// Fold every other account with the same email (case-insensitive) into
+    /// `new_account_id`: their cards and snoozes move over, their per-account
+    /// caches and rows are dropped. Returns the ids of the removed accounts.
+    pub fn merge_accounts_by_email(&self, email: &str, new_account_id: &str) -> Result<Vec<String>, CacheError> {
+        let mut conn = self.conn.lock().map_err(|_| CacheError::Lock)?;
+        let tx = conn.transaction()?;
+        let stale_ids = {
+            let mut stmt = tx.prepare(
+                "SELECT id FROM accounts WHERE email = ?1 COLLATE NOCASE AND id != ?2",
+            )?;
+            let rows = stmt.query_map(params![email, new_account_id], |row| row.get::<_, String>(0))?;
+            rows.collect::<Result<Vec<_>, _>>()?
+        };
+        for stale_id in &stale_ids {
+            tx.execute(
+                "UPDATE cards SET account_id = ?1 WHERE account_id = ?2",
+                params![new_account_id, stale_id],
+            )?;
+            tx.execute(
+                "UPDATE OR REPLACE snoozed_threads SET account_id = ?1 WHERE account_id = ?2",
+                params![new_account_id, stale_id],
+            )?;
+            tx.execute(
+                "UPDATE OR REPLACE followup_reminders SET account_id = ?1 WHERE account_id = ?2",
+                params![new_account_id, stale_id],
+            )?;
+            tx.execute(
+                "UPDATE sent_log SET account_id = ?1 WHERE account_id = ?2",
+                params![new_account_id, stale_id],
+            )?;
+            tx.execute("DELETE FROM threads WHERE account_id = ?1", params![stale_id])?;
+            tx.execute("DELETE FROM messages WHERE account_id = ?1", params![stale_id])?;
+            tx.execute("DELETE FROM contacts WHERE account_id = ?1", params![stale_id])?;
+            tx.execute("DELETE FROM attachment_cache WHERE account_id = ?1", params![stale_id])?;
+            tx.execute("DELETE FROM sync_state WHERE account_id = ?1", params![stale_id])?;
+            tx.execute("DELETE FROM accounts WHERE id = ?1", params![stale_id])?;
+        }
+        tx.commit()?;
+        drop(conn);
+
+        if !stale_ids.is_empty() {
+            self.normalize_card_positions(new_account_id)?;
+        }
+        Ok(stale_ids)
+    }
+
     // Card operations
 
     pub fn get_cards(&self, account_id: &str) -> Result<Vec<Card>, CacheError> {
@@ -248,13 +410,95 @@ impl CacheDb {
         Ok(())
     }
 
-    pub fn reorder_cards(&self, orders: &[(String, i32)]) -> Result<(), CacheError> {
+    /// Move a card to the end of another account's cards, dropping its cached
+    /// results (they were fetched with the old account). Returns false if the
+    /// card doesn't exist.
+    pub fn move_card(&self, card_id: &str, target_account_id: &str) -> Result<bool, CacheError> {
+        let source_account_id = {
+            let mut conn = self.conn.lock().map_err(|_| CacheError::Lock)?;
+            let tx = conn.transaction()?;
+            let source = tx.query_row(
+                "SELECT account_id FROM cards WHERE id = ?1",
+                params![card_id],
+                |row| row.get::<_, String>(0),
+            );
+            let source = match source {
+                Ok(source) => source,
+                Err(rusqlite::Error::QueryReturnedNoRows) => return Ok(false),
+                Err(e) => return Err(e.into()),
+            };
+            let position: i32 = tx.query_row(
+                "SELECT COUNT(*) FROM cards WHERE account_id = ?1 AND id != ?2",
+                params![target_account_id, card_id],
+                |row| row.get(0),
+            )?;
+            tx.execute(
+                "UPDATE cards SET account_id = ?1, position = ?2 WHERE id = ?3",
+                params![target_account_id, position, card_id],
+            )?;
+            tx.execute("DELETE FROM card_thread_cache WHERE card_id = ?1", params![card_id])?;
+            tx.execute("DELETE FROM card_calendar_cache WHERE card_id = ?1", params![card_id])?;
+            tx.commit()?;
+            source
+        };
+
+        // Close the gap the card left behind
+        self.normalize_card_positions(&source_account_id)?;
+        Ok(true)
+    }
+
+    /// Give an account's cards positions 0..n following `ordered_ids`. IDs
+    /// that no longer exist (or belong to another account) are skipped, and
+    /// cards missing from the list keep their relative order after the rest.
+    pub fn reorder_cards(&self, account_id: &str, ordered_ids: &[String]) -> Result<(), CacheError> {
         let mut conn = self.conn.lock().map_err(|_| CacheError::Lock)?;
         let tx = conn.transaction()?;
-        for (card_id, position) in orders {
+        let existing = {
+            let mut stmt = tx.prepare(
+                "SELECT id FROM cards WHERE account_id = ?1 ORDER BY position, name",
+            )?;
+            let rows = stmt.query_map(params![account_id], |row| row.get::<_, String>(0))?;
+            rows.collect::<Result<Vec<_>, _>>()?
+        };
+
+        let mut order: Vec<&String> = Vec::with_capacity(existing.len());
+        for id in ordered_ids {
+            if existing.contains(id) && !order.contains(&id) {
+                order.push(id);
+            }
+        }
+        for id in &existing {
+            if !order.contains(&id) {
+                order.push(id);
+            }
+        }
+
+        for (position, card_id) in order.iter().enumerate() {
+            tx.execute(
+                "UPDATE cards SET position = ?1 WHERE id = ?2",
+                params![position as i32, card_id],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Re-pack an account's card positions into a contiguous 0..n sequence,
+    /// ordered by current position then name, so merged cards never collide
+    pub fn normalize_card_positions(&self, account_id: &str) -> Result<(), CacheError> {
+        let mut conn = self.conn.lock().map_err(|_| CacheError::Lock)?;
+        let tx = conn.transaction()?;
+        let card_ids = {
+            let mut stmt = tx.prepare(
+                "SELECT id FROM cards WHERE account_id = ?1 ORDER BY position, name",
+            )?;
+            let rows = stmt.query_map(params![account_id], |row| row.get::<_, String>(0))?;
+            rows.collect::<Result<Vec<_>, _>>()?
+        };
+        for (position, card_id) in card_ids.iter().enumerate() {
             tx.execute(
                 "UPDATE cards SET position = ?1 WHERE id = ?2",
-                params![position, card_id],
+                params![position as i32, card_id],
             )?;
         }
         tx.commit()?;
@@ -263,47 +507,185 @@ impl CacheDb {
 
     // Thread cache operations
 
+    /// Cache a page of threads and their messages in one transaction: either
+    /// all of them are written or, on any failure, none are
     pub fn cache_threads(&self, threads: &[Thread]) -> Result<(), CacheError> {
         let mut conn = self.conn.lock().map_err(|_| CacheError::Lock)?;
         let now = chrono::Utc::now().timestamp();
 
         let tx = conn.transaction()?;
-        for thread in threads {
-            let date = thread.last_message_date.timestamp();
-            let labels = serde_json::to_string(&thread.labels).unwrap_or_default();
-            let participants = serde_json::to_string(&thread.participants).unwrap_or_default();
+        insert_threads(&tx, threads, now)?;
+        tx.commit()?;
+
+        Ok(())
+    }
+
+    /// Set a thread's unread count in the thread cache and any cached card pages
+    pub fn set_thread_unread_count(&self, account_id: &str, thread_id: &str, unread_count: i32) -> Result<(), CacheError> {
+        let mut conn = self.conn.lock().map_err(|_| CacheError::Lock)?;
+        let tx = conn.transaction()?;
+        tx.execute(
+            "UPDATE threads SET unread_count = ?1 WHERE account_id = ?2 AND gmail_thread_id = ?3",
+            params![unread_count, account_id, thread_id],
+        )?;
 
+        rewrite_card_pages(&tx, account_id, |_, groups| {
+            let mut changed = false;
+            for thread in groups.iter_mut().flat_map(|g| g.threads.iter_mut()) {
+                if thread.gmail_thread_id == thread_id && thread.unread_count != unread_count {
+                    thread.unread_count = unread_count;
+                    changed = true;
+                }
+            }
+            changed
+        })?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Apply an incremental sync: upsert changed threads, drop deleted threads
+    /// and messages, and bring the account's cached card pages in line
+    pub fn apply_history_delta(
+        &self,
+        account_id: &str,
+        modified: &[Thread],
+        deleted_thread_ids: &[String],
+        deleted_message_ids: &[String],
+    ) -> Result<(), CacheError> {
+        let mut conn = self.conn.lock().map_err(|_| CacheError::Lock)?;
+        let now = chrono::Utc::now().timestamp();
+
+        let tx = conn.transaction()?;
+        insert_threads(&tx, modified, now)?;
+        for thread_id in deleted_thread_ids {
+            tx.execute(
+                "DELETE FROM threads WHERE account_id = ?1 AND gmail_thread_id = ?2",
+                params![account_id, thread_id],
+            )?;
+            tx.execute(
+                "DELETE FROM messages WHERE account_id = ?1 AND thread_id = ?2",
+                params![account_id, thread_id],
+            )?;
+        }
+        for message_id in deleted_message_ids {
             tx.execute(
-                r#"INSERT OR REPLACE INTO threads
-                   (gmail_thread_id, account_id, subject, snippet, last_message_date, unread_count, labels, participants, cached_at)
-                   VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)"#,
-                params![
-                    thread.gmail_thread_id,
-                    thread.account_id,
-                    thread.subject,
-                    thread.snippet,
-                    date,
-                    thread.unread_count,
-                    labels,
-                    participants,
-                    now
-                ],
+                "DELETE FROM messages WHERE account_id = ?1 AND gmail_msg_id = ?2",
+                params![account_id, message_id],
             )?;
         }
+
+        // Card pages: swap in updated threads, drop deleted ones and those that
+        // lost a label the card's query needs (e.g. archived out of `in:inbox`).
+        // Threads that newly match a card's query show up on its next refresh.
+        rewrite_card_pages(&tx, account_id, |query, groups| {
+            let required = crate::gmail::query_required_labels(query);
+            for group in groups.iter_mut() {
+                group.threads.retain(|t| !deleted_thread_ids.contains(&t.gmail_thread_id));
+                for thread in &mut group.threads {
+                    if let Some(updated) = modified.iter().find(|m| m.gmail_thread_id == thread.gmail_thread_id) {
+                        *thread = updated.clone();
+                    }
+                }
+                group
+                    .threads
+                    .retain(|t| required.iter().all(|label| t.labels.iter().any(|l| l == label)));
+            }
+            groups.retain(|g| !g.threads.is_empty());
+            true
+        })?;
         tx.commit()?;
 
         Ok(())
     }
 
+    /// Cache decoded message bodies so threads can be opened offline
+    pub fn cache_messages(&self, account_id: &str, messages: &[Message]) -> Result<(), CacheError> {
+        let mut conn = self.conn.lock().map_err(|_| CacheError::Lock)?;
+        let now = chrono::Utc::now().timestamp();
+        let tx = conn.transaction()?;
+        insert_messages(&tx, account_id, messages, now)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Cached messages of a thread, oldest first
+    pub fn get_cached_messages(&self, account_id: &str, thread_id: &str) -> Result<Vec<Message>, CacheError> {
+        let conn = self.conn.lock().map_err(|_| CacheError::Lock)?;
+        let mut stmt = conn.prepare(
+            "SELECT gmail_msg_id, thread_id, from_addr, to_addrs, date, subject, label_ids, body_text, body_html
+             FROM messages WHERE account_id = ?1 AND thread_id = ?2 ORDER BY date ASC",
+        )?;
+        let rows = stmt.query_map(params![account_id, thread_id], |row| {
+            let to_addrs: Option<String> = row.get(3)?;
+            let date: i64 = row.get(4)?;
+            let label_ids: Option<String> = row.get(6)?;
+            Ok(Message {
+                gmail_msg_id: row.get(0)?,
+                thread_id: row.get(1)?,
+                from_addr: row.get::<_, Option<String>>(2)?.unwrap_or_default(),
+                to_addrs: to_addrs.and_then(|t| serde_json::from_str(&t).ok()).unwrap_or_default(),
+                date: chrono::DateTime::from_timestamp_millis(date).unwrap_or_default(),
+                subject: row.get::<_, Option<String>>(5)?.unwrap_or_default(),
+                label_ids: label_ids.and_then(|l| serde_json::from_str(&l).ok()).unwrap_or_default(),
+                body_text: row.get(7)?,
+                body_html: row.get(8)?,
+            })
+        })?;
+        rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+    }
+
+    // Attachment cache operations
+
+    pub fn save_attachment(
+        &self,
+        account_id: &str,
+        message_id: &str,
+        attachment_id: &str,
+        data: &[u8],
+    ) -> Result<(), CacheError> {
+        let conn = self.conn.lock().map_err(|_| CacheError::Lock)?;
+        let now = chrono::Utc::now().timestamp();
+        conn.execute(
+            "INSERT OR REPLACE INTO attachment_cache (account_id, message_id, attachment_id, data, cached_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![account_id, message_id, attachment_id, data, now],
+        )?;
+        Ok(())
+    }
+
+    pub fn get_attachment(&self, account_id: &str, message_id: &str, attachment_id: &str) -> Result<Option<Vec<u8>>, CacheError> {
+        let conn = self.conn.lock().map_err(|_| CacheError::Lock)?;
+        let result = conn.query_row(
+            "SELECT data FROM attachment_cache WHERE account_id = ?1 AND message_id = ?2 AND attachment_id = ?3",
+            params![account_id, message_id, attachment_id],
+            |row| row.get::<_, Vec<u8>>(0),
+        );
+        match result {
+            Ok(data) => Ok(Some(data)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn clear_old_attachments(&self, max_age_hours: i64) -> Result<usize, CacheError> {
+        let conn = self.conn.lock().map_err(|_| CacheError::Lock)?;
+        let cutoff = chrono::Utc::now().timestamp() - (max_age_hours * 3600);
+        let count = conn.execute("DELETE FROM attachment_cache WHERE cached_at < ?1", params![cutoff])?;
+        Ok(count)
+    }
+
     pub fn clear_old_cache(&self, max_age_hours: i64) -> Result<usize, CacheError> {
         let conn = self.conn.lock().map_err(|_| CacheError::Lock)?;
         let cutoff = chrono::Utc::now().timestamp() - (max_age_hours * 3600);
-        // Keep starred and important threads even if old
+        // Keep starred and important threads and messages even if old
         let count = conn.execute(
             "DELETE FROM threads WHERE cached_at < ?1 AND labels NOT LIKE '%STARRED%' AND labels NOT LIKE '%IMPORTANT%'",
             params![cutoff],
         )?;
-        Ok(count)
+        let message_count = conn.execute(
+            "DELETE FROM messages WHERE cached_at < ?1 AND COALESCE(label_ids, '') NOT LIKE '%STARRED%' AND COALESCE(label_ids, '') NOT LIKE '%IMPORTANT%'",
+            params![cutoff],
+        )?;
+        Ok(count + message_count)
     }
 
     /// Clear stale card caches (older than max_age_hours)
@@ -439,6 +821,135 @@ impl CacheDb {
         }
     }
 
+    // Snooze operations
+
+    pub fn snooze_threads(
+        &self,
+        account_id: &str,
+        thread_ids: &[String],
+        wake_at: i64,
+    ) -> Result<(), CacheError> {
+        let mut conn = self.conn.lock().map_err(|_| CacheError::Lock)?;
+        let now = chrono::Utc::now().timestamp_millis();
+        let tx = conn.transaction()?;
+        for thread_id in thread_ids {
+            tx.execute(
+                "INSERT OR REPLACE INTO snoozed_threads (account_id, thread_id, wake_at, snoozed_at) VALUES (?1, ?2, ?3, ?4)",
+                params![account_id, thread_id, wake_at, now],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Forget snoozes for threads that have been woken
+    pub fn delete_snoozed_threads(&self, account_id: &str, thread_ids: &[String]) -> Result<(), CacheError> {
+        let mut conn = self.conn.lock().map_err(|_| CacheError::Lock)?;
+        let tx = conn.transaction()?;
+        for thread_id in thread_ids {
+            tx.execute(
+                "DELETE FROM snoozed_threads WHERE account_id = ?1 AND thread_id = ?2",
+                params![account_id, thread_id],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Snoozed threads for an account as (thread_id, wake_at) pairs, soonest first
+    pub fn get_snoozed_threads(&self, account_id: &str) -> Result<Vec<(String, i64)>, CacheError> {
+        let conn = self.conn.lock().map_err(|_| CacheError::Lock)?;
+        let mut stmt = conn.prepare(
+            "SELECT thread_id, wake_at FROM snoozed_threads WHERE account_id = ?1 ORDER BY wake_at, thread_id",
+        )?;
+        let rows = stmt.query_map(params![account_id], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+    }
+
+    // Follow-up reminder operations
+
+    pub fn set_followup(&self, reminder: &FollowupReminder) -> Result<(), CacheError> {
+        let conn = self.conn.lock().map_err(|_| CacheError::Lock)?;
+        conn.execute(
+            "INSERT OR REPLACE INTO followup_reminders (account_id, thread_id, remind_at, created_at, recipient, subject) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                reminder.account_id,
+                reminder.thread_id,
+                reminder.remind_at,
+                reminder.created_at,
+                reminder.recipient,
+                reminder.subject,
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Follow-up reminders, soonest first; all accounts when `account_id` is None
+    pub fn get_followups(&self, account_id: Option<&str>) -> Result<Vec<FollowupReminder>, CacheError> {
+        let conn = self.conn.lock().map_err(|_| CacheError::Lock)?;
+        let mut stmt = conn.prepare(
+            "SELECT account_id, thread_id, remind_at, created_at, recipient, subject FROM followup_reminders WHERE ?1 IS NULL OR account_id = ?1 ORDER BY remind_at, thread_id",
+        )?;
+        let rows = stmt.query_map(params![account_id], |row| {
+            Ok(FollowupReminder {
+                account_id: row.get(0)?,
+                thread_id: row.get(1)?,
+                remind_at: row.get(2)?,
+                created_at: row.get(3)?,
+                recipient: row.get(4)?,
+                subject: row.get(5)?,
+            })
+        })?;
+        rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+    }
+
+    /// Returns false if no reminder was set for the thread
+    pub fn delete_followup(&self, account_id: &str, thread_id: &str) -> Result<bool, CacheError> {
+        let conn = self.conn.lock().map_err(|_| CacheError::Lock)?;
+        let deleted = conn.execute(
+            "DELETE FROM followup_reminders WHERE account_id = ?1 AND thread_id = ?2",
+            params![account_id, thread_id],
+        )?;
+        Ok(deleted > 0)
+    }
+
+    // Sent log operations
+
+    /// Add a send to the sent log. Older entries past `MAX_SENT_LOG` for the
+    /// account are dropped.
+    pub fn log_sent(&self, entry: &SentLogEntry) -> Result<(), CacheError> {
+        let conn = self.conn.lock().map_err(|_| CacheError::Lock)?;
+        conn.execute(
+            "INSERT INTO sent_log (account_id, thread_id, recipient, subject, sent_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![entry.account_id, entry.thread_id, entry.recipient, entry.subject, entry.sent_at],
+        )?;
+        conn.execute(
+            "DELETE FROM sent_log WHERE account_id = ?1 AND id NOT IN (
+                SELECT id FROM sent_log WHERE account_id = ?1 ORDER BY sent_at DESC, id DESC LIMIT ?2
+            )",
+            params![entry.account_id, MAX_SENT_LOG],
+        )?;
+        Ok(())
+    }
+
+    /// Most recent sends, newest first; all accounts when `account_id` is None
+    pub fn recent_sent(&self, account_id: Option<&str>, limit: usize) -> Result<Vec<SentLogEntry>, CacheError> {
+        let conn = self.conn.lock().map_err(|_| CacheError::Lock)?;
+        let mut stmt = conn.prepare(
+            "SELECT account_id, thread_id, recipient, subject, sent_at FROM sent_log WHERE ?1 IS NULL OR account_id = ?1 ORDER BY sent_at DESC, id DESC LIMIT ?2",
+        )?;
+        let rows = stmt.query_map(params![account_id, limit as i64], |row| {
+            Ok(SentLogEntry {
+                account_id: row.get(0)?,
+                thread_id: row.get(1)?,
+                recipient: row.get(2)?,
+                subject: row.get(3)?,
+                sent_at: row.get(4)?,
+            })
+        })?;
+        rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+    }
+
     // Sync state operations (for incremental sync via History API)
 
     pub fn get_history_id(&self, account_id: &str) -> Result<Option<String>, CacheError> {
@@ -468,4 +979,498 @@ impl CacheDb {
         conn.execute("DELETE FROM sync_state WHERE account_id = ?1", params![account_id])?;
         Ok(())
     }
+
+    // App settings
+
+    pub fn get_setting(&self, key: &str) -> Result<Option<String>, CacheError> {
+        let conn = self.conn.lock().map_err(|_| CacheError::Lock)?;
+        let result = conn.query_row("SELECT value FROM app_settings WHERE key = ?1", params![key], |row| row.get(0));
+        match result {
+            Ok(value) => Ok(Some(value)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn set_setting(&self, key: &str, value: &str) -> Result<(), CacheError> {
+        let conn = self.conn.lock().map_err(|_| CacheError::Lock)?;
+        conn.execute(
+            "INSERT OR REPLACE INTO app_settings (key, value) VALUES (?1, ?2)",
+            params![key, value],
+        )?;
+        Ok(())
+    }
+
+    // Contact cache operations
+
+    /// Replace an account's cached contacts
+    pub fn save_contacts(&self, account_id: &str, contacts: &[Contact]) -> Result<(), CacheError> {
+        let mut conn = self.conn.lock().map_err(|_| CacheError::Lock)?;
+        let now = chrono::Utc::now().timestamp();
+        let tx = conn.transaction()?;
+        tx.execute("DELETE FROM contacts WHERE account_id = ?1", params![account_id])?;
+        for contact in contacts {
+            for email in &contact.email_addresses {
+                tx.execute(
+                    "INSERT OR REPLACE INTO contacts (account_id, email, resource_name, display_name, photo_url, cached_at)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                    params![
+                        account_id,
+                        email.to_lowercase(),
+                        contact.resource_name,
+                        contact.display_name,
+                        contact.photo_url,
+                        now
+                    ],
+                )?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Cached contacts for a batch of emails, keyed by lowercased email
+    pub fn get_contacts_by_email(
+        &self,
+        account_id: &str,
+        emails: &[String],
+    ) -> Result<HashMap<String, Contact>, CacheError> {
+        if emails.is_empty() {
+            return Ok(HashMap::new());
+        }
+        let conn = self.conn.lock().map_err(|_| CacheError::Lock)?;
+        let placeholders = vec!["?"; emails.len()].join(", ");
+        let mut stmt = conn.prepare(&format!(
+            "SELECT email, resource_name, display_name, photo_url FROM contacts
+             WHERE account_id = ? AND email IN ({})",
+            placeholders
+        ))?;
+        let lowered: Vec<String> = emails.iter().map(|e| e.to_lowercase()).collect();
+        let values = std::iter::once(account_id).chain(lowered.iter().map(String::as_str));
+        let rows = stmt.query_map(rusqlite::params_from_iter(values), |row| {
+            let email: String = row.get(0)?;
+            Ok((
+                email.clone(),
+                Contact {
+                    resource_name: row.get(1)?,
+                    display_name: row.get(2)?,
+                    email_addresses: vec![email],
+                    photo_url: row.get(3)?,
+                },
+            ))
+        })?;
+        rows.collect::<Result<HashMap<_, _>, _>>().map_err(Into::into)
+    }
+}
+
+/// Run `edit` with each card's query over the cached pages of every card in
+/// the account, writing back the pages it reports as changed
+fn rewrite_card_pages(
+    tx: &rusqlite::Transaction,
+    account_id: &str,
+    mut edit: impl FnMut(&str, &mut Vec<crate::models::ThreadGroup>) -> bool,
+) -> Result<(), CacheError> {
+    let cached: Vec<(String, String, String)> = {
+        let mut stmt = tx.prepare(
+            "SELECT card_thread_cache.card_id, card_thread_cache.thread_data, cards.query
+             FROM card_thread_cache JOIN cards ON cards.id = card_thread_cache.card_id
+             WHERE cards.account_id = ?1",
+        )?;
+        let rows = stmt.query_map(params![account_id], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?;
+        rows.collect::<Result<Vec<_>, _>>()?
+    };
+    for (card_id, thread_data, query) in cached {
+        let mut groups: Vec<crate::models::ThreadGroup> = serde_json::from_str(&thread_data).unwrap_or_default();
+        if edit(&query, &mut groups) {
+            tx.execute(
+                "UPDATE card_thread_cache SET thread_data = ?1 WHERE card_id = ?2",
+                params![serde_json::to_string(&groups).unwrap_or_default(), card_id],
+            )?;
+        }
+    }
+    Ok(())
+}
+
+fn insert_threads(tx: &rusqlite::Transaction, threads: &[Thread], now: i64) -> Result<(), CacheError> {
+    for thread in threads {
+        let date = thread.last_message_date.timestamp();
+        let labels = serde_json::to_string(&thread.labels).unwrap_or_default();
+        let participants = serde_json::to_string(&thread.participants).unwrap_or_default();
+
+        tx.execute(
+            r#"INSERT OR REPLACE INTO threads
+               (gmail_thread_id, account_id, subject, snippet, last_message_date, unread_count, labels, participants, cached_at)
+               VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)"#,
+            params![
+                thread.gmail_thread_id,
+                thread.account_id,
+                thread.subject,
+                thread.snippet,
+                date,
+                thread.unread_count,
+                labels,
+                participants,
+                now
+            ],
+        )?;
+    }
+    Ok(())
+}
+
+fn insert_messages(
+    tx: &rusqlite::Transaction,
+    account_id: &str,
+    messages: &[Message],
+    now: i64,
+) -> Result<(), CacheError> {
+    for message in messages {
+        tx.execute(
+            r#"INSERT OR REPLACE INTO messages
+               (gmail_msg_id, thread_id, account_id, from_addr, to_addrs, date, subject, label_ids, body_text, body_html, cached_at)
+               VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)"#,
+            params![
+                message.gmail_msg_id,
+                message.thread_id,
+                account_id,
+                message.from_addr,
+                serde_json::to_string(&message.to_addrs).unwrap_or_default(),
+                message.date.timestamp_millis(),
+                message.subject,
+                serde_json::to_string(&message.label_ids).unwrap_or_default(),
+                message.body_text,
+                message.body_html,
+                now
+            ],
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_db() -> CacheDb {
+        CacheDb::new(Path::new(":memory:")).unwrap()
+    }
+
+    fn card(id: &str, account_id: &str, name: &str, position: i32) -> Card {
+        Card {
+            id: id.to_string(),
+            account_id: account_id.to_string(),
+            name: name.to_string(),
+            query: "in:inbox".to_string(),
+            position,
+            collapsed: false,
+            color: None,
+            group_by: "date".to_string(),
+            card_type: "email".to_string(),
+        }
+    }
+
+    fn thread(id: &str, subject: &str) -> Thread {
+        Thread {
+            gmail_thread_id: id.to_string(),
+            account_id: "a1".to_string(),
+            subject: subject.to_string(),
+            snippet: String::new(),
+            last_message_date: chrono::Utc::now(),
+            unread_count: 1,
+            labels: vec!["INBOX".to_string()],
+            participants: Vec::new(),
+            has_attachment: false,
+            attachments: Vec::new(),
+            calendar_event: None,
+            category: "Primary".to_string(),
+        }
+    }
+
+    #[test]
+    fn re_auth_of_same_email_keeps_account_id() {
+        let db = test_db();
+        let first = db.upsert_account_by_email("me@example.com", None).unwrap();
+        let second = db
+            .upsert_account_by_email("me@example.com", Some("https://example.com/me.png".to_string()))
+            .unwrap();
+
+        assert_eq!(first.id, second.id);
+        let accounts = db.get_accounts().unwrap();
+        assert_eq!(accounts.len(), 1);
+        assert_eq!(accounts[0].picture.as_deref(), Some("https://example.com/me.png"));
+    }
+
+    #[test]
+    fn re_adding_an_email_moves_cards_to_the_new_account() {
+        let db = test_db();
+        let old = Account::new("me@example.com".to_string(), None);
+        let new = Account::new("Me@Example.com".to_string(), None);
+        db.insert_account(&old).unwrap();
+        db.insert_card(&card("c1", &old.id, "Inbox", 0)).unwrap();
+        db.insert_card(&card("c2", &old.id, "Receipts", 1)).unwrap();
+        db.insert_account(&new).unwrap();
+        db.insert_card(&card("c3", &new.id, "Fresh", 0)).unwrap();
+
+        let removed = db.merge_accounts_by_email(&new.email, &new.id).unwrap();
+        assert_eq!(removed, vec![old.id.clone()]);
+
+        let ids: Vec<String> = db.get_cards(&new.id).unwrap().into_iter().map(|c| c.id).collect();
+        assert_eq!(ids.len(), 3);
+        assert!(ids.contains(&"c1".to_string()) && ids.contains(&"c2".to_string()));
+        assert!(db.get_cards(&old.id).unwrap().is_empty());
+
+        let accounts = db.get_accounts().unwrap();
+        assert_eq!(accounts.len(), 1);
+        assert_eq!(accounts[0].id, new.id);
+    }
+
+    #[test]
+    fn normalize_card_positions_packs_collisions_and_gaps() {
+        let db = test_db();
+        db.insert_card(&card("c1", "acc", "Zeta", 0)).unwrap();
+        db.insert_card(&card("c2", "acc", "Alpha", 0)).unwrap();
+        db.insert_card(&card("c3", "acc", "Beta", 5)).unwrap();
+        db.insert_card(&card("c4", "acc", "Gamma", 2)).unwrap();
+        db.insert_card(&card("other", "acc2", "Other", 7)).unwrap();
+
+        db.normalize_card_positions("acc").unwrap();
+
+        let cards = db.get_cards("acc").unwrap();
+        let order: Vec<(&str, i32)> = cards.iter().map(|c| (c.id.as_str(), c.position)).collect();
+        assert_eq!(order, vec![("c2", 0), ("c1", 1), ("c4", 2), ("c3", 3)]);
+
+        // Other accounts are untouched
+        assert_eq!(db.get_cards("acc2").unwrap()[0].position, 7);
+    }
+
+    #[test]
+    fn reorder_cards_keeps_positions_contiguous() {
+        let db = test_db();
+        db.insert_card(&card("c1", "acc", "One", 0)).unwrap();
+        db.insert_card(&card("c2", "acc", "Two", 1)).unwrap();
+        db.insert_card(&card("c3", "acc", "Three", 2)).unwrap();
+        db.insert_card(&card("other", "acc2", "Other", 0)).unwrap();
+
+        // A deleted card and another account's card in the list are skipped;
+        // c2 was left out and goes last
+        let ids: Vec<String> = ["c3", "gone", "other", "c1"].iter().map(|s| s.to_string()).collect();
+        db.reorder_cards("acc", &ids).unwrap();
+
+        let cards = db.get_cards("acc").unwrap();
+        let order: Vec<(&str, i32)> = cards.iter().map(|c| (c.id.as_str(), c.position)).collect();
+        assert_eq!(order, vec![("c3", 0), ("c1", 1), ("c2", 2)]);
+        assert_eq!(db.get_cards("acc2").unwrap()[0].position, 0);
+    }
+
+    #[test]
+    fn snooze_threads_records_wake_time_for_every_thread() {
+        let db = test_db();
+        let card_threads = vec!["t1".to_string(), "t2".to_string(), "t3".to_string()];
+        let wake_at = 1_700_000_000_000;
+
+        db.snooze_threads("acc", &card_threads, wake_at).unwrap();
+
+        let snoozed = db.get_snoozed_threads("acc").unwrap();
+        assert_eq!(
+            snoozed,
+            vec![
+                ("t1".to_string(), wake_at),
+                ("t2".to_string(), wake_at),
+                ("t3".to_string(), wake_at),
+            ]
+        );
+        assert!(db.get_snoozed_threads("other").unwrap().is_empty());
+
+        // Re-snoozing moves the wake time instead of duplicating rows
+        db.snooze_threads("acc", &card_threads[..1], wake_at + 1).unwrap();
+        let snoozed = db.get_snoozed_threads("acc").unwrap();
+        assert_eq!(snoozed.len(), 3);
+        assert_eq!(snoozed[2], ("t1".to_string(), wake_at + 1));
+
+        // Woken threads are forgotten
+        db.delete_snoozed_threads("acc", &card_threads[..2]).unwrap();
+        assert_eq!(db.get_snoozed_threads("acc").unwrap(), vec![("t3".to_string(), wake_at)]);
+    }
+
+    #[test]
+    fn cached_messages_round_trip_per_account() {
+        let db = test_db();
+        let message = |id: &str, date_ms: i64| Message {
+            gmail_msg_id: id.to_string(),
+            thread_id: "t1".to_string(),
+            from_addr: "Ann <ann@x.com>".to_string(),
+            to_addrs: vec!["me@x.com".to_string()],
+            date: chrono::DateTime::from_timestamp_millis(date_ms).unwrap(),
+            subject: "Lunch".to_string(),
+            label_ids: vec!["INBOX".to_string()],
+            body_text: Some("See you".to_string()),
+            body_html: None,
+        };
+        db.cache_messages("a1", &[message("m2", 2_000), message("m1", 1_000)]).unwrap();
+
+        let cached = db.get_cached_messages("a1", "t1").unwrap();
+        let ids: Vec<_> = cached.iter().map(|m| m.gmail_msg_id.as_str()).collect();
+        assert_eq!(ids, vec!["m1", "m2"]);
+        assert_eq!(cached[0].to_addrs, vec!["me@x.com"]);
+        assert_eq!(cached[0].body_text.as_deref(), Some("See you"));
+        assert!(db.get_cached_messages("a2", "t1").unwrap().is_empty());
+    }
+
+    #[test]
+    fn old_cached_messages_are_pruned_unless_starred_or_important() {
+        let db = test_db();
+        let message = |id: &str, label: &str| Message {
+            gmail_msg_id: id.to_string(),
+            thread_id: "t1".to_string(),
+            from_addr: "Ann <ann@x.com>".to_string(),
+            to_addrs: Vec::new(),
+            date: chrono::Utc::now(),
+            subject: "Lunch".to_string(),
+            label_ids: vec![label.to_string()],
+            body_text: Some("See you".to_string()),
+            body_html: None,
+        };
+        db.cache_messages("a1", &[message("m1", "INBOX"), message("m2", "STARRED"), message("m3", "IMPORTANT")])
+            .unwrap();
+        db.cache_messages("a1", &[Message { thread_id: "t2".to_string(), ..message("m4", "INBOX") }])
+            .unwrap();
+
+        // Age everything but m4 past the cutoff
+        let old = chrono::Utc::now().timestamp() - 48 * 3600;
+        db.conn
+            .lock()
+            .unwrap()
+            .execute("UPDATE messages SET cached_at = ?1 WHERE gmail_msg_id != 'm4'", params![old])
+            .unwrap();
+
+        assert_eq!(db.clear_old_cache(24).unwrap(), 1);
+        let mut kept: Vec<_> = db.get_cached_messages("a1", "t1").unwrap().into_iter().map(|m| m.gmail_msg_id).collect();
+        kept.sort();
+        assert_eq!(kept, vec!["m2", "m3"]);
+        assert_eq!(db.get_cached_messages("a1", "t2").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn account_nickname_and_color_round_trip() {
+        let db = test_db();
+        let account = db.upsert_account_by_email("me@example.com", None).unwrap();
+        db.update_account_appearance(&account.id, Some("Work"), Some("#3366ff")).unwrap();
+
+        let stored = db.get_account_by_email("me@example.com").unwrap().unwrap();
+        assert_eq!(stored.nickname.as_deref(), Some("Work"));
+        assert_eq!(stored.color.as_deref(), Some("#3366ff"));
+
+        // Re-auth keeps the appearance
+        db.upsert_account_by_email("me@example.com", Some("https://example.com/me.png")).unwrap();
+        let accounts = db.get_accounts().unwrap();
+        assert_eq!(accounts[0].nickname.as_deref(), Some("Work"));
+        assert_eq!(accounts[0].color.as_deref(), Some("#3366ff"));
+
+        db.update_account_appearance(&account.id, None, None).unwrap();
+        let cleared = db.get_accounts().unwrap();
+        assert_eq!(cleared[0].nickname, None);
+        assert_eq!(cleared[0].color, None);
+    }
+
+    #[test]
+    fn moving_a_card_changes_account_and_clears_its_cache() {
+        let db = test_db();
+        for c in [card("c1", "a1", "Inbox", 0), card("c2", "a1", "Work", 1), card("c3", "a2", "Team", 0)] {
+            db.insert_card(&c).unwrap();
+        }
+        db.save_card_threads("c1", &[], Some("page-2")).unwrap();
+
+        assert!(db.move_card("c1", "a2").unwrap());
+
+        let target = db.get_cards("a2").unwrap();
+        let moved = target.iter().find(|c| c.id == "c1").unwrap();
+        assert_eq!(moved.account_id, "a2");
+        assert_eq!(moved.position, 1);
+        assert!(db.get_card_threads("c1").unwrap().is_none());
+
+        let source = db.get_cards("a1").unwrap();
+        assert_eq!(source.len(), 1);
+        assert_eq!(source[0].position, 0);
+
+        assert!(!db.move_card("missing", "a2").unwrap());
+    }
+
+    #[test]
+    fn history_delta_updates_and_prunes_cached_card_pages() {
+        let db = test_db();
+        db.insert_card(&card("c1", "a1", "Inbox", 0)).unwrap();
+        let group = crate::models::ThreadGroup {
+            label: "Today".to_string(),
+            threads: vec![thread("t1", "Old subject"), thread("t2", "Gone"), thread("t3", "Archived")],
+        };
+        db.cache_threads(&group.threads).unwrap();
+        db.save_card_threads("c1", &[group], None).unwrap();
+
+        let mut updated = thread("t1", "New subject");
+        updated.unread_count = 0;
+        // labelRemoved: t3 left the inbox, so it no longer matches `in:inbox`
+        let mut archived = thread("t3", "Archived");
+        archived.labels.clear();
+        db.apply_history_delta("a1", &[updated, archived], &["t2".to_string()], &[]).unwrap();
+
+        let (groups, _, _) = db.get_card_threads("c1").unwrap().unwrap();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].threads.len(), 1);
+        assert_eq!(groups[0].threads[0].subject, "New subject");
+        assert_eq!(groups[0].threads[0].unread_count, 0);
+    }
+
+    #[test]
+    fn caching_threads_is_all_or_nothing() {
+        let db = test_db();
+        let threads: Vec<Thread> = ["t1", "t2", "t3"].iter().map(|id| thread(id, "")).collect();
+        let cached_count = |db: &CacheDb| -> i64 {
+            let conn = db.conn.lock().unwrap();
+            conn.query_row("SELECT COUNT(*) FROM threads", [], |row| row.get(0)).unwrap()
+        };
+
+        // Fail on the last thread, after the first two were written
+        db.conn
+            .lock()
+            .unwrap()
+            .execute_batch(
+                "CREATE TRIGGER fail_t3 BEFORE INSERT ON threads WHEN NEW.gmail_thread_id = 't3'
+                 BEGIN SELECT RAISE(ABORT, 'simulated failure'); END;",
+            )
+            .unwrap();
+        assert!(db.cache_threads(&threads).is_err());
+        assert_eq!(cached_count(&db), 0);
+
+        db.conn.lock().unwrap().execute_batch("DROP TRIGGER fail_t3;").unwrap();
+        db.cache_threads(&threads).unwrap();
+        assert_eq!(cached_count(&db), 3);
+    }
+
+    #[test]
+    fn sent_log_is_newest_first_per_account() {
+        let db = test_db();
+        let entry = |subject: &str, sent_at: i64| SentLogEntry {
+            account_id: "acc".to_string(),
+            thread_id: None,
+            recipient: "bob@example.com".to_string(),
+            subject: subject.to_string(),
+            sent_at,
+        };
+
+        db.log_sent(&entry("Proposal", 1_000)).unwrap();
+        db.log_sent(&entry("Follow-up", 3_000)).unwrap();
+
+        let sent = db.recent_sent(Some("acc"), 10).unwrap();
+        assert_eq!(sent.iter().map(|e| e.subject.as_str()).collect::<Vec<_>>(), vec!["Follow-up", "Proposal"]);
+        assert_eq!(db.recent_sent(Some("acc"), 1).unwrap().len(), 1);
+        assert!(db.recent_sent(Some("other"), 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn attachment_round_trips_through_blob_storage() {
+        let db = test_db();
+        let bytes: Vec<u8> = (0..=255u8).chain([0, 0xff, 0xfe, 0x00]).collect();
+        db.save_attachment("a1", "m1", "att1", &bytes).unwrap();
+        assert_eq!(db.get_attachment("a1", "m1", "att1").unwrap(), Some(bytes.clone()));
+        assert_eq!(db.get_attachment("a1", "m1", "other").unwrap(), None);
+    }
 }
@@ -1,12 +1,16 @@
 // Google Calendar API client
 
-use chrono::{DateTime, Duration, Local, NaiveDate, TimeZone, Utc};
+use crate::diagnostics::timed_send;
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc, Weekday};
 use chrono_tz::Tz;
 use reqwest::StatusCode;
 use serde::{Deserialize, Serialize};
 
 const CALENDAR_API_BASE: &str = "https://www.googleapis.com/calendar/v3";
 
+/// Calendars fetched at once when searching across all of them
+const SEARCH_CONCURRENCY: usize = 6;
+
 /// Calendar info returned to frontend
 #[derive(Debug, Clone, Serialize)]
 pub struct CalendarInfo {
@@ -28,13 +32,25 @@ fn friendly_calendar_error(status: StatusCode, body: &str) -> String {
         return "Calendar access expired. Please re-login to refresh permissions.".to_string();
     }
 
-    if body.contains("insufficientPermissions") || body.contains("access denied") {
-        return "Calendar permission denied. Please re-login to grant calendar access.".to_string();
+    if body.contains("insufficientPermissions") || body.contains("ACCESS_TOKEN_SCOPE_INSUFFICIENT") {
+        return "Calendar scope not granted. Enable calendar access in Settings and sign in again.".to_string();
+    }
+
+    // Calendar reports rate and quota limits as 403s too
+    if ["rateLimitExceeded", "userRateLimitExceeded", "quotaExceeded", "usageLimits"]
+        .iter()
+        .any(|reason| body.contains(reason))
+    {
+        return "Too many requests. Please try again later.".to_string();
+    }
+
+    if body.contains("forbiddenForNonOrganizer") {
+        return "Only the organizer can change this event.".to_string();
     }
 
     match status {
         StatusCode::UNAUTHORIZED => "Calendar access expired. Please re-login.".to_string(),
-        StatusCode::FORBIDDEN => "Calendar access denied. Please re-login to grant permissions.".to_string(),
+        StatusCode::FORBIDDEN => "Calendar access denied.".to_string(),
         StatusCode::NOT_FOUND => "Calendar not found.".to_string(),
         StatusCode::TOO_MANY_REQUESTS => "Too many requests. Please try again later.".to_string(),
         _ => format!("Calendar error ({})", status),
@@ -60,6 +76,10 @@ pub struct CalendarEvent {
     pub response_status: Option<String>, // accepted, declined, tentative, needsAction
     #[serde(default)]
     pub can_edit: bool, // whether the current user can edit this event
+    #[serde(default)]
+    pub color_id: Option<String>, // Google event palette id ("1".."11"), None for calendar default
+    #[serde(default)]
+    pub time_zone: Option<String>, // IANA zone the event was created in, only when its clock differs from ours
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -90,6 +110,41 @@ struct CalendarListEntry {
 #[derive(Debug, Deserialize)]
 struct EventsListResponse {
     items: Option<Vec<ApiEvent>>,
+    #[serde(rename = "nextPageToken")]
+    next_page_token: Option<String>,
+}
+
+/// Filter every calendar's events with `query`, then sort and keep the first
+/// `max_results` overall. Each calendar is fetched up to `max_results` on its
+/// own, so the limit only holds once they're merged.
+fn merge_search_results(events: Vec<CalendarEvent>, query: &CalendarQuery, max_results: i32) -> Vec<CalendarEvent> {
+    let mut merged: Vec<CalendarEvent> = events.into_iter().filter(|e| query.matches(e)).collect();
+    merged.sort_by_key(|e| e.start_time);
+    merged.truncate(max_results.max(0) as usize);
+    merged
+}
+
+/// Follow `nextPageToken` until the pages run out or `limit` events are in.
+/// `None` if the first page fails; a later failure keeps what was collected.
+async fn collect_event_pages<F, Fut>(limit: usize, mut fetch_page: F) -> Option<Vec<ApiEvent>>
+where
+    F: FnMut(Option<String>) -> Fut,
+    Fut: std::future::Future<Output = Option<EventsListResponse>>,
+{
+    let mut events = Vec::new();
+    let mut page_token = None;
+    let mut first_page = true;
+    loop {
+        let Some(page) = fetch_page(page_token.take()).await else {
+            return (!first_page).then_some(events);
+        };
+        first_page = false;
+        events.extend(page.items.unwrap_or_default());
+        match page.next_page_token {
+            Some(token) if events.len() < limit => page_token = Some(token),
+            _ => return Some(events),
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -115,9 +170,38 @@ struct ApiEvent {
     html_link: Option<String>,
     #[serde(rename = "hangoutLink")]
     hangout_link: Option<String>,
+    #[serde(rename = "conferenceData")]
+    conference_data: Option<ApiConferenceData>,
     #[serde(rename = "guestsCanModify")]
     guests_can_modify: Option<bool>,
     locked: Option<bool>,
+    #[serde(rename = "colorId")]
+    color_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiConferenceData {
+    #[serde(rename = "entryPoints", default)]
+    entry_points: Vec<ApiEntryPoint>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiEntryPoint {
+    #[serde(rename = "entryPointType")]
+    entry_point_type: Option<String>,
+    uri: Option<String>,
+}
+
+/// The event's video call link: `hangoutLink` when Google sets it, otherwise
+/// the video entry point of its conference data
+fn video_link(hangout_link: Option<String>, conference_data: Option<ApiConferenceData>) -> Option<String> {
+    hangout_link.or_else(|| {
+        conference_data?
+            .entry_points
+            .into_iter()
+            .find(|e| e.entry_point_type.as_deref() == Some("video"))
+            .and_then(|e| e.uri)
+    })
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -125,6 +209,19 @@ struct EventDateTime {
     #[serde(rename = "dateTime")]
     date_time: Option<String>,
     date: Option<String>,
+    #[serde(rename = "timeZone")]
+    time_zone: Option<String>,
+}
+
+/// The event's zone, if at its start it is at a different UTC offset than
+/// the user (so "10:00" means something else there)
+fn foreign_time_zone(event_zone: Option<&str>, start_ms: i64, user_offset_secs: i32) -> Option<String> {
+    use chrono::Offset;
+
+    let name = event_zone?;
+    let tz: Tz = name.parse().ok()?;
+    let event_offset = tz.timestamp_millis_opt(start_ms).single()?.offset().fix().local_minus_utc();
+    (event_offset != user_offset_secs).then(|| name.to_string())
 }
 
 #[derive(Debug, Deserialize)]
@@ -148,15 +245,57 @@ struct ApiAttendee {
     organizer: Option<bool>,
 }
 
+/// Body for inserting an event; unset optional fields are left out
 #[derive(Debug, Serialize)]
 struct CreateEventRequest {
     summary: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
     description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     location: Option<String>,
     start: EventDateTimeInput,
     end: EventDateTimeInput,
+    #[serde(skip_serializing_if = "Option::is_none")]
     attendees: Option<Vec<AttendeeInput>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     recurrence: Option<Vec<String>>,
+    #[serde(rename = "conferenceData", skip_serializing_if = "Option::is_none")]
+    conference_data: Option<ConferenceDataInput>,
+}
+
+/// Asks Calendar to create a Google Meet call for the event
+#[derive(Debug, Serialize)]
+struct ConferenceDataInput {
+    #[serde(rename = "createRequest")]
+    create_request: CreateConferenceRequest,
+}
+
+#[derive(Debug, Serialize)]
+struct CreateConferenceRequest {
+    /// Must be unique per request; Google dedupes retries on it
+    #[serde(rename = "requestId")]
+    request_id: String,
+    #[serde(rename = "conferenceSolutionKey")]
+    conference_solution_key: ConferenceSolutionKey,
+}
+
+#[derive(Debug, Serialize)]
+struct ConferenceSolutionKey {
+    #[serde(rename = "type")]
+    key_type: String,
+}
+
+impl ConferenceDataInput {
+    fn meet() -> Self {
+        ConferenceDataInput {
+            create_request: CreateConferenceRequest {
+                request_id: uuid::Uuid::new_v4().to_string(),
+                conference_solution_key: ConferenceSolutionKey {
+                    key_type: "hangoutsMeet".to_string(),
+                },
+            },
+        }
+    }
 }
 
 #[derive(Debug, Serialize)]
@@ -171,6 +310,38 @@ struct AttendeeInput {
     email: String,
 }
 
+/// An edit to an existing event. `None` keeps the event's current value; an
+/// empty string or list clears it.
+#[derive(Debug, Default)]
+pub struct EventPatch {
+    pub summary: Option<String>,
+    pub description: Option<String>,
+    pub location: Option<String>,
+    /// Start, end (milliseconds) and all-day flag, always changed together
+    pub times: Option<(i64, i64, bool)>,
+    pub attendees: Option<Vec<String>>,
+    pub recurrence: Option<Vec<String>>,
+}
+
+/// PATCH body carrying only the fields being changed
+#[derive(Debug, Serialize)]
+struct PatchEventRequest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    summary: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    location: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    start: Option<EventDateTimeInput>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    end: Option<EventDateTimeInput>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    attendees: Option<Vec<AttendeeInput>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    recurrence: Option<Vec<String>>,
+}
+
 #[derive(Deserialize)]
 struct CalEventSearchResponse {
     items: Option<Vec<CalEventSearchItem>>,
@@ -192,11 +363,349 @@ struct CalEventAttendee {
     is_self: Option<bool>,
 }
 
+/// Result of an RSVP request
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RsvpOutcome {
+    /// The response was changed and attendees were notified
+    Updated,
+    /// The user had already given this response; nothing was sent
+    AlreadySet,
+}
+
+/// The user's response status among an event's attendees
+fn self_response_status<'a>(attendees: &'a [CalEventAttendee], user_email: &str) -> Option<&'a str> {
+    attendees
+        .iter()
+        .find(|a| a.email.eq_ignore_ascii_case(user_email))
+        .and_then(|a| a.response_status.as_deref())
+}
+
+/// Attendee list with the user's response set to `status`, or None when it
+/// already is (so no patch, and no attendee notifications, are needed)
+fn rsvp_attendees(mut attendees: Vec<CalEventAttendee>, user_email: &str, status: &str) -> Option<Vec<CalEventAttendee>> {
+    if self_response_status(&attendees, user_email) == Some(status) {
+        return None;
+    }
+
+    match attendees.iter_mut().find(|a| a.email.eq_ignore_ascii_case(user_email)) {
+        Some(attendee) => attendee.response_status = Some(status.to_string()),
+        // Add ourselves as an attendee
+        None => attendees.push(CalEventAttendee {
+            email: user_email.to_string(),
+            response_status: Some(status.to_string()),
+            is_self: Some(true),
+        }),
+    }
+    Some(attendees)
+}
+
+/// Google Calendar's fixed event palette: (colorId, name)
+pub const EVENT_COLORS: [(&str, &str); 11] = [
+    ("1", "lavender"),
+    ("2", "sage"),
+    ("3", "grape"),
+    ("4", "flamingo"),
+    ("5", "banana"),
+    ("6", "tangerine"),
+    ("7", "peacock"),
+    ("8", "graphite"),
+    ("9", "blueberry"),
+    ("10", "basil"),
+    ("11", "tomato"),
+];
+
+/// Resolve a color given as a palette id ("10"), palette name ("basil") or
+/// plain color name ("green") to a valid event colorId
+pub fn resolve_color_id(color: &str) -> Option<&'static str> {
+    let color = color.trim().to_lowercase();
+    if let Some((id, _)) = EVENT_COLORS.iter().find(|(id, name)| *id == color || *name == color) {
+        return Some(id);
+    }
+    let id = match color.as_str() {
+        "purple" | "violet" => "3",
+        "pink" => "4",
+        "yellow" => "5",
+        "orange" => "6",
+        "cyan" | "teal" | "turquoise" => "7",
+        "gray" | "grey" => "8",
+        "blue" => "9",
+        "green" => "10",
+        "red" => "11",
+        _ => return None,
+    };
+    Some(id)
+}
+
+/// Title keyword to event color, e.g. "1:1" -> "green"
+#[derive(Debug, Clone, Deserialize)]
+pub struct ColorRule {
+    pub keyword: String,
+    pub color: String,
+}
+
+/// colorId of the first rule whose keyword appears in `title` (case-insensitive)
+pub fn match_color_rule(title: &str, rules: &[ColorRule]) -> Option<&'static str> {
+    let title = title.to_lowercase();
+    rules
+        .iter()
+        .filter(|rule| !rule.keyword.trim().is_empty())
+        .find(|rule| title.contains(&rule.keyword.trim().to_lowercase()))
+        .and_then(|rule| resolve_color_id(&rule.color))
+}
+
+/// Whether an attendee's busy times could be read
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FreeBusyStatus {
+    Known,
+    /// Their calendar isn't shared with us (or doesn't exist); busy is empty
+    Unknown,
+}
+
+/// One attendee's availability over a queried window
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AttendeeAvailability {
+    pub email: String,
+    pub status: FreeBusyStatus,
+    /// Busy intervals as (start, end) Unix timestamps in milliseconds
+    pub busy: Vec<(i64, i64)>,
+}
+
+#[derive(Debug, Deserialize)]
+struct FreeBusyResponse {
+    #[serde(default)]
+    calendars: std::collections::HashMap<String, FreeBusyCalendar>,
+}
+
+#[derive(Debug, Deserialize)]
+struct FreeBusyCalendar {
+    #[serde(default)]
+    busy: Vec<FreeBusyPeriod>,
+    /// Set per calendar (e.g. `notFound`) instead of failing the whole query
+    #[serde(default)]
+    errors: Vec<serde_json::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+struct FreeBusyPeriod {
+    start: String,
+    end: String,
+}
+
+/// Map a freeBusy response back onto the requested emails, in request order.
+/// Attendees with errors or missing from the response are `Unknown`.
+fn availability_from_response(emails: &[String], response: FreeBusyResponse) -> Vec<AttendeeAvailability> {
+    let mut calendars = response.calendars;
+    emails
+        .iter()
+        .map(|email| {
+            let calendar = calendars
+                .remove(email)
+                .filter(|c| c.errors.is_empty());
+            let Some(calendar) = calendar else {
+                return AttendeeAvailability {
+                    email: email.clone(),
+                    status: FreeBusyStatus::Unknown,
+                    busy: Vec::new(),
+                };
+            };
+            let busy = calendar
+                .busy
+                .iter()
+                .filter_map(|p| {
+                    let start = DateTime::parse_from_rfc3339(&p.start).ok()?;
+                    let end = DateTime::parse_from_rfc3339(&p.end).ok()?;
+                    Some((start.timestamp_millis(), end.timestamp_millis()))
+                })
+                .collect();
+            AttendeeAvailability {
+                email: email.clone(),
+                status: FreeBusyStatus::Known,
+                busy,
+            }
+        })
+        .collect()
+}
+
+/// Working day used when offering free time: 9:00 to 17:00, Monday to Friday
+pub const WORKING_DAY_START_HOUR: u32 = 9;
+pub const WORKING_DAY_END_HOUR: u32 = 17;
+
+fn is_working_day(date: NaiveDate) -> bool {
+    !matches!(date.weekday(), Weekday::Sat | Weekday::Sun)
+}
+
+/// Free slots of `slot_minutes` inside working hours (in `tz`) between
+/// `start_ms` and `end_ms`, skipping any that overlap `busy`. Slots sit on a
+/// grid starting at the beginning of each working day, and back-to-back free
+/// slots are merged into one range.
+pub fn free_slots(busy: &[(i64, i64)], start_ms: i64, end_ms: i64, tz: Tz, slot_minutes: u32) -> Vec<(i64, i64)> {
+    let slot_ms = slot_minutes as i64 * 60_000;
+    let (Some(start), Some(end)) = (
+        DateTime::<Utc>::from_timestamp_millis(start_ms),
+        DateTime::<Utc>::from_timestamp_millis(end_ms),
+    ) else {
+        return Vec::new();
+    };
+    if slot_ms <= 0 || end_ms <= start_ms {
+        return Vec::new();
+    }
+
+    let mut ranges: Vec<(i64, i64)> = Vec::new();
+    let last_date = end.with_timezone(&tz).date_naive();
+    let mut date = start.with_timezone(&tz).date_naive();
+    while date <= last_date {
+        let at_hour = |hour: u32| {
+            tz.from_local_datetime(&date.and_hms_opt(hour, 0, 0)?)
+                .earliest()
+                .map(|dt| dt.timestamp_millis())
+        };
+        if let (true, Some(day_start), Some(day_end)) =
+            (is_working_day(date), at_hour(WORKING_DAY_START_HOUR), at_hour(WORKING_DAY_END_HOUR))
+        {
+            let mut slot_start = day_start;
+            while slot_start + slot_ms <= day_end {
+                let slot_end = slot_start + slot_ms;
+                let in_window = slot_start >= start_ms && slot_end <= end_ms;
+                let free = !busy.iter().any(|&(b_start, b_end)| b_start < slot_end && b_end > slot_start);
+                if in_window && free {
+                    match ranges.last_mut() {
+                        Some(last) if last.1 == slot_start => last.1 = slot_end,
+                        _ => ranges.push((slot_start, slot_end)),
+                    }
+                }
+                slot_start = slot_end;
+            }
+        }
+        let Some(next) = date.succ_opt() else { break };
+        date = next;
+    }
+    ranges
+}
+
+/// Free ranges as a plain-text list for pasting into an email, one line per
+/// day with the date spelled out, ending with the timezone the times are in
+pub fn format_availability(slots: &[(i64, i64)], tz: Tz) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    let mut current_date: Option<NaiveDate> = None;
+    for &(start_ms, end_ms) in slots {
+        let (Some(start), Some(end)) = (
+            DateTime::<Utc>::from_timestamp_millis(start_ms),
+            DateTime::<Utc>::from_timestamp_millis(end_ms),
+        ) else {
+            continue;
+        };
+        let (start, end) = (start.with_timezone(&tz), end.with_timezone(&tz));
+        let range = format!("{} – {}", start.format("%-I:%M %p"), end.format("%-I:%M %p"));
+        match lines.last_mut() {
+            Some(line) if current_date == Some(start.date_naive()) => {
+                line.push_str(", ");
+                line.push_str(&range);
+            }
+            _ => {
+                current_date = Some(start.date_naive());
+                lines.push(format!("- {}: {}", start.format("%a, %b %-d, %Y"), range));
+            }
+        }
+    }
+
+    if lines.is_empty() {
+        lines.push("No free times in this window.".to_string());
+    }
+    lines.push(String::new());
+    lines.push(format!("Times in {}", tz.name()));
+    lines.join("\n")
+}
+
 pub struct CalendarClient {
     http_client: reqwest::Client,
     access_token: String,
 }
 
+/// Length of agenda entries written without an end time
+const DEFAULT_AGENDA_MINUTES: i64 = 30;
+
+/// One timed entry from a typed day plan
+#[derive(Debug, Clone, PartialEq)]
+pub struct AgendaItem {
+    pub title: String,
+    pub start: NaiveDateTime,
+    pub end: NaiveDateTime,
+}
+
+/// Parse "10:00", "9:30am" or "2pm" into a time of day
+fn parse_agenda_time(s: &str) -> Option<NaiveTime> {
+    let s = s.trim().to_ascii_lowercase();
+    let (clock, meridiem) = if let Some(rest) = s.strip_suffix("am") {
+        (rest.trim(), Some(false))
+    } else if let Some(rest) = s.strip_suffix("pm") {
+        (rest.trim(), Some(true))
+    } else {
+        (s.as_str(), None)
+    };
+
+    let (hour, minute) = match clock.split_once(':') {
+        Some((h, m)) if m.len() == 2 => (h.parse::<u32>().ok()?, m.parse::<u32>().ok()?),
+        None if meridiem.is_some() => (clock.parse::<u32>().ok()?, 0),
+        _ => return None,
+    };
+
+    let hour = match meridiem {
+        Some(_) if !(1..=12).contains(&hour) => return None,
+        Some(false) => hour % 12,
+        Some(true) => hour % 12 + 12,
+        None => hour,
+    };
+    NaiveTime::from_hms_opt(hour, minute, 0)
+}
+
+/// Parse one agenda line: "10:00 Standup" or "14:00-15:00 Review".
+/// Leading list bullets are ignored.
+fn parse_agenda_line(line: &str, base_date: NaiveDate) -> Option<AgendaItem> {
+    let line = line.trim().trim_start_matches(['-', '*', '•']).trim_start();
+    let (times, title) = line.split_once(char::is_whitespace)?;
+    let title = title.trim();
+    if title.is_empty() {
+        return None;
+    }
+
+    let (start, end) = match times.split_once(['-', '–']) {
+        Some((start, end)) => {
+            let start = parse_agenda_time(start)?;
+            let end = parse_agenda_time(end)?;
+            (base_date.and_time(start), base_date.and_time(end))
+        }
+        None => {
+            let start = base_date.and_time(parse_agenda_time(times)?);
+            (start, start + Duration::minutes(DEFAULT_AGENDA_MINUTES))
+        }
+    };
+    if end <= start {
+        return None;
+    }
+
+    Some(AgendaItem {
+        title: title.to_string(),
+        start,
+        end,
+    })
+}
+
+/// Parse a typed day plan relative to `base_date`. Returns the parsed items
+/// and the non-blank lines that could not be understood.
+pub fn parse_agenda(text: &str, base_date: NaiveDate) -> (Vec<AgendaItem>, Vec<String>) {
+    let mut items = Vec::new();
+    let mut unparsed = Vec::new();
+    for line in text.lines().filter(|l| !l.trim().is_empty()) {
+        match parse_agenda_line(line, base_date) {
+            Some(item) => items.push(item),
+            None => unparsed.push(line.trim().to_string()),
+        }
+    }
+    (items, unparsed)
+}
+
 /// Build a CreateEventRequest from raw parameters (shared by create and update)
 fn build_event_request(
     summary: String,
@@ -208,12 +717,47 @@ fn build_event_request(
     attendees: Option<Vec<String>>,
     recurrence: Option<Vec<String>>,
 ) -> Result<CreateEventRequest, String> {
+    let (start, end) = event_times(start_time, end_time, all_day)?;
+
+    Ok(CreateEventRequest {
+        summary,
+        description,
+        location,
+        start,
+        end,
+        attendees: attendees.map(attendee_inputs),
+        recurrence: recurrence.map(recurrence_rules),
+        conference_data: None,
+    })
+}
+
+fn build_event_patch(patch: EventPatch) -> Result<PatchEventRequest, String> {
+    let (start, end) = match patch.times {
+        Some((start_time, end_time, all_day)) => {
+            let (start, end) = event_times(start_time, end_time, all_day)?;
+            (Some(start), Some(end))
+        }
+        None => (None, None),
+    };
+
+    Ok(PatchEventRequest {
+        summary: patch.summary,
+        description: patch.description,
+        location: patch.location,
+        start,
+        end,
+        attendees: patch.attendees.map(attendee_inputs),
+        recurrence: patch.recurrence.map(recurrence_rules),
+    })
+}
+
+fn event_times(start_time: i64, end_time: i64, all_day: bool) -> Result<(EventDateTimeInput, EventDateTimeInput), String> {
     let start_dt = DateTime::<Utc>::from_timestamp(start_time / 1000, (start_time % 1000 * 1_000_000) as u32)
         .ok_or("Invalid start time")?;
     let end_dt = DateTime::<Utc>::from_timestamp(end_time / 1000, (end_time % 1000 * 1_000_000) as u32)
         .ok_or("Invalid end time")?;
 
-    let (start, end) = if all_day {
+    Ok(if all_day {
         // The form's end date is inclusive; Google's all-day end date is exclusive
         let exclusive_end = end_dt + Duration::days(1);
         (
@@ -237,41 +781,114 @@ fn build_event_request(
                 date: None,
             },
         )
-    };
+    })
+}
 
-    Ok(CreateEventRequest {
-        summary,
-        description,
-        location,
-        start,
-        end,
-        attendees: attendees.map(|emails| {
-            emails
-                .into_iter()
-                .map(|email| AttendeeInput { email })
-                .collect()
-        }),
-        // Google requires RFC 5545 property names ("RRULE:FREQ=DAILY"); the
-        // form emits bare rule strings ("FREQ=DAILY")
-        recurrence: recurrence.map(|rules| {
-            rules
-                .into_iter()
-                .map(|rule| {
-                    if rule.starts_with("RRULE:")
-                        || rule.starts_with("RDATE")
-                        || rule.starts_with("EXRULE")
-                        || rule.starts_with("EXDATE")
-                    {
-                        rule
-                    } else {
-                        format!("RRULE:{}", rule)
-                    }
-                })
-                .collect()
-        }),
+fn attendee_inputs(emails: Vec<String>) -> Vec<AttendeeInput> {
+    emails.into_iter().map(|email| AttendeeInput { email }).collect()
+}
+
+/// Google requires RFC 5545 property names ("RRULE:FREQ=DAILY"); the form
+/// emits bare rule strings ("FREQ=DAILY")
+fn recurrence_rules(rules: Vec<String>) -> Vec<String> {
+    rules
+        .into_iter()
+        .map(|rule| {
+            if rule.starts_with("RRULE:")
+                || rule.starts_with("RDATE")
+                || rule.starts_with("EXRULE")
+                || rule.starts_with("EXDATE")
+            {
+                rule
+            } else {
+                format!("RRULE:{}", rule)
+            }
+        })
+        .collect()
+}
+
+/// Escape a TEXT value (RFC 5545 3.3.11)
+fn escape_ics_text(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+/// Fold a content line to at most 75 octets per physical line, never
+/// splitting a UTF-8 character. Continuation lines start with a space.
+fn fold_ics_line(line: &str) -> String {
+    const MAX_OCTETS: usize = 75;
+
+    let mut out = String::with_capacity(line.len() + line.len() / MAX_OCTETS * 3);
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > MAX_OCTETS {
+            out.push_str("\r\n ");
+            width = 1;
+        }
+        out.push(c);
+        width += c.len_utf8();
+    }
+    out.push_str("\r\n");
+    out
+}
+
+/// DTSTART/DTEND property: a DATE for all-day events, a UTC DATE-TIME otherwise
+fn ics_time_property(name: &str, millis: i64, all_day: bool) -> Option<String> {
+    let time = DateTime::<Utc>::from_timestamp_millis(millis)?;
+    Some(if all_day {
+        format!("{};VALUE=DATE:{}", name, time.format("%Y%m%d"))
+    } else {
+        format!("{}:{}", name, time.format("%Y%m%dT%H%M%SZ"))
     })
 }
 
+/// Serialize events as a VCALENDAR with one VEVENT each
+pub fn events_to_ics(events: &[CalendarEvent]) -> String {
+    let stamp = Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//Posta//Calendar Export//EN".to_string(),
+        "CALSCALE:GREGORIAN".to_string(),
+    ];
+
+    for event in events {
+        let Some(start) = ics_time_property("DTSTART", event.start_time, event.all_day) else {
+            continue;
+        };
+
+        lines.push("BEGIN:VEVENT".to_string());
+        lines.push(format!("UID:{}", escape_ics_text(&event.id)));
+        lines.push(format!("DTSTAMP:{}", stamp));
+        lines.push(start);
+        if let Some(end) = event
+            .end_time
+            .and_then(|end| ics_time_property("DTEND", end, event.all_day))
+        {
+            lines.push(end);
+        }
+        lines.push(format!("SUMMARY:{}", escape_ics_text(&event.title)));
+        if let Some(location) = event.location.as_deref().filter(|l| !l.is_empty()) {
+            lines.push(format!("LOCATION:{}", escape_ics_text(location)));
+        }
+        if let Some(description) = event.description.as_deref().filter(|d| !d.is_empty()) {
+            lines.push(format!("DESCRIPTION:{}", escape_ics_text(description)));
+        }
+        if event.status == "tentative" || event.status == "cancelled" {
+            lines.push(format!("STATUS:{}", event.status.to_uppercase()));
+        }
+        lines.push("END:VEVENT".to_string());
+    }
+
+    lines.push("END:VCALENDAR".to_string());
+    lines.iter().map(|line| fold_ics_line(line)).collect()
+}
+
 impl CalendarClient {
     pub fn new(access_token: String) -> Self {
         Self {
@@ -284,11 +901,7 @@ impl CalendarClient {
     pub async fn list_calendars(&self) -> Result<Vec<CalendarInfo>, String> {
         let url = format!("{}/users/me/calendarList", CALENDAR_API_BASE);
 
-        let resp = self
-            .http_client
-            .get(&url)
-            .bearer_auth(&self.access_token)
-            .send()
+        let resp = timed_send("calendar.calendarList", self.http_client.get(&url).bearer_auth(&self.access_token))
             .await
             .map_err(|e| format!("Calendar API request failed: {}", e))?;
 
@@ -336,11 +949,7 @@ impl CalendarClient {
             max_results
         );
 
-        let resp = self
-            .http_client
-            .get(&url)
-            .bearer_auth(&self.access_token)
-            .send()
+        let resp = timed_send("calendar.events.list", self.http_client.get(&url).bearer_auth(&self.access_token))
             .await
             .map_err(|e| format!("Calendar events request failed: {}", e))?;
 
@@ -371,6 +980,8 @@ impl CalendarClient {
         query: &CalendarQuery,
         max_results: i32,
     ) -> Result<Vec<CalendarEvent>, String> {
+        use futures::StreamExt;
+
         let calendars = self.list_calendars().await?;
         let mut all_events = Vec::new();
 
@@ -383,9 +994,19 @@ impl CalendarClient {
 
         // Determine time range from query using calendar timezone
         let (time_min, time_max) = query.get_time_range(timezone);
+        tracing::debug!(
+            "Calendar search {:?}: {} to {} across {} calendars (tz {:?})",
+            query.time_range,
+            time_min.to_rfc3339(),
+            time_max.to_rfc3339(),
+            calendars.len(),
+            timezone
+        );
 
-        let fetch_futures: Vec<_> = calendars.iter().map(|cal| {
-            let mut url = format!(
+        // Calendars are independent, so fetch several at a time; one that
+        // fails is skipped rather than failing the search
+        let results: Vec<_> = futures::stream::iter(calendars.iter()).map(|cal| {
+            let mut base_url = format!(
                 "{}/calendars/{}/events?timeMin={}&timeMax={}&maxResults={}&singleEvents=true&orderBy=startTime",
                 CALENDAR_API_BASE,
                 urlencoding::encode(&cal.id),
@@ -395,34 +1016,38 @@ impl CalendarClient {
             );
 
             if let Some(q) = &query.text {
-                url.push_str(&format!("&q={}", urlencoding::encode(q)));
+                base_url.push_str(&format!("&q={}", urlencoding::encode(q)));
             }
 
             async move {
-                let resp = self
-                    .http_client
-                    .get(&url)
-                    .bearer_auth(&self.access_token)
-                    .send()
-                    .await
-                    .ok()?;
-
-                if !resp.status().is_success() {
-                    return None;
-                }
+                let items = collect_event_pages(max_results.max(0) as usize, |page_token| {
+                    let url = match page_token {
+                        Some(token) => format!("{}&pageToken={}", base_url, urlencoding::encode(&token)),
+                        None => base_url.clone(),
+                    };
+                    async move {
+                        let resp = timed_send("calendar.events.list", self.http_client.get(&url).bearer_auth(&self.access_token))
+                            .await
+                            .ok()?;
+
+                        if !resp.status().is_success() {
+                            return None;
+                        }
 
-                let data: EventsListResponse = resp.json().await.ok()?;
-                Some((data, cal))
+                        resp.json::<EventsListResponse>().await.ok()
+                    }
+                })
+                .await?;
+                Some((items, cal))
             }
-        }).collect();
-
-        let results = futures::future::join_all(fetch_futures).await;
+        })
+        .buffer_unordered(SEARCH_CONCURRENCY)
+        .collect()
+        .await;
 
         for result in results {
-            if let Some((data, cal)) = result {
-                let events: Vec<CalendarEvent> = data
-                    .items
-                    .unwrap_or_default()
+            if let Some((items, cal)) = result {
+                let events: Vec<CalendarEvent> = items
                     .into_iter()
                     .filter_map(|e| self.api_event_to_calendar_event(e, &cal.id, &cal.name, &cal.access_role))
                     .collect();
@@ -430,23 +1055,52 @@ impl CalendarClient {
             }
         }
 
-        // Apply additional filters
-        let filtered: Vec<CalendarEvent> = all_events
-            .into_iter()
-            .filter(|e| query.matches(e))
-            .collect();
+        let merged = merge_search_results(all_events, query, max_results);
+        tracing::debug!("Calendar search matched {} events", merged.len());
+        Ok(merged)
+    }
 
-        // Sort by start time
-        let mut sorted = filtered;
-        sorted.sort_by_key(|e| e.start_time);
+    /// Busy intervals for each email between `time_min` and `time_max`
+    /// (milliseconds). Unshared calendars come back `Unknown` rather than
+    /// failing the query.
+    pub async fn query_freebusy(
+        &self,
+        emails: &[String],
+        time_min: i64,
+        time_max: i64,
+    ) -> Result<Vec<AttendeeAvailability>, String> {
+        let url = format!("{}/freeBusy", CALENDAR_API_BASE);
+        let time_min = DateTime::<Utc>::from_timestamp_millis(time_min).ok_or("Invalid start time")?;
+        let time_max = DateTime::<Utc>::from_timestamp_millis(time_max).ok_or("Invalid end time")?;
+
+        let body = serde_json::json!({
+            "timeMin": time_min.to_rfc3339(),
+            "timeMax": time_max.to_rfc3339(),
+            "items": emails.iter().map(|email| serde_json::json!({ "id": email })).collect::<Vec<_>>(),
+        });
+
+        let resp = timed_send(
+            "calendar.freeBusy",
+            self.http_client.post(&url).bearer_auth(&self.access_token).json(&body),
+        )
+        .await
+        .map_err(|e| format!("Free/busy request failed: {}", e))?;
 
-        // Limit results
-        sorted.truncate(max_results as usize);
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let body = resp.text().await.unwrap_or_default();
+            return Err(friendly_calendar_error(status, &body));
+        }
 
-        Ok(sorted)
+        let data: FreeBusyResponse = resp
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse free/busy: {}", e))?;
+
+        Ok(availability_from_response(emails, data))
     }
 
-    /// Create a new event
+    /// Create a new event, with a Google Meet call when `with_meet` is set
     pub async fn create_event(
         &self,
         calendar_id: &str,
@@ -458,16 +1112,22 @@ impl CalendarClient {
         location: Option<String>,
         attendees: Option<Vec<String>>,
         recurrence: Option<Vec<String>>,
+        with_meet: bool,
     ) -> Result<CalendarEvent, String> {
-        let url = format!(
+        let mut url = format!(
             "{}/calendars/{}/events",
             CALENDAR_API_BASE,
             urlencoding::encode(calendar_id)
         );
 
-        let body = build_event_request(
+        let mut body = build_event_request(
             summary, description, start_time, end_time, all_day, location, attendees, recurrence,
         )?;
+        if with_meet {
+            // Without conferenceDataVersion=1 Google ignores the create request
+            url.push_str("?conferenceDataVersion=1");
+            body.conference_data = Some(ConferenceDataInput::meet());
+        }
 
         let resp = self
             .http_client
@@ -533,17 +1193,20 @@ impl CalendarClient {
             .ok_or_else(|| "Failed to convert moved event".to_string())
     }
 
-    /// Delete an event from a calendar
+    /// Delete an event from a calendar. `send_updates` is Calendar's
+    /// `sendUpdates` value: "all" notifies the attendees, "none" doesn't.
     pub async fn delete_event(
         &self,
         calendar_id: &str,
         event_id: &str,
+        send_updates: &str,
     ) -> Result<(), String> {
         let url = format!(
-            "{}/calendars/{}/events/{}",
+            "{}/calendars/{}/events/{}?sendUpdates={}",
             CALENDAR_API_BASE,
             urlencoding::encode(calendar_id),
-            urlencoding::encode(event_id)
+            urlencoding::encode(event_id),
+            urlencoding::encode(send_updates)
         );
 
         let resp = self
@@ -563,18 +1226,49 @@ impl CalendarClient {
         Ok(())
     }
 
+    /// Set an event's color; `color_id` must be one of Google's palette ids
+    pub async fn set_event_color(
+        &self,
+        calendar_id: &str,
+        event_id: &str,
+        color_id: &str,
+    ) -> Result<(), String> {
+        if !EVENT_COLORS.iter().any(|(id, _)| *id == color_id) {
+            return Err(format!("Invalid event color: {}", color_id));
+        }
+
+        let url = format!(
+            "{}/calendars/{}/events/{}",
+            CALENDAR_API_BASE,
+            urlencoding::encode(calendar_id),
+            urlencoding::encode(event_id)
+        );
+
+        let resp = self
+            .http_client
+            .patch(&url)
+            .bearer_auth(&self.access_token)
+            .json(&serde_json::json!({ "colorId": color_id }))
+            .send()
+            .await
+            .map_err(|e| format!("Set event color request failed: {}", e))?;
+
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let body = resp.text().await.unwrap_or_default();
+            return Err(friendly_calendar_error(status, &body));
+        }
+
+        Ok(())
+    }
+
+    /// Patch an existing event. Only the fields set in `patch` are sent;
+    /// anything left as `None` keeps its current value on the event.
     pub async fn update_event(
         &self,
         calendar_id: &str,
         event_id: &str,
-        summary: String,
-        description: Option<String>,
-        start_time: i64,
-        end_time: i64,
-        all_day: bool,
-        location: Option<String>,
-        attendees: Option<Vec<String>>,
-        recurrence: Option<Vec<String>>,
+        patch: EventPatch,
     ) -> Result<CalendarEvent, String> {
         let url = format!(
             "{}/calendars/{}/events/{}",
@@ -583,13 +1277,11 @@ impl CalendarClient {
             urlencoding::encode(event_id)
         );
 
-        let body = build_event_request(
-            summary, description, start_time, end_time, all_day, location, attendees, recurrence,
-        )?;
+        let body = build_event_patch(patch)?;
 
         let resp = self
             .http_client
-            .put(&url)
+            .patch(&url)
             .bearer_auth(&self.access_token)
             .json(&body)
             .send()
@@ -614,6 +1306,15 @@ impl CalendarClient {
     fn api_event_to_calendar_event(&self, event: ApiEvent, calendar_id: &str, calendar_name: &str, calendar_access_role: &str) -> Option<CalendarEvent> {
         let (start_time, all_day) = self.parse_event_datetime(&event.start)?;
         let end_time = event.end.as_ref().and_then(|e| self.parse_event_datetime(&Some(e.clone())).map(|(t, _)| t));
+        let time_zone = if all_day {
+            None
+        } else {
+            let event_zone = event.start.as_ref().and_then(|s| s.time_zone.as_deref());
+            Local
+                .timestamp_millis_opt(start_time)
+                .single()
+                .and_then(|local_start| foreign_time_zone(event_zone, start_time, local_start.offset().local_minus_utc()))
+        };
 
         let attendees: Vec<EventAttendee> = event
             .attendees
@@ -682,9 +1383,11 @@ impl CalendarClient {
             organizer: organizer_display,
             attendees,
             html_link: event.html_link,
-            hangout_link: event.hangout_link,
+            hangout_link: video_link(event.hangout_link, event.conference_data),
             response_status,
             can_edit,
+            color_id: event.color_id,
+            time_zone,
         })
     }
 
@@ -717,23 +1420,53 @@ impl CalendarClient {
         let event = items.first()?;
         let attendees = event.attendees.as_ref()?;
 
-        for attendee in attendees {
-            if attendee.email.to_lowercase() == user_email.to_lowercase() {
-                return attendee.response_status.clone();
-            }
+        self_response_status(attendees, user_email).map(str::to_string)
+    }
+
+    /// Find the primary-calendar event ID for an invite's iCalUID
+    pub async fn find_event_id_by_uid(&self, event_uid: &str) -> Result<Option<String>, String> {
+        let search_url = format!(
+            "{}/calendars/primary/events?iCalUID={}",
+            CALENDAR_API_BASE,
+            urlencoding::encode(event_uid)
+        );
+
+        let resp = self
+            .http_client
+            .get(&search_url)
+            .bearer_auth(&self.access_token)
+            .send()
+            .await
+            .map_err(|e| format!("Calendar API request failed: {}", e))?;
+
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let body = resp.text().await.unwrap_or_default();
+            return Err(friendly_calendar_error(status, &body));
         }
 
-        None
+        let events: CalEventSearchResponse = resp
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse events: {}", e))?;
+
+        Ok(events
+            .items
+            .unwrap_or_default()
+            .into_iter()
+            .map(|e| e.id)
+            .find(|id| !id.is_empty()))
     }
 
     /// Send RSVP response to a calendar event via Google Calendar API
-    /// status should be "accepted", "tentative", or "declined"
+    /// status should be "accepted", "tentative", or "declined".
+    /// Does nothing if the user's response already matches.
     pub async fn rsvp_calendar_event(
         &self,
         user_email: &str,
         event_uid: &str,
         status: &str,
-    ) -> Result<(), String> {
+    ) -> Result<RsvpOutcome, String> {
         // First, find the event by iCalUID
         let search_url = format!(
             "https://www.googleapis.com/calendar/v3/calendars/primary/events?iCalUID={}",
@@ -763,26 +1496,10 @@ impl CalendarClient {
         let event = &items[0];
         let event_id = &event.id;
 
-        // Update attendee status
-        let mut attendees: Vec<CalEventAttendee> = event.attendees.clone().unwrap_or_default();
-        let mut found_self = false;
-
-        for attendee in attendees.iter_mut() {
-            if attendee.email.to_lowercase() == user_email.to_lowercase() {
-                attendee.response_status = Some(status.to_string());
-                found_self = true;
-                break;
-            }
-        }
-
-        if !found_self {
-            // Add ourselves as an attendee
-            attendees.push(CalEventAttendee {
-                email: user_email.to_string(),
-                response_status: Some(status.to_string()),
-                is_self: Some(true),
-            });
-        }
+        // Patching notifies every attendee, so skip it when nothing would change
+        let Some(attendees) = rsvp_attendees(event.attendees.clone().unwrap_or_default(), user_email, status) else {
+            return Ok(RsvpOutcome::AlreadySet);
+        };
 
         // Patch the event with updated attendees
         let patch_url = format!(
@@ -811,7 +1528,7 @@ impl CalendarClient {
             return Err(format!("Failed to update RSVP: {}", error_text));
         }
 
-        Ok(())
+        Ok(RsvpOutcome::Updated)
     }
 
     fn parse_event_datetime(&self, dt: &Option<EventDateTime>) -> Option<(i64, bool)> {
@@ -833,7 +1550,7 @@ impl CalendarClient {
 }
 
 /// Calendar query parser
-#[derive(Debug, Default)]
+#[derive(Debug)]
 pub struct CalendarQuery {
     pub time_range: TimeRange,
     pub text: Option<String>,
@@ -843,6 +1560,25 @@ pub struct CalendarQuery {
     pub status: Option<String>, // confirmed, tentative, cancelled
     pub response: Option<String>, // accepted, declined, tentative, needsAction
     pub exclude: Vec<String>,   // Keywords to exclude
+    pub hide_declined: bool,    // Drop events the user declined (unless response: asks for them); on by default
+    pub text_fields_only: bool, // Text must appear in the title, description or location
+}
+
+impl Default for CalendarQuery {
+    fn default() -> Self {
+        CalendarQuery {
+            time_range: TimeRange::default(),
+            text: None,
+            with: Vec::new(),
+            organizer: None,
+            location: None,
+            status: None,
+            response: None,
+            exclude: Vec::new(),
+            hide_declined: true,
+            text_fields_only: false,
+        }
+    }
 }
 
 fn parse_duration(s: &str) -> Option<Duration> {
@@ -867,17 +1603,89 @@ pub enum TimeRange {
     Today,
     Tomorrow,
     Week,
+    /// Next Monday 00:00 to the Monday after
+    NextWeek,
+    /// Previous Monday 00:00 to this Monday
+    LastWeek,
     Month,
     Upcoming(Duration),
     Custom { start: DateTime<Utc>, end: DateTime<Utc> },
 }
 
+/// Monday-to-Monday window `weeks_from_now` weeks from the week containing
+/// `today_start` (1 = next week, -1 = last week)
+fn week_bounds(today_start: DateTime<Utc>, today: Weekday, weeks_from_now: i64) -> (DateTime<Utc>, DateTime<Utc>) {
+    let this_monday = today_start - Duration::days(today.num_days_from_monday() as i64);
+    let start = this_monday + Duration::weeks(weeks_from_now);
+    (start, start + Duration::weeks(1))
+}
+
+/// Split a query on whitespace, keeping double-quoted stretches together so
+/// `with:"John Smith"` stays one token (quotes included)
+fn tokenize_query(query: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+    for c in query.chars() {
+        if c == '"' {
+            in_quotes = !in_quotes;
+            current.push(c);
+        } else if c.is_whitespace() && !in_quotes {
+            if !current.is_empty() {
+                tokens.push(std::mem::take(&mut current));
+            }
+        } else {
+            current.push(c);
+        }
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+    tokens
+}
+
+/// Days covered by an open-ended range such as `2024-01-01..`
+const OPEN_RANGE_DAYS: i64 = 30;
+
+/// Local midnight at the start of `date`, in UTC
+fn local_midnight(date: NaiveDate) -> Option<DateTime<Utc>> {
+    Local
+        .from_local_datetime(&date.and_hms_opt(0, 0, 0)?)
+        .earliest()
+        .map(|dt| dt.with_timezone(&Utc))
+}
+
+/// Parse `2024-01-01..2024-02-15` or the open-ended `2024-01-01..` (30 days
+/// from the start). Both ends are local midnights.
+fn parse_date_range(s: &str) -> Option<TimeRange> {
+    let (start, end) = s.split_once("..")?;
+    let start = local_midnight(NaiveDate::parse_from_str(start, "%Y-%m-%d").ok()?)?;
+    let end = if end.is_empty() {
+        start + Duration::days(OPEN_RANGE_DAYS)
+    } else {
+        local_midnight(NaiveDate::parse_from_str(end, "%Y-%m-%d").ok()?)?
+    };
+    (end > start).then_some(TimeRange::Custom { start, end })
+}
+
 impl CalendarQuery {
+    /// Keyword search over the next `horizon_days`, ignoring the "today"
+    /// default range and matching only titles, descriptions and locations
+    pub fn upcoming_text(text: &str, horizon_days: i64) -> Self {
+        CalendarQuery {
+            time_range: TimeRange::Upcoming(Duration::days(horizon_days)),
+            text: Some(text.trim().to_string()).filter(|t| !t.is_empty()),
+            text_fields_only: true,
+            ..Default::default()
+        }
+    }
+
     pub fn parse(query: &str) -> Self {
         let mut cq = CalendarQuery::default();
         let mut remaining_text = Vec::new();
 
-        for token in query.split_whitespace() {
+        for token in tokenize_query(query) {
+            let token = token.as_str();
             let token_lower = token.to_lowercase();
 
             if token_lower.starts_with("calendar:") {
@@ -886,20 +1694,24 @@ impl CalendarQuery {
                     "today" => TimeRange::Today,
                     "tomorrow" => TimeRange::Tomorrow,
                     "week" => TimeRange::Week,
+                    "nextweek" => TimeRange::NextWeek,
+                    "lastweek" => TimeRange::LastWeek,
                     "month" => TimeRange::Month,
                     other => {
-                        // Try to parse as duration (e.g. 3d, 2w)
+                        // Try to parse as duration (e.g. 3d, 2w) or a date range
                         if let Some(duration) = parse_duration(other) {
                             TimeRange::Upcoming(duration)
+                        } else if let Some(range) = parse_date_range(other) {
+                            range
                         } else {
                             TimeRange::Today
                         }
                     }
                 };
             } else if token_lower.starts_with("with:") {
-                cq.with.push(token[5..].to_string());
+                cq.with.push(token[5..].trim_matches('"').to_string());
             } else if token_lower.starts_with("organizer:") {
-                cq.organizer = Some(token[10..].to_string());
+                cq.organizer = Some(token[10..].trim_matches('"').to_string());
             } else if token_lower.starts_with("location:") {
                 cq.location = Some(token[9..].trim_matches('"').to_string());
             } else if token_lower.starts_with("status:") {
@@ -917,6 +1729,7 @@ impl CalendarQuery {
             cq.text = Some(remaining_text.join(" "));
         }
 
+        tracing::debug!("Parsed calendar query {:?} as {:?}", query, cq);
         cq
     }
 
@@ -956,6 +1769,14 @@ impl CalendarQuery {
                 today_start + Duration::days(2),
             ),
             TimeRange::Week => (today_start, today_start + Duration::days(7)),
+            TimeRange::NextWeek | TimeRange::LastWeek => {
+                let today = timezone
+                    .and_then(|tz| tz.parse::<Tz>().ok())
+                    .map(|tz| now.with_timezone(&tz).weekday())
+                    .unwrap_or_else(|| Local::now().weekday());
+                let weeks_from_now = if matches!(self.time_range, TimeRange::NextWeek) { 1 } else { -1 };
+                week_bounds(today_start, today, weeks_from_now)
+            }
             TimeRange::Month => (today_start, today_start + Duration::days(30)),
             // For upcoming, we start from NOW to avoid missing things that just started
             TimeRange::Upcoming(duration) => (now, now + *duration),
@@ -1014,6 +1835,15 @@ impl CalendarQuery {
             }
         }
 
+        // Hide declined events, like Google Calendar's "show declined events" off,
+        // unless the query explicitly filters on a response
+        if self.hide_declined
+            && self.response.is_none()
+            && event.response_status.as_deref() == Some("declined")
+        {
+            return false;
+        }
+
         // Check response filter
         if let Some(response) = &self.response {
             let event_response = event.response_status.as_deref().unwrap_or("needsAction");
@@ -1022,6 +1852,20 @@ impl CalendarQuery {
             }
         }
 
+        // Google's q= also matches attendees and organizers; narrow it down
+        if self.text_fields_only {
+            if let Some(text) = &self.text {
+                let text = text.to_lowercase();
+                let in_field = |field: Option<&str>| field.map(|f| f.to_lowercase().contains(&text)).unwrap_or(false);
+                if !in_field(Some(&event.title))
+                    && !in_field(event.description.as_deref())
+                    && !in_field(event.location.as_deref())
+                {
+                    return false;
+                }
+            }
+        }
+
         // Check exclusions
         for exclude in &self.exclude {
             let exclude_lower = exclude.to_lowercase();
@@ -1076,6 +1920,71 @@ mod tests {
         assert_eq!(cq.with, vec!["john".to_string()]);
     }
 
+    #[test]
+    fn test_parse_query_date_range() {
+        let midnight = |y, m, d| local_midnight(NaiveDate::from_ymd_opt(y, m, d).unwrap()).unwrap();
+
+        match CalendarQuery::parse("calendar:2024-01-01..2024-02-15").time_range {
+            TimeRange::Custom { start, end } => {
+                assert_eq!(start, midnight(2024, 1, 1));
+                assert_eq!(end, midnight(2024, 2, 15));
+            }
+            _ => panic!("Expected Custom range"),
+        }
+
+        match CalendarQuery::parse("calendar:2024-01-01.. standup").time_range {
+            TimeRange::Custom { start, end } => {
+                assert_eq!(start, midnight(2024, 1, 1));
+                assert_eq!(end, start + Duration::days(30));
+            }
+            _ => panic!("Expected open-ended Custom range"),
+        }
+    }
+
+    #[test]
+    fn test_parse_query_quoted_values() {
+        let cq = CalendarQuery::parse(r#"calendar:week with:"John Smith" organizer:"Ann Lee" location:"Conference Room A" sync"#);
+        assert_eq!(cq.with, vec!["John Smith".to_string()]);
+        assert_eq!(cq.organizer.as_deref(), Some("Ann Lee"));
+        assert_eq!(cq.location.as_deref(), Some("Conference Room A"));
+        assert_eq!(cq.text.as_deref(), Some("sync"));
+
+        let cq = CalendarQuery::parse("with:john organizer:ann location:roomA");
+        assert_eq!(cq.with, vec!["john".to_string()]);
+        assert_eq!(cq.organizer.as_deref(), Some("ann"));
+        assert_eq!(cq.location.as_deref(), Some("roomA"));
+        assert!(cq.text.is_none());
+
+        // An unclosed quote runs to the end of the query
+        let cq = CalendarQuery::parse(r#"location:"Room 4"#);
+        assert_eq!(cq.location.as_deref(), Some("Room 4"));
+    }
+
+    #[test]
+    fn test_relative_weeks() {
+        assert!(matches!(CalendarQuery::parse("calendar:nextweek").time_range, TimeRange::NextWeek));
+        assert!(matches!(CalendarQuery::parse("calendar:LastWeek").time_range, TimeRange::LastWeek));
+
+        // Wednesday 2024-03-06
+        let wednesday = Utc.with_ymd_and_hms(2024, 3, 6, 0, 0, 0).unwrap();
+        let day = |d| Utc.with_ymd_and_hms(2024, 3, d, 0, 0, 0).unwrap();
+        assert_eq!(week_bounds(wednesday, Weekday::Wed, 1), (day(11), day(18)));
+        assert_eq!(
+            week_bounds(wednesday, Weekday::Wed, -1),
+            (Utc.with_ymd_and_hms(2024, 2, 26, 0, 0, 0).unwrap(), day(4))
+        );
+
+        // On a Monday, next week starts a full week later
+        assert_eq!(week_bounds(day(11), Weekday::Mon, 1), (day(18), day(25)));
+    }
+
+    #[test]
+    fn test_parse_query_invalid_date_range() {
+        for query in ["calendar:2024-13-01..2024-02-15", "calendar:2024-02-15..2024-01-01", "calendar:..2024-01-01"] {
+            assert!(matches!(CalendarQuery::parse(query).time_range, TimeRange::Today), "{}", query);
+        }
+    }
+
     #[test]
     fn test_get_time_range() {
         // Test "week" which uses today-based range (no timezone = local)
@@ -1109,6 +2018,396 @@ mod tests {
         // Should successfully parse and return a valid time
         assert!(start_tz <= Utc::now());
     }
+
+    #[test]
+    fn event_patch_omits_unset_fields() {
+        let body = build_event_request(
+            "Standup".to_string(),
+            None,
+            1_700_000_000_000,
+            1_700_001_800_000,
+            false,
+            Some("Room 4".to_string()),
+            None,
+            None,
+        )
+        .unwrap();
+        let json = serde_json::to_value(&body).unwrap();
+
+        assert_eq!(json["summary"], "Standup");
+        assert_eq!(json["location"], "Room 4");
+        assert!(json["start"]["dateTime"].is_string());
+        for unset in ["description", "attendees", "recurrence"] {
+            assert!(json.get(unset).is_none(), "{} should be left out", unset);
+        }
+        assert!(json.get("conferenceData").is_none());
+    }
+
+    #[test]
+    fn event_patch_sends_only_changed_fields() {
+        let json = serde_json::to_value(
+            build_event_patch(EventPatch {
+                summary: Some("Standup".to_string()),
+                description: Some(String::new()),
+                attendees: Some(Vec::new()),
+                ..Default::default()
+            })
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({"summary": "Standup", "description": "", "attendees": []})
+        );
+
+        let moved = serde_json::to_value(
+            build_event_patch(EventPatch {
+                times: Some((1_700_000_000_000, 1_700_001_800_000, false)),
+                ..Default::default()
+            })
+            .unwrap(),
+        )
+        .unwrap();
+        assert!(moved["start"]["dateTime"].is_string());
+        assert!(moved["end"]["dateTime"].is_string());
+        assert!(moved.get("summary").is_none());
+    }
+
+    fn events_page(ids: &[&str], next_page_token: Option<&str>) -> EventsListResponse {
+        serde_json::from_value(serde_json::json!({
+            "items": ids.iter().map(|id| serde_json::json!({"id": id})).collect::<Vec<_>>(),
+            "nextPageToken": next_page_token,
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn search_follows_every_result_page() {
+        let requested = std::sync::Mutex::new(Vec::new());
+        let events = collect_event_pages(50, |token: Option<String>| {
+            requested.lock().unwrap().push(token.clone());
+            async move {
+                match token.as_deref() {
+                    None => Some(events_page(&["e1", "e2"], Some("p2"))),
+                    Some("p2") => Some(events_page(&["e3"], None)),
+                    Some(_) => None,
+                }
+            }
+        })
+        .await
+        .unwrap();
+
+        let ids: Vec<_> = events.iter().map(|e| e.id.as_str()).collect();
+        assert_eq!(ids, vec!["e1", "e2", "e3"]);
+        assert_eq!(*requested.lock().unwrap(), vec![None, Some("p2".to_string())]);
+
+        // Stops once enough events are in, and a failed first page skips the calendar
+        let capped = collect_event_pages(2, |_| async { Some(events_page(&["a", "b"], Some("more"))) }).await;
+        assert_eq!(capped.unwrap().len(), 2);
+        assert!(collect_event_pages(2, |_| async { None }).await.is_none());
+    }
+
+    #[test]
+    fn cross_zone_event_keeps_its_time_zone() {
+        // 2024-03-04 10:00 in New York (UTC-5)
+        let start = 1_709_564_400_000;
+        let buenos_aires = -3 * 3600;
+        let new_york = -5 * 3600;
+
+        assert_eq!(foreign_time_zone(Some("America/New_York"), start, buenos_aires).as_deref(), Some("America/New_York"));
+        assert_eq!(foreign_time_zone(Some("America/New_York"), start, new_york), None);
+        assert_eq!(foreign_time_zone(None, start, buenos_aires), None);
+        assert_eq!(foreign_time_zone(Some("Not/AZone"), start, buenos_aires), None);
+
+        let api: ApiEvent = serde_json::from_value(serde_json::json!({
+            "id": "evt",
+            "start": {"dateTime": "2024-03-04T10:00:00-05:00", "timeZone": "America/New_York"}
+        }))
+        .unwrap();
+        assert_eq!(api.start.unwrap().time_zone.as_deref(), Some("America/New_York"));
+    }
+
+    #[test]
+    fn unshared_calendar_is_unknown() {
+        let response: FreeBusyResponse = serde_json::from_value(serde_json::json!({
+            "calendars": {
+                "ann@example.com": {
+                    "busy": [{"start": "2024-03-04T10:00:00Z", "end": "2024-03-04T11:00:00Z"}]
+                },
+                "bob@other.com": {
+                    "errors": [{"domain": "global", "reason": "notFound"}],
+                    "busy": []
+                }
+            }
+        }))
+        .unwrap();
+        let emails = vec!["ann@example.com".to_string(), "bob@other.com".to_string(), "cy@example.com".to_string()];
+
+        let availability = availability_from_response(&emails, response);
+        assert_eq!(availability[0].status, FreeBusyStatus::Known);
+        assert_eq!(availability[0].busy, vec![(1_709_546_400_000, 1_709_550_000_000)]);
+        assert_eq!(availability[1].status, FreeBusyStatus::Unknown);
+        assert_eq!(availability[2].status, FreeBusyStatus::Unknown);
+    }
+
+    #[test]
+    fn meet_request_and_link() {
+        let request = serde_json::to_value(ConferenceDataInput::meet()).unwrap();
+        assert_eq!(request["createRequest"]["conferenceSolutionKey"]["type"], "hangoutsMeet");
+        assert!(!request["createRequest"]["requestId"].as_str().unwrap().is_empty());
+
+        let conference: ApiConferenceData = serde_json::from_value(serde_json::json!({
+            "entryPoints": [
+                {"entryPointType": "phone", "uri": "tel:+1-555-0100"},
+                {"entryPointType": "video", "uri": "https://meet.google.com/abc-defg-hij"}
+            ]
+        }))
+        .unwrap();
+        assert_eq!(video_link(None, Some(conference)).as_deref(), Some("https://meet.google.com/abc-defg-hij"));
+        assert_eq!(video_link(Some("https://meet.google.com/x".to_string()), None).as_deref(), Some("https://meet.google.com/x"));
+    }
+
+    #[test]
+    fn availability_text_uses_slot_grid_and_names_timezone() {
+        let tz: Tz = "America/Los_Angeles".parse().unwrap();
+        let at = |d: u32, h: u32, m: u32| {
+            tz.with_ymd_and_hms(2026, 10, d, h, m, 0).unwrap().timestamp_millis()
+        };
+        // Monday Oct 19 through the weekend; a meeting 10:10-10:40 on Monday
+        let busy = vec![(at(19, 10, 10), at(19, 10, 40))];
+        let start = at(19, 0, 0);
+        let end = at(20, 0, 0);
+
+        // 30-minute slots: the meeting blocks 10:00-11:00
+        let slots = free_slots(&busy, start, end, tz, 30);
+        assert_eq!(slots, vec![(at(19, 9, 0), at(19, 10, 0)), (at(19, 11, 0), at(19, 17, 0))]);
+
+        // 15-minute slots: only 10:00-10:45 is lost
+        let quarter = free_slots(&busy, start, end, tz, 15);
+        assert_eq!(quarter[0], (at(19, 9, 0), at(19, 10, 0)));
+        assert_eq!(quarter[1], (at(19, 10, 45), at(19, 17, 0)));
+
+        let text = format_availability(&slots, tz);
+        assert!(text.contains("- Mon, Oct 19, 2026: 9:00 AM – 10:00 AM, 11:00 AM – 5:00 PM"));
+        assert!(text.ends_with("Times in America/Los_Angeles"));
+
+        // Weekends aren't offered
+        assert!(free_slots(&[], at(24, 0, 0), at(26, 0, 0), tz, 30).is_empty());
+    }
+
+    #[test]
+    fn only_scope_errors_ask_for_calendar_access() {
+        let forbidden = |reason: &str| {
+            friendly_calendar_error(
+                StatusCode::FORBIDDEN,
+                &format!(r#"{{"error": {{"code": 403, "errors": [{{"domain": "global", "reason": "{}"}}]}}}}"#, reason),
+            )
+        };
+        assert!(forbidden("insufficientPermissions").starts_with("Calendar scope not granted"));
+        assert!(friendly_calendar_error(
+            StatusCode::FORBIDDEN,
+            r#"{"error": {"details": [{"reason": "ACCESS_TOKEN_SCOPE_INSUFFICIENT"}]}}"#
+        )
+        .starts_with("Calendar scope not granted"));
+
+        assert!(forbidden("rateLimitExceeded").starts_with("Too many requests"));
+        assert!(forbidden("userRateLimitExceeded").starts_with("Too many requests"));
+        assert_eq!(forbidden("forbiddenForNonOrganizer"), "Only the organizer can change this event.");
+        assert_eq!(forbidden("forbidden"), "Calendar access denied.");
+    }
+
+    /// An accepted event with no end time on the primary calendar; tests
+    /// override the fields they care about
+    fn event(title: &str, start_time: i64) -> CalendarEvent {
+        CalendarEvent {
+            id: "evt1".to_string(),
+            calendar_id: "primary".to_string(),
+            calendar_name: "Work".to_string(),
+            title: title.to_string(),
+            description: None,
+            location: None,
+            start_time,
+            end_time: None,
+            all_day: false,
+            status: "confirmed".to_string(),
+            organizer: None,
+            attendees: Vec::new(),
+            html_link: None,
+            hangout_link: None,
+            response_status: Some("accepted".to_string()),
+            can_edit: true,
+            color_id: None,
+            time_zone: None,
+        }
+    }
+
+    fn attendee(email: &str, status: &str) -> CalEventAttendee {
+        CalEventAttendee {
+            email: email.to_string(),
+            response_status: Some(status.to_string()),
+            is_self: None,
+        }
+    }
+
+    #[test]
+    fn repeated_rsvp_needs_no_patch() {
+        let attendees = vec![attendee("host@example.com", "accepted"), attendee("Me@Example.com", "accepted")];
+        assert!(rsvp_attendees(attendees.clone(), "me@example.com", "accepted").is_none());
+
+        let patched = rsvp_attendees(attendees, "me@example.com", "declined").unwrap();
+        assert_eq!(self_response_status(&patched, "me@example.com"), Some("declined"));
+        assert_eq!(self_response_status(&patched, "host@example.com"), Some("accepted"));
+    }
+
+    #[test]
+    fn rsvp_adds_missing_self() {
+        let patched = rsvp_attendees(vec![attendee("host@example.com", "accepted")], "me@example.com", "tentative").unwrap();
+        assert_eq!(patched.len(), 2);
+        assert_eq!(self_response_status(&patched, "me@example.com"), Some("tentative"));
+    }
+
+    fn rule(keyword: &str, color: &str) -> ColorRule {
+        ColorRule {
+            keyword: keyword.to_string(),
+            color: color.to_string(),
+        }
+    }
+
+    #[test]
+    fn one_on_one_matches_green_rule() {
+        let rules = vec![rule("Interview", "red"), rule("1:1", "green")];
+        assert_eq!(match_color_rule("1:1 with Sam", &rules), Some("10"));
+        assert_eq!(match_color_rule("Onsite interview loop", &rules), Some("11"));
+        assert_eq!(match_color_rule("Standup", &rules), None);
+    }
+
+    #[test]
+    fn validates_colors_against_palette() {
+        assert_eq!(resolve_color_id("7"), Some("7"));
+        assert_eq!(resolve_color_id("Basil"), Some("10"));
+        assert_eq!(resolve_color_id("12"), None);
+        assert_eq!(resolve_color_id("chartreuse"), None);
+    }
+
+    fn at(date: NaiveDate, h: u32, m: u32) -> NaiveDateTime {
+        date.and_hms_opt(h, m, 0).unwrap()
+    }
+
+    #[test]
+    fn parses_two_line_agenda() {
+        let day = NaiveDate::from_ymd_opt(2024, 3, 18).unwrap();
+        let (items, unparsed) = parse_agenda("10:00 Standup\n14:00-15:00 Review\n", day);
+
+        assert!(unparsed.is_empty());
+        assert_eq!(
+            items,
+            vec![
+                AgendaItem {
+                    title: "Standup".to_string(),
+                    start: at(day, 10, 0),
+                    end: at(day, 10, 30),
+                },
+                AgendaItem {
+                    title: "Review".to_string(),
+                    start: at(day, 14, 0),
+                    end: at(day, 15, 0),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_unparseable_lines() {
+        let day = NaiveDate::from_ymd_opt(2024, 3, 18).unwrap();
+        let (items, unparsed) = parse_agenda("- 2pm Dentist\nlunch sometime\n\n16:00-15:00 Backwards", day);
+
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].start, at(day, 14, 0));
+        assert_eq!(unparsed, vec!["lunch sometime", "16:00-15:00 Backwards"]);
+    }
+
+    #[test]
+    fn exported_calendar_parses_back() {
+        let mut review = event("Review; budget, Q3", 1_705_312_800_000);
+        review.end_time = Some(1_705_316_400_000);
+        review.location = Some("Room 4, Building B".to_string());
+        review.description = Some(format!("Agenda:\n{}", "Go over every line item before the offsite. ".repeat(4).trim_end()));
+        let offsite = CalendarEvent {
+            id: "evt2".to_string(),
+            end_time: Some(1_705_449_600_000),
+            all_day: true,
+            ..event("Offsite — día libre", 1_705_363_200_000)
+        };
+
+        let ics = events_to_ics(&[review.clone(), offsite.clone()]);
+        assert!(ics.lines().all(|line| line.len() <= 75));
+        assert!(ics.contains("DTSTART;VALUE=DATE:20240116"));
+
+        let parsed = crate::gmail::parse_ics_events(&ics);
+        assert_eq!(parsed.len(), 2);
+        for (original, parsed) in [review, offsite].iter().zip(&parsed) {
+            assert_eq!(parsed.uid.as_deref(), Some(original.id.as_str()));
+            assert_eq!(parsed.title, original.title);
+            assert_eq!(parsed.start_time, original.start_time);
+            assert_eq!(parsed.end_time, original.end_time);
+            assert_eq!(parsed.all_day, original.all_day);
+            assert_eq!(parsed.location, original.location);
+            assert_eq!(parsed.description, original.description);
+        }
+    }
+
+    #[test]
+    fn keyword_matches_event_months_out() {
+        let in_90_days = (Utc::now() + Duration::days(90)).timestamp_millis();
+        let query = CalendarQuery::upcoming_text("offsite", 365);
+
+        let (start, end) = query.get_time_range(None);
+        assert!(start.timestamp_millis() <= in_90_days && in_90_days < end.timestamp_millis());
+
+        let planning = CalendarEvent {
+            description: Some("Agenda for the team offsite".to_string()),
+            ..event("Planning", in_90_days)
+        };
+        assert!(query.matches(&planning));
+        assert!(query.matches(&event("Offsite logistics", in_90_days)));
+        // Matched by Google only through an attendee, not a text field
+        assert!(!query.matches(&event("1:1", in_90_days)));
+    }
+
+    #[test]
+    fn limit_applies_to_merged_calendars() {
+        let soon = (Utc::now() + Duration::days(1)).timestamp_millis();
+        let at = |hours: i64| soon + hours * 3_600_000;
+        // Two calendars fetched up to the limit each, earliest events interleaved
+        let work = [1, 3, 5].map(|h| event("Offsite prep", at(h)));
+        let home = [2, 4, 6].map(|h| event("Offsite travel", at(h)));
+        let query = CalendarQuery::upcoming_text("offsite", 30);
+
+        let merged = merge_search_results(work.into_iter().chain(home).collect(), &query, 3);
+        assert_eq!(merged.iter().map(|e| e.start_time).collect::<Vec<_>>(), vec![at(1), at(2), at(3)]);
+    }
+
+    #[test]
+    fn declined_events_are_hidden_unless_asked_for() {
+        let accepted = event("Planning", 1_709_546_400_000);
+        let declined = CalendarEvent {
+            response_status: Some("declined".to_string()),
+            ..accepted.clone()
+        };
+
+        // Every search hides them by default, card queries and keyword search alike
+        let mut query = CalendarQuery::parse("calendar:week");
+        assert!(!query.matches(&declined));
+        assert!(query.matches(&accepted));
+        assert!(!CalendarQuery::upcoming_text("Planning", 30).matches(&declined));
+
+        query.hide_declined = false;
+        assert!(query.matches(&declined));
+
+        // Asking for declined events explicitly still shows them
+        let explicit = CalendarQuery::parse("calendar:week response:declined");
+        assert!(explicit.matches(&declined));
+    }
 }
 
 #[cfg(test)]
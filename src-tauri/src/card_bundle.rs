@@ -0,0 +1,194 @@
+// Shareable card bundles
+//
+// A bundle is the JSON a team shares to hand out a set of cards (e.g.
+// onboarding queries). It is either `{ "cards": [...] }` or a bare card
+// array, the shape of the iCloud card backup. Account-specific fields such
+// as ids and positions are ignored on import.
+
+use crate::models::Card;
+use serde::{Deserialize, Serialize};
+
+/// Longest query accepted from a bundle
+const MAX_QUERY_LEN: usize = 500;
+/// Most cards accepted from a single bundle
+pub const MAX_BUNDLE_CARDS: usize = 100;
+
+/// Search operators Gmail understands in email card queries
+const GMAIL_OPERATORS: &[&str] = &[
+    "from", "to", "cc", "bcc", "subject", "label", "has", "is", "in", "after", "before", "older",
+    "newer", "older_than", "newer_than", "category", "filename", "larger", "smaller", "size", "list",
+    "deliveredto", "rfc822msgid", "around",
+];
+
+/// Operators understood by calendar card queries (see `CalendarQuery::parse`)
+const CALENDAR_OPERATORS: &[&str] = &[
+    "calendar", "with", "organizer", "location", "status", "response",
+];
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct BundleCard {
+    pub name: String,
+    pub query: String,
+    #[serde(default)]
+    pub color: Option<String>,
+    #[serde(default)]
+    pub group_by: Option<String>,
+    #[serde(default)]
+    pub card_type: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum BundleFile {
+    Bundle { cards: Vec<BundleCard> },
+    Cards(Vec<BundleCard>),
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ImportCardsResult {
+    pub created: usize,
+    /// Cards the account already had (same name and query)
+    pub skipped: usize,
+    /// Cards rejected by validation, with the reason
+    pub invalid: Vec<String>,
+}
+
+/// Parse a bundle's JSON into its cards
+pub fn parse_bundle(json: &str) -> Result<Vec<BundleCard>, String> {
+    let cards = match serde_json::from_str::<BundleFile>(json) {
+        Ok(BundleFile::Bundle { cards }) | Ok(BundleFile::Cards(cards)) => cards,
+        Err(e) => return Err(format!("Not a card bundle: {}", e)),
+    };
+    if cards.len() > MAX_BUNDLE_CARDS {
+        return Err(format!("Bundle has {} cards (limit {})", cards.len(), MAX_BUNDLE_CARDS));
+    }
+    Ok(cards)
+}
+
+/// Check a query only uses operators its card type understands, so a bundle
+/// can't smuggle in anything the search box wouldn't accept
+pub fn validate_query(query: &str, card_type: &str) -> Result<(), String> {
+    if query.trim().is_empty() {
+        return Err("empty query".to_string());
+    }
+    if query.len() > MAX_QUERY_LEN {
+        return Err(format!("query longer than {} characters", MAX_QUERY_LEN));
+    }
+    if query.chars().any(|c| c.is_control()) {
+        return Err("query contains control characters".to_string());
+    }
+
+    let operators = if card_type == "calendar" { CALENDAR_OPERATORS } else { GMAIL_OPERATORS };
+    for token in query.split_whitespace() {
+        let token = token.trim_start_matches(['-', '(', '{']);
+        let Some((operator, _)) = token.split_once(':') else {
+            continue;
+        };
+        // Quoted phrases may contain colons ("re: budget")
+        if operator.is_empty() || operator.starts_with('"') {
+            continue;
+        }
+        if !operators.contains(&operator.to_ascii_lowercase().as_str()) {
+            return Err(format!("unknown operator '{}:'", operator));
+        }
+    }
+    Ok(())
+}
+
+/// Split a bundle into cards to create for `account_id` and a result that
+/// counts skipped and invalid ones. Existing cards match on name and query
+/// (name case-insensitively), and so do duplicates within the bundle.
+pub fn plan_import(bundle: Vec<BundleCard>, existing: &[Card], account_id: &str) -> (Vec<Card>, ImportCardsResult) {
+    let mut result = ImportCardsResult::default();
+    let mut seen: Vec<(String, String)> = existing
+        .iter()
+        .map(|c| (c.name.trim().to_lowercase(), c.query.trim().to_string()))
+        .collect();
+    let mut position = existing.len() as i32;
+    let mut to_create = Vec::new();
+
+    for entry in bundle {
+        let name = entry.name.trim().to_string();
+        let query = entry.query.trim().to_string();
+        let card_type = match entry.card_type.as_deref() {
+            None | Some("email") => "email",
+            Some("calendar") => "calendar",
+            Some(other) => {
+                result.invalid.push(format!("{}: unknown card type '{}'", name, other));
+                continue;
+            }
+        };
+        if name.is_empty() {
+            result.invalid.push(format!("(unnamed): {}", query));
+            continue;
+        }
+        if let Err(reason) = validate_query(&query, card_type) {
+            result.invalid.push(format!("{}: {}", name, reason));
+            continue;
+        }
+
+        let key = (name.to_lowercase(), query.clone());
+        if seen.contains(&key) {
+            result.skipped += 1;
+            continue;
+        }
+        seen.push(key);
+
+        let account_id = account_id.to_string();
+        let mut card = if card_type == "calendar" {
+            Card::new_calendar(account_id, name, query, position)
+        } else {
+            Card::new(account_id, name, query, position)
+        };
+        card.color = entry.color;
+        if let Some(group_by) = entry.group_by {
+            card.group_by = group_by;
+        }
+        to_create.push(card);
+        position += 1;
+    }
+
+    result.created = to_create.len();
+    (to_create, result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn imports_new_cards_and_skips_existing() {
+        let existing = vec![Card::new("acc".to_string(), "Team".to_string(), "from:team@corp.com".to_string(), 0)];
+        let bundle = parse_bundle(
+            r#"{"cards": [
+                {"name": "team", "query": "from:team@corp.com"},
+                {"name": "Onboarding", "query": "label:onboarding is:unread", "color": "green"}
+            ]}"#,
+        )
+        .unwrap();
+
+        let (created, result) = plan_import(bundle, &existing, "acc");
+        assert_eq!(result.created, 1);
+        assert_eq!(result.skipped, 1);
+        assert!(result.invalid.is_empty());
+        assert_eq!(created[0].name, "Onboarding");
+        assert_eq!(created[0].account_id, "acc");
+        assert_eq!(created[0].position, 1);
+        assert_eq!(created[0].color.as_deref(), Some("green"));
+    }
+
+    #[test]
+    fn rejects_unknown_operators() {
+        assert!(validate_query("from:boss is:unread -label:done", "email").is_ok());
+        assert!(validate_query("subject:\"re: budget\"", "email").is_ok());
+        assert!(validate_query("calendar:7d with:ann", "calendar").is_ok());
+        assert!(validate_query("javascript:alert(1)", "email").is_err());
+        assert!(validate_query("from:boss\u{0}", "email").is_err());
+
+        // Bare arrays (the backup shape) parse too
+        let bundle = parse_bundle(r#"[{"id": "x", "account_id": "old", "name": "Bad", "query": "evil:1", "position": 3}]"#).unwrap();
+        let (created, result) = plan_import(bundle, &[], "acc");
+        assert!(created.is_empty());
+        assert_eq!(result.invalid, vec!["Bad: unknown operator 'evil:'"]);
+    }
+}
@@ -1,18 +1,21 @@
 // Tauri command handlers
 
 use crate::auth::{self, CallbackServer, GmailAuth};
-use crate::ai::GeminiClient;
-use crate::cache::CacheDb;
+use crate::ai::{AiBackend, AiProvider, GeminiClient};
+use crate::cache::{CacheDb, PrefetchBuffer};
+use crate::debounce::Debouncer;
+use crate::gmail::base64util::decode_gmail_b64;
 use crate::gmail::{GmailClient, GmailDraft, GmailLabel, SearchResult};
 use crate::icloud::ICloudKVStore;
-use crate::models::{Account, Card, SendAttachment, ThreadGroup};
+use crate::inflight::{run_cancellable, InflightRequests};
+use crate::models::{Account, AccountAppearance, Card, SendAttachment, ThreadGroup};
 use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
-use tauri::{Manager, State};
+use tauri::{Emitter, Manager, State};
 
 use tokio::sync::Mutex;
 
@@ -24,6 +27,16 @@ pub struct AppState {
     pub oauth_cancel: Arc<std::sync::Mutex<Option<Arc<AtomicBool>>>>,
     /// Cached access tokens per account_id; never hold this lock across an await
     pub token_cache: Arc<std::sync::Mutex<HashMap<String, (String, Instant)>>>,
+    /// Next page of each card, fetched ahead of "load more"
+    pub prefetch: Arc<std::sync::Mutex<PrefetchBuffer<SearchResult>>>,
+    /// Abort handles for fetches tagged with a request id
+    pub inflight: Arc<std::sync::Mutex<InflightRequests>>,
+    /// Battery saver: background jobs skip their work while set
+    pub background_paused: Arc<AtomicBool>,
+    /// Coalesces card edits into one iCloud write
+    pub icloud_sync: Debouncer,
+    /// Outcome of each account's recent token refreshes
+    pub token_health: Arc<std::sync::Mutex<auth::TokenHealth>>,
 }
 
 impl AppState {
@@ -34,6 +47,11 @@ impl AppState {
             icloud: Arc::new(std::sync::Mutex::new(ICloudKVStore::new())),
             oauth_cancel: Arc::new(std::sync::Mutex::new(None)),
             token_cache: Arc::new(std::sync::Mutex::new(HashMap::new())),
+            prefetch: Arc::new(std::sync::Mutex::new(PrefetchBuffer::default())),
+            inflight: Arc::new(std::sync::Mutex::new(InflightRequests::default())),
+            background_paused: Arc::new(AtomicBool::new(false)),
+            icloud_sync: Debouncer::new(ICLOUD_SYNC_DEBOUNCE),
+            token_health: Arc::new(std::sync::Mutex::new(auth::TokenHealth::default())),
         }
     }
 }
@@ -47,7 +65,7 @@ impl Default for AppState {
 // --- Helper functions to reduce boilerplate ---
 
 /// Get app data directory from handle
-fn get_app_data_dir(app_handle: &tauri::AppHandle) -> Result<std::path::PathBuf, String> {
+pub(crate) fn get_app_data_dir(app_handle: &tauri::AppHandle) -> Result<std::path::PathBuf, String> {
     app_handle
         .path()
         .app_data_dir()
@@ -55,7 +73,7 @@ fn get_app_data_dir(app_handle: &tauri::AppHandle) -> Result<std::path::PathBuf,
 }
 
 /// Execute a closure with database access
-fn with_db<T, F>(state: &AppState, f: F) -> Result<T, String>
+pub(crate) fn with_db<T, F>(state: &AppState, f: F) -> Result<T, String>
 where
     F: FnOnce(&CacheDb) -> Result<T, String>,
 {
@@ -64,6 +82,25 @@ where
     f(db)
 }
 
+/// App setting holding the per-card-load inline image budget
+const INLINE_FETCH_BUDGET_SETTING: &str = "inline_fetch_budget";
+
+/// Upper bound accepted by `set_inline_fetch_budget`
+const MAX_INLINE_FETCH_BUDGET: usize = 200;
+
+fn inline_fetch_budget(state: &AppState) -> usize {
+    with_db(state, |db| db.get_setting(INLINE_FETCH_BUDGET_SETTING).map_err(|e| e.to_string()))
+        .ok()
+        .flatten()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(crate::gmail::DEFAULT_INLINE_FETCH_BUDGET)
+}
+
+/// Gmail client for loading a card's threads, with the configured inline image budget
+fn card_load_client(state: &AppState, access_token: String) -> GmailClient {
+    GmailClient::new(access_token).with_inline_fetch_budget(inline_fetch_budget(state))
+}
+
 /// Verify that an account exists
 fn verify_account_exists(state: &AppState, account_id: &str) -> Result<(), String> {
     with_db(state, |db| {
@@ -88,9 +125,22 @@ fn get_account_email(state: &AppState, account_id: &str) -> Result<String, Strin
     })
 }
 
-// Sync all cards to iCloud after any card operation
+/// Quiet period after the last card edit before cards are written to iCloud
+const ICLOUD_SYNC_DEBOUNCE: Duration = Duration::from_secs(2);
+
+// Sync all cards to iCloud after any card operation. Bursts of edits are
+// coalesced into a single write once they settle.
 fn sync_cards_to_icloud(state: &AppState) {
-    let db_guard = match state.db.lock() {
+    let db = state.db.clone();
+    let icloud = state.icloud.clone();
+    state.icloud_sync.call(move || write_cards_to_icloud(&db, &icloud));
+}
+
+fn write_cards_to_icloud(
+    db: &std::sync::Mutex<Option<CacheDb>>,
+    icloud: &std::sync::Mutex<ICloudKVStore>,
+) {
+    let db_guard = match db.lock() {
         Ok(g) => g,
         Err(_) => return,
     };
@@ -107,6 +157,7 @@ fn sync_cards_to_icloud(state: &AppState) {
 
     let mut all_cards = Vec::new();
     let mut account_mappings = std::collections::HashMap::new();
+    let account_appearances = account_appearances(&accounts);
 
     for account in &accounts {
         // Build account_id -> email mapping for iCloud restore
@@ -119,9 +170,10 @@ fn sync_cards_to_icloud(state: &AppState) {
 
     // Sync to iCloud (no-op on non-iOS)
     drop(db_guard); // Release db lock before acquiring icloud lock
-    if let Ok(icloud) = state.icloud.lock() {
+    if let Ok(icloud) = icloud.lock() {
         let _ = icloud.sync_cards(&all_cards);
         let _ = icloud.sync_account_mappings(&account_mappings);
+        let _ = icloud.sync_account_appearances(&account_appearances);
     }
 }
 
@@ -167,6 +219,22 @@ pub fn init_app(app_handle: tauri::AppHandle, state: State<'_, AppState>) -> Res
         }
         Err(e) => tracing::warn!("Failed to clean card cache: {}", e),
     }
+    match db.clear_old_attachments(ATTACHMENT_CACHE_MAX_AGE_HOURS) {
+        Ok(count) => {
+            if count > 0 {
+                tracing::info!("Cleaned up {} cached attachments", count);
+            }
+        }
+        Err(e) => tracing::warn!("Failed to clean attachment cache: {}", e),
+    }
+
+    // Battery saver stays on across restarts
+    let paused = db
+        .get_setting(crate::background::BACKGROUND_PAUSED_SETTING)
+        .ok()
+        .flatten()
+        .is_some_and(|v| v == "true");
+    state.background_paused.store(paused, Ordering::SeqCst);
 
     let mut db_guard = state.db.lock().map_err(|_| "Lock error".to_string())?;
     *db_guard = Some(db);
@@ -175,6 +243,20 @@ pub fn init_app(app_handle: tauri::AppHandle, state: State<'_, AppState>) -> Res
     Ok(())
 }
 
+/// Parse a pasted mailto: link, rejecting ones with nothing to compose or
+/// with malformed recipients
+#[tauri::command]
+pub fn parse_mailto_command(url: String) -> Result<crate::MailtoData, String> {
+    if !url.trim_start().to_ascii_lowercase().starts_with("mailto:") {
+        return Err("Not a mailto: link".to_string());
+    }
+    let data = crate::parse_mailto(url.trim());
+    if !data.is_valid() {
+        return Err("Invalid mailto: link".to_string());
+    }
+    Ok(data)
+}
+
 #[tauri::command]
 pub async fn configure_auth(config: AuthConfig, app_handle: tauri::AppHandle, state: State<'_, AppState>) -> Result<(), String> {
     let app_data_dir = get_app_data_dir(&app_handle)?;
@@ -202,6 +284,46 @@ pub fn get_stored_credentials(app_handle: tauri::AppHandle) -> Result<Option<Aut
     }
 }
 
+/// App setting holding the optional scopes to request at sign-in (JSON array)
+const REQUESTED_SCOPES_SETTING: &str = "requested_scopes";
+
+/// Optional scopes chosen in settings; all of them until the user opts out
+fn requested_scopes(state: &AppState) -> Vec<String> {
+    with_db(state, |db| db.get_setting(REQUESTED_SCOPES_SETTING).map_err(|e| e.to_string()))
+        .ok()
+        .flatten()
+        .and_then(|v| serde_json::from_str(&v).ok())
+        .unwrap_or_else(|| auth::OPTIONAL_SCOPES.iter().map(|s| s.to_string()).collect())
+}
+
+fn requested_scope_set(state: &AppState) -> Result<auth::ScopeSet, String> {
+    auth::ScopeSet::from_selected(&requested_scopes(state))
+}
+
+/// Optional OAuth scopes and which of them the next sign-in asks for
+#[derive(Debug, Serialize)]
+pub struct ScopeSelection {
+    pub supported: Vec<&'static str>,
+    pub requested: Vec<String>,
+}
+
+#[tauri::command]
+pub fn get_requested_scopes(state: State<'_, AppState>) -> ScopeSelection {
+    ScopeSelection {
+        supported: auth::OPTIONAL_SCOPES.to_vec(),
+        requested: requested_scopes(&state),
+    }
+}
+
+/// Choose the optional scopes to request. Takes effect at the next sign-in;
+/// mail access is always requested.
+#[tauri::command]
+pub fn set_requested_scopes(scopes: Vec<String>, state: State<'_, AppState>) -> Result<(), String> {
+    auth::ScopeSet::from_selected(&scopes)?;
+    let value = serde_json::to_string(&scopes).map_err(|e| e.to_string())?;
+    with_db(&state, |db| db.set_setting(REQUESTED_SCOPES_SETTING, &value).map_err(|e| e.to_string()))
+}
+
 #[tauri::command]
 pub async fn start_oauth_flow(state: State<'_, AppState>) -> Result<AuthUrl, String> {
     let auth_guard = state.auth.lock().await;
@@ -209,8 +331,10 @@ pub async fn start_oauth_flow(state: State<'_, AppState>) -> Result<AuthUrl, Str
         .as_ref()
         .ok_or("Auth not configured. Call configure_auth first.")?;
 
+    let scopes = requested_scope_set(&state)?;
+    let redirect_uri = auth::redirect_uri(*auth::CALLBACK_PORTS.start());
     let (url, csrf_token) = auth
-        .start_auth_flow()
+        .start_auth_flow(&redirect_uri, &scopes)
         .await
         .map_err(|e| e.to_string())?;
 
@@ -249,7 +373,7 @@ pub async fn run_oauth_flow(
 ) -> Result<Account, String> {
     let _app_data_dir = get_app_data_dir(&app_handle)?;
 
-    // Cancel any previous in-flight flow so it releases port 8420 promptly
+    // Cancel any previous in-flight flow so it releases its callback port promptly
     let cancel_flag = {
         let mut slot = state.oauth_cancel.lock().map_err(|_| "Lock error")?;
         if let Some(prev) = slot.take() {
@@ -261,8 +385,9 @@ pub async fn run_oauth_flow(
     };
 
     // Bind the callback listener BEFORE opening the browser so the redirect
-    // can't race the bind. Retry briefly: a just-cancelled flow may still be
-    // releasing the port (its wait loop polls every ~100ms).
+    // can't race the bind. Busy ports fall through to the next in range; retry
+    // briefly in case a just-cancelled flow is still releasing its port (its
+    // wait loop polls every ~100ms).
     let server = tokio::task::spawn_blocking(|| {
         let mut last_err = String::new();
         for _ in 0..20 {
@@ -279,6 +404,8 @@ pub async fn run_oauth_flow(
     .await
     .map_err(|e| format!("Task error: {}", e))?
     .map_err(|e| format!("OAuth callback error: {}", e))?;
+    let redirect_uri = server.redirect_uri();
+    let scopes = requested_scope_set(&state)?;
 
     // Start OAuth flow and get authorization URL
     let (auth_url, _csrf_token) = {
@@ -287,7 +414,7 @@ pub async fn run_oauth_flow(
             .as_ref()
             .ok_or("Auth not configured. Call configure_auth first.")?;
 
-        auth.start_auth_flow()
+        auth.start_auth_flow(&redirect_uri, &scopes)
             .await
             .map_err(|e| e.to_string())?
     };
@@ -341,28 +468,35 @@ async fn finalize_oauth(
     // Get user info from Google API
     let user_info = get_user_info(access_token).await?;
 
+    // Get app data directory for secure storage
+    let app_data_dir = get_app_data_dir(&app_handle)?;
+
     // Reuse the existing account id on re-login so cards keep pointing at it;
     // only mint a new UUID for genuinely new emails
-    let existing = with_db(&state, |db| {
-        db.get_account_by_email(&user_info.email).map_err(|e| e.to_string())
+    let (account, is_new) = with_db(&state, |db| {
+        let is_new = db.get_account_by_email(&user_info.email).map_err(|e| e.to_string())?.is_none();
+        let account = db
+            .upsert_account_by_email(&user_info.email, user_info.picture)
+            .map_err(|e| e.to_string())?;
+        Ok((account, is_new))
     })?;
-    let account = match existing {
-        Some(mut account) => {
-            account.picture = user_info.picture;
-            account
-        }
-        None => Account::new(user_info.email, user_info.picture),
-    };
-
-    // Get app data directory for secure storage
-    let app_data_dir = get_app_data_dir(&app_handle)?;
 
-    // Store refresh token securely
-    auth::store_refresh_token(&account.id, refresh_token, &app_data_dir)
-        .map_err(|e| e.to_string())?;
+    // Store refresh token securely. A new account without one could never
+    // sync, so don't leave its row behind.
+    if let Err(e) = auth::store_refresh_token(&account.id, refresh_token, &app_data_dir) {
+        if is_new {
+            if let Err(delete_error) = with_db(&state, |db| db.delete_account(&account.id).map_err(|e| e.to_string())) {
+                tracing::warn!("Failed to remove account {} after keyring error: {}", account.id, delete_error);
+            }
+        }
+        return Err(e.to_string());
+    }
 
-    // Save account to database
-    with_db(&state, |db| db.insert_account(&account).map_err(|e| e.to_string()))?;
+    // Adopt cards from any older account row for the same email
+    let merged = merge_duplicate_accounts(&account.email, &account.id, &app_data_dir, state)?;
+    if merged > 0 {
+        sync_cards_to_icloud(state);
+    }
 
     // Cache the fresh access token, replacing any stale entry for this account
     let expiry = Instant::now() + Duration::from_secs(expires_in.unwrap_or(3600));
@@ -411,9 +545,17 @@ pub fn get_accounts(state: State<'_, AppState>) -> Result<Vec<Account>, String>
 }
 
 #[tauri::command]
-pub fn delete_account(account_id: String, app_handle: tauri::AppHandle, state: State<'_, AppState>) -> Result<(), String> {
+pub async fn delete_account(account_id: String, app_handle: tauri::AppHandle, state: State<'_, AppState>) -> Result<(), String> {
     let app_data_dir = get_app_data_dir(&app_handle)?;
 
+    // Revoke with Google so removing the account also ends the app's access.
+    // Non-fatal: the local account is removed either way.
+    if let Ok(refresh_token) = auth::get_refresh_token(&account_id, &app_data_dir) {
+        if let Err(e) = GmailAuth::revoke_token(&refresh_token).await {
+            tracing::warn!("Failed to revoke token for account {}: {}", account_id, e);
+        }
+    }
+
     // Delete from database
     with_db(&state, |db| db.delete_account(&account_id).map_err(|e| e.to_string()))?;
 
@@ -424,10 +566,119 @@ pub fn delete_account(account_id: String, app_handle: tauri::AppHandle, state: S
     if let Ok(mut cache) = state.token_cache.lock() {
         cache.remove(&account_id);
     }
+    if let Ok(mut health) = state.token_health.lock() {
+        health.remove(&account_id);
+    }
+
+    Ok(())
+}
+
+/// Token refresh status of every account, for the settings health dots
+#[tauri::command]
+pub fn account_health(state: State<'_, AppState>) -> Result<Vec<auth::AccountHealth>, String> {
+    let accounts = with_db(&state, |db| db.get_accounts().map_err(|e| e.to_string()))?;
+    let health = state.token_health.lock().map_err(|_| "Lock error")?;
+    Ok(accounts.iter().map(|a| health.health(&a.id)).collect())
+}
+
+/// Fold older account rows for `email` into `new_account_id`, removing their
+/// stored credentials. Returns how many stale accounts were merged.
+fn merge_duplicate_accounts(
+    email: &str,
+    new_account_id: &str,
+    app_data_dir: &std::path::Path,
+    state: &AppState,
+) -> Result<usize, String> {
+    let stale_ids = with_db(state, |db| {
+        db.merge_accounts_by_email(email, new_account_id).map_err(|e| e.to_string())
+    })?;
+
+    for stale_id in &stale_ids {
+        let _ = auth::delete_refresh_token(stale_id, app_data_dir);
+        if let Ok(mut cache) = state.token_cache.lock() {
+            cache.remove(stale_id);
+        }
+    }
+
+    Ok(stale_ids.len())
+}
+
+/// Move cards from any other account with `old_email` onto `new_account_id`
+/// and delete the stale accounts. Returns how many accounts were merged.
+#[tauri::command]
+pub fn merge_accounts_by_email(
+    old_email: String,
+    new_account_id: String,
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> Result<usize, String> {
+    let app_data_dir = get_app_data_dir(&app_handle)?;
+    verify_account_exists(&state, &new_account_id)?;
+
+    let merged = merge_duplicate_accounts(&old_email, &new_account_id, &app_data_dir, &state)?;
+    if merged > 0 {
+        sync_cards_to_icloud(&state);
+    }
+    Ok(merged)
+}
+
+/// Set the nickname and color used to tell accounts apart. Empty values clear them.
+#[tauri::command]
+pub fn set_account_appearance(
+    account_id: String,
+    nickname: Option<String>,
+    color: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    verify_account_exists(&state, &account_id)?;
+    let nickname = nickname.map(|n| n.trim().to_string()).filter(|n| !n.is_empty());
+    let color = color.map(|c| c.trim().to_string()).filter(|c| !c.is_empty());
+    with_db(&state, |db| {
+        db.update_account_appearance(&account_id, nickname.as_deref(), color.as_deref())
+            .map_err(|e| e.to_string())
+    })?;
 
+    sync_cards_to_icloud(&state);
     Ok(())
 }
 
+/// Every account's nickname and color by lowercased email. Accounts without
+/// them are included too, so clearing one reaches the other devices.
+fn account_appearances(accounts: &[Account]) -> HashMap<String, AccountAppearance> {
+    accounts
+        .iter()
+        .map(|account| {
+            (
+                account.email.to_lowercase(),
+                AccountAppearance {
+                    nickname: account.nickname.clone(),
+                    color: account.color.clone(),
+                },
+            )
+        })
+        .collect()
+}
+
+/// Apply nicknames and colors synced from another device, matched by email;
+/// a missing nickname or color clears it. Returns true if any account changed.
+fn apply_account_appearances(
+    db: &CacheDb,
+    appearances: &HashMap<String, AccountAppearance>,
+) -> Result<bool, String> {
+    let mut changed = false;
+    for account in db.get_accounts().map_err(|e| e.to_string())? {
+        let Some(appearance) = appearances.get(&account.email.to_lowercase()) else {
+            continue;
+        };
+        if account.nickname != appearance.nickname || account.color != appearance.color {
+            db.update_account_appearance(&account.id, appearance.nickname.as_deref(), appearance.color.as_deref())
+                .map_err(|e| e.to_string())?;
+            changed = true;
+        }
+    }
+    Ok(changed)
+}
+
 #[tauri::command]
 pub fn update_account_signature(account_id: String, signature: Option<String>, state: State<'_, AppState>) -> Result<(), String> {
     with_db(&state, |db| db.update_account_signature(&account_id, signature.as_deref()).map_err(|e| e.to_string()))
@@ -452,14 +703,16 @@ pub fn create_card(
         let cards = db.get_cards(&account_id).map_err(|e| e.to_string())?;
         let position = cards.len() as i32;
 
-        let card_type_value = card_type.unwrap_or_else(|| "email".to_string());
-        let mut card = if card_type_value == "calendar" {
+        // Anything but an explicit calendar card is an email card
+        let mut card = if card_type.as_deref() == Some("calendar") {
             Card::new_calendar(account_id, name, query, position)
         } else {
             Card::new(account_id, name, query, position)
         };
         card.color = color;
-        card.group_by = group_by.unwrap_or_else(|| "date".to_string());
+        if let Some(group_by) = group_by {
+            card.group_by = group_by;
+        }
         db.insert_card(&card).map_err(|e| e.to_string())?;
         Ok(card)
     })?;
@@ -468,9 +721,61 @@ pub fn create_card(
     Ok(card)
 }
 
+/// Largest card bundle accepted from a URL
+const MAX_BUNDLE_BYTES: usize = 1_000_000;
+
+/// Fetch a shared card bundle and add its cards to an account. Cards the
+/// account already has (same name and query) are skipped, and cards whose
+/// queries fail validation are reported rather than created.
+#[tauri::command]
+pub async fn import_cards_from_url(
+    account_id: String,
+    url: String,
+    state: State<'_, AppState>,
+) -> Result<crate::card_bundle::ImportCardsResult, String> {
+    verify_account_exists(&state, &account_id)?;
+    let parsed = reqwest::Url::parse(url.trim()).map_err(|e| format!("Invalid URL: {}", e))?;
+    if parsed.scheme() != "https" {
+        return Err("Card bundles must be served over https".to_string());
+    }
+
+    let resp = reqwest::Client::new()
+        .get(parsed)
+        .timeout(Duration::from_secs(20))
+        .send()
+        .await
+        .map_err(|e| format!("Request failed: {}", e))?;
+    if !resp.status().is_success() {
+        return Err(format!("Failed to fetch bundle: HTTP {}", resp.status()));
+    }
+    if resp.content_length().is_some_and(|len| len as usize > MAX_BUNDLE_BYTES) {
+        return Err("Card bundle is too large".to_string());
+    }
+    let body = resp.bytes().await.map_err(|e| format!("Request failed: {}", e))?;
+    if body.len() > MAX_BUNDLE_BYTES {
+        return Err("Card bundle is too large".to_string());
+    }
+    let bundle = crate::card_bundle::parse_bundle(&String::from_utf8_lossy(&body))?;
+
+    let result = with_db(&state, |db| {
+        let existing = db.get_cards(&account_id).map_err(|e| e.to_string())?;
+        let (cards, result) = crate::card_bundle::plan_import(bundle, &existing, &account_id);
+        for card in &cards {
+            db.insert_card(card).map_err(|e| e.to_string())?;
+        }
+        Ok(result)
+    })?;
+
+    if result.created > 0 {
+        sync_cards_to_icloud(&state);
+    }
+    Ok(result)
+}
+
 #[tauri::command]
 pub fn update_card(card: Card, state: State<'_, AppState>) -> Result<(), String> {
     with_db(&state, |db| db.update_card(&card).map_err(|e| e.to_string()))?;
+    clear_prefetched_page(&state, &card.id);
 
     sync_cards_to_icloud(&state);
     Ok(())
@@ -479,14 +784,30 @@ pub fn update_card(card: Card, state: State<'_, AppState>) -> Result<(), String>
 #[tauri::command]
 pub fn delete_card(id: String, state: State<'_, AppState>) -> Result<(), String> {
     with_db(&state, |db| db.delete_card(&id).map_err(|e| e.to_string()))?;
+    clear_prefetched_page(&state, &id);
 
     sync_cards_to_icloud(&state);
     Ok(())
 }
 
+/// Move a card to another account, at the end of its cards. The card's cached
+/// results are dropped since its query now runs against a different mailbox.
 #[tauri::command]
-pub fn reorder_cards(orders: Vec<(String, i32)>, state: State<'_, AppState>) -> Result<(), String> {
-    with_db(&state, |db| db.reorder_cards(&orders).map_err(|e| e.to_string()))?;
+pub fn move_card_to_account(card_id: String, target_account_id: String, state: State<'_, AppState>) -> Result<(), String> {
+    verify_account_exists(&state, &target_account_id)?;
+    let moved = with_db(&state, |db| db.move_card(&card_id, &target_account_id).map_err(|e| e.to_string()))?;
+    if !moved {
+        return Err("Card not found".to_string());
+    }
+    clear_prefetched_page(&state, &card_id);
+
+    sync_cards_to_icloud(&state);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn reorder_cards(account_id: String, ordered_ids: Vec<String>, state: State<'_, AppState>) -> Result<(), String> {
+    with_db(&state, |db| db.reorder_cards(&account_id, &ordered_ids).map_err(|e| e.to_string()))?;
 
     sync_cards_to_icloud(&state);
     Ok(())
@@ -516,7 +837,7 @@ fn get_account_and_card(
 }
 
 /// Helper to get a valid access token for an account (refreshing if needed)
-async fn get_access_token(state: &AppState, account_id: &str, app_data_dir: &std::path::Path) -> Result<String, String> {
+pub(crate) async fn get_access_token(state: &AppState, account_id: &str, app_data_dir: &std::path::Path) -> Result<String, String> {
     // Serve from cache if the token is good for at least another 60s
     {
         let cache = state.token_cache.lock().map_err(|_| "Lock error")?;
@@ -527,21 +848,46 @@ async fn get_access_token(state: &AppState, account_id: &str, app_data_dir: &std
         }
     }
 
-    // Get stored refresh token
-    let refresh_token = auth::get_refresh_token(account_id, app_data_dir).map_err(|e| e.to_string())?;
+    // Get stored refresh token, then refresh the access token
+    let refreshed = match auth::get_refresh_token(account_id, app_data_dir) {
+        Ok(refresh_token) => {
+            let auth_guard = state.auth.lock().await;
+            let auth = auth_guard
+                .as_ref()
+                .ok_or("Auth not configured. Please configure auth first.")?;
 
-    // Refresh the access token
-    let (access_token, expires_in) = {
-        let auth_guard = state.auth.lock().await;
-        let auth = auth_guard
-            .as_ref()
-            .ok_or("Auth not configured. Please configure auth first.")?;
+            auth.refresh_access_token(&refresh_token).await
+        }
+        Err(e) => Err(e),
+    };
 
-        auth.refresh_access_token(&refresh_token)
-            .await
-            .map_err(|e| e.to_string())?
+    let now = chrono::Utc::now().timestamp_millis();
+    let (access_token, expires_in, rotated_refresh_token) = {
+        let mut health = state.token_health.lock().map_err(|_| "Lock error")?;
+        match refreshed {
+            Ok(token) => {
+                health.record_success(account_id, now);
+                token
+            }
+            Err(e) => {
+                health.record_failure(account_id, &e, now);
+                // Prefixed so the frontend can prompt a sign-in instead of showing a generic failure
+                if e.needs_reauth() {
+                    return Err(format!("{}{}", auth::REAUTH_ERROR_PREFIX, e));
+                }
+                return Err(e.to_string());
+            }
+        }
     };
 
+    // Google may rotate the refresh token; the old one stops working once it does
+    if let Some(new_refresh_token) = rotated_refresh_token {
+        tracing::info!("Refresh token rotated for account: {}", account_id);
+        if let Err(e) = auth::store_refresh_token(account_id, &new_refresh_token, app_data_dir) {
+            tracing::warn!("Failed to store rotated refresh token for {}: {}", account_id, e);
+        }
+    }
+
     let expiry = Instant::now() + Duration::from_secs(expires_in.unwrap_or(3600));
     state
         .token_cache
@@ -564,7 +910,7 @@ pub async fn fetch_threads(
     let (account, card) = get_account_and_card(&state, &account_id, &card_id)?;
     let access_token = get_access_token(&state, &account.id, &app_data_dir).await?;
 
-    let client = GmailClient::new(access_token);
+    let client = card_load_client(&state, access_token);
     let threads = client
         .search_threads(&card.query)
         .await
@@ -578,6 +924,7 @@ pub async fn fetch_threads_paginated(
     account_id: String,
     card_id: String,
     page_token: Option<String>,
+    request_id: Option<String>,
     app_handle: tauri::AppHandle, state: State<'_, AppState>,
 ) -> Result<SearchResult, String> {
     let app_data_dir = get_app_data_dir(&app_handle)?;
@@ -585,17 +932,99 @@ pub async fn fetch_threads_paginated(
     tracing::info!("fetch_threads_paginated for card: {}, page_token: {:?}", card_id, page_token);
 
     let (account, card) = get_account_and_card(&state, &account_id, &card_id)?;
-    let access_token = get_access_token(&state, &account.id, &app_data_dir).await?;
 
-    let gmail = GmailClient::new(access_token);
-    let result = gmail
-        .search_threads_paginated(&card.query, page_token.as_deref())
-        .await
-        .map_err(|e| format!("Search failed: {}", e))?;
+    // "Load more" is served from the prefetch buffer when the page is ready
+    if let Some(token) = page_token.as_deref() {
+        if let Some(result) = take_prefetched_page(&state, &card_id, token)? {
+            tracing::info!("Serving prefetched page for card: {}", card_id);
+            return Ok(result);
+        }
+    }
 
-    tracing::info!("Found {} groups, has_more: {}", result.groups.len(), result.has_more);
+    run_cancellable(&state.inflight, request_id.as_deref(), async {
+        let access_token = get_access_token(&state, &account.id, &app_data_dir).await?;
 
-    Ok(result)
+        let gmail = card_load_client(&state, access_token);
+        let result = gmail
+            .search_threads_paginated(&card.query, page_token.as_deref())
+            .await
+            .map_err(|e| format!("Search failed: {}", e))?;
+
+        tracing::info!("Found {} groups, has_more: {}", result.groups.len(), result.has_more);
+
+        Ok(result)
+    })
+    .await
+}
+
+/// Abort a fetch started with the given `request_id`.
+/// Returns false if it had already finished.
+#[tauri::command]
+pub fn cancel_request(request_id: String, state: State<'_, AppState>) -> Result<bool, String> {
+    Ok(state.inflight.lock().map_err(|_| "Lock error")?.cancel(&request_id))
+}
+
+fn take_prefetched_page(state: &AppState, card_id: &str, page_token: &str) -> Result<Option<SearchResult>, String> {
+    let now = chrono::Utc::now().timestamp();
+    Ok(state.prefetch.lock().map_err(|_| "Lock error")?.take(card_id, page_token, now))
+}
+
+fn clear_prefetched_page(state: &AppState, card_id: &str) {
+    if let Ok(mut prefetch) = state.prefetch.lock() {
+        prefetch.clear(card_id);
+    }
+}
+
+/// Fetch the page after a card's cached `next_page_token` into the prefetch
+/// buffer. Returns true when a page is buffered and ready for "load more";
+/// false when the card is at its last page or the network is unavailable.
+#[tauri::command]
+pub async fn prefetch_next_page(
+    account_id: String,
+    card_id: String,
+    request_id: Option<String>,
+    app_handle: tauri::AppHandle, state: State<'_, AppState>,
+) -> Result<bool, String> {
+    let app_data_dir = get_app_data_dir(&app_handle)?;
+    let (account, card) = get_account_and_card(&state, &account_id, &card_id)?;
+
+    let page_token = with_db(&state, |db| {
+        db.get_card_threads(&card_id).map_err(|e| e.to_string())
+    })?
+    .and_then(|(_, next_page_token, _)| next_page_token);
+
+    let Some(page_token) = page_token else {
+        return Ok(false);
+    };
+
+    let now = chrono::Utc::now().timestamp();
+    if state.prefetch.lock().map_err(|_| "Lock error")?.has(&card_id, &page_token, now) {
+        return Ok(true);
+    }
+
+    run_cancellable(&state.inflight, request_id.as_deref(), async {
+        let access_token = get_access_token(&state, &account.id, &app_data_dir).await?;
+        let gmail = card_load_client(&state, access_token);
+
+        // Prefetch is opportunistic: a failed request (e.g. offline) just means
+        // "load more" falls back to a normal fetch
+        let result = match gmail.search_threads_paginated(&card.query, Some(&page_token)).await {
+            Ok(result) => result,
+            Err(e) => {
+                tracing::debug!("Prefetch for card {} skipped: {}", card_id, e);
+                return Ok(false);
+            }
+        };
+
+        state
+            .prefetch
+            .lock()
+            .map_err(|_| "Lock error")?
+            .store(&card_id, &page_token, result, chrono::Utc::now().timestamp());
+
+        Ok(true)
+    })
+    .await
 }
 
 /// Result of incremental sync
@@ -670,10 +1099,17 @@ pub async fn sync_threads_incremental(
                         }
                     }
 
-                    // Update stored history ID
+                    // Apply the delta to the cache, then update the stored history ID
                     {
                         let db_guard = state.db.lock().map_err(|_| "Lock error")?;
                         let db = db_guard.as_ref().ok_or("Database not initialized")?;
+                        db.apply_history_delta(
+                            &account_id,
+                            &modified_threads,
+                            &deleted_thread_ids,
+                            &changes.deleted_message_ids,
+                        )
+                        .map_err(|e| e.to_string())?;
                         db.set_history_id(&account_id, &changes.new_history_id)
                             .map_err(|e| e.to_string())?;
                     }
@@ -685,7 +1121,7 @@ pub async fn sync_threads_incremental(
                         is_full_sync: false,
                     })
                 }
-                Err(e) if e.contains("expired") => {
+                Err(e) if e == crate::gmail::HISTORY_EXPIRED_ERROR => {
                     tracing::warn!("History ID expired, performing full sync");
                     // Clear the stale history ID and do full sync
                     {
@@ -736,54 +1172,150 @@ async fn perform_full_sync(
     })
 }
 
+/// Apply label changes to many threads in batched requests. Returns each
+/// thread's outcome; fails outright only when no thread could be modified.
 #[tauri::command]
 pub async fn modify_threads(
     account_id: String,
     thread_ids: Vec<String>,
     add_labels: Vec<String>,
     remove_labels: Vec<String>,
+    confirmed: Option<bool>,
     app_handle: tauri::AppHandle, state: State<'_, AppState>,
-) -> Result<(), String> {
+) -> Result<HashMap<String, crate::gmail::ModifyOutcome>, String> {
     let app_data_dir = get_app_data_dir(&app_handle)?;
 
     verify_account_exists(&state, &account_id)?;
 
+    // Archiving or trashing by label is as destructive as the Trash commands
+    let destructive = remove_labels.iter().any(|l| l == "INBOX") || add_labels.iter().any(|l| l == "TRASH");
+    if destructive {
+        crate::gmail::check_bulk_confirmation(thread_ids.len(), confirmed.unwrap_or(false))?;
+    }
+
     let access_token = get_access_token(&state, &account_id, &app_data_dir).await?;
-    let gmail = std::sync::Arc::new(GmailClient::new(access_token));
+    let gmail = GmailClient::new(access_token);
 
-    // Process in parallel for better performance
-    let futures: Vec<_> = thread_ids
-        .into_iter()
-        .map(|thread_id| {
-            let gmail = gmail.clone();
-            let add = add_labels.clone();
-            let remove = remove_labels.clone();
+    let outcomes = gmail
+        .modify_threads_batch(&thread_ids, &add_labels, &remove_labels)
+        .await;
+
+    if !outcomes.is_empty() && outcomes.values().all(|o| !o.ok) {
+        let first_error = thread_ids
+            .iter()
+            .find_map(|id| outcomes.get(id).and_then(|o| o.error.clone()))
+            .unwrap_or_default();
+        return Err(format!("Failed to modify threads: {}", first_error));
+    }
+
+    Ok(outcomes)
+}
+
+/// Which per-thread Trash operation to run
+#[derive(Clone, Copy)]
+enum TrashAction {
+    Trash,
+    Untrash,
+    Delete,
+}
+
+/// Run a Trash operation on every thread, a few at a time, reporting each
+/// thread's outcome. Fails only when every thread failed.
+async fn run_trash_action(
+    account_id: &str,
+    thread_ids: Vec<String>,
+    action: TrashAction,
+    confirmed: bool,
+    app_handle: &tauri::AppHandle,
+    state: &AppState,
+) -> Result<HashMap<String, crate::gmail::ModifyOutcome>, String> {
+    use crate::gmail::ModifyOutcome;
+    use futures::StreamExt;
+
+    let app_data_dir = get_app_data_dir(app_handle)?;
+
+    verify_account_exists(state, account_id)?;
+
+    if !matches!(action, TrashAction::Untrash) {
+        crate::gmail::check_bulk_confirmation(thread_ids.len(), confirmed)?;
+    }
+
+    let access_token = get_access_token(state, account_id, &app_data_dir).await?;
+    let gmail = GmailClient::new(access_token);
+
+    let outcomes: HashMap<String, ModifyOutcome> = futures::stream::iter(thread_ids.iter().cloned())
+        .map(|id| {
+            let gmail = &gmail;
             async move {
-                gmail
-                    .modify_thread(&thread_id, add, remove)
-                    .await
-                    .map_err(|e| format!("Failed to modify thread {}: {}", thread_id, e))
+                let result = match action {
+                    TrashAction::Trash => gmail.trash_thread(&id).await,
+                    TrashAction::Untrash => gmail.untrash_thread(&id).await,
+                    TrashAction::Delete => gmail.delete_thread(&id).await,
+                };
+                let outcome = match result {
+                    Ok(()) => ModifyOutcome::ok(),
+                    Err(e) => ModifyOutcome::failed(e),
+                };
+                (id, outcome)
             }
         })
-        .collect();
+        .buffer_unordered(8)
+        .collect()
+        .await;
+
+    if !outcomes.is_empty() && outcomes.values().all(|o| !o.ok) {
+        let first_error = thread_ids
+            .iter()
+            .find_map(|id| outcomes.get(id).and_then(|o| o.error.clone()))
+            .unwrap_or_default();
+        return Err(first_error);
+    }
 
-    let results = futures::future::join_all(futures).await;
+    Ok(outcomes)
+}
 
-    // Return first error if any
-    for result in results {
-        result?;
-    }
+#[tauri::command]
+pub async fn trash_threads(
+    account_id: String,
+    thread_ids: Vec<String>,
+    confirmed: Option<bool>,
+    app_handle: tauri::AppHandle, state: State<'_, AppState>,
+) -> Result<HashMap<String, crate::gmail::ModifyOutcome>, String> {
+    run_trash_action(&account_id, thread_ids, TrashAction::Trash, confirmed.unwrap_or(false), &app_handle, &state).await
+}
 
-    Ok(())
+#[tauri::command]
+pub async fn untrash_threads(
+    account_id: String,
+    thread_ids: Vec<String>,
+    app_handle: tauri::AppHandle, state: State<'_, AppState>,
+) -> Result<HashMap<String, crate::gmail::ModifyOutcome>, String> {
+    run_trash_action(&account_id, thread_ids, TrashAction::Untrash, false, &app_handle, &state).await
 }
 
-/// Search threads by query (for preview, limited results)
+/// Delete threads for good, bypassing Trash
 #[tauri::command]
-pub async fn search_threads_preview(
+pub async fn delete_threads_permanently(
+    account_id: String,
+    thread_ids: Vec<String>,
+    confirmed: Option<bool>,
+    app_handle: tauri::AppHandle, state: State<'_, AppState>,
+) -> Result<HashMap<String, crate::gmail::ModifyOutcome>, String> {
+    run_trash_action(&account_id, thread_ids, TrashAction::Delete, confirmed.unwrap_or(false), &app_handle, &state).await
+}
+
+/// Preview a bulk trash/delete/archive over `query` without performing it:
+/// the estimated thread count and a few subjects, so the UI can ask for
+/// confirmation before large operations.
+#[tauri::command]
+pub async fn preview_bulk_action(
     account_id: String,
     query: String,
+    action: String,
     app_handle: tauri::AppHandle, state: State<'_, AppState>,
-) -> Result<Vec<ThreadGroup>, String> {
+) -> Result<crate::gmail::BulkActionPreview, String> {
+    crate::gmail::check_bulk_action(&action)?;
+
     let app_data_dir = get_app_data_dir(&app_handle)?;
 
     verify_account_exists(&state, &account_id)?;
@@ -791,73 +1323,795 @@ pub async fn search_threads_preview(
     let access_token = get_access_token(&state, &account_id, &app_data_dir).await?;
     let gmail = GmailClient::new(access_token);
 
-    // Limit to 5 threads for preview
-    gmail
-        .search_threads_limited(&query, 5)
-        .await
-        .map_err(|e| format!("Search failed: {}", e))
+    let (estimate, sample) = gmail.estimate_query(&query, crate::gmail::BULK_PREVIEW_SAMPLE).await?;
+    crate::gmail::bulk_action_preview(&action, estimate, &sample)
+}
+
+#[derive(Debug, Serialize)]
+pub struct CardFilterResult {
+    pub label_id: String,
+    pub label_name: String,
+    pub filter_id: String,
 }
 
+/// Mirror an email card into Gmail: a label named after the card and a filter
+/// applying it to mail matching the card's query, so the card works in Gmail
+/// web too (Gmail has no saved-search API)
 #[tauri::command]
-pub async fn get_thread_details(
+pub async fn create_label_and_filter_from_card(
     account_id: String,
-    thread_id: String,
+    card_id: String,
     app_handle: tauri::AppHandle, state: State<'_, AppState>,
-) -> Result<crate::gmail::FullThread, String> {
+) -> Result<CardFilterResult, String> {
     let app_data_dir = get_app_data_dir(&app_handle)?;
 
-    verify_account_exists(&state, &account_id)?;
+    let (_, card) = get_account_and_card(&state, &account_id, &card_id)?;
+    let (label_name, criteria) = crate::gmail::card_filter(&card)?;
 
     let access_token = get_access_token(&state, &account_id, &app_data_dir).await?;
     let gmail = GmailClient::new(access_token);
 
-    gmail.get_thread(&thread_id).await
+    let label = gmail.get_or_create_label(&label_name).await?;
+    let filter = crate::gmail::FilterSpec {
+        criteria,
+        action: crate::gmail::FilterAction {
+            add_label_ids: vec![label.id.clone()],
+            ..Default::default()
+        },
+    };
+    let filter_id = gmail.create_filter(&filter).await?;
+
+    Ok(CardFilterResult {
+        label_id: label.id,
+        label_name: label.name,
+        filter_id,
+    })
 }
 
+/// Create a Gmail filter labeling all mail from `email` with `label_name`,
+/// creating the label if needed. `skip_inbox` also archives matching mail.
 #[tauri::command]
-pub async fn send_email(
+pub async fn create_contact_filter(
     account_id: String,
-    to: String,
-    cc: String,
-    bcc: String,
-    subject: String,
-    body: String,
-    attachments: Vec<SendAttachment>,
-    is_html: Option<bool>,
+    email: String,
+    label_name: String,
+    skip_inbox: Option<bool>,
     app_handle: tauri::AppHandle, state: State<'_, AppState>,
-) -> Result<(), String> {
+) -> Result<CardFilterResult, String> {
     let app_data_dir = get_app_data_dir(&app_handle)?;
 
     verify_account_exists(&state, &account_id)?;
 
+    let label_name = label_name.trim();
+    if label_name.is_empty() {
+        return Err("Label name is empty".to_string());
+    }
+    // Validate before creating the label so a bad address leaves nothing behind
+    crate::gmail::contact_filter(&email, "", false)?;
+
     let access_token = get_access_token(&state, &account_id, &app_data_dir).await?;
     let gmail = GmailClient::new(access_token);
 
-    gmail.send_email(&to, &cc, &bcc, &subject, &body, &attachments, is_html.unwrap_or(false)).await
+    let label = gmail.get_or_create_label(label_name).await?;
+    let filter = crate::gmail::contact_filter(&email, &label.id, skip_inbox.unwrap_or(false))?;
+    let filter_id = gmail.create_filter(&filter).await?;
+
+    Ok(CardFilterResult {
+        label_id: label.id,
+        label_name: label.name,
+        filter_id,
+    })
 }
 
+/// Maximum number of threads snoozed by a single snooze_card call
+const MAX_SNOOZE_CARD_THREADS: usize = 100;
+
+/// Label applied to snoozed threads while they are out of the inbox
+pub const SNOOZE_LABEL_NAME: &str = "Posta/Snoozed";
+
+/// Snooze every currently-visible thread in a card until `until` (Unix ms).
+/// Returns the number of threads snoozed. The background snooze waker puts
+/// them back in the inbox once `until` passes.
 #[tauri::command]
-pub async fn reply_to_thread(
+pub async fn snooze_card(
     account_id: String,
-    thread_id: String,
-    to: String,
-    cc: String,
+    card_id: String,
+    until: i64,
+    app_handle: tauri::AppHandle, state: State<'_, AppState>,
+) -> Result<usize, String> {
+    let app_data_dir = get_app_data_dir(&app_handle)?;
+
+    if until <= chrono::Utc::now().timestamp_millis() {
+        return Err("Snooze time must be in the future".to_string());
+    }
+
+    let (_, card) = get_account_and_card(&state, &account_id, &card_id)?;
+
+    // Currently-visible threads are the card's cached pages
+    let thread_ids: Vec<String> = with_db(&state, |db| {
+        let cached = db.get_card_threads(&card.id).map_err(|e| e.to_string())?;
+        let mut ids: Vec<String> = Vec::new();
+        for group in cached.map(|(groups, _, _)| groups).unwrap_or_default() {
+            for thread in group.threads {
+                if !ids.contains(&thread.gmail_thread_id) {
+                    ids.push(thread.gmail_thread_id);
+                }
+            }
+        }
+        ids.truncate(MAX_SNOOZE_CARD_THREADS);
+        Ok(ids)
+    })?;
+
+    if thread_ids.is_empty() {
+        return Ok(0);
+    }
+
+    let access_token = get_access_token(&state, &account_id, &app_data_dir).await?;
+    let gmail = GmailClient::new(access_token);
+
+    let label = gmail.get_or_create_label(SNOOZE_LABEL_NAME).await?;
+    let outcomes = gmail
+        .modify_threads_batch(&thread_ids, &[label.id], &["INBOX".to_string()])
+        .await;
+
+    let snoozed: Vec<String> = thread_ids
+        .into_iter()
+        .filter(|id| match outcomes.get(id) {
+            Some(outcome) if outcome.ok => true,
+            outcome => {
+                let error = outcome.and_then(|o| o.error.as_deref()).unwrap_or("no response");
+                tracing::warn!("Failed to snooze thread {}: {}", id, error);
+                false
+            }
+        })
+        .collect();
+
+    with_db(&state, |db| {
+        db.snooze_threads(&account_id, &snoozed, until)
+            .map_err(|e| e.to_string())
+    })?;
+
+    Ok(snoozed.len())
+}
+
+/// Search threads by query (for preview, limited results)
+#[tauri::command]
+pub async fn search_threads_preview(
+    account_id: String,
+    query: String,
+    app_handle: tauri::AppHandle, state: State<'_, AppState>,
+) -> Result<Vec<ThreadGroup>, String> {
+    let app_data_dir = get_app_data_dir(&app_handle)?;
+
+    verify_account_exists(&state, &account_id)?;
+
+    let access_token = get_access_token(&state, &account_id, &app_data_dir).await?;
+    let gmail = GmailClient::new(access_token);
+
+    // Limit to 5 threads for preview
+    gmail
+        .search_threads_limited(&query, 5)
+        .await
+        .map_err(|e| format!("Search failed: {}", e))
+}
+
+/// Recent threads exchanged with a contact, newest first
+#[tauri::command]
+pub async fn interaction_history(
+    account_id: String,
+    email: String,
+    limit: Option<usize>,
+    app_handle: tauri::AppHandle, state: State<'_, AppState>,
+) -> Result<Vec<crate::models::Thread>, String> {
+    let app_data_dir = get_app_data_dir(&app_handle)?;
+
+    verify_account_exists(&state, &account_id)?;
+
+    let query = crate::gmail::interaction_query(&email)?;
+
+    let access_token = get_access_token(&state, &account_id, &app_data_dir).await?;
+    let gmail = GmailClient::new(access_token);
+
+    let groups = gmail
+        .search_threads_limited(&query, limit.unwrap_or(20))
+        .await
+        .map_err(|e| format!("Search failed: {}", e))?;
+
+    let mut threads: Vec<crate::models::Thread> =
+        groups.into_iter().flat_map(|g| g.threads).collect();
+    threads.sort_by(|a, b| b.last_message_date.cmp(&a.last_message_date));
+    Ok(threads)
+}
+
+/// A thread plus where to open it
+#[derive(Debug, Serialize)]
+pub struct ThreadDetails {
+    #[serde(flatten)]
+    pub thread: crate::gmail::FullThread,
+    /// Earliest unread message, to scroll to on open
+    pub first_unread_message_id: Option<String>,
+    /// Quoting style of each message with a plain-text body, by message id
+    pub quote_styles: HashMap<String, crate::gmail::QuoteStyle>,
+    /// Signature and encryption state of each signed or encrypted message, by message id
+    pub security: HashMap<String, crate::gmail::MessageSecurity>,
+    /// Detected language of the conversation ("French"), English when unsure
+    pub language: &'static str,
+    /// Runs of consecutive same-sender messages, when grouping was requested
+    pub grouped_messages: Option<Vec<crate::gmail::MessageGroup>>,
+    /// Every attachment's metadata; content is fetched per attachment with
+    /// `download_attachment`
+    pub attachments: Vec<crate::models::Attachment>,
+}
+
+/// Fetch a thread with every message. `dedupe_thread_messages` hides copies
+/// sharing a Message-ID (e.g. a send-and-CC-self); `dedupe_by_content` also
+/// hides copies with the same sender, subject and body. With
+/// `mark_first_unread_read`, only the first unread message is marked read.
+/// `group_consecutive` adds blocks of rapid same-sender messages, within
+/// `group_window_minutes` of each other. Attachments come back as a manifest
+/// only; none are downloaded here.
+#[tauri::command]
+pub async fn get_thread_details(
+    account_id: String,
+    thread_id: String,
+    dedupe_thread_messages: Option<bool>,
+    dedupe_by_content: Option<bool>,
+    mark_first_unread_read: Option<bool>,
+    group_consecutive: Option<bool>,
+    group_window_minutes: Option<i64>,
+    app_handle: tauri::AppHandle, state: State<'_, AppState>,
+) -> Result<ThreadDetails, String> {
+    let app_data_dir = get_app_data_dir(&app_handle)?;
+
+    verify_account_exists(&state, &account_id)?;
+
+    let access_token = get_access_token(&state, &account_id, &app_data_dir).await?;
+    let gmail = GmailClient::new(access_token);
+
+    let mut thread = gmail.get_thread(&thread_id).await?;
+
+    // Keep the bodies so the thread can be reopened offline
+    let messages: Vec<_> = thread.messages.iter().map(crate::gmail::message_from_full).collect();
+    if let Err(e) = with_db(&state, |db| db.cache_messages(&account_id, &messages).map_err(|e| e.to_string())) {
+        tracing::warn!("Failed to cache messages for thread {}: {}", thread_id, e);
+    }
+
+    if dedupe_thread_messages.unwrap_or(false) {
+        crate::gmail::dedupe_thread_messages(&mut thread, dedupe_by_content.unwrap_or(false));
+    }
+
+    let group_window = group_consecutive
+        .unwrap_or(false)
+        .then(|| group_window_minutes.unwrap_or(crate::gmail::DEFAULT_MESSAGE_GROUP_WINDOW_MINUTES));
+    let details = thread_details(thread, group_window);
+
+    if let (Some(message_id), true) = (&details.first_unread_message_id, mark_first_unread_read.unwrap_or(false)) {
+        if let Err(e) = gmail.modify_message(message_id, Vec::new(), vec!["UNREAD".to_string()]).await {
+            tracing::warn!("Failed to mark message {} read: {}", message_id, e);
+        }
+    }
+
+    Ok(details)
+}
+
+/// Everything `get_thread_details` derives from a fetched thread. Takes no
+/// client, so attachments are listed from message metadata, never downloaded.
+/// Consecutive messages are grouped when `group_window_minutes` is given.
+fn thread_details(thread: crate::gmail::FullThread, group_window_minutes: Option<i64>) -> ThreadDetails {
+    let first_unread_message_id = crate::gmail::first_unread_message_id(&thread);
+
+    let quote_styles = thread
+        .messages
+        .iter()
+        .filter_map(|m| {
+            let text = crate::gmail::extract_body_text_from_message(m)?;
+            Some((m.id.clone(), crate::gmail::classify_quote_style(&text)))
+        })
+        .collect();
+
+    let security = thread
+        .messages
+        .iter()
+        .filter_map(|m| {
+            let security = crate::gmail::detect_message_security(m.payload.as_ref()?);
+            (security.is_signed || security.is_encrypted).then(|| (m.id.clone(), security))
+        })
+        .collect();
+
+    let language = thread_language(&thread);
+
+    let grouped_messages =
+        group_window_minutes.map(|window| crate::gmail::group_consecutive_messages(&thread, window));
+
+    let attachments = crate::gmail::attachment_manifest(&thread);
+
+    ThreadDetails {
+        thread,
+        first_unread_message_id,
+        quote_styles,
+        security,
+        language,
+        grouped_messages,
+        attachments,
+    }
+}
+
+/// A thread as opened, plus the messages this open marked read
+#[derive(Debug, Serialize)]
+pub struct OpenedThread {
+    #[serde(flatten)]
+    pub thread: crate::gmail::FullThread,
+    /// Messages that were unread and have now been marked read
+    pub marked_read_message_ids: Vec<String>,
+    pub unread_count: usize,
+}
+
+/// Fetch a thread for reading. With `mark_read`, only the messages that were
+/// unread lose `UNREAD` (already-read ones aren't touched) and the cached
+/// unread count is updated to match.
+#[tauri::command]
+pub async fn open_thread(
+    account_id: String,
+    thread_id: String,
+    mark_read: bool,
+    app_handle: tauri::AppHandle, state: State<'_, AppState>,
+) -> Result<OpenedThread, String> {
+    let app_data_dir = get_app_data_dir(&app_handle)?;
+
+    verify_account_exists(&state, &account_id)?;
+
+    let access_token = get_access_token(&state, &account_id, &app_data_dir).await?;
+    let gmail = GmailClient::new(access_token);
+
+    let mut thread = gmail.get_thread(&thread_id).await?;
+    let unread = crate::gmail::unread_message_ids(&thread);
+
+    if !mark_read || unread.is_empty() {
+        return Ok(OpenedThread {
+            thread,
+            marked_read_message_ids: Vec::new(),
+            unread_count: unread.len(),
+        });
+    }
+
+    gmail
+        .batch_modify_messages(&unread, &[], &["UNREAD".to_string()])
+        .await
+        .map_err(|e| format!("Failed to mark messages read: {}", e))?;
+    let unread_count = crate::gmail::mark_messages_read_locally(&mut thread, &unread);
+
+    if let Err(e) = with_db(&state, |db| {
+        db.set_thread_unread_count(&account_id, &thread_id, unread_count as i32)
+            .map_err(|e| e.to_string())
+    }) {
+        tracing::warn!("Failed to update cached unread count for {}: {}", thread_id, e);
+    }
+
+    Ok(OpenedThread {
+        thread,
+        marked_read_message_ids: unread,
+        unread_count,
+    })
+}
+
+/// A thread rebuilt from cached message bodies, for reading offline. None
+/// when none of its messages have been cached.
+#[tauri::command]
+pub fn get_cached_thread_details(
+    account_id: String,
+    thread_id: String,
+    state: State<'_, AppState>,
+) -> Result<Option<crate::gmail::FullThread>, String> {
+    let messages = with_db(&state, |db| {
+        db.get_cached_messages(&account_id, &thread_id).map_err(|e| e.to_string())
+    })?;
+    if messages.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(crate::gmail::full_thread_from_cache(&thread_id, &messages)))
+}
+
+/// HTML body of one message in a thread, plus a dark-mode adaptation when
+/// `dark_mode` is set. None when the message has no HTML part.
+#[tauri::command]
+pub async fn get_message_html(
+    account_id: String,
+    thread_id: String,
+    message_id: String,
+    dark_mode: Option<bool>,
+    app_handle: tauri::AppHandle, state: State<'_, AppState>,
+) -> Result<Option<crate::gmail::html::MessageHtml>, String> {
+    let app_data_dir = get_app_data_dir(&app_handle)?;
+
+    verify_account_exists(&state, &account_id)?;
+
+    let access_token = get_access_token(&state, &account_id, &app_data_dir).await?;
+    let gmail = GmailClient::new(access_token);
+
+    let thread = gmail.get_thread(&thread_id).await?;
+    let message = thread
+        .messages
+        .iter()
+        .find(|m| m.id == message_id)
+        .ok_or_else(|| format!("Message {} not found in thread", message_id))?;
+
+    Ok(crate::gmail::extract_html_from_message(message)
+        .map(|html| crate::gmail::html::MessageHtml::new(html, dark_mode.unwrap_or(false))))
+}
+
+/// Like `get_thread_details`, but stitches in sibling threads when Gmail
+/// has split a long conversation
+#[tauri::command]
+pub async fn get_full_conversation(
+    account_id: String,
+    thread_id: String,
+    app_handle: tauri::AppHandle, state: State<'_, AppState>,
+) -> Result<crate::gmail::StitchedConversation, String> {
+    let app_data_dir = get_app_data_dir(&app_handle)?;
+
+    verify_account_exists(&state, &account_id)?;
+
+    let access_token = get_access_token(&state, &account_id, &app_data_dir).await?;
+    let gmail = GmailClient::new(access_token);
+
+    gmail.get_full_conversation(&thread_id).await
+}
+
+#[tauri::command]
+pub fn validate_attachment(
+    attachment: SendAttachment,
+) -> Result<crate::gmail::AttachmentValidation, String> {
+    crate::gmail::validate_attachment(&attachment)
+}
+
+#[tauri::command]
+pub async fn send_email(
+    account_id: String,
+    to: String,
+    cc: String,
+    bcc: String,
+    subject: String,
+    body: String,
+    attachments: Vec<SendAttachment>,
+    is_html: Option<bool>,
+    app_handle: tauri::AppHandle, state: State<'_, AppState>,
+) -> Result<(), String> {
+    let app_data_dir = get_app_data_dir(&app_handle)?;
+
+    verify_account_exists(&state, &account_id)?;
+
+    let access_token = get_access_token(&state, &account_id, &app_data_dir).await?;
+    let gmail = GmailClient::new(access_token);
+
+    let outcome = gmail.send_email(&to, &cc, &bcc, &subject, &body, &attachments, is_html.unwrap_or(false)).await;
+    mirror_sent(&state, &account_id, None, &to, &subject, outcome)
+}
+
+/// Record a send in the local sent log when it succeeded. Logging problems
+/// never fail the send itself.
+fn mirror_sent(
+    state: &AppState,
+    account_id: &str,
+    thread_id: Option<&str>,
+    to: &str,
+    subject: &str,
+    outcome: Result<(), String>,
+) -> Result<(), String> {
+    if outcome.is_err() {
+        return outcome;
+    }
+    let entry = crate::models::SentLogEntry {
+        account_id: account_id.to_string(),
+        thread_id: thread_id.map(str::to_string),
+        recipient: to.to_string(),
+        subject: subject.to_string(),
+        sent_at: chrono::Utc::now().timestamp_millis(),
+    };
+    if let Err(e) = with_db(state, |db| db.log_sent(&entry).map_err(|e| e.to_string())) {
+        tracing::warn!("Failed to record sent message: {}", e);
+    }
+    outcome
+}
+
+/// Recently sent messages from the local log, newest first; works offline
+#[tauri::command]
+pub fn recent_sent(
+    account_id: Option<String>,
+    limit: Option<usize>,
+    state: State<'_, AppState>,
+) -> Result<Vec<crate::models::SentLogEntry>, String> {
+    let limit = limit.unwrap_or(20).clamp(1, crate::cache::sqlite::MAX_SENT_LOG as usize);
+    with_db(&state, |db| db.recent_sent(account_id.as_deref(), limit).map_err(|e| e.to_string()))
+}
+
+/// One message plus its decoded display body
+#[derive(Debug, Serialize)]
+pub struct SingleMessage {
+    #[serde(flatten)]
+    pub message: crate::gmail::FullMessage,
+    /// text/html when present, otherwise text/plain
+    pub body: Option<crate::gmail::DecodedBody>,
+}
+
+/// Fetch a single message, for re-rendering it without reloading its thread
+#[tauri::command]
+pub async fn get_message(
+    account_id: String,
+    message_id: String,
+    app_handle: tauri::AppHandle, state: State<'_, AppState>,
+) -> Result<SingleMessage, String> {
+    let app_data_dir = get_app_data_dir(&app_handle)?;
+
+    verify_account_exists(&state, &account_id)?;
+
+    let access_token = get_access_token(&state, &account_id, &app_data_dir).await?;
+    let gmail = GmailClient::new(access_token);
+
+    let message = gmail.get_message(&message_id).await?;
+    let body = crate::gmail::best_body(&message);
+    Ok(SingleMessage { message, body })
+}
+
+/// Address a message asks a read receipt to go to, so the UI can note the
+/// request (no receipt is ever sent)
+#[tauri::command]
+pub async fn get_read_receipt_request(
+    account_id: String,
+    message_id: String,
+    app_handle: tauri::AppHandle, state: State<'_, AppState>,
+) -> Result<Option<String>, String> {
+    let app_data_dir = get_app_data_dir(&app_handle)?;
+
+    verify_account_exists(&state, &account_id)?;
+
+    let access_token = get_access_token(&state, &account_id, &app_data_dir).await?;
+    let gmail = GmailClient::new(access_token);
+
+    let message = gmail.get_message(&message_id).await?;
+    Ok(message.payload.as_ref().and_then(crate::gmail::read_receipt_request))
+}
+
+/// Forward a message, with its attachments, to new recipients
+#[tauri::command]
+pub async fn forward_email(
+    account_id: String,
+    message_id: String,
+    to: String,
+    cc: String,
+    bcc: String,
+    note: Option<String>,
+    app_handle: tauri::AppHandle, state: State<'_, AppState>,
+) -> Result<(), String> {
+    let app_data_dir = get_app_data_dir(&app_handle)?;
+
+    verify_account_exists(&state, &account_id)?;
+
+    if to.trim().is_empty() {
+        return Err("Add at least one recipient".to_string());
+    }
+
+    let access_token = get_access_token(&state, &account_id, &app_data_dir).await?;
+    let gmail = GmailClient::new(access_token);
+
+    gmail
+        .forward_message(&message_id, &to, &cc, &bcc, note.as_deref().unwrap_or(""))
+        .await
+}
+
+#[tauri::command]
+pub async fn reply_to_thread(
+    account_id: String,
+    thread_id: String,
+    to: String,
+    cc: String,
     bcc: String,
     subject: String,
     body: String,
     message_id: Option<String>,
-    attachments: Vec<SendAttachment>,
-    is_html: Option<bool>,
+    attachments: Vec<SendAttachment>,
+    is_html: Option<bool>,
+    references: Option<String>,
+    app_handle: tauri::AppHandle, state: State<'_, AppState>,
+) -> Result<(), String> {
+    let app_data_dir = get_app_data_dir(&app_handle)?;
+
+    verify_account_exists(&state, &account_id)?;
+
+    let access_token = get_access_token(&state, &account_id, &app_data_dir).await?;
+    let gmail = GmailClient::new(access_token);
+
+    let outcome = gmail
+        .reply_to_thread(
+            &thread_id,
+            &to,
+            &cc,
+            &bcc,
+            &subject,
+            &body,
+            message_id.as_deref(),
+            references.as_deref(),
+            &attachments,
+            is_html.unwrap_or(false),
+        )
+        .await;
+    mirror_sent(&state, &account_id, Some(&thread_id), &to, &subject, outcome)
+}
+
+/// Files `email` has sent, newest first, for a per-contact files panel
+#[tauri::command]
+pub async fn sender_attachments(
+    account_id: String,
+    email: String,
+    limit: Option<usize>,
+    app_handle: tauri::AppHandle, state: State<'_, AppState>,
+) -> Result<Vec<crate::gmail::SenderAttachment>, String> {
+    use futures::StreamExt;
+
+    let app_data_dir = get_app_data_dir(&app_handle)?;
+
+    verify_account_exists(&state, &account_id)?;
+
+    let address = crate::gmail::extract_email_address(&email);
+    if !address.contains('@') || address.contains(char::is_whitespace) {
+        return Err(format!("Invalid email address: {}", email));
+    }
+    let limit = limit.unwrap_or(20).clamp(1, 100);
+
+    let access_token = get_access_token(&state, &account_id, &app_data_dir).await?;
+    let gmail = GmailClient::new(access_token);
+
+    // Attachments are listed per thread; fetch the messages to keep only the
+    // sender's own files
+    let query = format!("from:{} has:attachment", address);
+    let thread_ids = gmail.list_thread_ids(&query, limit).await?;
+    let threads: Vec<crate::gmail::FullThread> = futures::stream::iter(thread_ids)
+        .map(|id| {
+            let gmail = &gmail;
+            async move { gmail.get_thread(&id).await }
+        })
+        .buffer_unordered(8)
+        .filter_map(|thread| async move { thread.ok() })
+        .collect()
+        .await;
+
+    Ok(crate::gmail::sender_attachments(&threads, &address, limit))
+}
+
+/// Sent threads older than `days` where nobody has replied since the user's
+/// last message, newest first
+#[tauri::command]
+pub async fn awaiting_reply(
+    account_id: String,
+    days: i64,
+    app_handle: tauri::AppHandle, state: State<'_, AppState>,
+) -> Result<Vec<crate::gmail::AwaitingReply>, String> {
+    use futures::StreamExt;
+
+    const MAX_CANDIDATES: usize = 50;
+    // Conversations idle for longer than this are unlikely to get a reply
+    const LOOKBACK_DAYS: i64 = 90;
+
+    let app_data_dir = get_app_data_dir(&app_handle)?;
+
+    let account_email = get_account_email(&state, &account_id)?;
+    let access_token = get_access_token(&state, &account_id, &app_data_dir).await?;
+    let gmail = GmailClient::new(access_token);
+
+    let days = days.max(0);
+    let query = format!("in:sent older_than:{}d newer_than:{}d", days, LOOKBACK_DAYS.max(days + 1));
+    let thread_ids = gmail.list_thread_ids(&query, MAX_CANDIDATES).await?;
+
+    let now = chrono::Utc::now();
+    let mut pending: Vec<crate::gmail::AwaitingReply> = futures::stream::iter(thread_ids)
+        .map(|id| {
+            let gmail = &gmail;
+            async move { gmail.get_thread(&id).await }
+        })
+        .buffer_unordered(8)
+        .filter_map(|thread| {
+            let account_email = &account_email;
+            async move { crate::gmail::awaiting_reply(&thread.ok()?, account_email, now, days) }
+        })
+        .collect()
+        .await;
+
+    pending.sort_by(|a, b| b.sent_at.cmp(&a.sent_at));
+    Ok(pending)
+}
+
+/// Remind the user to follow up on `thread_id` if nobody replies within
+/// `after_days`. A reply arriving first cancels the reminder.
+#[tauri::command]
+pub async fn set_followup_reminder(
+    account_id: String,
+    thread_id: String,
+    after_days: i64,
+    app_handle: tauri::AppHandle, state: State<'_, AppState>,
+) -> Result<crate::models::FollowupReminder, String> {
+    let app_data_dir = get_app_data_dir(&app_handle)?;
+
+    if after_days <= 0 {
+        return Err("Follow-up window must be at least one day".to_string());
+    }
+
+    let account_email = get_account_email(&state, &account_id)?;
+    let access_token = get_access_token(&state, &account_id, &app_data_dir).await?;
+    let thread = GmailClient::new(access_token).get_thread(&thread_id).await?;
+
+    // Describe the user's latest message, which is what awaits a reply
+    let latest_sent = thread
+        .messages
+        .iter()
+        .filter(|m| {
+            m.header("From").is_some_and(|from| {
+                crate::gmail::extract_email_address(from).eq_ignore_ascii_case(&account_email)
+            })
+        })
+        .max_by_key(|m| m.internal_date.as_deref().and_then(|d| d.parse::<i64>().ok()));
+    let latest = latest_sent.or(thread.messages.last()).ok_or("Thread has no messages")?;
+
+    let now = chrono::Utc::now().timestamp_millis();
+    let reminder = crate::models::FollowupReminder {
+        account_id,
+        thread_id,
+        remind_at: now + after_days * 86_400_000,
+        created_at: now,
+        recipient: latest.header("To").unwrap_or_default().to_string(),
+        subject: latest.header("Subject").unwrap_or("(No subject)").to_string(),
+    };
+
+    with_db(&state, |db| db.set_followup(&reminder).map_err(|e| e.to_string()))?;
+    Ok(reminder)
+}
+
+#[tauri::command]
+pub fn list_followups(
+    account_id: String,
+    state: State<'_, AppState>,
+) -> Result<Vec<crate::models::FollowupReminder>, String> {
+    with_db(&state, |db| db.get_followups(Some(&account_id)).map_err(|e| e.to_string()))
+}
+
+/// Returns false if the thread had no reminder
+#[tauri::command]
+pub fn cancel_followup(
+    account_id: String,
+    thread_id: String,
+    state: State<'_, AppState>,
+) -> Result<bool, String> {
+    with_db(&state, |db| db.delete_followup(&account_id, &thread_id).map_err(|e| e.to_string()))
+}
+
+/// Warnings to show before sending a reply (never blocks the send itself)
+#[tauri::command]
+pub async fn prepare_reply(
+    account_id: String,
+    thread_id: String,
+    message_id: Option<String>,
+    stale_after_days: Option<i64>,
+    large_recipient_threshold: Option<usize>,
     app_handle: tauri::AppHandle, state: State<'_, AppState>,
-) -> Result<(), String> {
+) -> Result<crate::gmail::ReplyPreflight, String> {
     let app_data_dir = get_app_data_dir(&app_handle)?;
 
-    verify_account_exists(&state, &account_id)?;
+    let user_email = get_account_email(&state, &account_id)?;
 
     let access_token = get_access_token(&state, &account_id, &app_data_dir).await?;
     let gmail = GmailClient::new(access_token);
 
-    gmail.reply_to_thread(&thread_id, &to, &cc, &bcc, &subject, &body, message_id.as_deref(), &attachments, is_html.unwrap_or(false)).await
+    let thread = gmail
+        .get_thread(&thread_id)
+        .await
+        .map_err(|e| format!("Failed to fetch thread: {}", e))?;
+
+    Ok(crate::gmail::reply_preflight(
+        &thread,
+        message_id.as_deref(),
+        &user_email,
+        chrono::Utc::now(),
+        stale_after_days.unwrap_or(crate::gmail::DEFAULT_STALE_THREAD_DAYS),
+        large_recipient_threshold.unwrap_or(crate::gmail::DEFAULT_LARGE_RECIPIENT_THRESHOLD),
+    ))
 }
 
 #[tauri::command]
@@ -903,6 +2157,70 @@ pub fn get_cached_card_threads(
     })
 }
 
+/// Unread thread counts per date bucket for a card's sub-badges. Served from
+/// the card cache when present, so it only hits the network for uncached cards.
+#[tauri::command]
+pub async fn card_bucket_unread(
+    account_id: String,
+    card_id: String,
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> Result<HashMap<String, usize>, String> {
+    // Resolve the card within the account first, so a card id from another
+    // account can't read that account's cached threads
+    let (account, card) = get_account_and_card(&state, &account_id, &card_id)?;
+    if card.card_type == "calendar" {
+        return Err("Calendar cards have no unread threads".to_string());
+    }
+
+    let cached = with_db(&state, |db| db.get_card_threads(&card.id).map_err(|e| e.to_string()))?;
+    if let Some((groups, _, _)) = cached {
+        return Ok(crate::gmail::bucket_unread_counts(groups.iter().flat_map(|g| &g.threads)));
+    }
+
+    let app_data_dir = get_app_data_dir(&app_handle)?;
+    let access_token = get_access_token(&state, &account.id, &app_data_dir).await?;
+    let groups = GmailClient::new(access_token)
+        .search_threads(&card.query)
+        .await
+        .map_err(|e| format!("Search failed: {}", e))?;
+
+    Ok(crate::gmail::bucket_unread_counts(groups.iter().flat_map(|g| &g.threads)))
+}
+
+/// A card's threads with each thread's primary participant (name, photo or
+/// monogram) resolved from cached contacts, so the list renders in one call.
+/// Threads come from the card cache when present.
+#[tauri::command]
+pub async fn card_view(
+    account_id: String,
+    card_id: String,
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> Result<Vec<crate::people::CardViewGroup>, String> {
+    let (account, card) = get_account_and_card(&state, &account_id, &card_id)?;
+
+    let cached = with_db(&state, |db| db.get_card_threads(&card_id).map_err(|e| e.to_string()))?;
+    let groups = match cached {
+        Some((groups, _, _)) => groups,
+        None => {
+            let app_data_dir = get_app_data_dir(&app_handle)?;
+            let access_token = get_access_token(&state, &account.id, &app_data_dir).await?;
+            GmailClient::new(access_token)
+                .search_threads(&card.query)
+                .await
+                .map_err(|e| format!("Search failed: {}", e))?
+        }
+    };
+
+    let emails = crate::people::primary_participant_emails(&groups, &account.email);
+    let contacts = with_db(&state, |db| {
+        db.get_contacts_by_email(&account.id, &emails).map_err(|e| e.to_string())
+    })?;
+
+    Ok(crate::people::build_card_view(groups, &account.email, &contacts))
+}
+
 #[tauri::command]
 pub fn save_cached_card_threads(
     card_id: String,
@@ -918,6 +2236,7 @@ pub fn save_cached_card_threads(
 
 #[tauri::command]
 pub fn clear_card_cache(card_id: String, state: State<'_, AppState>) -> Result<(), String> {
+    clear_prefetched_page(&state, &card_id);
     with_db(&state, |db| db.clear_card_cache(&card_id).map_err(|e| e.to_string()))
 }
 
@@ -968,10 +2287,42 @@ pub async fn download_attachment(
 
     verify_account_exists(&state, &account_id)?;
 
-    let access_token = get_access_token(&state, &account_id, &app_data_dir).await?;
+    let bytes = fetch_attachment_bytes(&state, &account_id, &message_id, &attachment_id, &app_data_dir).await?;
+    Ok(URL_SAFE_NO_PAD.encode(bytes))
+}
+
+/// How long downloaded attachments stay in the cache
+const ATTACHMENT_CACHE_MAX_AGE_HOURS: i64 = 7 * 24;
+
+/// Attachment bytes from the cache, or from Gmail (and then cached)
+async fn fetch_attachment_bytes(
+    state: &AppState,
+    account_id: &str,
+    message_id: &str,
+    attachment_id: &str,
+    app_data_dir: &std::path::Path,
+) -> Result<Vec<u8>, String> {
+    let cached = with_db(state, |db| {
+        db.get_attachment(account_id, message_id, attachment_id)
+            .map_err(|e| e.to_string())
+    })?;
+    if let Some(bytes) = cached {
+        return Ok(bytes);
+    }
+
+    let access_token = get_access_token(state, account_id, app_data_dir).await?;
     let gmail = GmailClient::new(access_token);
+    let data = gmail.get_attachment(message_id, attachment_id).await?;
+
+    let bytes = decode_gmail_b64(&data)?;
 
-    gmail.get_attachment(&message_id, &attachment_id).await
+    if let Err(e) = with_db(state, |db| {
+        db.save_attachment(account_id, message_id, attachment_id, &bytes)
+            .map_err(|e| e.to_string())
+    }) {
+        tracing::warn!("Failed to cache attachment: {}", e);
+    }
+    Ok(bytes)
 }
 
 fn get_extension_for_mime(mime_type: &str) -> Option<&'static str> {
@@ -1015,24 +2366,16 @@ async fn resolve_attachment_file(
     app_handle: &tauri::AppHandle,
     state: &State<'_, AppState>,
 ) -> Result<(String, Vec<u8>), String> {
-    let base64_data = if let Some(data) = inline_data {
-        data
+    let bytes = if let Some(data) = inline_data {
+        decode_gmail_b64(&data)?
     } else {
         let attachment_id = attachment_id.ok_or("No attachment ID or inline data")?;
         verify_account_exists(state, account_id)?;
 
         let app_data_dir = get_app_data_dir(app_handle)?;
-        let access_token = get_access_token(state, account_id, &app_data_dir).await?;
-        let gmail = GmailClient::new(access_token);
-        gmail.get_attachment(message_id, &attachment_id).await?
+        fetch_attachment_bytes(state, account_id, message_id, &attachment_id, &app_data_dir).await?
     };
 
-    // Decode base64 - Gmail uses URL-safe encoding, handle with/without padding
-    let cleaned = base64_data.trim_end_matches('=');
-    let bytes = URL_SAFE_NO_PAD
-        .decode(cleaned)
-        .map_err(|e| format!("Failed to decode base64: {}", e))?;
-
     // Ensure filename has extension based on mime type
     let final_filename = if !filename.contains('.') {
         mime_type
@@ -1112,6 +2455,80 @@ pub async fn save_attachment(
     Ok(path.to_string_lossy().to_string())
 }
 
+/// Render a thread to a paginated PDF at `dest` (a file path or a directory)
+#[tauri::command]
+pub async fn export_thread_pdf(
+    account_id: String,
+    thread_id: String,
+    dest: String,
+    app_handle: tauri::AppHandle, state: State<'_, AppState>,
+) -> Result<String, String> {
+    use crate::pdf::{Block, JpegImage};
+
+    let app_data_dir = get_app_data_dir(&app_handle)?;
+
+    verify_account_exists(&state, &account_id)?;
+
+    let access_token = get_access_token(&state, &account_id, &app_data_dir).await?;
+    let gmail = GmailClient::new(access_token);
+
+    let thread = gmail
+        .get_thread(&thread_id)
+        .await
+        .map_err(|e| format!("Failed to fetch thread: {}", e))?;
+
+    let subject = thread
+        .messages
+        .first()
+        .and_then(|m| m.header("Subject"))
+        .unwrap_or("(No Subject)")
+        .to_string();
+
+    let mut blocks = vec![Block::Heading(subject.clone())];
+
+    for msg in &thread.messages {
+        blocks.push(Block::Rule);
+        blocks.push(Block::Meta(format!("From: {}", msg.header("From").unwrap_or("Unknown"))));
+        if let Some(to) = msg.header("To") {
+            blocks.push(Block::Meta(format!("To: {}", to)));
+        }
+        if let Some(date) = msg.header("Date") {
+            blocks.push(Block::Meta(format!("Date: {}", date)));
+        }
+        blocks.push(Block::Paragraph(crate::gmail::extract_readable_body(msg)));
+
+        // Embed inline JPEG images; other formats are skipped
+        for (attachment_id, mime_type) in crate::gmail::inline_image_attachments(msg) {
+            if mime_type != "image/jpeg" {
+                continue;
+            }
+            let Ok(data) = gmail.get_attachment(&msg.id, &attachment_id).await else {
+                continue;
+            };
+            let image = decode_gmail_b64(&data).ok().and_then(JpegImage::from_bytes);
+            if let Some(image) = image {
+                blocks.push(Block::Image(image));
+            }
+        }
+    }
+
+    let pdf = crate::pdf::render(blocks);
+
+    let mut path = std::path::PathBuf::from(&dest);
+    if path.is_dir() {
+        let safe_name: String = subject
+            .chars()
+            .map(|c| if c.is_alphanumeric() || c == ' ' || c == '-' { c } else { '_' })
+            .take(80)
+            .collect();
+        path = path.join(format!("{}.pdf", safe_name.trim()));
+    }
+
+    std::fs::write(&path, &pdf).map_err(|e| format!("Failed to write PDF: {}", e))?;
+
+    Ok(path.to_string_lossy().to_string())
+}
+
 #[tauri::command]
 pub async fn list_labels(
     account_id: String,
@@ -1127,6 +2544,53 @@ pub async fn list_labels(
     gmail.list_labels().await
 }
 
+/// Cards whose query references a `label:` that no longer exists, each with
+/// the closest existing label as a suggested fix
+#[tauri::command]
+pub async fn validate_cards(
+    account_id: String,
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> Result<Vec<crate::gmail::CardLabelIssue>, String> {
+    let app_data_dir = get_app_data_dir(&app_handle)?;
+
+    verify_account_exists(&state, &account_id)?;
+    let cards = with_db(&state, |db| db.get_cards(&account_id).map_err(|e| e.to_string()))?;
+
+    let access_token = get_access_token(&state, &account_id, &app_data_dir).await?;
+    let labels = GmailClient::new(access_token).list_labels().await?;
+
+    Ok(cards
+        .iter()
+        .filter_map(|card| crate::gmail::card_label_issue(card, &labels))
+        .collect())
+}
+
+/// Apply the suggested label fixes to a card's query, returning the updated card
+#[tauri::command]
+pub async fn repair_card_labels(
+    account_id: String,
+    card_id: String,
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> Result<Card, String> {
+    let app_data_dir = get_app_data_dir(&app_handle)?;
+
+    let (_, mut card) = get_account_and_card(&state, &account_id, &card_id)?;
+
+    let access_token = get_access_token(&state, &account_id, &app_data_dir).await?;
+    let labels = GmailClient::new(access_token).list_labels().await?;
+
+    let issue = crate::gmail::card_label_issue(&card, &labels).ok_or("The card's labels all exist")?;
+    card.query = issue.repaired_query.ok_or("No similar label found to repair the card with")?;
+
+    with_db(&state, |db| db.update_card(&card).map_err(|e| e.to_string()))?;
+    clear_prefetched_page(&state, &card.id);
+
+    sync_cards_to_icloud(&state);
+    Ok(card)
+}
+
 #[tauri::command]
 pub async fn save_draft(
     account_id: String,
@@ -1137,6 +2601,7 @@ pub async fn save_draft(
     subject: String,
     body: String,
     thread_id: Option<String>,
+    is_html: Option<bool>,
     app_handle: tauri::AppHandle, state: State<'_, AppState>,
 ) -> Result<GmailDraft, String> {
     let app_data_dir = get_app_data_dir(&app_handle)?;
@@ -1149,12 +2614,12 @@ pub async fn save_draft(
     match draft_id {
         Some(id) => {
             gmail
-                .update_draft(&id, &to, &cc, &bcc, &subject, &body, thread_id.as_deref(), false)
+                .update_draft(&id, &to, &cc, &bcc, &subject, &body, thread_id.as_deref(), is_html.unwrap_or(false))
                 .await
         }
         None => {
             gmail
-                .create_draft(&to, &cc, &bcc, &subject, &body, thread_id.as_deref(), false)
+                .create_draft(&to, &cc, &bcc, &subject, &body, thread_id.as_deref(), is_html.unwrap_or(false))
                 .await
         }
     }
@@ -1182,7 +2647,7 @@ pub async fn rsvp_calendar_event(
     event_uid: String,
     status: String, // "accepted", "tentative", or "declined"
     app_handle: tauri::AppHandle, state: State<'_, AppState>,
-) -> Result<(), String> {
+) -> Result<crate::calendar::RsvpOutcome, String> {
     let app_data_dir = get_app_data_dir(&app_handle)?;
 
     // Validate status
@@ -1220,19 +2685,25 @@ pub async fn get_calendar_rsvp_status(
 /// Pull cards from iCloud and merge with local. Returns true if changes were made.
 #[tauri::command]
 pub fn pull_from_icloud(state: State<'_, AppState>) -> Result<bool, String> {
-    let (icloud_cards, account_mappings) = {
+    let (icloud_cards, account_mappings, account_appearances) = {
         let icloud = state.icloud.lock().map_err(|_| "Lock error")?;
         let cards = icloud.load_cards().map_err(|e| e.to_string())?;
         let mappings = icloud.load_account_mappings().map_err(|e| e.to_string())?;
-        (cards, mappings)
+        let appearances = icloud.load_account_appearances().map_err(|e| e.to_string())?;
+        (cards, mappings, appearances)
+    };
+
+    let appearance_changed = match account_appearances {
+        Some(appearances) => with_db(&state, |db| apply_account_appearances(db, &appearances))?,
+        None => false,
     };
 
     let Some(icloud_cards) = icloud_cards else {
-        return Ok(false);
+        return Ok(appearance_changed);
     };
 
     if icloud_cards.is_empty() {
-        return Ok(false);
+        return Ok(appearance_changed);
     }
 
     // Account mappings: old_account_id -> email (from iCloud)
@@ -1257,7 +2728,7 @@ pub fn pull_from_icloud(state: State<'_, AppState>) -> Result<bool, String> {
     let local_account_by_email: std::collections::HashMap<String, &crate::models::Account> =
         accounts.iter().map(|a| (a.email.to_lowercase(), a)).collect();
 
-    let mut changes_made = false;
+    let mut changes_made = appearance_changed;
 
     tracing::info!(
         "pull_from_icloud: {} iCloud cards, {} local accounts, {} account mappings",
@@ -1312,16 +2783,125 @@ pub fn pull_from_icloud(state: State<'_, AppState>) -> Result<bool, String> {
         }
     }
 
+    // Merged cards can collide on position; re-pack so ordering is stable
+    if changes_made {
+        for account in &accounts {
+            db.normalize_card_positions(&account.id).map_err(|e| e.to_string())?;
+        }
+    }
+
     Ok(changes_made)
 }
 
+/// Battery saver: stop background polling and reminder checks. Commands the
+/// user triggers keep working.
+#[tauri::command]
+pub fn pause_background(app_handle: tauri::AppHandle, state: State<'_, AppState>) -> Result<(), String> {
+    set_background_paused(&app_handle, &state, true)
+}
+
+#[tauri::command]
+pub fn resume_background(app_handle: tauri::AppHandle, state: State<'_, AppState>) -> Result<(), String> {
+    set_background_paused(&app_handle, &state, false)
+}
+
+#[tauri::command]
+pub fn is_background_paused(state: State<'_, AppState>) -> bool {
+    state.background_paused.load(Ordering::SeqCst)
+}
+
+fn set_background_paused(app_handle: &tauri::AppHandle, state: &AppState, paused: bool) -> Result<(), String> {
+    with_db(state, |db| {
+        db.set_setting(crate::background::BACKGROUND_PAUSED_SETTING, if paused { "true" } else { "false" })
+            .map_err(|e| e.to_string())
+    })?;
+    state.background_paused.store(paused, Ordering::SeqCst);
+    let _ = app_handle.emit(crate::background::BACKGROUND_PAUSED_EVENT, paused);
+    Ok(())
+}
+
+/// Inline image downloads allowed per card load
+#[tauri::command]
+pub fn get_inline_fetch_budget(state: State<'_, AppState>) -> usize {
+    inline_fetch_budget(&state)
+}
+
+/// Set the inline image budget per card load. 0 defers every inline image
+/// until its thread is opened.
+#[tauri::command]
+pub fn set_inline_fetch_budget(budget: usize, state: State<'_, AppState>) -> Result<(), String> {
+    if budget > MAX_INLINE_FETCH_BUDGET {
+        return Err(format!("Inline image budget must be at most {}", MAX_INLINE_FETCH_BUDGET));
+    }
+    with_db(&state, |db| {
+        db.set_setting(INLINE_FETCH_BUDGET_SETTING, &budget.to_string())
+            .map_err(|e| e.to_string())
+    })
+}
+
 /// Force sync all cards to iCloud
 #[tauri::command]
 pub fn force_icloud_sync(state: State<'_, AppState>) -> Result<(), String> {
-    sync_cards_to_icloud(&state);
+    write_cards_to_icloud(&state.db, &state.icloud);
     Ok(())
 }
 
+#[derive(Debug, Serialize)]
+pub struct StorageUsage {
+    pub messages_total: u64,
+    pub threads_total: u64,
+    /// Bytes used across Gmail, Drive and Photos
+    pub used_bytes: Option<u64>,
+    /// Total bytes available; None when unknown or unlimited
+    pub total_bytes: Option<u64>,
+    pub percent_used: Option<f64>,
+    /// True when the token lacks a Drive scope and the user must re-login to see quota
+    pub drive_scope_missing: bool,
+}
+
+#[tauri::command]
+pub async fn storage_usage(
+    account_id: String,
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> Result<StorageUsage, String> {
+    let app_data_dir = get_app_data_dir(&app_handle)?;
+
+    verify_account_exists(&state, &account_id)?;
+
+    let access_token = get_access_token(&state, &account_id, &app_data_dir).await?;
+    let gmail = GmailClient::new(access_token.clone());
+    let drive = crate::drive::DriveClient::new(access_token);
+
+    let (profile, quota) = futures::join!(gmail.get_profile(), drive.get_storage_quota());
+    let profile = profile?;
+
+    // Quota is best-effort: report mailbox totals even if Drive is unavailable
+    let (quota, drive_scope_missing) = match quota {
+        Ok(Some(quota)) => (Some(quota), false),
+        Ok(None) => (None, true),
+        Err(e) => {
+            tracing::warn!("Failed to fetch storage quota: {}", e);
+            (None, false)
+        }
+    };
+
+    let used_bytes = quota.as_ref().map(|q| q.usage);
+    let total_bytes = quota.as_ref().and_then(|q| q.limit).filter(|l| *l > 0);
+    let percent_used = used_bytes
+        .zip(total_bytes)
+        .map(|(used, total)| used as f64 / total as f64 * 100.0);
+
+    Ok(StorageUsage {
+        messages_total: profile.messages_total,
+        threads_total: profile.threads_total,
+        used_bytes,
+        total_bytes,
+        percent_used,
+        drive_scope_missing,
+    })
+}
+
 // People API commands (contacts)
 
 #[tauri::command]
@@ -1338,7 +2918,13 @@ pub async fn fetch_contacts(
     let people = crate::people::PeopleClient::new(access_token);
 
     // Fetch up to 200 contacts
-    people.fetch_all_contacts(200).await
+    let contacts = people.fetch_all_contacts(200).await?;
+
+    // Cached for resolving thread participants in `card_view`
+    if let Err(e) = with_db(&state, |db| db.save_contacts(&account_id, &contacts).map_err(|e| e.to_string())) {
+        tracing::warn!("Failed to cache contacts: {}", e);
+    }
+    Ok(contacts)
 }
 
 #[tauri::command]
@@ -1352,18 +2938,103 @@ pub async fn search_contacts(
     verify_account_exists(&state, &account_id)?;
 
     let access_token = get_access_token(&state, &account_id, &app_data_dir).await?;
-    let people = crate::people::PeopleClient::new(access_token);
+    let people = crate::people::PeopleClient::new(access_token);
+
+    people.search_contacts(&query).await
+}
+
+// Calendar API commands
+
+#[tauri::command]
+pub async fn list_calendars(
+    account_id: String,
+    app_handle: tauri::AppHandle, state: State<'_, AppState>,
+) -> Result<Vec<crate::calendar::CalendarInfo>, String> {
+    let app_data_dir = get_app_data_dir(&app_handle)?;
+
+    verify_account_exists(&state, &account_id)?;
+
+    let access_token = get_access_token(&state, &account_id, &app_data_dir).await?;
+    let calendar = crate::calendar::CalendarClient::new(access_token);
+
+    calendar.list_calendars().await
+}
+
+#[tauri::command]
+pub async fn fetch_calendar_events(
+    account_id: String,
+    query: String,
+    request_id: Option<String>,
+    hide_declined: Option<bool>,
+    app_handle: tauri::AppHandle, state: State<'_, AppState>,
+) -> Result<Vec<crate::calendar::CalendarEvent>, String> {
+    let app_data_dir = get_app_data_dir(&app_handle)?;
+
+    verify_account_exists(&state, &account_id)?;
+
+    run_cancellable(&state.inflight, request_id.as_deref(), async {
+        let access_token = get_access_token(&state, &account_id, &app_data_dir).await?;
+        let calendar = crate::calendar::CalendarClient::new(access_token);
+
+        let mut parsed_query = crate::calendar::CalendarQuery::parse(&query);
+        // Declined events are hidden unless the caller opts in
+        parsed_query.hide_declined = hide_declined.unwrap_or(true);
+        calendar.search_events(&parsed_query, 50).await
+    })
+    .await
+}
+
+/// Default reach of `search_all_upcoming_events`
+const UPCOMING_SEARCH_DEFAULT_DAYS: i64 = 365;
+/// Furthest `search_all_upcoming_events` looks ahead
+const UPCOMING_SEARCH_MAX_DAYS: i64 = 5 * 365;
+
+/// Keyword search over every calendar's events in the next `horizon_days`
+/// (default a year), matching titles, descriptions and locations. Unlike
+/// card queries there's no "today" default range to hide later matches.
+#[tauri::command]
+pub async fn search_all_upcoming_events(
+    account_id: String,
+    text: String,
+    horizon_days: Option<i64>,
+    app_handle: tauri::AppHandle, state: State<'_, AppState>,
+) -> Result<Vec<crate::calendar::CalendarEvent>, String> {
+    if text.trim().is_empty() {
+        return Err("Search text is required".to_string());
+    }
+    let horizon_days = horizon_days
+        .unwrap_or(UPCOMING_SEARCH_DEFAULT_DAYS)
+        .clamp(1, UPCOMING_SEARCH_MAX_DAYS);
+
+    let app_data_dir = get_app_data_dir(&app_handle)?;
+
+    verify_account_exists(&state, &account_id)?;
+
+    let access_token = get_access_token(&state, &account_id, &app_data_dir).await?;
+    let calendar = crate::calendar::CalendarClient::new(access_token);
 
-    people.search_contacts(&query).await
+    let query = crate::calendar::CalendarQuery::upcoming_text(&text, horizon_days);
+    calendar.search_events(&query, 250).await
 }
 
-// Calendar API commands
-
+/// Busy times of meeting attendees over a window (milliseconds); attendees
+/// whose calendars aren't shared come back as unknown
 #[tauri::command]
-pub async fn list_calendars(
+pub async fn check_freebusy(
     account_id: String,
-    app_handle: tauri::AppHandle, state: State<'_, AppState>,
-) -> Result<Vec<crate::calendar::CalendarInfo>, String> {
+    emails: Vec<String>,
+    time_min: i64,
+    time_max: i64,
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> Result<Vec<crate::calendar::AttendeeAvailability>, String> {
+    if time_max <= time_min {
+        return Err("End of the window must be after its start".to_string());
+    }
+    if emails.is_empty() {
+        return Ok(Vec::new());
+    }
+
     let app_data_dir = get_app_data_dir(&app_handle)?;
 
     verify_account_exists(&state, &account_id)?;
@@ -1371,24 +3042,42 @@ pub async fn list_calendars(
     let access_token = get_access_token(&state, &account_id, &app_data_dir).await?;
     let calendar = crate::calendar::CalendarClient::new(access_token);
 
-    calendar.list_calendars().await
+    calendar.query_freebusy(&emails, time_min, time_max).await
 }
 
+/// The account's free time between `start` and `end` (milliseconds) as
+/// paste-ready text, in `slot_minutes` slots within working hours in `tz`
 #[tauri::command]
-pub async fn fetch_calendar_events(
+pub async fn export_availability_text(
     account_id: String,
-    query: String,
-    app_handle: tauri::AppHandle, state: State<'_, AppState>,
-) -> Result<Vec<crate::calendar::CalendarEvent>, String> {
+    start: i64,
+    end: i64,
+    tz: String,
+    slot_minutes: u32,
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> Result<String, String> {
+    if end <= start {
+        return Err("End of the window must be after its start".to_string());
+    }
+    let working_minutes = (crate::calendar::WORKING_DAY_END_HOUR - crate::calendar::WORKING_DAY_START_HOUR) * 60;
+    if slot_minutes == 0 || slot_minutes > working_minutes {
+        return Err(format!("Slot length must be between 1 and {} minutes", working_minutes));
+    }
+    let tz: chrono_tz::Tz = tz.parse().map_err(|_| format!("Unknown timezone: {}", tz))?;
+
     let app_data_dir = get_app_data_dir(&app_handle)?;
 
-    verify_account_exists(&state, &account_id)?;
+    let email = get_account_email(&state, &account_id)?;
 
     let access_token = get_access_token(&state, &account_id, &app_data_dir).await?;
     let calendar = crate::calendar::CalendarClient::new(access_token);
 
-    let parsed_query = crate::calendar::CalendarQuery::parse(&query);
-    calendar.search_events(&parsed_query, 50).await
+    let availability = calendar.query_freebusy(&[email], start, end).await?;
+    let busy = availability.into_iter().next().map(|a| a.busy).unwrap_or_default();
+
+    let slots = crate::calendar::free_slots(&busy, start, end, tz, slot_minutes);
+    Ok(crate::calendar::format_availability(&slots, tz))
 }
 
 #[tauri::command]
@@ -1403,6 +3092,7 @@ pub async fn create_calendar_event(
     all_day: bool,
     attendees: Option<Vec<String>>,
     recurrence: Option<Vec<String>>,
+    with_meet: Option<bool>,
     app_handle: tauri::AppHandle,
     state: State<'_, AppState>,
 ) -> Result<crate::models::GoogleCalendarEvent, String> {
@@ -1422,10 +3112,226 @@ pub async fn create_calendar_event(
             location,
             attendees,
             recurrence,
+            with_meet.unwrap_or(false),
         )
         .await
 }
 
+#[derive(Debug, Serialize)]
+pub struct AgendaImportResult {
+    pub created_ids: Vec<String>,
+    /// Lines that could not be parsed as "HH:MM[-HH:MM] Title"
+    pub unparsed_lines: Vec<String>,
+    /// Titles of parsed entries whose event could not be created
+    pub failed_lines: Vec<String>,
+}
+
+/// Create one event per line of a typed day plan such as
+/// "10:00 Standup" / "14:00-15:00 Review". Times are local to `base_date`
+/// (YYYY-MM-DD).
+#[tauri::command]
+pub async fn import_agenda(
+    account_id: String,
+    calendar_id: String,
+    text: String,
+    base_date: String,
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> Result<AgendaImportResult, String> {
+    use chrono::TimeZone;
+
+    let app_data_dir = get_app_data_dir(&app_handle)?;
+
+    verify_account_exists(&state, &account_id)?;
+
+    let base_date = chrono::NaiveDate::parse_from_str(&base_date, "%Y-%m-%d")
+        .map_err(|e| format!("Invalid date '{}': {}", base_date, e))?;
+    let (items, unparsed_lines) = crate::calendar::parse_agenda(&text, base_date);
+
+    let access_token = get_access_token(&state, &account_id, &app_data_dir).await?;
+    let calendar = crate::calendar::CalendarClient::new(access_token);
+
+    let mut created_ids = Vec::new();
+    let mut failed_lines = Vec::new();
+    for item in items {
+        let start = chrono::Local.from_local_datetime(&item.start).earliest();
+        let end = chrono::Local.from_local_datetime(&item.end).earliest();
+        let (Some(start), Some(end)) = (start, end) else {
+            failed_lines.push(item.title);
+            continue;
+        };
+
+        match calendar
+            .create_event(
+                &calendar_id,
+                item.title.clone(),
+                None,
+                start.timestamp_millis(),
+                end.timestamp_millis(),
+                false,
+                None,
+                None,
+                None,
+                false,
+            )
+            .await
+        {
+            Ok(event) => created_ids.push(event.id),
+            Err(e) => {
+                tracing::warn!("Failed to create agenda event '{}': {}", item.title, e);
+                failed_lines.push(item.title);
+            }
+        }
+    }
+
+    Ok(AgendaImportResult {
+        created_ids,
+        unparsed_lines,
+        failed_lines,
+    })
+}
+
+/// Export a calendar's events between `time_min` and `time_max` (ms) as ICS.
+/// Writes to `dest` when given (a directory gets "<calendar>.ics") and returns
+/// the path; otherwise returns the ICS text.
+#[tauri::command]
+pub async fn export_calendar_ics(
+    account_id: String,
+    calendar_id: String,
+    time_min: i64,
+    time_max: i64,
+    dest: Option<String>,
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> Result<String, String> {
+    let app_data_dir = get_app_data_dir(&app_handle)?;
+
+    verify_account_exists(&state, &account_id)?;
+
+    let (Some(time_min), Some(time_max)) = (
+        chrono::DateTime::from_timestamp_millis(time_min),
+        chrono::DateTime::from_timestamp_millis(time_max),
+    ) else {
+        return Err("Invalid time range".to_string());
+    };
+
+    let access_token = get_access_token(&state, &account_id, &app_data_dir).await?;
+    let calendar = crate::calendar::CalendarClient::new(access_token);
+
+    // 2500 is the API's page cap; exports are bounded by the requested range
+    let events = calendar
+        .list_events(&calendar_id, &calendar_id, "reader", time_min, time_max, 2500)
+        .await?;
+    let ics = crate::calendar::events_to_ics(&events);
+
+    let Some(dest) = dest else {
+        return Ok(ics);
+    };
+
+    let mut path = std::path::PathBuf::from(&dest);
+    if path.is_dir() {
+        let safe_name: String = calendar_id
+            .chars()
+            .map(|c| if c.is_alphanumeric() || c == '-' || c == '.' { c } else { '_' })
+            .take(80)
+            .collect();
+        path = path.join(format!("{}.ics", safe_name));
+    }
+
+    std::fs::write(&path, ics).map_err(|e| format!("Failed to write ICS: {}", e))?;
+
+    Ok(path.to_string_lossy().to_string())
+}
+
+/// Remove a cancelled invite from the user's primary calendar by its ICS UID.
+/// Returns false when the event is not (or no longer) on the calendar.
+#[tauri::command]
+pub async fn remove_cancelled_event(
+    account_id: String,
+    event_uid: String,
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> Result<bool, String> {
+    let app_data_dir = get_app_data_dir(&app_handle)?;
+
+    verify_account_exists(&state, &account_id)?;
+
+    let access_token = get_access_token(&state, &account_id, &app_data_dir).await?;
+    let calendar = crate::calendar::CalendarClient::new(access_token);
+
+    let Some(event_id) = calendar.find_event_id_by_uid(&event_uid).await? else {
+        return Ok(false);
+    };
+
+    // The organizer already cancelled it; don't notify anyone again
+    calendar.delete_event("primary", &event_id, "none").await?;
+    Ok(true)
+}
+
+#[tauri::command]
+pub async fn set_event_color(
+    account_id: String,
+    calendar_id: String,
+    event_id: String,
+    color_id: String,
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let app_data_dir = get_app_data_dir(&app_handle)?;
+
+    verify_account_exists(&state, &account_id)?;
+
+    let access_token = get_access_token(&state, &account_id, &app_data_dir).await?;
+    let calendar = crate::calendar::CalendarClient::new(access_token);
+
+    calendar.set_event_color(&calendar_id, &event_id, &color_id).await
+}
+
+/// Color upcoming events by title keyword. The first matching rule wins;
+/// events the user can't edit or that already have the color are skipped.
+/// Returns how many events were recolored.
+#[tauri::command]
+pub async fn auto_color_events(
+    account_id: String,
+    rules: Vec<crate::calendar::ColorRule>,
+    days: Option<i64>,
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> Result<usize, String> {
+    let app_data_dir = get_app_data_dir(&app_handle)?;
+
+    verify_account_exists(&state, &account_id)?;
+
+    if let Some(bad) = rules
+        .iter()
+        .find(|rule| crate::calendar::resolve_color_id(&rule.color).is_none())
+    {
+        return Err(format!("Invalid color for \"{}\": {}", bad.keyword, bad.color));
+    }
+
+    let access_token = get_access_token(&state, &account_id, &app_data_dir).await?;
+    let calendar = crate::calendar::CalendarClient::new(access_token);
+
+    let query = crate::calendar::CalendarQuery::parse(&format!("calendar:{}d", days.unwrap_or(30).max(1)));
+    let events = calendar.search_events(&query, 250).await?;
+
+    let mut recolored = 0;
+    for event in events.iter().filter(|e| e.can_edit) {
+        let Some(color_id) = crate::calendar::match_color_rule(&event.title, &rules) else {
+            continue;
+        };
+        if event.color_id.as_deref() == Some(color_id) {
+            continue;
+        }
+        match calendar.set_event_color(&event.calendar_id, &event.id, color_id).await {
+            Ok(()) => recolored += 1,
+            Err(e) => tracing::warn!("Failed to color event {}: {}", event.id, e),
+        }
+    }
+
+    Ok(recolored)
+}
+
 #[tauri::command]
 pub async fn move_calendar_event(
     account_id: String,
@@ -1455,10 +3361,12 @@ pub async fn delete_calendar_event(
 ) -> Result<(), String> {
     let app_data_dir = get_app_data_dir(&app_handle)?;
 
+    verify_account_exists(&state, &account_id)?;
+
     let access_token = get_access_token(&state, &account_id, &app_data_dir).await?;
     let calendar = crate::calendar::CalendarClient::new(access_token);
 
-    calendar.delete_event(&calendar_id, &event_id).await
+    calendar.delete_event(&calendar_id, &event_id, "all").await
 }
 
 #[tauri::command]
@@ -1466,36 +3374,38 @@ pub async fn update_calendar_event(
     account_id: String,
     calendar_id: String,
     event_id: String,
-    summary: String,
+    summary: Option<String>,
     description: Option<String>,
     location: Option<String>,
-    start_time: i64,
-    end_time: i64,
-    all_day: bool,
+    start_time: Option<i64>,
+    end_time: Option<i64>,
+    all_day: Option<bool>,
     attendees: Option<Vec<String>>,
     recurrence: Option<Vec<String>>,
     app_handle: tauri::AppHandle,
     state: State<'_, AppState>,
 ) -> Result<crate::models::GoogleCalendarEvent, String> {
+    let times = match (start_time, end_time, all_day) {
+        (Some(start), Some(end), all_day) => Some((start, end, all_day.unwrap_or(false))),
+        (None, None, None) => None,
+        _ => return Err("Start and end time must be changed together".to_string()),
+    };
+
     let app_data_dir = get_app_data_dir(&app_handle)?;
+    verify_account_exists(&state, &account_id)?;
 
     let access_token = get_access_token(&state, &account_id, &app_data_dir).await?;
     let calendar = crate::calendar::CalendarClient::new(access_token);
 
-    calendar
-        .update_event(
-            &calendar_id,
-            &event_id,
-            summary,
-            description,
-            start_time,
-            end_time,
-            all_day,
-            location,
-            attendees,
-            recurrence,
-        )
-        .await
+    let patch = crate::calendar::EventPatch {
+        summary,
+        description,
+        location,
+        times,
+        attendees,
+        recurrence,
+    };
+    calendar.update_event(&calendar_id, &event_id, patch).await
 }
 
 #[tauri::command]
@@ -1503,13 +3413,11 @@ pub async fn suggest_replies(
     account_id: String,
     thread_id: String,
     api_key: String,
+    ai_settings: Option<crate::ai::AiSettings>,
     app_handle: tauri::AppHandle, state: State<'_, AppState>,
 ) -> Result<Vec<String>, String> {
     let app_data_dir = get_app_data_dir(&app_handle)?;
-
-    if api_key.is_empty() {
-        return Err("Gemini API key is required for smart replies.".to_string());
-    }
+    let backend = resolve_ai_backend(&app_data_dir, api_key, ai_settings.unwrap_or_default(), "smart replies")?;
 
     let user_email = get_account_email(&state, &account_id)?;
 
@@ -1523,6 +3431,28 @@ pub async fn suggest_replies(
         .map_err(|e| format!("Failed to fetch thread: {}", e))?;
 
     // 2. Build email context from the last few messages with FULL bodies
+    let context = thread_ai_context(&thread);
+
+    // 3. Call the AI provider, replying in the thread's language
+    backend.suggest_replies(&context, &user_email, thread_language(&thread)).await
+}
+
+/// Language of a thread's last few message bodies, for localized prompts
+fn thread_language(thread: &crate::gmail::FullThread) -> &'static str {
+    let text = thread
+        .messages
+        .iter()
+        .rev()
+        .take(3)
+        .filter_map(crate::gmail::extract_body_text_from_message)
+        .collect::<Vec<_>>()
+        .join("\n");
+    crate::ai::detect_language(&text)
+}
+
+/// Prompt context for a thread: its subject and the last three messages with
+/// full bodies, quotes of earlier messages trimmed
+fn thread_ai_context(thread: &crate::gmail::FullThread) -> String {
     let mut context = String::new();
 
     // Get subject
@@ -1552,8 +3482,11 @@ pub async fn suggest_replies(
             .map(|x| x.value.as_str())
             .unwrap_or("");
 
-        // Get full body text instead of snippet
+        // Get full body text instead of snippet, minus quotes of earlier
+        // messages that are already in the context
         let body = crate::gmail::extract_body_text_from_message(msg)
+            .map(|text| crate::gmail::new_content(&text))
+            .filter(|text| !text.is_empty())
             .unwrap_or_else(|| msg.snippet.clone().unwrap_or_default());
 
         // Truncate very long messages to avoid token limits
@@ -1566,7 +3499,220 @@ pub async fn suggest_replies(
         context.push_str(&format!("From: {}\nDate: {}\n{}\n\n---\n\n", from, date, body_truncated));
     }
 
-    // 3. Call Gemini API
-    let gemini = GeminiClient::new(api_key);
-    gemini.suggest_replies(&context, &user_email).await
+    context
+}
+
+/// Draft a full email body from a short instruction. With `thread_id` the
+/// draft is a reply and the thread is given to the model as context.
+#[tauri::command]
+pub async fn compose_draft(
+    account_id: String,
+    instruction: String,
+    thread_id: Option<String>,
+    tone: Option<crate::ai::Tone>,
+    api_key: String,
+    ai_settings: Option<crate::ai::AiSettings>,
+    app_handle: tauri::AppHandle, state: State<'_, AppState>,
+) -> Result<String, String> {
+    if instruction.trim().is_empty() {
+        return Err("Tell the assistant what to write".to_string());
+    }
+
+    let app_data_dir = get_app_data_dir(&app_handle)?;
+    let backend = resolve_ai_backend(&app_data_dir, api_key, ai_settings.unwrap_or_default(), "AI drafts")?;
+
+    let user_email = get_account_email(&state, &account_id)?;
+
+    let context = match thread_id {
+        Some(thread_id) => {
+            let access_token = get_access_token(&state, &account_id, &app_data_dir).await?;
+            let thread = GmailClient::new(access_token)
+                .get_thread(&thread_id)
+                .await
+                .map_err(|e| format!("Failed to fetch thread: {}", e))?;
+            Some(thread_ai_context(&thread))
+        }
+        None => None,
+    };
+
+    backend
+        .compose_draft(&instruction, context.as_deref(), &user_email, tone)
+        .await
+}
+
+/// One-paragraph summary and action items for a long thread
+#[tauri::command]
+pub async fn summarize_thread(
+    account_id: String,
+    thread_id: String,
+    api_key: String,
+    ai_settings: Option<crate::ai::AiSettings>,
+    app_handle: tauri::AppHandle, state: State<'_, AppState>,
+) -> Result<crate::ai::ThreadSummary, String> {
+    let app_data_dir = get_app_data_dir(&app_handle)?;
+    let backend = resolve_ai_backend(&app_data_dir, api_key, ai_settings.unwrap_or_default(), "thread summaries")?;
+
+    verify_account_exists(&state, &account_id)?;
+    let access_token = get_access_token(&state, &account_id, &app_data_dir).await?;
+
+    let thread = GmailClient::new(access_token)
+        .get_thread(&thread_id)
+        .await
+        .map_err(|e| format!("Failed to fetch thread: {}", e))?;
+
+    backend.summarize_thread(&thread_ai_context(&thread)).await
+}
+
+/// A provider picked via set_ai_provider wins; otherwise fall back to Gemini
+/// with the key from settings. A model in `settings` overrides the stored one.
+fn resolve_ai_backend(
+    app_data_dir: &std::path::Path,
+    api_key: String,
+    settings: crate::ai::AiSettings,
+    feature: &str,
+) -> Result<AiBackend, String> {
+    match auth::get_ai_credentials(app_data_dir) {
+        Ok(mut credentials) => {
+            if settings.model.is_some() {
+                credentials.model = settings.model;
+            }
+            AiBackend::from_credentials(&credentials)
+        }
+        Err(_) if api_key.is_empty() => Err(format!("Gemini API key is required for {}.", feature)),
+        Err(_) => Ok(AiBackend::Gemini(GeminiClient::new(api_key, settings.model)?)),
+    }
+}
+
+/// Topic-grouped summary of the past week's unread and important threads
+#[tauri::command]
+pub async fn weekly_digest(
+    account_id: String,
+    api_key: String,
+    app_handle: tauri::AppHandle, state: State<'_, AppState>,
+) -> Result<crate::ai::WeeklyDigest, String> {
+    let app_data_dir = get_app_data_dir(&app_handle)?;
+    let backend = resolve_ai_backend(&app_data_dir, api_key, Default::default(), "the weekly digest")?;
+
+    verify_account_exists(&state, &account_id)?;
+    let access_token = get_access_token(&state, &account_id, &app_data_dir).await?;
+
+    let groups = GmailClient::new(access_token)
+        .search_threads_limited(
+            "newer_than:7d (is:unread OR is:important) -in:spam -in:trash",
+            crate::ai::MAX_DIGEST_THREADS,
+        )
+        .await
+        .map_err(|e| format!("Search failed: {}", e))?;
+    let mut threads: Vec<crate::models::Thread> = groups.into_iter().flat_map(|g| g.threads).collect();
+    if threads.is_empty() {
+        return Ok(crate::ai::WeeklyDigest { topics: Vec::new() });
+    }
+    threads.sort_by(|a, b| b.last_message_date.cmp(&a.last_message_date));
+
+    backend.weekly_digest(&crate::ai::digest_context(&threads)).await
+}
+
+/// Choose the AI backend for smart replies. The API key is kept in the
+/// keychain. Selecting "gemini" with an empty key reverts to the default
+/// (Gemini with the key from settings).
+#[tauri::command]
+pub fn set_ai_provider(
+    provider: String,
+    api_key: String,
+    base_url: Option<String>,
+    model: Option<String>,
+    app_handle: tauri::AppHandle,
+) -> Result<(), String> {
+    let app_data_dir = get_app_data_dir(&app_handle)?;
+
+    if provider == "gemini" && api_key.trim().is_empty() {
+        return auth::delete_ai_credentials(&app_data_dir).map_err(|e| e.to_string());
+    }
+    if api_key.trim().is_empty() {
+        return Err("An API key is required.".to_string());
+    }
+
+    let credentials = auth::AiCredentials {
+        provider,
+        api_key: api_key.trim().to_string(),
+        base_url,
+        model,
+    };
+    // Reject unknown providers before persisting anything
+    AiBackend::from_credentials(&credentials)?;
+
+    auth::store_ai_credentials(&credentials, &app_data_dir).map_err(|e| e.to_string())
+}
+
+/// Name of the configured AI provider, or None when using the default
+#[tauri::command]
+pub fn get_ai_provider(app_handle: tauri::AppHandle) -> Result<Option<String>, String> {
+    let app_data_dir = get_app_data_dir(&app_handle)?;
+    Ok(auth::get_ai_credentials(&app_data_dir).ok().map(|c| c.provider))
+}
+
+#[tauri::command]
+pub fn get_api_diagnostics() -> Vec<crate::diagnostics::EndpointStats> {
+    crate::diagnostics::snapshot()
+}
+
+/// Gmail rate limiter state: current backoff, bucket level and the latest quota headers
+#[tauri::command]
+pub fn get_quota_status() -> crate::rate_limit::QuotaStatus {
+    crate::rate_limit::status()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_successful_sends_are_mirrored() {
+        let state = AppState::new();
+        *state.db.lock().unwrap() = Some(CacheDb::new(std::path::Path::new(":memory:")).unwrap());
+
+        mirror_sent(&state, "acc", None, "bob@example.com", "Proposal", Ok(())).unwrap();
+        assert!(mirror_sent(&state, "acc", Some("t1"), "bob@example.com", "Bounced", Err("API error 400".to_string())).is_err());
+
+        let sent = with_db(&state, |db| db.recent_sent(Some("acc"), 10).map_err(|e| e.to_string())).unwrap();
+        assert_eq!(sent.iter().map(|e| e.subject.as_str()).collect::<Vec<_>>(), vec!["Proposal"]);
+    }
+
+    #[test]
+    fn cleared_account_appearance_syncs_as_a_clear() {
+        let phone = CacheDb::new(std::path::Path::new(":memory:")).unwrap();
+        phone.upsert_account_by_email("me@example.com", None).unwrap();
+        let appearances = account_appearances(&phone.get_accounts().unwrap());
+        assert_eq!(appearances["me@example.com"], AccountAppearance { nickname: None, color: None });
+
+        let laptop = CacheDb::new(std::path::Path::new(":memory:")).unwrap();
+        let account = laptop.upsert_account_by_email("Me@Example.com", None).unwrap();
+        laptop.update_account_appearance(&account.id, Some("Work"), Some("#3366ff")).unwrap();
+
+        assert!(apply_account_appearances(&laptop, &appearances).unwrap());
+        let accounts = laptop.get_accounts().unwrap();
+        assert_eq!(accounts[0].nickname, None);
+        assert_eq!(accounts[0].color, None);
+    }
+
+    #[test]
+    fn load_more_is_served_from_the_prefetch_buffer() {
+        // No database or auth: any network path would fail
+        let state = AppState::new();
+        let page = SearchResult {
+            groups: Vec::new(),
+            next_page_token: Some("token-3".to_string()),
+            has_more: true,
+        };
+        let now = chrono::Utc::now().timestamp();
+        state.prefetch.lock().unwrap().store("card", "token-2", page, now);
+
+        let served = take_prefetched_page(&state, "card", "token-2").unwrap().unwrap();
+        assert_eq!(served.next_page_token.as_deref(), Some("token-3"));
+
+        // Deleting or moving a card drops its buffered page
+        state.prefetch.lock().unwrap().store("card", "token-2", served, now);
+        clear_prefetched_page(&state, "card");
+        assert!(take_prefetched_page(&state, "card", "token-2").unwrap().is_none());
+    }
 }
@@ -0,0 +1,64 @@
+// Trailing-edge debouncing
+//
+// Bursts of edits (a bulk reorder, a bundle import) each ask for the same
+// follow-up work. A debouncer runs that work once, a short delay after the
+// last request in the burst; every new call supersedes the pending one.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+pub struct Debouncer {
+    delay: Duration,
+    /// Bumped by every call; a scheduled job only runs if it is still the latest
+    generation: Arc<AtomicU64>,
+}
+
+impl Debouncer {
+    pub fn new(delay: Duration) -> Self {
+        Self {
+            delay,
+            generation: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Run `job` once `delay` passes without another call
+    pub fn call<F>(&self, job: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let generation = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+        let latest = self.generation.clone();
+        let delay = self.delay;
+        tauri::async_runtime::spawn(async move {
+            tokio::time::sleep(delay).await;
+            if latest.load(Ordering::SeqCst) == generation {
+                job();
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    #[tokio::test]
+    async fn rapid_calls_run_the_job_once() {
+        let debouncer = Debouncer::new(Duration::from_millis(50));
+        let runs = Arc::new(AtomicUsize::new(0));
+
+        for _ in 0..10 {
+            let runs = runs.clone();
+            debouncer.call(move || {
+                runs.fetch_add(1, Ordering::SeqCst);
+            });
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        assert_eq!(runs.load(Ordering::SeqCst), 0);
+
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+    }
+}
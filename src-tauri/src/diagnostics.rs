@@ -0,0 +1,170 @@
+// API latency diagnostics
+//
+// Records per-endpoint request timings in a bounded in-memory buffer so
+// "Posta is slow" reports can be triaged with real numbers.
+
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+/// Maximum number of samples kept per endpoint (oldest are dropped first)
+const MAX_SAMPLES_PER_ENDPOINT: usize = 200;
+
+static RECORDER: Lazy<Mutex<LatencyRecorder>> = Lazy::new(|| Mutex::new(LatencyRecorder::default()));
+
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    duration_ms: u64,
+    ok: bool,
+}
+
+/// Latency summary for a single endpoint
+#[derive(Debug, Clone, Serialize)]
+pub struct EndpointStats {
+    pub endpoint: String,
+    pub count: usize,
+    pub p50_ms: u64,
+    pub p95_ms: u64,
+    pub error_rate: f64,
+}
+
+/// Ring buffer of recent request timings, keyed by endpoint name
+#[derive(Debug, Default)]
+pub struct LatencyRecorder {
+    endpoints: HashMap<String, VecDeque<Sample>>,
+}
+
+impl LatencyRecorder {
+    pub fn record(&mut self, endpoint: &str, duration: Duration, ok: bool) {
+        let samples = self.endpoints.entry(endpoint.to_string()).or_default();
+        if samples.len() >= MAX_SAMPLES_PER_ENDPOINT {
+            samples.pop_front();
+        }
+        samples.push_back(Sample {
+            duration_ms: duration.as_millis() as u64,
+            ok,
+        });
+    }
+
+    pub fn snapshot(&self) -> Vec<EndpointStats> {
+        let mut stats: Vec<EndpointStats> = self
+            .endpoints
+            .iter()
+            .filter(|(_, samples)| !samples.is_empty())
+            .map(|(endpoint, samples)| {
+                let mut durations: Vec<u64> = samples.iter().map(|s| s.duration_ms).collect();
+                durations.sort_unstable();
+                let errors = samples.iter().filter(|s| !s.ok).count();
+
+                EndpointStats {
+                    endpoint: endpoint.clone(),
+                    count: samples.len(),
+                    p50_ms: percentile(&durations, 50.0),
+                    p95_ms: percentile(&durations, 95.0),
+                    error_rate: errors as f64 / samples.len() as f64,
+                }
+            })
+            .collect();
+
+        stats.sort_by(|a, b| a.endpoint.cmp(&b.endpoint));
+        stats
+    }
+}
+
+/// Nearest-rank percentile over an already sorted slice
+fn percentile(sorted: &[u64], pct: f64) -> u64 {
+    if sorted.is_empty() {
+        return 0;
+    }
+    let rank = ((pct / 100.0) * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+/// Record a single request timing
+pub fn record(endpoint: &str, duration: Duration, ok: bool) {
+    RECORDER.lock().record(endpoint, duration, ok);
+}
+
+/// Current latency summary for every endpoint seen so far
+pub fn snapshot() -> Vec<EndpointStats> {
+    RECORDER.lock().snapshot()
+}
+
+/// Send a request and record its latency under `endpoint`.
+/// Transport failures and non-2xx responses both count as errors.
+pub async fn timed_send(
+    endpoint: &str,
+    request: reqwest::RequestBuilder,
+) -> Result<reqwest::Response, reqwest::Error> {
+    let start = Instant::now();
+    let result = request.send().await;
+    let ok = matches!(&result, Ok(resp) if resp.status().is_success());
+    record(endpoint, start.elapsed(), ok);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed(recorder: &mut LatencyRecorder, endpoint: &str, timings_ms: &[u64]) {
+        for &ms in timings_ms {
+            recorder.record(endpoint, Duration::from_millis(ms), true);
+        }
+    }
+
+    #[test]
+    fn computes_p50_and_p95() {
+        let mut recorder = LatencyRecorder::default();
+        let timings: Vec<u64> = (1..=100).collect();
+        feed(&mut recorder, "gmail.threads.list", &timings);
+
+        let stats = recorder.snapshot();
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].count, 100);
+        assert_eq!(stats[0].p50_ms, 50);
+        assert_eq!(stats[0].p95_ms, 95);
+        assert_eq!(stats[0].error_rate, 0.0);
+    }
+
+    #[test]
+    fn percentiles_ignore_insertion_order() {
+        let mut recorder = LatencyRecorder::default();
+        feed(&mut recorder, "calendar.events", &[900, 100, 300, 200, 400]);
+
+        let stats = recorder.snapshot();
+        assert_eq!(stats[0].p50_ms, 300);
+        assert_eq!(stats[0].p95_ms, 900);
+    }
+
+    #[test]
+    fn tracks_error_rate() {
+        let mut recorder = LatencyRecorder::default();
+        recorder.record("gmail.batch", Duration::from_millis(10), true);
+        recorder.record("gmail.batch", Duration::from_millis(10), false);
+        recorder.record("gmail.batch", Duration::from_millis(10), true);
+        recorder.record("gmail.batch", Duration::from_millis(10), false);
+
+        let stats = recorder.snapshot();
+        assert_eq!(stats[0].error_rate, 0.5);
+    }
+
+    #[test]
+    fn buffer_is_bounded() {
+        let mut recorder = LatencyRecorder::default();
+        let timings: Vec<u64> = (0..(MAX_SAMPLES_PER_ENDPOINT as u64 + 50)).collect();
+        feed(&mut recorder, "gmail.threads.get", &timings);
+
+        let stats = recorder.snapshot();
+        assert_eq!(stats[0].count, MAX_SAMPLES_PER_ENDPOINT);
+        // The oldest 50 samples were evicted, leaving 50..=249
+        assert_eq!(stats[0].p50_ms, 149);
+    }
+
+    #[test]
+    fn percentile_of_empty_is_zero() {
+        assert_eq!(percentile(&[], 95.0), 0);
+    }
+}
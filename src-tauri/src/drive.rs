@@ -0,0 +1,132 @@
+// Google Drive API client (storage quota only)
+
+use serde::{Deserialize, Serialize};
+
+const DRIVE_API_BASE: &str = "https://www.googleapis.com/drive/v3";
+
+/// Account-wide storage quota shared by Gmail, Drive and Photos
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct StorageQuota {
+    /// Total bytes available; None for unlimited plans
+    pub limit: Option<u64>,
+    pub usage: u64,
+    pub usage_in_drive: Option<u64>,
+    pub usage_in_drive_trash: Option<u64>,
+}
+
+// Drive returns int64 fields as JSON strings
+#[derive(Debug, Deserialize)]
+struct AboutResponse {
+    #[serde(rename = "storageQuota")]
+    storage_quota: Option<ApiStorageQuota>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiStorageQuota {
+    limit: Option<String>,
+    usage: Option<String>,
+    #[serde(rename = "usageInDrive")]
+    usage_in_drive: Option<String>,
+    #[serde(rename = "usageInDriveTrash")]
+    usage_in_drive_trash: Option<String>,
+}
+
+/// Parse a `about?fields=storageQuota` response body
+fn parse_storage_quota(body: &str) -> Result<StorageQuota, String> {
+    let about: AboutResponse =
+        serde_json::from_str(body).map_err(|e| format!("Failed to parse storage quota: {}", e))?;
+    let quota = about.storage_quota.ok_or("Response has no storageQuota")?;
+
+    let parse = |v: Option<String>| v.and_then(|s| s.parse::<u64>().ok());
+
+    Ok(StorageQuota {
+        limit: parse(quota.limit),
+        usage: parse(quota.usage).unwrap_or(0),
+        usage_in_drive: parse(quota.usage_in_drive),
+        usage_in_drive_trash: parse(quota.usage_in_drive_trash),
+    })
+}
+
+pub struct DriveClient {
+    http_client: reqwest::Client,
+    access_token: String,
+}
+
+impl DriveClient {
+    pub fn new(access_token: String) -> Self {
+        Self {
+            http_client: reqwest::Client::new(),
+            access_token,
+        }
+    }
+
+    /// Fetch the account's storage quota.
+    /// Returns Ok(None) when the token was not granted the storage scope
+    /// (`drive.metadata.readonly`), e.g. for sign-ins from before it was requested.
+    pub async fn get_storage_quota(&self) -> Result<Option<StorageQuota>, String> {
+        let url = format!("{}/about?fields=storageQuota", DRIVE_API_BASE);
+
+        let resp = self
+            .http_client
+            .get(&url)
+            .bearer_auth(&self.access_token)
+            .send()
+            .await
+            .map_err(|e| format!("Drive API request failed: {}", e))?;
+
+        let status = resp.status();
+        let body = resp.text().await.unwrap_or_default();
+
+        if status == reqwest::StatusCode::FORBIDDEN
+            && (body.contains("insufficientPermissions") || body.contains("ACCESS_TOKEN_SCOPE_INSUFFICIENT"))
+        {
+            return Ok(None);
+        }
+
+        if !status.is_success() {
+            return Err(format!("Drive API error ({}): {}", status, body));
+        }
+
+        parse_storage_quota(&body).map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_storage_quota_strings() {
+        let body = r#"{
+            "storageQuota": {
+                "limit": "16106127360",
+                "usage": "14979236249",
+                "usageInDrive": "2147483648",
+                "usageInDriveTrash": "1024"
+            }
+        }"#;
+
+        let quota = parse_storage_quota(body).unwrap();
+        assert_eq!(
+            quota,
+            StorageQuota {
+                limit: Some(16_106_127_360),
+                usage: 14_979_236_249,
+                usage_in_drive: Some(2_147_483_648),
+                usage_in_drive_trash: Some(1024),
+            }
+        );
+    }
+
+    #[test]
+    fn unlimited_plans_have_no_limit() {
+        let quota = parse_storage_quota(r#"{"storageQuota": {"usage": "42"}}"#).unwrap();
+        assert_eq!(quota.limit, None);
+        assert_eq!(quota.usage, 42);
+    }
+
+    #[test]
+    fn rejects_responses_without_quota() {
+        assert!(parse_storage_quota("{}").is_err());
+    }
+}
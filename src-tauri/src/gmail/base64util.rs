@@ -0,0 +1,66 @@
+// Base64 as Gmail and the frontend produce it
+//
+// Gmail hands out URL-safe base64, padded or not; the frontend and older
+// cache rows use the standard alphabet, sometimes wrapped. Everything decodes
+// through `decode_gmail_b64` so either alphabet, optional padding and
+// embedded whitespace are accepted alike.
+
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use base64::Engine;
+
+/// Decode URL-safe or standard base64, with or without padding
+pub fn decode_gmail_b64(data: &str) -> Result<Vec<u8>, String> {
+    let normalized: String = data
+        .chars()
+        .filter(|c| !c.is_ascii_whitespace() && *c != '=')
+        .map(|c| match c {
+            '+' => '-',
+            '/' => '_',
+            c => c,
+        })
+        .collect();
+    URL_SAFE_NO_PAD
+        .decode(normalized)
+        .map_err(|e| format!("Invalid base64 data: {}", e))
+}
+
+/// Encode as padded standard base64, one CRLF-terminated line per `width`
+/// characters (76 for MIME bodies). A `width` of 0 means no wrapping.
+pub fn encode_std_b64_wrapped(bytes: &[u8], width: usize) -> String {
+    let encoded = STANDARD.encode(bytes);
+    if width == 0 {
+        return encoded;
+    }
+
+    let mut wrapped = String::with_capacity(encoded.len() + encoded.len() / width * 2 + 2);
+    // Base64 output is ASCII, so byte chunks are valid str boundaries
+    for line in encoded.as_bytes().chunks(width) {
+        wrapped.push_str(std::str::from_utf8(line).unwrap_or(""));
+        wrapped.push_str("\r\n");
+    }
+    wrapped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_either_alphabet_with_or_without_padding() {
+        // 0xfb 0xff encodes to "+/8=" (standard) or "-_8=" (URL-safe)
+        let bytes = vec![0xfb, 0xff];
+        assert_eq!(decode_gmail_b64("-_8=").unwrap(), bytes);
+        assert_eq!(decode_gmail_b64("-_8").unwrap(), bytes);
+        assert_eq!(decode_gmail_b64("+/8=").unwrap(), bytes);
+        assert_eq!(decode_gmail_b64("aGVs\r\nbG8=").unwrap(), b"hello");
+        assert!(decode_gmail_b64("not base64!!").is_err());
+        assert!(decode_gmail_b64("abcde").is_err());
+    }
+
+    #[test]
+    fn wraps_standard_base64() {
+        assert_eq!(encode_std_b64_wrapped(b"hello", 76), "aGVsbG8=\r\n");
+        assert_eq!(encode_std_b64_wrapped(b"hello", 4), "aGVs\r\nbG8=\r\n");
+        assert_eq!(encode_std_b64_wrapped(&[0xfb, 0xff], 0), "+/8=");
+    }
+}
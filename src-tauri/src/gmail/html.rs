@@ -0,0 +1,248 @@
+// HTML email body transforms
+//
+// Newsletters and signatures often hardcode a white background and black
+// text, which reads as a glaring white box in dark mode. The dark-mode
+// transform neutralizes those colors so the body inherits the theme, and
+// leaves emails that were designed dark alone.
+
+use serde::Serialize;
+
+/// A message's HTML body, raw and (when requested) adapted for dark mode,
+/// so the UI can switch back to the original
+#[derive(Debug, Clone, Serialize)]
+pub struct MessageHtml {
+    pub raw: String,
+    pub dark: Option<String>,
+}
+
+impl MessageHtml {
+    pub fn new(raw: String, dark_mode: bool) -> Self {
+        let dark = dark_mode.then(|| dark_mode_html(&raw));
+        Self { raw, dark }
+    }
+}
+
+/// Backgrounds at least this light are treated as hardcoded white
+const LIGHT_BACKGROUND: f64 = 0.9;
+/// Gray text at most this light is treated as hardcoded black
+const DARK_TEXT: f64 = 0.2;
+/// An email with any background this dark was designed for dark mode
+const DARK_BACKGROUND: f64 = 0.3;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ColorProperty {
+    /// CSS `background-color` or a single-color `background`
+    Background,
+    /// CSS `color`
+    Text,
+    /// The legacy `bgcolor` attribute
+    BgColorAttr,
+}
+
+/// A color declaration found in the HTML; `start..end` spans its value
+struct ColorDeclaration {
+    property: ColorProperty,
+    start: usize,
+    end: usize,
+    color: Option<(u8, u8, u8)>,
+}
+
+/// Neutralize hardcoded light backgrounds and black text so the body inherits
+/// the theme colors. Only plain color values are touched, so background images
+/// and layout survive. Emails that already use dark backgrounds are returned
+/// unchanged.
+pub fn dark_mode_html(html: &str) -> String {
+    let declarations = find_color_declarations(html);
+
+    let designed_dark = declarations.iter().any(|d| {
+        d.property != ColorProperty::Text && d.color.is_some_and(|c| relative_luminance(c) < DARK_BACKGROUND)
+    });
+    if designed_dark {
+        return html.to_string();
+    }
+
+    let mut out = String::with_capacity(html.len() + 80);
+    let mut copied = 0;
+    for declaration in &declarations {
+        let Some(color) = declaration.color else {
+            continue;
+        };
+        let luminance = relative_luminance(color);
+        let replacement = match declaration.property {
+            ColorProperty::Background if luminance >= LIGHT_BACKGROUND => "transparent",
+            ColorProperty::BgColorAttr if luminance >= LIGHT_BACKGROUND => "",
+            // Colored text (links, brand colors) stays readable on dark themes
+            ColorProperty::Text if luminance <= DARK_TEXT && is_gray(color) => "inherit",
+            _ => continue,
+        };
+        out.push_str(&html[copied..declaration.start]);
+        out.push_str(replacement);
+        copied = declaration.end;
+    }
+    out.push_str(&html[copied..]);
+
+    format!(
+        "<div data-posta-dark=\"true\" style=\"background-color: transparent; color: inherit;\">{}</div>",
+        out
+    )
+}
+
+fn find_color_declarations(html: &str) -> Vec<ColorDeclaration> {
+    // ASCII lowercasing keeps byte offsets aligned with `html`
+    let lower = html.to_ascii_lowercase();
+    let bytes = lower.as_bytes();
+    let mut declarations = Vec::new();
+
+    let mut i = 0;
+    while i < bytes.len() {
+        let boundary = i == 0 || !(bytes[i - 1].is_ascii_alphanumeric() || bytes[i - 1] == b'-');
+        let matched = if !boundary {
+            None
+        } else if lower[i..].starts_with("background-color") {
+            Some((ColorProperty::Background, "background-color".len(), b':'))
+        } else if lower[i..].starts_with("background") {
+            Some((ColorProperty::Background, "background".len(), b':'))
+        } else if lower[i..].starts_with("bgcolor") {
+            Some((ColorProperty::BgColorAttr, "bgcolor".len(), b'='))
+        } else if lower[i..].starts_with("color") {
+            Some((ColorProperty::Text, "color".len(), b':'))
+        } else {
+            None
+        };
+
+        let Some((property, name_len, separator)) = matched else {
+            i += 1;
+            continue;
+        };
+
+        let mut j = i + name_len;
+        while j < bytes.len() && bytes[j].is_ascii_whitespace() {
+            j += 1;
+        }
+        if bytes.get(j) != Some(&separator) {
+            i += name_len;
+            continue;
+        }
+        j += 1;
+        while j < bytes.len() && bytes[j].is_ascii_whitespace() {
+            j += 1;
+        }
+
+        let (start, end) = if property == ColorProperty::BgColorAttr {
+            match bytes.get(j) {
+                Some(&quote @ (b'"' | b'\'')) => {
+                    let start = j + 1;
+                    let len = bytes[start..].iter().position(|&b| b == quote).unwrap_or(bytes.len() - start);
+                    (start, start + len)
+                }
+                _ => {
+                    let len = bytes[j..]
+                        .iter()
+                        .position(|&b| b.is_ascii_whitespace() || b == b'>')
+                        .unwrap_or(bytes.len() - j);
+                    (j, j + len)
+                }
+            }
+        } else {
+            let len = bytes[j..]
+                .iter()
+                .position(|&b| matches!(b, b';' | b'"' | b'\'' | b'}' | b'!' | b'<'))
+                .unwrap_or(bytes.len() - j);
+            (j, j + html[j..j + len].trim_end().len())
+        };
+
+        declarations.push(ColorDeclaration {
+            property,
+            start,
+            end,
+            color: parse_color(&lower[start..end]),
+        });
+        i = end.max(i + 1);
+    }
+
+    declarations
+}
+
+/// Parse a single CSS color value. Values with more than a color (such as
+/// `background: url(...) #fff`) return None and are left untouched.
+fn parse_color(value: &str) -> Option<(u8, u8, u8)> {
+    let value = value.trim();
+    match value {
+        "white" => return Some((255, 255, 255)),
+        "black" => return Some((0, 0, 0)),
+        _ => {}
+    }
+
+    if let Some(hex) = value.strip_prefix('#') {
+        let channel = |s: &str| u8::from_str_radix(s, 16).ok();
+        return match hex.len() {
+            3 => {
+                let expand = |i: usize| channel(&hex[i..i + 1].repeat(2));
+                Some((expand(0)?, expand(1)?, expand(2)?))
+            }
+            6 => Some((channel(&hex[0..2])?, channel(&hex[2..4])?, channel(&hex[4..6])?)),
+            _ => None,
+        };
+    }
+
+    let args = value
+        .strip_prefix("rgba(")
+        .or_else(|| value.strip_prefix("rgb("))?
+        .strip_suffix(')')?;
+    let mut channels = args.split(',').map(|c| c.trim().parse::<u8>().ok());
+    Some((channels.next()??, channels.next()??, channels.next()??))
+}
+
+/// Whether a color is (close to) a shade of gray
+fn is_gray((r, g, b): (u8, u8, u8)) -> bool {
+    r.max(g).max(b) - r.min(g).min(b) <= 32
+}
+
+/// WCAG relative luminance, 0.0 (black) to 1.0 (white)
+fn relative_luminance((r, g, b): (u8, u8, u8)) -> f64 {
+    let linear = |c: u8| {
+        let c = c as f64 / 255.0;
+        if c <= 0.03928 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    0.2126 * linear(r) + 0.7152 * linear(g) + 0.0722 * linear(b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn white_background_email_is_neutralized() {
+        let html = r##"<table bgcolor="#FFFFFF" style="background-color: #fff; color:#000000"><tr><td style="background:white">Hi <img src="cid:logo" style="width:40px"></td></tr></table>"##;
+        let dark = dark_mode_html(html);
+
+        assert!(dark.starts_with("<div data-posta-dark"));
+        assert!(dark.contains(r#"bgcolor="""#));
+        assert!(dark.contains("background-color: transparent; color:inherit"));
+        assert!(dark.contains("background:transparent"));
+        // Images and layout are untouched
+        assert!(dark.contains(r#"<img src="cid:logo" style="width:40px">"#));
+
+        let message = MessageHtml::new(html.to_string(), true);
+        assert_eq!(message.raw, html);
+        assert_eq!(message.dark.as_deref(), Some(dark.as_str()));
+    }
+
+    #[test]
+    fn dark_email_is_left_alone() {
+        let html = r#"<body style="background-color:#111111;color:#eeeeee"><p style="color:#000">Night mode</p></body>"#;
+        assert_eq!(dark_mode_html(html), html);
+    }
+
+    #[test]
+    fn background_images_and_brand_colors_survive() {
+        let html = r#"<div style="background: url(hero.png) #fff; color: #0066cc">Sale</div>"#;
+        let dark = dark_mode_html(html);
+        assert!(dark.contains("background: url(hero.png) #fff"));
+        assert!(dark.contains("color: #0066cc"));
+    }
+}
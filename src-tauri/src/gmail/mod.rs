@@ -1,7 +1,13 @@
 // Gmail REST API client
 
-use crate::models::{Attachment, CalendarEvent, DateBucket, SendAttachment, Thread, ThreadGroup};
+pub mod base64util;
+pub mod html;
+
+use base64util::{decode_gmail_b64, encode_std_b64_wrapped};
+use crate::diagnostics::timed_send;
+use crate::models::{Attachment, CalendarEvent, DateBucket, Message, SendAttachment, Thread, ThreadGroup};
 use chrono::{DateTime, Datelike, Duration, Local, TimeZone, Utc};
+use rand::Rng;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
@@ -10,6 +16,7 @@ const BATCH_API_ENDPOINT: &str = "https://www.googleapis.com/batch/gmail/v1";
 const PAGE_SIZE: usize = 20;
 const MAX_BATCH_SIZE: usize = 50; // Gmail allows up to 100, but 50 is safer
 const MAX_INLINE_IMAGE_SIZE: i32 = 100_000; // 100KB max for inline images
+const MAX_FORWARD_ATTACHMENT_BYTES: i64 = 18_000_000; // base64 growth must stay under Gmail's 25MB send limit
 
 #[derive(Debug, Serialize)]
 pub struct SearchResult {
@@ -21,6 +28,52 @@ pub struct SearchResult {
 pub struct GmailClient {
     client: reqwest::Client,
     access_token: String,
+    /// Inline image downloads allowed per `batch_get_thread_details` call
+    inline_fetch_budget: usize,
+}
+
+/// Default cap on inline image downloads for one card load
+pub const DEFAULT_INLINE_FETCH_BUDGET: usize = 20;
+
+/// Most inline images fetched for a single thread
+const MAX_INLINE_IMAGES_PER_THREAD: usize = 3;
+
+/// Inline image downloads left for a batch of threads. Once spent, further
+/// images keep `inline_data: None` and are loaded lazily when opened.
+pub struct InlineFetchBudget {
+    remaining: std::sync::atomic::AtomicUsize,
+    used: std::sync::atomic::AtomicUsize,
+}
+
+impl InlineFetchBudget {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            remaining: std::sync::atomic::AtomicUsize::new(limit),
+            used: std::sync::atomic::AtomicUsize::new(0),
+        }
+    }
+
+    /// For single-thread fetches the user asked for explicitly
+    pub fn unlimited() -> Self {
+        Self::new(usize::MAX)
+    }
+
+    /// Claim up to `wanted` downloads; returns how many were granted
+    pub fn take(&self, wanted: usize) -> usize {
+        use std::sync::atomic::Ordering;
+        let previous = self
+            .remaining
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |left| Some(left - left.min(wanted)))
+            .unwrap_or(0);
+        let granted = previous.min(wanted);
+        self.used.fetch_add(granted, Ordering::SeqCst);
+        granted
+    }
+
+    /// Downloads granted so far
+    pub fn used(&self) -> usize {
+        self.used.load(std::sync::atomic::Ordering::SeqCst)
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -28,6 +81,8 @@ struct ThreadListResponse {
     threads: Option<Vec<ThreadRef>>,
     #[serde(rename = "nextPageToken")]
     next_page_token: Option<String>,
+    #[serde(rename = "resultSizeEstimate")]
+    result_size_estimate: Option<u32>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -52,6 +107,34 @@ struct MessageDetail {
     payload: Option<MessagePayload>,
 }
 
+/// Most ids `users.messages.batchModify` accepts per call
+const MAX_BATCH_MODIFY_IDS: usize = 1000;
+
+#[derive(Serialize)]
+struct BatchModifyRequest<'a> {
+    ids: &'a [String],
+    #[serde(rename = "addLabelIds")]
+    add_label_ids: &'a [String],
+    #[serde(rename = "removeLabelIds")]
+    remove_label_ids: &'a [String],
+}
+
+/// One `batchModify` body per chunk of at most `MAX_BATCH_MODIFY_IDS` ids
+fn batch_modify_requests<'a>(
+    message_ids: &'a [String],
+    add_label_ids: &'a [String],
+    remove_label_ids: &'a [String],
+) -> Vec<BatchModifyRequest<'a>> {
+    message_ids
+        .chunks(MAX_BATCH_MODIFY_IDS)
+        .map(|ids| BatchModifyRequest {
+            ids,
+            add_label_ids,
+            remove_label_ids,
+        })
+        .collect()
+}
+
 #[derive(Serialize)]
 struct ModifyThreadRequest {
     #[serde(rename = "addLabelIds")]
@@ -60,6 +143,177 @@ struct ModifyThreadRequest {
     remove_label_ids: Vec<String>,
 }
 
+/// Attempts per request, including the first
+const MAX_SEND_ATTEMPTS: u32 = 3;
+/// Backoff before the first retry; doubles on each later one
+const RETRY_BASE_DELAY: std::time::Duration = std::time::Duration::from_millis(500);
+/// Longest wait honored from a Retry-After header
+const MAX_RETRY_DELAY: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// Rate limiting is always retried: the request was rejected before it ran.
+/// Transient server errors are retried only for idempotent methods, since a
+/// 5xx on a POST (e.g. messages.send) may come after the server acted on it.
+/// Every other status fails fast.
+fn is_retryable_status(status: reqwest::StatusCode, idempotent: bool) -> bool {
+    match status.as_u16() {
+        429 => true,
+        500 | 503 => idempotent,
+        _ => false,
+    }
+}
+
+/// Wait before retry number `attempt` (1-based): the server's Retry-After
+/// when given, otherwise exponential backoff plus jitter
+fn retry_delay(attempt: u32, retry_after: Option<std::time::Duration>, jitter: std::time::Duration) -> std::time::Duration {
+    match retry_after {
+        Some(delay) => delay.min(MAX_RETRY_DELAY),
+        None => RETRY_BASE_DELAY * 2u32.pow(attempt.saturating_sub(1)) + jitter,
+    }
+}
+
+/// Send a request (recording its latency like `timed_send`), retrying 429
+/// responses, and 500/503 for idempotent methods, with backoff. Every attempt
+/// waits on the shared rate limiter and reports its quota signals back to it.
+/// Requests whose body cannot be cloned are sent once.
+async fn send_with_retry(
+    endpoint: &str,
+    request: reqwest::RequestBuilder,
+) -> Result<reqwest::Response, reqwest::Error> {
+    let idempotent = request
+        .try_clone()
+        .and_then(|r| r.build().ok())
+        .is_some_and(|r| r.method().is_idempotent());
+    let mut attempt = 1;
+    loop {
+        crate::rate_limit::throttle().await;
+        let Some(this_try) = request.try_clone() else {
+            let resp = timed_send(endpoint, request).await?;
+            crate::rate_limit::observe_response(&resp);
+            return Ok(resp);
+        };
+
+        let resp = timed_send(endpoint, this_try).await?;
+        crate::rate_limit::observe_response(&resp);
+        if attempt >= MAX_SEND_ATTEMPTS || !is_retryable_status(resp.status(), idempotent) {
+            return Ok(resp);
+        }
+
+        let jitter = std::time::Duration::from_millis(rand::thread_rng().gen_range(0..250));
+        let delay = retry_delay(attempt, crate::rate_limit::parse_retry_after(&resp), jitter);
+        tracing::debug!("{} returned {}, retrying in {:?}", endpoint, resp.status(), delay);
+        tokio::time::sleep(delay).await;
+        attempt += 1;
+    }
+}
+
+/// Result of modifying one thread in a batch
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ModifyOutcome {
+    pub ok: bool,
+    pub error: Option<String>,
+}
+
+impl ModifyOutcome {
+    pub fn ok() -> Self {
+        Self { ok: true, error: None }
+    }
+
+    pub fn failed(error: String) -> Self {
+        Self {
+            ok: false,
+            error: Some(error),
+        }
+    }
+}
+
+/// Bulk actions that need confirmation once they reach `BULK_CONFIRM_THRESHOLD` threads
+const BULK_ACTIONS: &[&str] = &["trash", "delete", "archive"];
+
+/// Thread count at which a bulk destructive action must be confirmed
+pub const BULK_CONFIRM_THRESHOLD: usize = 100;
+
+/// Subjects shown when previewing a bulk action
+pub const BULK_PREVIEW_SAMPLE: usize = 5;
+
+/// What a bulk action over a search would touch, computed without touching it
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct BulkActionPreview {
+    pub action: String,
+    /// Gmail's `resultSizeEstimate` for the query
+    pub estimated_count: u32,
+    pub sample_subjects: Vec<String>,
+    pub requires_confirmation: bool,
+}
+
+/// Reject anything but a known bulk action
+pub fn check_bulk_action(action: &str) -> Result<(), String> {
+    if !BULK_ACTIONS.contains(&action) {
+        return Err(format!("Unknown bulk action '{}'", action));
+    }
+    Ok(())
+}
+
+/// Build the preview for `action` from a query's size estimate and a sample of its threads
+pub fn bulk_action_preview(action: &str, estimated_count: u32, sample: &[Thread]) -> Result<BulkActionPreview, String> {
+    check_bulk_action(action)?;
+    Ok(BulkActionPreview {
+        action: action.to_string(),
+        estimated_count,
+        sample_subjects: sample
+            .iter()
+            .take(BULK_PREVIEW_SAMPLE)
+            .map(|t| t.subject.clone())
+            .collect(),
+        requires_confirmation: estimated_count as usize >= BULK_CONFIRM_THRESHOLD,
+    })
+}
+
+/// Refuse a large destructive action unless the user confirmed its preview
+pub fn check_bulk_confirmation(count: usize, confirmed: bool) -> Result<(), String> {
+    if count >= BULK_CONFIRM_THRESHOLD && !confirmed {
+        return Err(format!(
+            "This would affect {} threads; confirm the action to continue",
+            count
+        ));
+    }
+    Ok(())
+}
+
+/// Map each part of a batch response to its request index, HTTP status and body.
+/// Parts are matched by their `Content-ID: <response-itemN>` header.
+fn parse_batch_statuses(body: &str, boundary: &str) -> HashMap<usize, (u16, String)> {
+    let delimiter = format!("--{}", boundary);
+    let mut statuses = HashMap::new();
+
+    for part in body.split(delimiter.as_str()).skip(1) {
+        let Some(index) = part
+            .split("response-item")
+            .nth(1)
+            .and_then(|rest| rest.split('>').next())
+            .and_then(|n| n.trim().parse::<usize>().ok())
+        else {
+            continue;
+        };
+        let Some(status_line) = part.lines().find(|line| line.starts_with("HTTP/")) else {
+            continue;
+        };
+        let Some(status) = status_line.split_whitespace().nth(1).and_then(|c| c.parse::<u16>().ok()) else {
+            continue;
+        };
+        // The inner response body follows the inner headers' blank line
+        let inner = &part[part.find(status_line).unwrap_or(0)..];
+        let response_body = inner
+            .split_once("\r\n\r\n")
+            .or_else(|| inner.split_once("\n\n"))
+            .map(|(_, b)| b.trim().to_string())
+            .unwrap_or_default();
+
+        statuses.insert(index, (status, response_body));
+    }
+
+    statuses
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct MessageBody {
     pub size: Option<i32>,
@@ -108,6 +362,19 @@ pub struct FullMessage {
     pub payload: Option<MessagePayload>,
 }
 
+impl FullMessage {
+    /// Value of the first top-level header with the given name (case-insensitive)
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.payload
+            .as_ref()?
+            .headers
+            .as_ref()?
+            .iter()
+            .find(|h| h.name.eq_ignore_ascii_case(name))
+            .map(|h| h.value.as_str())
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct FullThread {
     pub id: String,
@@ -128,6 +395,18 @@ pub struct GmailLabel {
     pub label_type: Option<String>,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct GmailProfile {
+    #[serde(rename = "emailAddress")]
+    pub email_address: String,
+    #[serde(rename = "messagesTotal", default)]
+    pub messages_total: u64,
+    #[serde(rename = "threadsTotal", default)]
+    pub threads_total: u64,
+    #[serde(rename = "historyId")]
+    pub history_id: String,
+}
+
 #[derive(Debug, Deserialize)]
 struct ListLabelsResponse {
     labels: Option<Vec<GmailLabel>>,
@@ -151,9 +430,16 @@ impl GmailClient {
         Self {
             client: reqwest::Client::new(),
             access_token,
+            inline_fetch_budget: DEFAULT_INLINE_FETCH_BUDGET,
         }
     }
 
+    /// Cap inline image downloads per batch of threads (e.g. one card load)
+    pub fn with_inline_fetch_budget(mut self, budget: usize) -> Self {
+        self.inline_fetch_budget = budget;
+        self
+    }
+
     pub async fn search_threads(&self, query: &str) -> Result<Vec<ThreadGroup>, String> {
         let result = self.search_threads_paginated(query, None).await?;
         Ok(result.groups)
@@ -168,11 +454,7 @@ impl GmailClient {
             max_results
         );
 
-        let resp = self
-            .client
-            .get(&url)
-            .bearer_auth(&self.access_token)
-            .send()
+        let resp = send_with_retry("gmail.threads.list", self.client.get(&url).bearer_auth(&self.access_token))
             .await
             .map_err(|e| format!("Request failed: {}", e))?;
 
@@ -200,6 +482,41 @@ impl GmailClient {
         Ok(group_threads_by_date(threads))
     }
 
+    /// Estimated number of threads matching `query`, with details for the
+    /// first `sample_size` of them. Read-only, for previewing bulk actions.
+    pub async fn estimate_query(&self, query: &str, sample_size: usize) -> Result<(u32, Vec<Thread>), String> {
+        let url = format!(
+            "{}/users/me/threads?q={}&maxResults={}",
+            GMAIL_API_BASE,
+            urlencoding::encode(query),
+            sample_size
+        );
+
+        let resp = send_with_retry("gmail.threads.list", self.client.get(&url).bearer_auth(&self.access_token))
+            .await
+            .map_err(|e| format!("Request failed: {}", e))?;
+
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let body = resp.text().await.unwrap_or_default();
+            return Err(format!("API error {}: {}", status, body));
+        }
+
+        let list: ThreadListResponse = resp
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse response: {}", e))?;
+
+        let thread_ids: Vec<String> = list.threads.unwrap_or_default().into_iter().map(|t| t.id).collect();
+        let estimate = list.result_size_estimate.unwrap_or(thread_ids.len() as u32);
+        if thread_ids.is_empty() {
+            return Ok((estimate, Vec::new()));
+        }
+
+        let threads = self.batch_get_thread_details(&thread_ids).await?;
+        Ok((estimate, threads))
+    }
+
     pub async fn search_threads_paginated(&self, query: &str, page_token: Option<&str>) -> Result<SearchResult, String> {
         // Search for threads
         let mut url = format!(
@@ -213,11 +530,7 @@ impl GmailClient {
             url.push_str(&format!("&pageToken={}", urlencoding::encode(token)));
         }
 
-        let resp = self
-            .client
-            .get(&url)
-            .bearer_auth(&self.access_token)
-            .send()
+        let resp = send_with_retry("gmail.threads.list", self.client.get(&url).bearer_auth(&self.access_token))
             .await
             .map_err(|e| format!("Request failed: {}", e))?;
 
@@ -259,11 +572,7 @@ impl GmailClient {
     pub async fn get_thread(&self, thread_id: &str) -> Result<FullThread, String> {
         let url = format!("{}/users/me/threads/{}?format=full", GMAIL_API_BASE, thread_id);
 
-        let resp = self
-            .client
-            .get(&url)
-            .bearer_auth(&self.access_token)
-            .send()
+        let resp = send_with_retry("gmail.threads.get", self.client.get(&url).bearer_auth(&self.access_token))
             .await
             .map_err(|e| format!("Request failed: {}", e))?;
 
@@ -281,6 +590,108 @@ impl GmailClient {
         Ok(thread)
     }
 
+    /// Fetch a single message with its full payload
+    pub async fn get_message(&self, message_id: &str) -> Result<FullMessage, String> {
+        let url = format!("{}/users/me/messages/{}?format=full", GMAIL_API_BASE, message_id);
+
+        let resp = send_with_retry("gmail.messages.get", self.client.get(&url).bearer_auth(&self.access_token))
+            .await
+            .map_err(|e| format!("Request failed: {}", e))?;
+
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let body = resp.text().await.unwrap_or_default();
+            return Err(format!("API error {}: {}", status, body));
+        }
+
+        resp.json()
+            .await
+            .map_err(|e| format!("Failed to parse message: {}", e))
+    }
+
+    /// IDs of threads matching a search query, without fetching their details
+    pub async fn list_thread_ids(&self, query: &str, max_results: usize) -> Result<Vec<String>, String> {
+        let url = format!(
+            "{}/users/me/threads?q={}&maxResults={}",
+            GMAIL_API_BASE,
+            urlencoding::encode(query),
+            max_results
+        );
+
+        let resp = send_with_retry("gmail.threads.list", self.client.get(&url).bearer_auth(&self.access_token))
+            .await
+            .map_err(|e| format!("Request failed: {}", e))?;
+
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let body = resp.text().await.unwrap_or_default();
+            return Err(format!("API error {}: {}", status, body));
+        }
+
+        let list: ThreadListResponse = resp
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse response: {}", e))?;
+
+        Ok(list.threads.unwrap_or_default().into_iter().map(|t| t.id).collect())
+    }
+
+    /// Fetch a thread and, when Gmail appears to have split it, the sibling
+    /// threads that share its References chain, stitched into one conversation
+    pub async fn get_full_conversation(&self, thread_id: &str) -> Result<StitchedConversation, String> {
+        let primary = self.get_thread(thread_id).await?;
+        if !thread_looks_truncated(&primary) {
+            return Ok(stitch_threads(primary, Vec::new()));
+        }
+
+        // Referenced messages missing from this split live in an older sibling;
+        // newer siblings are found by subject and filtered by chain below
+        let own_ids: std::collections::HashSet<String> = primary
+            .messages
+            .iter()
+            .filter_map(|m| m.header("Message-ID"))
+            .flat_map(extract_message_ids)
+            .collect();
+        let missing: Vec<String> = conversation_ids(&primary)
+            .into_iter()
+            .filter(|id| !own_ids.contains(id))
+            .take(MAX_STITCH_SIBLINGS)
+            .collect();
+
+        let mut queries = Vec::new();
+        if !missing.is_empty() {
+            let terms: Vec<String> = missing
+                .iter()
+                .map(|id| format!("rfc822msgid:{}", id.trim_matches(|c| c == '<' || c == '>')))
+                .collect();
+            queries.push(terms.join(" OR "));
+        }
+        if let Some(subject) = primary.messages.first().and_then(|m| m.header("Subject")) {
+            let base = strip_reply_prefixes(subject);
+            if !base.is_empty() {
+                queries.push(format!("subject:\"{}\"", base.replace('"', "")));
+            }
+        }
+
+        let mut sibling_ids: Vec<String> = Vec::new();
+        for query in &queries {
+            for id in self.list_thread_ids(query, MAX_STITCH_SIBLINGS).await.unwrap_or_default() {
+                if id != primary.id && !sibling_ids.contains(&id) {
+                    sibling_ids.push(id);
+                }
+            }
+        }
+        sibling_ids.truncate(MAX_STITCH_SIBLINGS);
+
+        let siblings = futures::future::join_all(sibling_ids.iter().map(|id| self.get_thread(id)))
+            .await
+            .into_iter()
+            .filter_map(Result::ok)
+            .collect();
+
+        Ok(stitch_threads(primary, siblings))
+    }
+
     pub async fn modify_thread(
         &self,
         thread_id: &str,
@@ -294,14 +705,15 @@ impl GmailClient {
             remove_label_ids,
         };
 
-        let resp = self
-            .client
-            .post(&url)
-            .bearer_auth(&self.access_token)
-            .json(&body)
-            .send()
-            .await
-            .map_err(|e| format!("Request failed: {}", e))?;
+        let resp = send_with_retry(
+            "gmail.threads.modify",
+            self.client
+                .post(&url)
+                .bearer_auth(&self.access_token)
+                .json(&body),
+        )
+        .await
+        .map_err(|e| format!("Request failed: {}", e))?;
 
         if !resp.status().is_success() {
             let status = resp.status();
@@ -312,23 +724,29 @@ impl GmailClient {
         Ok(())
     }
 
-    pub async fn get_attachment(
+    /// Change the labels of a single message (e.g. mark just one reply read)
+    pub async fn modify_message(
         &self,
         message_id: &str,
-        attachment_id: &str,
-    ) -> Result<String, String> {
-        let url = format!(
-            "{}/users/me/messages/{}/attachments/{}",
-            GMAIL_API_BASE, message_id, attachment_id
-        );
+        add_label_ids: Vec<String>,
+        remove_label_ids: Vec<String>,
+    ) -> Result<(), String> {
+        let url = format!("{}/users/me/messages/{}/modify", GMAIL_API_BASE, message_id);
 
-        let resp = self
-            .client
-            .get(&url)
-            .bearer_auth(&self.access_token)
-            .send()
-            .await
-            .map_err(|e| format!("Request failed: {}", e))?;
+        let body = ModifyThreadRequest {
+            add_label_ids,
+            remove_label_ids,
+        };
+
+        let resp = send_with_retry(
+            "gmail.messages.modify",
+            self.client
+                .post(&url)
+                .bearer_auth(&self.access_token)
+                .json(&body),
+        )
+        .await
+        .map_err(|e| format!("Request failed: {}", e))?;
 
         if !resp.status().is_success() {
             let status = resp.status();
@@ -336,31 +754,41 @@ impl GmailClient {
             return Err(format!("API error {}: {}", status, body));
         }
 
-        #[derive(Deserialize)]
-        struct AttachmentResponse {
-            data: String,
-        }
+        Ok(())
+    }
 
-        let attachment: AttachmentResponse = resp
-            .json()
+    /// Change the labels of specific messages in as few requests as possible
+    pub async fn batch_modify_messages(
+        &self,
+        message_ids: &[String],
+        add_label_ids: &[String],
+        remove_label_ids: &[String],
+    ) -> Result<(), String> {
+        let url = format!("{}/users/me/messages/batchModify", GMAIL_API_BASE);
+
+        for body in batch_modify_requests(message_ids, add_label_ids, remove_label_ids) {
+            let resp = send_with_retry(
+                "gmail.messages.batchModify",
+                self.client.post(&url).bearer_auth(&self.access_token).json(&body),
+            )
             .await
-            .map_err(|e| format!("Failed to parse attachment: {}", e))?;
+            .map_err(|e| format!("Request failed: {}", e))?;
 
-        Ok(attachment.data)
+            if !resp.status().is_success() {
+                let status = resp.status();
+                let body = resp.text().await.unwrap_or_default();
+                return Err(format!("API error {}: {}", status, body));
+            }
+        }
+
+        Ok(())
     }
 
-    async fn get_thread_detail(&self, thread_id: &str) -> Result<Thread, String> {
-        // Use format=full to get attachment info, but limit fields to avoid downloading bodies
-        let url = format!(
-            "{}/users/me/threads/{}?format=full&fields=id,messages(id,threadId,labelIds,snippet,internalDate,payload(headers,mimeType,parts(mimeType,filename,body(size,attachmentId),parts(mimeType,filename,body(size,attachmentId)))))",
-            GMAIL_API_BASE, thread_id
-        );
+    /// Move a thread to Trash (Gmail deletes it for good after 30 days)
+    pub async fn trash_thread(&self, thread_id: &str) -> Result<(), String> {
+        let url = format!("{}/users/me/threads/{}/trash", GMAIL_API_BASE, thread_id);
 
-        let resp = self
-            .client
-            .get(&url)
-            .bearer_auth(&self.access_token)
-            .send()
+        let resp = send_with_retry("gmail.threads.trash", self.client.post(&url).bearer_auth(&self.access_token))
             .await
             .map_err(|e| format!("Request failed: {}", e))?;
 
@@ -370,32 +798,217 @@ impl GmailClient {
             return Err(format!("API error {}: {}", status, body));
         }
 
-        let detail: ThreadDetail = resp
-            .json()
+        Ok(())
+    }
+
+    /// Restore a thread from Trash
+    pub async fn untrash_thread(&self, thread_id: &str) -> Result<(), String> {
+        let url = format!("{}/users/me/threads/{}/untrash", GMAIL_API_BASE, thread_id);
+
+        let resp = send_with_retry("gmail.threads.untrash", self.client.post(&url).bearer_auth(&self.access_token))
             .await
-            .map_err(|e| format!("Failed to parse thread: {}", e))?;
+            .map_err(|e| format!("Request failed: {}", e))?;
+
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let body = resp.text().await.unwrap_or_default();
+            return Err(format!("API error {}: {}", status, body));
+        }
 
-        self.thread_detail_to_thread(detail).await
+        Ok(())
     }
 
-    /// Batch fetch thread details for multiple thread IDs
-    /// This is much more efficient than fetching one at a time
-    pub async fn batch_get_thread_details(&self, thread_ids: &[String]) -> Result<Vec<Thread>, String> {
-        if thread_ids.is_empty() {
-            return Ok(Vec::new());
-        }
+    /// Permanently delete a thread, skipping Trash. Requires the full
+    /// https://mail.google.com/ scope; narrower grants get a 403.
+    pub async fn delete_thread(&self, thread_id: &str) -> Result<(), String> {
+        let url = format!("{}/users/me/threads/{}", GMAIL_API_BASE, thread_id);
 
-        let mut all_threads = Vec::new();
+        let resp = send_with_retry("gmail.threads.delete", self.client.delete(&url).bearer_auth(&self.access_token))
+            .await
+            .map_err(|e| format!("Request failed: {}", e))?;
 
-        // Process in chunks of MAX_BATCH_SIZE
-        for chunk in thread_ids.chunks(MAX_BATCH_SIZE) {
-            match self.execute_batch_thread_fetch(chunk).await {
+        if resp.status() == reqwest::StatusCode::FORBIDDEN {
+            return Err(
+                "Permanent delete needs full Gmail access (https://mail.google.com/). Please re-login to grant it."
+                    .to_string(),
+            );
+        }
+
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let body = resp.text().await.unwrap_or_default();
+            return Err(format!("API error {}: {}", status, body));
+        }
+
+        Ok(())
+    }
+
+    /// Apply the same label changes to many threads through the batch endpoint
+    /// (one HTTP request per 50 threads). Every thread id gets an outcome, so
+    /// partial failures can be reported.
+    pub async fn modify_threads_batch(
+        &self,
+        thread_ids: &[String],
+        add_label_ids: &[String],
+        remove_label_ids: &[String],
+    ) -> HashMap<String, ModifyOutcome> {
+        let payload = serde_json::to_string(&ModifyThreadRequest {
+            add_label_ids: add_label_ids.to_vec(),
+            remove_label_ids: remove_label_ids.to_vec(),
+        })
+        .unwrap_or_default();
+
+        let payload = payload.as_str();
+        let chunks = thread_ids.chunks(MAX_BATCH_SIZE).map(|chunk| async move {
+            let statuses = self.execute_batch_modify(chunk, payload).await;
+            chunk
+                .iter()
+                .enumerate()
+                .map(|(i, thread_id)| {
+                    let outcome = match &statuses {
+                        Err(e) => ModifyOutcome::failed(e.clone()),
+                        Ok(statuses) => match statuses.get(&i) {
+                            Some((status, _)) if (200..300).contains(status) => ModifyOutcome::ok(),
+                            Some((status, body)) => ModifyOutcome::failed(format!("API error {}: {}", status, body.trim())),
+                            None => ModifyOutcome::failed("No response in batch".to_string()),
+                        },
+                    };
+                    (thread_id.clone(), outcome)
+                })
+                .collect::<Vec<_>>()
+        });
+
+        futures::future::join_all(chunks).await.into_iter().flatten().collect()
+    }
+
+    async fn execute_batch_modify(
+        &self,
+        thread_ids: &[String],
+        payload: &str,
+    ) -> Result<HashMap<usize, (u16, String)>, String> {
+        let boundary = format!("batch_{}", uuid::Uuid::new_v4().to_string().replace('-', ""));
+
+        let mut body = String::new();
+        for (i, thread_id) in thread_ids.iter().enumerate() {
+            body.push_str(&format!("--{}\r\n", boundary));
+            body.push_str("Content-Type: application/http\r\n");
+            body.push_str(&format!("Content-ID: <item{}>\r\n\r\n", i));
+            body.push_str(&format!("POST /gmail/v1/users/me/threads/{}/modify HTTP/1.1\r\n", thread_id));
+            body.push_str("Content-Type: application/json\r\n\r\n");
+            body.push_str(payload);
+            body.push_str("\r\n");
+        }
+        body.push_str(&format!("--{}--\r\n", boundary));
+
+        let request = self
+            .client
+            .post(BATCH_API_ENDPOINT)
+            .bearer_auth(&self.access_token)
+            .header("Content-Type", format!("multipart/mixed; boundary={}", boundary))
+            .body(body);
+
+        let resp = send_with_retry("gmail.batch", request)
+            .await
+            .map_err(|e| format!("Batch request failed: {}", e))?;
+
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let body = resp.text().await.unwrap_or_default();
+            return Err(format!("Batch API error {}: {}", status, body));
+        }
+
+        let resp_boundary: String = resp
+            .headers()
+            .get("content-type")
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("")
+            .split("boundary=")
+            .nth(1)
+            .map(|b| b.trim_matches('"').to_string())
+            .ok_or("Missing boundary in response")?;
+
+        let resp_body = resp.text().await.map_err(|e| format!("Failed to read response: {}", e))?;
+
+        Ok(parse_batch_statuses(&resp_body, &resp_boundary))
+    }
+
+    pub async fn get_attachment(
+        &self,
+        message_id: &str,
+        attachment_id: &str,
+    ) -> Result<String, String> {
+        let url = format!(
+            "{}/users/me/messages/{}/attachments/{}",
+            GMAIL_API_BASE, message_id, attachment_id
+        );
+
+        let resp = send_with_retry("gmail.attachments.get", self.client.get(&url).bearer_auth(&self.access_token))
+            .await
+            .map_err(|e| format!("Request failed: {}", e))?;
+
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let body = resp.text().await.unwrap_or_default();
+            return Err(format!("API error {}: {}", status, body));
+        }
+
+        #[derive(Deserialize)]
+        struct AttachmentResponse {
+            data: String,
+        }
+
+        let attachment: AttachmentResponse = resp
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse attachment: {}", e))?;
+
+        Ok(attachment.data)
+    }
+
+    async fn get_thread_detail(&self, thread_id: &str, budget: &InlineFetchBudget) -> Result<Thread, String> {
+        // Use format=full to get attachment info, but limit fields to avoid downloading bodies
+        let url = format!(
+            "{}/users/me/threads/{}?format=full&fields=id,messages(id,threadId,labelIds,snippet,internalDate,payload(headers,mimeType,parts(mimeType,filename,body(size,attachmentId),parts(mimeType,filename,body(size,attachmentId)))))",
+            GMAIL_API_BASE, thread_id
+        );
+
+        let resp = send_with_retry("gmail.threads.get", self.client.get(&url).bearer_auth(&self.access_token))
+            .await
+            .map_err(|e| format!("Request failed: {}", e))?;
+
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let body = resp.text().await.unwrap_or_default();
+            return Err(format!("API error {}: {}", status, body));
+        }
+
+        let detail: ThreadDetail = resp
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse thread: {}", e))?;
+
+        self.thread_detail_to_thread(detail, budget).await
+    }
+
+    /// Batch fetch thread details for multiple thread IDs
+    /// This is much more efficient than fetching one at a time
+    pub async fn batch_get_thread_details(&self, thread_ids: &[String]) -> Result<Vec<Thread>, String> {
+        if thread_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut all_threads = Vec::new();
+        let budget = InlineFetchBudget::new(self.inline_fetch_budget);
+
+        // Process in chunks of MAX_BATCH_SIZE
+        for chunk in thread_ids.chunks(MAX_BATCH_SIZE) {
+            match self.execute_batch_thread_fetch(chunk, &budget).await {
                 Ok(threads) => all_threads.extend(threads),
                 Err(e) => {
                     tracing::warn!("Batch fetch failed, falling back to sequential: {}", e);
                     // Fallback to sequential fetch for this chunk
                     for thread_id in chunk {
-                        if let Ok(thread) = self.get_thread_detail(thread_id).await {
+                        if let Ok(thread) = self.get_thread_detail(thread_id, &budget).await {
                             all_threads.push(thread);
                         }
                     }
@@ -403,11 +1016,17 @@ impl GmailClient {
             }
         }
 
+        tracing::debug!(
+            "Fetched {} threads with {} inline images (budget {})",
+            all_threads.len(),
+            budget.used(),
+            self.inline_fetch_budget
+        );
         Ok(all_threads)
     }
 
     /// Execute a single batch request for thread details
-    async fn execute_batch_thread_fetch(&self, thread_ids: &[String]) -> Result<Vec<Thread>, String> {
+    async fn execute_batch_thread_fetch(&self, thread_ids: &[String], budget: &InlineFetchBudget) -> Result<Vec<Thread>, String> {
         let boundary = format!("batch_{}", uuid::Uuid::new_v4().to_string().replace('-', ""));
 
         // Build multipart request body
@@ -425,13 +1044,14 @@ impl GmailClient {
         }
         body.push_str(&format!("--{}--\r\n", boundary));
 
-        let resp = self
+        let request = self
             .client
             .post(BATCH_API_ENDPOINT)
             .bearer_auth(&self.access_token)
             .header("Content-Type", format!("multipart/mixed; boundary={}", boundary))
-            .body(body)
-            .send()
+            .body(body);
+
+        let resp = send_with_retry("gmail.batch", request)
             .await
             .map_err(|e| format!("Batch request failed: {}", e))?;
 
@@ -454,11 +1074,11 @@ impl GmailClient {
 
         let resp_body = resp.text().await.map_err(|e| format!("Failed to read response: {}", e))?;
 
-        self.parse_batch_response(&resp_body, &resp_boundary).await
+        self.parse_batch_response(&resp_body, &resp_boundary, budget).await
     }
 
     /// Parse a batch response and extract thread details
-    async fn parse_batch_response(&self, body: &str, boundary: &str) -> Result<Vec<Thread>, String> {
+    async fn parse_batch_response(&self, body: &str, boundary: &str, budget: &InlineFetchBudget) -> Result<Vec<Thread>, String> {
         let mut threads = Vec::new();
         let delimiter = format!("--{}", boundary);
 
@@ -480,7 +1100,7 @@ impl GmailClient {
 
                     match serde_json::from_str::<ThreadDetail>(json_str) {
                         Ok(detail) => {
-                            if let Ok(thread) = self.thread_detail_to_thread(detail).await {
+                            if let Ok(thread) = self.thread_detail_to_thread(detail, budget).await {
                                 threads.push(thread);
                             }
                         }
@@ -497,7 +1117,7 @@ impl GmailClient {
 
                     match serde_json::from_str::<ThreadDetail>(json_str) {
                         Ok(detail) => {
-                            if let Ok(thread) = self.thread_detail_to_thread(detail).await {
+                            if let Ok(thread) = self.thread_detail_to_thread(detail, budget).await {
                                 threads.push(thread);
                             }
                         }
@@ -513,7 +1133,7 @@ impl GmailClient {
     }
 
     /// Convert ThreadDetail to Thread (extracted from get_thread_detail for reuse)
-    async fn thread_detail_to_thread(&self, detail: ThreadDetail) -> Result<Thread, String> {
+    async fn thread_detail_to_thread(&self, detail: ThreadDetail, budget: &InlineFetchBudget) -> Result<Thread, String> {
         let messages = detail.messages.unwrap_or_default();
         let latest_msg = messages.last();
 
@@ -581,20 +1201,23 @@ impl GmailClient {
                         size: info.size,
                         inline_data: None,
                         content_id: info.content_id,
+                        is_inline: info.is_inline,
                     });
                 }
             }
         }
 
-        // Fetch small image attachments inline (limit to first 3 images, < 100KB each)
-        // Collect indices and metadata for parallel fetch
-        let image_indices: Vec<(usize, String, String)> = attachments
+        // Fetch small image attachments inline (limit to first 3 images, < 100KB each),
+        // as far as the batch's budget allows. Collect indices and metadata for parallel fetch
+        let mut image_indices: Vec<(usize, String, String)> = attachments
             .iter()
             .enumerate()
             .filter(|(_, a)| a.mime_type.starts_with("image/") && a.size < MAX_INLINE_IMAGE_SIZE)
-            .take(3)
+            .take(MAX_INLINE_IMAGES_PER_THREAD)
             .map(|(i, a)| (i, a.message_id.clone(), a.attachment_id.clone()))
             .collect();
+        let granted = budget.take(image_indices.len());
+        image_indices.truncate(granted);
 
         // Fetch all images in parallel
         let fetch_futures = image_indices.iter().map(|(_, msg_id, att_id)| {
@@ -614,19 +1237,17 @@ impl GmailClient {
             }
         }
 
-        // Parse calendar events from ICS attachments
-        let mut calendar_event: Option<CalendarEvent> = None;
+        // Parse calendar events from every ICS attachment so updated invites
+        // can be told apart from the originals
+        let mut invites: Vec<CalendarEvent> = Vec::new();
         for attachment in attachments.iter() {
             if attachment.is_calendar() {
                 match self.get_attachment(&attachment.message_id, &attachment.attachment_id).await {
                     Ok(data) => {
-                        use base64::Engine;
-                        let normalized = data.replace('-', "+").replace('_', "/");
-                        if let Ok(decoded_bytes) = base64::engine::general_purpose::STANDARD.decode(&normalized) {
+                        if let Ok(decoded_bytes) = decode_gmail_b64(&data) {
                             if let Ok(ics_content) = String::from_utf8(decoded_bytes) {
                                 if let Some(event) = parse_ics_content(&ics_content) {
-                                    calendar_event = Some(event);
-                                    break;
+                                    invites.push(event);
                                 }
                             }
                         }
@@ -637,8 +1258,18 @@ impl GmailClient {
                 }
             }
         }
+        // Some senders inline the invite as a text/calendar body part with no
+        // attachment id, so it never shows up among the attachments
+        for msg in &messages {
+            if let Some(payload) = &msg.payload {
+                invites.extend(inline_calendar_events(payload));
+            }
+        }
+        let calendar_event = pick_latest_invite(invites);
 
-        let has_attachment = !attachments.is_empty();
+        // Inline images stay in the list for cid: rendering but aren't files
+        let has_attachment = attachments.iter().any(|a| !a.is_inline);
+        let category = category_for_labels(&labels).to_string();
 
         Ok(Thread {
             gmail_thread_id: detail.id,
@@ -652,6 +1283,7 @@ impl GmailClient {
             has_attachment,
             attachments,
             calendar_event,
+            category,
         })
     }
 
@@ -678,14 +1310,15 @@ impl GmailClient {
             "raw": encoded
         });
 
-        let resp = self
-            .client
-            .post(&url)
-            .bearer_auth(&self.access_token)
-            .json(&request_body)
-            .send()
-            .await
-            .map_err(|e| format!("Request failed: {}", e))?;
+        let resp = send_with_retry(
+            "gmail.messages.send",
+            self.client
+                .post(&url)
+                .bearer_auth(&self.access_token)
+                .json(&request_body),
+        )
+        .await
+        .map_err(|e| format!("Request failed: {}", e))?;
 
         if !resp.status().is_success() {
             let status = resp.status();
@@ -730,8 +1363,7 @@ impl GmailClient {
 
         if !is_html && attachments.is_empty() {
             // Simple plain text message
-            message.push_str("Content-Type: text/plain; charset=utf-8\r\n\r\n");
-            message.push_str(body);
+            message.push_str(&text_part("text/plain", body));
         } else if is_html && attachments.is_empty() {
             // HTML message with plain text fallback (multipart/alternative)
             let alt_boundary = format!("----=_Alt_{}", uuid::Uuid::new_v4().to_string().replace("-", ""));
@@ -740,14 +1372,12 @@ impl GmailClient {
             // Plain text part (strip HTML for fallback)
             let plain_body = strip_html_tags(body);
             message.push_str(&format!("--{}\r\n", alt_boundary));
-            message.push_str("Content-Type: text/plain; charset=utf-8\r\n\r\n");
-            message.push_str(&plain_body);
+            message.push_str(&text_part("text/plain", &plain_body));
             message.push_str("\r\n");
 
             // HTML part
             message.push_str(&format!("--{}\r\n", alt_boundary));
-            message.push_str("Content-Type: text/html; charset=utf-8\r\n\r\n");
-            message.push_str(body);
+            message.push_str(&text_part("text/html", body));
             message.push_str("\r\n");
 
             message.push_str(&format!("--{}--\r\n", alt_boundary));
@@ -765,47 +1395,48 @@ impl GmailClient {
                 // Plain text part
                 let plain_body = strip_html_tags(body);
                 message.push_str(&format!("--{}\r\n", alt_boundary));
-                message.push_str("Content-Type: text/plain; charset=utf-8\r\n\r\n");
-                message.push_str(&plain_body);
+                message.push_str(&text_part("text/plain", &plain_body));
                 message.push_str("\r\n");
 
                 // HTML part
                 message.push_str(&format!("--{}\r\n", alt_boundary));
-                message.push_str("Content-Type: text/html; charset=utf-8\r\n\r\n");
-                message.push_str(body);
+                message.push_str(&text_part("text/html", body));
                 message.push_str("\r\n");
 
                 message.push_str(&format!("--{}--\r\n", alt_boundary));
             } else {
                 // Plain text part only
                 message.push_str(&format!("--{}\r\n", boundary));
-                message.push_str("Content-Type: text/plain; charset=utf-8\r\n\r\n");
-                message.push_str(body);
+                message.push_str(&text_part("text/plain", body));
                 message.push_str("\r\n");
             }
 
             // Attachment parts
             for attachment in attachments {
                 message.push_str(&format!("--{}\r\n", boundary));
+                let filename = encode_filename_param(&attachment.filename);
                 message.push_str(&format!(
                     "Content-Type: {}; name=\"{}\"\r\n",
-                    attachment.mime_type, attachment.filename
+                    attachment.mime_type, filename
                 ));
                 message.push_str("Content-Transfer-Encoding: base64\r\n");
+                let disposition = match &attachment.content_id {
+                    Some(content_id) => {
+                        message.push_str(&format!("Content-ID: <{}>\r\n", content_id));
+                        "inline"
+                    }
+                    None => "attachment",
+                };
                 message.push_str(&format!(
-                    "Content-Disposition: attachment; filename=\"{}\"\r\n\r\n",
-                    attachment.filename
+                    "Content-Disposition: {}; filename=\"{}\"\r\n\r\n",
+                    disposition, filename
                 ));
-                // The data is already base64-encoded from frontend, but Gmail expects standard base64
-                // We need to normalize it (remove URL-safe chars if any)
-                let normalized_data = attachment.data
-                    .replace('-', "+")
-                    .replace('_', "/");
-                // Add line breaks every 76 chars for RFC compliance
-                for chunk in normalized_data.as_bytes().chunks(76) {
-                    message.push_str(std::str::from_utf8(chunk).unwrap_or(""));
-                    message.push_str("\r\n");
-                }
+                // The data is already base64-encoded from frontend, but may be URL-safe,
+                // unpadded or wrapped; re-encode it as canonical standard base64
+                // with line breaks every 76 chars for RFC compliance
+                let bytes = decode_attachment_data(&attachment.data)
+                    .map_err(|e| format!("Attachment \"{}\": {}", attachment.filename, e))?;
+                message.push_str(&encode_std_b64_wrapped(&bytes, 76));
             }
 
             // Final boundary
@@ -815,6 +1446,52 @@ impl GmailClient {
         Ok(message)
     }
 
+    /// Forward a message with its attachments, quoting the original below
+    /// `note`. Attachments are downloaded only to re-encode them into the
+    /// outgoing message; nothing is kept for thread previews.
+    pub async fn forward_message(
+        &self,
+        message_id: &str,
+        to: &str,
+        cc: &str,
+        bcc: &str,
+        note: &str,
+    ) -> Result<(), String> {
+        let original = self.get_message(message_id).await?;
+
+        let infos = original
+            .payload
+            .as_ref()
+            .map(|p| extract_attachments_from_parts(&p.parts))
+            .unwrap_or_default();
+        let total_size: i64 = infos.iter().map(|a| a.size as i64).sum();
+        if total_size > MAX_FORWARD_ATTACHMENT_BYTES {
+            return Err(format!(
+                "Attachments are too large to forward ({} MB, limit {} MB)",
+                total_size / 1_000_000,
+                MAX_FORWARD_ATTACHMENT_BYTES / 1_000_000
+            ));
+        }
+
+        let downloads = infos.iter().map(|info| self.get_attachment(message_id, &info.attachment_id));
+        let mut attachments = Vec::with_capacity(infos.len());
+        for (info, data) in infos.iter().zip(futures::future::join_all(downloads).await) {
+            let data = data.map_err(|e| format!("Failed to download \"{}\": {}", info.filename, e))?;
+            attachments.push(SendAttachment {
+                filename: info.filename.clone(),
+                mime_type: info.mime_type.clone(),
+                data,
+                // Keep inline images addressable by the quoted body's cid: links
+                content_id: info.content_id.clone().filter(|_| info.is_inline),
+            });
+        }
+
+        let subject = forward_subject(original.header("Subject").unwrap_or(""));
+        let (body, is_html) = build_forward_body(&original, note);
+
+        self.send_email(to, cc, bcc, &subject, &body, &attachments, is_html).await
+    }
+
     /// Reply to a thread (with optional attachments)
     pub async fn reply_to_thread(
         &self,
@@ -825,12 +1502,13 @@ impl GmailClient {
         subject: &str,
         body: &str,
         message_id: Option<&str>,
+        references: Option<&str>,
         attachments: &[SendAttachment],
         is_html: bool,
     ) -> Result<(), String> {
         let url = format!("{}/users/me/messages/send", GMAIL_API_BASE);
 
-        let reply_headers = self.resolve_reply_headers(thread_id, message_id).await;
+        let reply_headers = self.resolve_reply_headers(thread_id, message_id, references).await;
         let reply_headers_ref = reply_headers
             .as_ref()
             .map(|(in_reply_to, references)| (in_reply_to.as_str(), references.as_str()));
@@ -846,14 +1524,15 @@ impl GmailClient {
             "threadId": thread_id
         });
 
-        let resp = self
-            .client
-            .post(&url)
-            .bearer_auth(&self.access_token)
-            .json(&request_body)
-            .send()
-            .await
-            .map_err(|e| format!("Request failed: {}", e))?;
+        let resp = send_with_retry(
+            "gmail.messages.send",
+            self.client
+                .post(&url)
+                .bearer_auth(&self.access_token)
+                .json(&request_body),
+        )
+        .await
+        .map_err(|e| format!("Request failed: {}", e))?;
 
         if !resp.status().is_success() {
             let status = resp.status();
@@ -866,45 +1545,47 @@ impl GmailClient {
 
     /// Resolve RFC 5322 threading headers (In-Reply-To, References) for a reply.
     ///
-    /// `message_id` may be a real Message-ID header value or a Gmail API hex id;
-    /// for the latter (or when absent) the parent's headers are fetched from the
-    /// thread. Returns None when no usable Message-ID can be found - Gmail-side
-    /// threading still works via the threadId field.
+    /// `message_id` may be a real Message-ID header value or a Gmail API hex id.
+    /// `references` is the parent's own References header, when the caller has
+    /// it. Otherwise the parent's headers are fetched from the thread. Returns
+    /// None when no usable Message-ID can be found - Gmail-side threading still
+    /// works via the threadId field.
     async fn resolve_reply_headers(
         &self,
         thread_id: &str,
         message_id: Option<&str>,
+        references: Option<&str>,
     ) -> Option<(String, String)> {
-        if let Some(id) = message_id {
-            if id.contains('@') {
-                let bracketed = ensure_angle_brackets(id);
-                return Some((bracketed.clone(), bracketed));
-            }
+        let header_id = message_id.filter(|id| id.contains('@'));
+        if let (Some(id), Some(references)) = (header_id, references) {
+            let parent_message_id = ensure_angle_brackets(id);
+            let references = build_references(Some(references), &parent_message_id);
+            return Some((parent_message_id, references));
         }
 
-        let thread = self.get_thread(thread_id).await.ok()?;
-        // Prefer the message matching the provided Gmail hex id; fall back to
-        // the last message in the thread
+        let Ok(thread) = self.get_thread(thread_id).await else {
+            // Without the thread, a header Message-ID alone still threads the reply
+            let parent_message_id = ensure_angle_brackets(header_id?);
+            return Some((parent_message_id.clone(), parent_message_id));
+        };
+
+        // Prefer the message matching the provided id (hex id or Message-ID
+        // header); fall back to the last message in the thread
         let parent = message_id
-            .and_then(|id| thread.messages.iter().find(|m| m.id == id))
+            .and_then(|id| {
+                thread.messages.iter().find(|m| {
+                    m.id == id
+                        || m.header("Message-ID")
+                            .is_some_and(|h| ensure_angle_brackets(h) == ensure_angle_brackets(id))
+                })
+            })
             .or_else(|| thread.messages.last())?;
-        let headers = parent.payload.as_ref()?.headers.as_ref()?;
-
-        let parent_message_id = headers
-            .iter()
-            .find(|h| h.name.eq_ignore_ascii_case("Message-ID"))
-            .map(|h| ensure_angle_brackets(&h.value))?;
-        let parent_references = headers
-            .iter()
-            .find(|h| h.name.eq_ignore_ascii_case("References"))
-            .map(|h| h.value.trim().to_string())
-            .unwrap_or_default();
 
-        let references = if parent_references.is_empty() {
-            parent_message_id.clone()
-        } else {
-            format!("{} {}", parent_references, parent_message_id)
-        };
+        let parent_message_id = parent
+            .header("Message-ID")
+            .map(ensure_angle_brackets)
+            .or_else(|| header_id.map(ensure_angle_brackets))?;
+        let references = build_references(references.or(parent.header("References")), &parent_message_id);
 
         Some((parent_message_id, references))
     }
@@ -913,11 +1594,7 @@ impl GmailClient {
     pub async fn list_labels(&self) -> Result<Vec<GmailLabel>, String> {
         let url = format!("{}/users/me/labels", GMAIL_API_BASE);
 
-        let resp = self
-            .client
-            .get(&url)
-            .bearer_auth(&self.access_token)
-            .send()
+        let resp = send_with_retry("gmail.labels.list", self.client.get(&url).bearer_auth(&self.access_token))
             .await
             .map_err(|e| format!("Request failed: {}", e))?;
 
@@ -963,14 +1640,15 @@ impl GmailClient {
             request_body["message"]["threadId"] = serde_json::json!(tid);
         }
 
-        let resp = self
-            .client
-            .post(&url)
-            .bearer_auth(&self.access_token)
-            .json(&request_body)
-            .send()
-            .await
-            .map_err(|e| format!("Request failed: {}", e))?;
+        let resp = send_with_retry(
+            "gmail.drafts.create",
+            self.client
+                .post(&url)
+                .bearer_auth(&self.access_token)
+                .json(&request_body),
+        )
+        .await
+        .map_err(|e| format!("Request failed: {}", e))?;
 
         if !resp.status().is_success() {
             let status = resp.status();
@@ -986,6 +1664,77 @@ impl GmailClient {
         Ok(draft)
     }
 
+    /// Create a user label
+    pub async fn create_label(&self, name: &str) -> Result<GmailLabel, String> {
+        let url = format!("{}/users/me/labels", GMAIL_API_BASE);
+
+        let body = serde_json::json!({
+            "name": name,
+            "labelListVisibility": "labelShow",
+            "messageListVisibility": "show",
+        });
+
+        let resp = send_with_retry(
+            "gmail.labels.create",
+            self.client
+                .post(&url)
+                .bearer_auth(&self.access_token)
+                .json(&body),
+        )
+        .await
+        .map_err(|e| format!("Request failed: {}", e))?;
+
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let body = resp.text().await.unwrap_or_default();
+            return Err(format!("API error {}: {}", status, body));
+        }
+
+        resp.json()
+            .await
+            .map_err(|e| format!("Failed to parse label: {}", e))
+    }
+
+    /// Create a filter; returns its id. Needs the gmail.settings.basic scope.
+    pub async fn create_filter(&self, filter: &FilterSpec) -> Result<String, String> {
+        let url = format!("{}/users/me/settings/filters", GMAIL_API_BASE);
+
+        let resp = send_with_retry(
+            "gmail.filters.create",
+            self.client
+                .post(&url)
+                .bearer_auth(&self.access_token)
+                .json(filter),
+        )
+        .await
+        .map_err(|e| format!("Request failed: {}", e))?;
+
+        if resp.status() == reqwest::StatusCode::FORBIDDEN {
+            return Err("Creating Gmail filters needs settings access. Please re-login to grant it.".to_string());
+        }
+
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let body = resp.text().await.unwrap_or_default();
+            return Err(format!("API error {}: {}", status, body));
+        }
+
+        let created: CreatedFilter = resp
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse filter: {}", e))?;
+        Ok(created.id)
+    }
+
+    /// Find a label by name (case-insensitive), creating it if it does not exist
+    pub async fn get_or_create_label(&self, name: &str) -> Result<GmailLabel, String> {
+        let labels = self.list_labels().await?;
+        if let Some(label) = labels.into_iter().find(|l| l.name.eq_ignore_ascii_case(name)) {
+            return Ok(label);
+        }
+        self.create_label(name).await
+    }
+
     /// Update an existing draft
     pub async fn update_draft(
         &self,
@@ -1015,14 +1764,15 @@ impl GmailClient {
             request_body["message"]["threadId"] = serde_json::json!(tid);
         }
 
-        let resp = self
-            .client
-            .put(&url)
-            .bearer_auth(&self.access_token)
-            .json(&request_body)
-            .send()
-            .await
-            .map_err(|e| format!("Request failed: {}", e))?;
+        let resp = send_with_retry(
+            "gmail.drafts.update",
+            self.client
+                .put(&url)
+                .bearer_auth(&self.access_token)
+                .json(&request_body),
+        )
+        .await
+        .map_err(|e| format!("Request failed: {}", e))?;
 
         if !resp.status().is_success() {
             let status = resp.status();
@@ -1042,11 +1792,7 @@ impl GmailClient {
     pub async fn delete_draft(&self, draft_id: &str) -> Result<(), String> {
         let url = format!("{}/users/me/drafts/{}", GMAIL_API_BASE, draft_id);
 
-        let resp = self
-            .client
-            .delete(&url)
-            .bearer_auth(&self.access_token)
-            .send()
+        let resp = send_with_retry("gmail.drafts.delete", self.client.delete(&url).bearer_auth(&self.access_token))
             .await
             .map_err(|e| format!("Request failed: {}", e))?;
 
@@ -1063,13 +1809,14 @@ impl GmailClient {
 
     /// Get the current history ID from the user's profile
     pub async fn get_current_history_id(&self) -> Result<String, String> {
+        Ok(self.get_profile().await?.history_id)
+    }
+
+    /// Fetch the mailbox profile (address, totals and current history ID)
+    pub async fn get_profile(&self) -> Result<GmailProfile, String> {
         let url = format!("{}/users/me/profile", GMAIL_API_BASE);
 
-        let resp = self
-            .client
-            .get(&url)
-            .bearer_auth(&self.access_token)
-            .send()
+        let resp = send_with_retry("gmail.profile", self.client.get(&url).bearer_auth(&self.access_token))
             .await
             .map_err(|e| format!("Request failed: {}", e))?;
 
@@ -1079,18 +1826,9 @@ impl GmailClient {
             return Err(format!("API error {}: {}", status, body));
         }
 
-        #[derive(Deserialize)]
-        struct Profile {
-            #[serde(rename = "historyId")]
-            history_id: String,
-        }
-
-        let profile: Profile = resp
-            .json()
+        resp.json()
             .await
-            .map_err(|e| format!("Failed to parse profile: {}", e))?;
-
-        Ok(profile.history_id)
+            .map_err(|e| format!("Failed to parse profile: {}", e))
     }
 
     /// Get changes since a given history ID
@@ -1114,17 +1852,13 @@ impl GmailClient {
                 url.push_str(&format!("&pageToken={}", urlencoding::encode(token)));
             }
 
-            let resp = self
-                .client
-                .get(&url)
-                .bearer_auth(&self.access_token)
-                .send()
+            let resp = send_with_retry("gmail.history.list", self.client.get(&url).bearer_auth(&self.access_token))
                 .await
                 .map_err(|e| format!("Request failed: {}", e))?;
 
             if resp.status().as_u16() == 404 {
                 // History ID is too old or invalid - caller should do full sync
-                return Err("History ID expired".to_string());
+                return Err(HISTORY_EXPIRED_ERROR.to_string());
             }
 
             if !resp.status().is_success() {
@@ -1209,11 +1943,7 @@ impl GmailClient {
             GMAIL_API_BASE, thread_id
         );
 
-        let resp = self
-            .client
-            .get(&url)
-            .bearer_auth(&self.access_token)
-            .send()
+        let resp = send_with_retry("gmail.threads.get", self.client.get(&url).bearer_auth(&self.access_token))
             .await
             .map_err(|e| format!("Request failed: {}", e))?;
 
@@ -1275,6 +2005,10 @@ struct HistoryMessage {
     thread_id: Option<String>,
 }
 
+/// Error from `get_history_changes` when the start history ID is too old;
+/// the caller must fall back to a full sync
+pub const HISTORY_EXPIRED_ERROR: &str = "History ID expired";
+
 /// Result of getting history changes
 #[derive(Debug, Serialize)]
 pub struct HistoryChanges {
@@ -1286,7 +2020,7 @@ pub struct HistoryChanges {
     pub new_history_id: String,
 }
 
-fn extract_email_address(from: &str) -> String {
+pub fn extract_email_address(from: &str) -> String {
     // Parse "Name <email@example.com>" format - extract the email part
     if let Some(start) = from.find('<') {
         if let Some(end) = from.find('>') {
@@ -1297,6 +2031,56 @@ fn extract_email_address(from: &str) -> String {
     from.trim().to_string()
 }
 
+/// Friendly name of the Gmail category a thread belongs to ("Primary" when uncategorized)
+pub fn thread_category(thread: &Thread) -> &'static str {
+    category_for_labels(&thread.labels)
+}
+
+fn category_for_labels(labels: &[String]) -> &'static str {
+    labels
+        .iter()
+        .find_map(|label| match label.as_str() {
+            "CATEGORY_SOCIAL" => Some("Social"),
+            "CATEGORY_PROMOTIONS" => Some("Promotions"),
+            "CATEGORY_UPDATES" => Some("Updates"),
+            "CATEGORY_FORUMS" => Some("Forums"),
+            "CATEGORY_PERSONAL" => Some("Primary"),
+            _ => None,
+        })
+        .unwrap_or("Primary")
+}
+
+/// System labels a thread must carry to match `query`, from terms like
+/// `in:inbox` or `is:starred`. Empty when the query uses `OR` or grouping,
+/// since a missing label then doesn't rule the thread out.
+pub fn query_required_labels(query: &str) -> Vec<&'static str> {
+    if query.contains(['(', '{']) || query.split_whitespace().any(|term| term == "OR") {
+        return Vec::new();
+    }
+    query
+        .split_whitespace()
+        .filter_map(|term| match term.to_ascii_lowercase().as_str() {
+            "in:inbox" => Some("INBOX"),
+            "in:sent" => Some("SENT"),
+            "in:spam" => Some("SPAM"),
+            "in:trash" => Some("TRASH"),
+            "is:starred" => Some("STARRED"),
+            "is:important" => Some("IMPORTANT"),
+            "is:unread" => Some("UNREAD"),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Gmail search query matching every thread exchanged with an address
+pub fn interaction_query(email: &str) -> Result<String, String> {
+    let address = extract_email_address(email);
+    if address.is_empty() || !address.contains('@') || address.contains(char::is_whitespace) {
+        return Err(format!("Invalid email address: {}", email));
+    }
+    Ok(format!("from:{} OR to:{}", address, address))
+}
+
 /// Check that `line` is property `name`, i.e. the name is followed by ':' or ';'
 /// (a bare prefix match would let DTSTART match DTSTAMP and vice versa)
 fn ics_property_matches(line: &str, name: &str) -> bool {
@@ -1330,28 +2114,105 @@ fn get_ics_property_with_params(block: &str, name: &str) -> Option<(String, Stri
 /// Parse ICS calendar data and extract the first event
 /// Uses simple text parsing since the icalendar crate has a complex API
 fn parse_ics_content(ics_data: &str) -> Option<CalendarEvent> {
+    // Long values (descriptions, Meet links) arrive folded across lines
+    let ics_data = unfold_ics_lines(ics_data);
+
     // Check if this is a valid calendar
     if !ics_data.contains("BEGIN:VCALENDAR") || !ics_data.contains("BEGIN:VEVENT") {
         return None;
     }
 
     // Get METHOD from calendar level
-    let method = get_ics_property(ics_data, "METHOD");
+    let method = get_ics_property(&ics_data, "METHOD");
 
     // Extract VEVENT block
     let event_start = ics_data.find("BEGIN:VEVENT")?;
     let event_end = ics_data.find("END:VEVENT")?;
-    let event_block = &ics_data[event_start..event_end];
+    parse_ics_event_block(&ics_data[event_start..event_end], method)
+}
 
-    let title = get_ics_property(event_block, "SUMMARY").unwrap_or_else(|| "(No title)".to_string());
-    let uid = get_ics_property(event_block, "UID");
-    let location = get_ics_property(event_block, "LOCATION");
-    let description = get_ics_property(event_block, "DESCRIPTION");
-    let status = get_ics_property(event_block, "STATUS");
+/// Parse every VEVENT in a calendar file (e.g. one written by `events_to_ics`).
+/// Folded lines are joined first so long values come back whole.
+pub fn parse_ics_events(ics_data: &str) -> Vec<CalendarEvent> {
+    let unfolded = unfold_ics_lines(ics_data);
+    if !unfolded.contains("BEGIN:VCALENDAR") {
+        return Vec::new();
+    }
 
-    // Parse DTSTART
-    let (dtstart_params, dtstart) = get_ics_property_with_params(event_block, "DTSTART")?;
-    let (start_time, all_day) = parse_ics_datetime(&dtstart, &dtstart_params)?;
+    let method = get_ics_property(&unfolded, "METHOD");
+    let mut events = Vec::new();
+    let mut rest = unfolded.as_str();
+    while let Some(start) = rest.find("BEGIN:VEVENT") {
+        let Some(end) = rest[start..].find("END:VEVENT") else {
+            break;
+        };
+        if let Some(event) = parse_ics_event_block(&rest[start..start + end], method.clone()) {
+            events.push(event);
+        }
+        rest = &rest[start + end + "END:VEVENT".len()..];
+    }
+    events
+}
+
+/// Join RFC 5545 continuation lines (CRLF followed by a space or tab)
+fn unfold_ics_lines(ics_data: &str) -> String {
+    ics_data
+        .replace("\r\n ", "")
+        .replace("\r\n\t", "")
+        .replace("\n ", "")
+        .replace("\n\t", "")
+}
+
+/// Undo TEXT value escaping (`\\`, `\;`, `\,`, `\n`)
+fn unescape_ics_text(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') | Some('N') => out.push('\n'),
+            Some(other) => out.push(other),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+/// Build an event from the lines between BEGIN:VEVENT and END:VEVENT
+fn parse_ics_event_block(event_block: &str, method: Option<String>) -> Option<CalendarEvent> {
+    let title = get_ics_property(event_block, "SUMMARY")
+        .map(|s| unescape_ics_text(&s))
+        .unwrap_or_else(|| "(No title)".to_string());
+    let uid = get_ics_property(event_block, "UID");
+    let location = get_ics_property(event_block, "LOCATION").map(|s| unescape_ics_text(&s));
+    let description = get_ics_property(event_block, "DESCRIPTION").map(|s| unescape_ics_text(&s));
+    let status = get_ics_property(event_block, "STATUS");
+    let sequence = get_ics_property(event_block, "SEQUENCE")
+        .and_then(|s| s.trim().parse::<i32>().ok())
+        .unwrap_or(0);
+
+    // A cancelled meeting arrives as METHOD:CANCEL, or as a STATUS:CANCELLED event
+    let cancelled = method.as_deref().is_some_and(|m| m.trim().eq_ignore_ascii_case("CANCEL"))
+        || status.as_deref().is_some_and(|s| s.trim().eq_ignore_ascii_case("CANCELLED"));
+
+    // Parse DTSTART
+    let (dtstart_params, dtstart) = get_ics_property_with_params(event_block, "DTSTART")?;
+    let (start_time, all_day) = parse_ics_datetime(&dtstart, &dtstart_params)?;
+
+    // Expand recurring events into their upcoming instances
+    let recurrence = get_ics_property(event_block, "RRULE").map(|r| r.trim().to_string());
+    let occurrences = recurrence
+        .as_deref()
+        .map(|rrule| {
+            let zone = IcsZone::for_start(&dtstart, &dtstart_params, all_day);
+            let now = Utc::now().timestamp_millis();
+            let window_end = now + RECURRENCE_WINDOW_DAYS * 86_400_000;
+            expand_rrule(rrule, start_time, &zone, &ics_exdates(event_block), now, window_end)
+        })
+        .unwrap_or_default();
 
     // Parse DTEND (optional)
     let end_time = get_ics_property_with_params(event_block, "DTEND")
@@ -1387,6 +2248,32 @@ fn parse_ics_content(ics_data: &str) -> Option<CalendarEvent> {
         method,
         status,
         response_status: None, // Will be fetched from Calendar API
+        sequence,
+        is_update: sequence > 0,
+        cancelled,
+        recurrence,
+        occurrences,
+    })
+}
+
+/// Pick the current version of an invite from every ICS part in a thread.
+/// Versions share a UID; the highest SEQUENCE wins, ties going to the later part.
+fn pick_latest_invite(events: Vec<CalendarEvent>) -> Option<CalendarEvent> {
+    // The most recent part decides which event the thread is about
+    let uid = events.last()?.uid.clone();
+
+    let mut latest: Option<CalendarEvent> = None;
+    let mut versions = 0;
+    for event in events.into_iter().filter(|e| e.uid == uid) {
+        versions += 1;
+        if latest.as_ref().is_none_or(|l| event.sequence >= l.sequence) {
+            latest = Some(event);
+        }
+    }
+
+    latest.map(|mut event| {
+        event.is_update = event.is_update || versions > 1;
+        event
     })
 }
 
@@ -1454,6 +2341,230 @@ fn parse_ics_datetime(s: &str, params: &str) -> Option<(i64, bool)> {
     None
 }
 
+// ============ Recurrence ============
+
+/// Most instances expanded from one RRULE
+const MAX_RECURRENCE_INSTANCES: usize = 50;
+/// How far ahead of today recurring invites are expanded
+const RECURRENCE_WINDOW_DAYS: i64 = 365;
+/// Periods stepped through before giving up, for rules that began long ago
+const MAX_RECURRENCE_PERIODS: i64 = 20_000;
+
+/// The zone an event's wall-clock times are in, so instances keep their
+/// local time across DST changes
+enum IcsZone {
+    Utc,
+    Named(chrono_tz::Tz),
+    Local,
+}
+
+impl IcsZone {
+    fn for_start(value: &str, params: &str, all_day: bool) -> Self {
+        if all_day || value.trim().ends_with('Z') {
+            return IcsZone::Utc;
+        }
+        params
+            .split(';')
+            .find_map(|p| p.strip_prefix("TZID="))
+            .and_then(|tz| tz.trim_matches('"').parse::<chrono_tz::Tz>().ok())
+            .map(IcsZone::Named)
+            .unwrap_or(IcsZone::Local)
+    }
+
+    fn wall_clock(&self, ms: i64) -> Option<chrono::NaiveDateTime> {
+        let utc = DateTime::from_timestamp_millis(ms)?;
+        Some(match self {
+            IcsZone::Utc => utc.naive_utc(),
+            IcsZone::Named(tz) => utc.with_timezone(tz).naive_local(),
+            IcsZone::Local => utc.with_timezone(&Local).naive_local(),
+        })
+    }
+
+    /// None for wall-clock times skipped by a DST change
+    fn timestamp(&self, naive: chrono::NaiveDateTime) -> Option<i64> {
+        let utc = match self {
+            IcsZone::Utc => DateTime::<Utc>::from_naive_utc_and_offset(naive, Utc),
+            IcsZone::Named(tz) => tz.from_local_datetime(&naive).earliest()?.with_timezone(&Utc),
+            IcsZone::Local => Local.from_local_datetime(&naive).earliest()?.with_timezone(&Utc),
+        };
+        Some(utc.timestamp_millis())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+}
+
+#[derive(Debug)]
+struct RecurrenceRule {
+    freq: Frequency,
+    interval: i64,
+    count: Option<usize>,
+    /// Last allowed start, Unix ms
+    until: Option<i64>,
+    by_day: Vec<chrono::Weekday>,
+    by_month_day: Vec<u32>,
+}
+
+/// Parse the RRULE parts we can expand; None for anything else (yearly
+/// rules, "second Tuesday" style monthly rules)
+fn parse_rrule(rrule: &str) -> Option<RecurrenceRule> {
+    let mut rule = RecurrenceRule {
+        freq: Frequency::Daily,
+        interval: 1,
+        count: None,
+        until: None,
+        by_day: Vec::new(),
+        by_month_day: Vec::new(),
+    };
+    let mut freq = None;
+    let mut ordinal_by_day = false;
+
+    for part in rrule.trim().trim_start_matches("RRULE:").split(';') {
+        let Some((key, value)) = part.split_once('=') else {
+            continue;
+        };
+        match key.trim().to_ascii_uppercase().as_str() {
+            "FREQ" => {
+                freq = Some(match value.trim().to_ascii_uppercase().as_str() {
+                    "DAILY" => Frequency::Daily,
+                    "WEEKLY" => Frequency::Weekly,
+                    "MONTHLY" => Frequency::Monthly,
+                    _ => return None,
+                })
+            }
+            "INTERVAL" => rule.interval = value.trim().parse::<i64>().ok()?.max(1),
+            "COUNT" => rule.count = Some(value.trim().parse().ok()?),
+            "UNTIL" => rule.until = Some(parse_ics_datetime(value, "")?.0),
+            "BYDAY" => {
+                for day in value.split(',') {
+                    let day = day.trim();
+                    let code = day.trim_start_matches(|c: char| c == '+' || c == '-' || c.is_ascii_digit());
+                    ordinal_by_day |= code.len() != day.len();
+                    rule.by_day.push(match code.to_ascii_uppercase().as_str() {
+                        "MO" => chrono::Weekday::Mon,
+                        "TU" => chrono::Weekday::Tue,
+                        "WE" => chrono::Weekday::Wed,
+                        "TH" => chrono::Weekday::Thu,
+                        "FR" => chrono::Weekday::Fri,
+                        "SA" => chrono::Weekday::Sat,
+                        "SU" => chrono::Weekday::Sun,
+                        _ => return None,
+                    });
+                }
+            }
+            "BYMONTHDAY" => {
+                for day in value.split(',') {
+                    match day.trim().parse::<u32>() {
+                        Ok(day @ 1..=31) => rule.by_month_day.push(day),
+                        _ => return None,
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    rule.freq = freq?;
+    if rule.freq == Frequency::Monthly && (ordinal_by_day || !rule.by_day.is_empty()) {
+        return None;
+    }
+    rule.by_day.sort_by_key(|d| d.num_days_from_monday());
+    rule.by_day.dedup();
+    rule.by_month_day.sort_unstable();
+    rule.by_month_day.dedup();
+    Some(rule)
+}
+
+/// Wall-clock starts the rule produces in its `period`th day/week/month
+fn period_candidates(rule: &RecurrenceRule, start: chrono::NaiveDateTime, period: i64) -> Vec<chrono::NaiveDateTime> {
+    let step = period * rule.interval;
+    match rule.freq {
+        Frequency::Daily => vec![start + Duration::days(step)],
+        Frequency::Weekly if rule.by_day.is_empty() => vec![start + Duration::weeks(step)],
+        Frequency::Weekly => {
+            let week_start =
+                start.date() - Duration::days(start.weekday().num_days_from_monday() as i64) + Duration::weeks(step);
+            rule.by_day
+                .iter()
+                .map(|day| (week_start + Duration::days(day.num_days_from_monday() as i64)).and_time(start.time()))
+                .collect()
+        }
+        Frequency::Monthly => {
+            let months = start.month0() as i64 + step;
+            let year = start.year() + (months / 12) as i32;
+            let month = (months % 12) as u32 + 1;
+            let days = if rule.by_month_day.is_empty() {
+                vec![start.day()]
+            } else {
+                rule.by_month_day.clone()
+            };
+            // Months without the day (e.g. the 31st) are skipped, per RFC 5545
+            days.into_iter()
+                .filter_map(|day| chrono::NaiveDate::from_ymd_opt(year, month, day))
+                .map(|date| date.and_time(start.time()))
+                .collect()
+        }
+    }
+}
+
+/// Instance starts (Unix ms) of a recurring event between `from_ms` and
+/// `until_ms`, capped at `MAX_RECURRENCE_INSTANCES`. COUNT counts from the
+/// first instance, including ones before `from_ms` and EXDATE exclusions.
+fn expand_rrule(rrule: &str, start_ms: i64, zone: &IcsZone, exdates: &[i64], from_ms: i64, until_ms: i64) -> Vec<i64> {
+    let (Some(rule), Some(start)) = (parse_rrule(rrule), zone.wall_clock(start_ms)) else {
+        return Vec::new();
+    };
+    let end = rule.until.map_or(until_ms, |until| until.min(until_ms));
+
+    let mut generated = 0;
+    let mut instances = Vec::new();
+    for period in 0..MAX_RECURRENCE_PERIODS {
+        for candidate in period_candidates(&rule, start, period) {
+            if candidate < start {
+                continue;
+            }
+            let Some(ms) = zone.timestamp(candidate) else {
+                continue;
+            };
+            if ms > end || rule.count.is_some_and(|count| generated >= count) {
+                return instances;
+            }
+            generated += 1;
+            if ms >= from_ms && !exdates.contains(&ms) {
+                instances.push(ms);
+                if instances.len() >= MAX_RECURRENCE_INSTANCES {
+                    return instances;
+                }
+            }
+        }
+    }
+    instances
+}
+
+/// Start times (Unix ms) removed from a series by EXDATE lines
+fn ics_exdates(event_block: &str) -> Vec<i64> {
+    event_block
+        .lines()
+        .map(str::trim)
+        .filter(|line| ics_property_matches(line, "EXDATE"))
+        .flat_map(|line| {
+            let Some(colon) = line.find(':') else {
+                return Vec::new();
+            };
+            let params = line["EXDATE".len()..colon].trim_start_matches(';');
+            line[colon + 1..]
+                .split(',')
+                .filter_map(|value| parse_ics_datetime(value, params))
+                .map(|(ms, _)| ms)
+                .collect()
+        })
+        .collect()
+}
+
 /// Represents attachment metadata extracted from message parts
 struct AttachmentInfo {
     attachment_id: String,
@@ -1461,12 +2572,62 @@ struct AttachmentInfo {
     mime_type: String,
     size: i32,
     content_id: Option<String>,
+    is_inline: bool,
+}
+
+/// Whether a part is meant to be shown in the body rather than as a file.
+/// `Content-Disposition` decides; without one, parts with a Content-ID are
+/// inline (that is how HTML bodies reference them).
+fn is_inline_part(headers: Option<&Vec<Header>>, content_id: Option<&str>) -> bool {
+    let disposition = headers.and_then(|headers| {
+        headers
+            .iter()
+            .find(|h| h.name.eq_ignore_ascii_case("Content-Disposition"))
+            .map(|h| h.value.trim().to_ascii_lowercase())
+    });
+    match disposition {
+        Some(value) if value.starts_with("attachment") => false,
+        Some(value) if value.starts_with("inline") => true,
+        _ => content_id.is_some(),
+    }
+}
+
+/// Calendar events from text/calendar parts carried inline in the message
+/// body (data, not an attachment id), whether or not they have a filename
+pub fn inline_calendar_events(payload: &MessagePayload) -> Vec<CalendarEvent> {
+    fn parse_inline(mime_type: &str, body: Option<&MessageBody>) -> Option<CalendarEvent> {
+        if !mime_type.eq_ignore_ascii_case("text/calendar") {
+            return None;
+        }
+        let data = body?.data.as_deref()?;
+        let ics = String::from_utf8(decode_gmail_b64(data).ok()?).ok()?;
+        parse_ics_content(&ics)
+    }
+
+    fn walk(parts: &Option<Vec<MessagePart>>, events: &mut Vec<CalendarEvent>) {
+        for part in parts.iter().flatten() {
+            events.extend(parse_inline(&part.mime_type, part.body.as_ref()));
+            walk(&part.parts, events);
+        }
+    }
+
+    let mut events = Vec::new();
+    if let Some(mime_type) = &payload.mime_type {
+        events.extend(parse_inline(mime_type, payload.body.as_ref()));
+    }
+    walk(&payload.parts, &mut events);
+    events
 }
 
 fn extract_attachments_from_parts(parts: &Option<Vec<MessagePart>>) -> Vec<AttachmentInfo> {
     let mut attachments = Vec::new();
     if let Some(parts) = parts {
         for part in parts {
+            // Signatures and PGP version parts are shown as a badge, not files
+            if is_security_control_part(&part.mime_type) {
+                continue;
+            }
+
             // Extract Content-ID header if present (for inline images)
             let content_id = part.headers.as_ref().and_then(|headers| {
                 headers.iter()
@@ -1480,21 +2641,26 @@ fn extract_attachments_from_parts(parts: &Option<Vec<MessagePart>>) -> Vec<Attac
                     let size = body.size.unwrap_or(0);
                     if size > 0 {
                         // Use filename if available, otherwise generate one for inline images
+                        // and filename-less invites
                         let filename = part.filename.clone()
                             .filter(|f| !f.is_empty())
                             .unwrap_or_else(|| {
-                                if let Some(ref cid) = content_id {
+                                if part.mime_type.eq_ignore_ascii_case("text/calendar") {
+                                    "invite.ics".to_string()
+                                } else if let Some(ref cid) = content_id {
                                     format!("{}.{}", cid, part.mime_type.split('/').last().unwrap_or("bin"))
                                 } else {
                                     format!("attachment.{}", part.mime_type.split('/').last().unwrap_or("bin"))
                                 }
                             });
+                        let is_inline = is_inline_part(part.headers.as_ref(), content_id.as_deref());
                         attachments.push(AttachmentInfo {
                             attachment_id: attachment_id.clone(),
                             filename,
                             mime_type: part.mime_type.clone(),
                             size,
                             content_id,
+                            is_inline,
                         });
                     }
                 }
@@ -1538,6 +2704,18 @@ fn classify_date(date: DateTime<Utc>) -> DateBucket {
     DateBucket::Older
 }
 
+/// Unread thread counts per date bucket label ("Today", "This week", ...).
+/// Buckets without unread threads are left out.
+pub fn bucket_unread_counts<'a>(threads: impl IntoIterator<Item = &'a Thread>) -> HashMap<String, usize> {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for thread in threads {
+        if thread.unread_count > 0 {
+            *counts.entry(classify_date(thread.last_message_date).as_str().to_string()).or_default() += 1;
+        }
+    }
+    counts
+}
+
 fn group_threads_by_date(threads: Vec<Thread>) -> Vec<ThreadGroup> {
     let mut groups: HashMap<String, Vec<Thread>> = HashMap::new();
 
@@ -1627,12 +2805,276 @@ fn extract_text_from_payload(payload: &MessagePayload) -> Option<String> {
     None
 }
 
-fn decode_base64_body(data: &str) -> Option<String> {
+/// Extract the text/html body of a message, searching nested multiparts
+pub fn extract_html_from_message(message: &FullMessage) -> Option<String> {
+    let payload = message.payload.as_ref()?;
+    if payload.mime_type.as_deref() == Some("text/html") {
+        return payload.body.as_ref()?.data.as_deref().and_then(decode_base64_body);
+    }
+    find_html_in_parts(payload.parts.as_deref()?)
+}
+
+fn find_html_in_parts(parts: &[MessagePart]) -> Option<String> {
+    for part in parts {
+        if part.mime_type == "text/html" {
+            if let Some(html) = part.body.as_ref().and_then(|b| b.data.as_deref()).and_then(decode_base64_body) {
+                return Some(html);
+            }
+        }
+        if let Some(html) = part.parts.as_deref().and_then(find_html_in_parts) {
+            return Some(html);
+        }
+    }
+    None
+}
+
+/// A message body decoded for display
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct DecodedBody {
+    /// "text/html" or "text/plain"
+    pub mime_type: &'static str,
+    pub content: String,
+}
+
+/// The body to render for a message: text/html when there is one, otherwise
+/// text/plain, searching nested multiparts
+pub fn best_body(message: &FullMessage) -> Option<DecodedBody> {
+    let (text, html) = payload_bodies(message.payload.as_ref()?);
+
+    html.map(|content| DecodedBody {
+        mime_type: "text/html",
+        content,
+    })
+    .or_else(|| {
+        text.map(|content| DecodedBody {
+            mime_type: "text/plain",
+            content,
+        })
+    })
+}
+
+/// Decoded text/plain and text/html bodies from a (possibly nested) multipart
+/// tree. The first part of each type wins; attachments are skipped.
+pub fn extract_bodies_from_parts(parts: &[MessagePart]) -> (Option<String>, Option<String>) {
+    let mut text = None;
+    let mut html = None;
+    collect_bodies(parts, &mut text, &mut html);
+    (text, html)
+}
+
+fn collect_bodies(parts: &[MessagePart], text: &mut Option<String>, html: &mut Option<String>) {
+    for part in parts {
+        if text.is_some() && html.is_some() {
+            return;
+        }
+        if part.filename.as_deref().is_some_and(|f| !f.is_empty()) {
+            continue;
+        }
+        let data = part.body.as_ref().and_then(|b| b.data.as_deref());
+        match part.mime_type.as_str() {
+            "text/plain" if text.is_none() => *text = data.and_then(decode_base64_body),
+            "text/html" if html.is_none() => *html = data.and_then(decode_base64_body),
+            _ => {}
+        }
+        if let Some(nested) = &part.parts {
+            collect_bodies(nested, text, html);
+        }
+    }
+}
+
+/// Bodies of a whole payload, which may itself be a single text part
+fn payload_bodies(payload: &MessagePayload) -> (Option<String>, Option<String>) {
+    let data = payload.body.as_ref().and_then(|b| b.data.as_deref());
+    match payload.mime_type.as_deref() {
+        Some("text/plain") => (data.and_then(decode_base64_body), None),
+        Some("text/html") => (None, data.and_then(decode_base64_body)),
+        _ => payload.parts.as_deref().map(extract_bodies_from_parts).unwrap_or_default(),
+    }
+}
+
+/// The cacheable form of a message: addressing, date and decoded bodies
+fn cached_message(
+    id: &str,
+    thread_id: &str,
+    label_ids: Option<&[String]>,
+    internal_date: Option<&str>,
+    payload: Option<&MessagePayload>,
+) -> Message {
+    let header = |name: &str| {
+        payload
+            .and_then(|p| p.headers.as_ref())
+            .and_then(|headers| headers.iter().find(|h| h.name.eq_ignore_ascii_case(name)))
+            .map(|h| h.value.clone())
+            .unwrap_or_default()
+    };
+    let (body_text, body_html) = payload.map(payload_bodies).unwrap_or_default();
+    let date = internal_date
+        .and_then(|d| d.parse::<i64>().ok())
+        .and_then(DateTime::from_timestamp_millis)
+        .unwrap_or_else(Utc::now);
+
+    Message {
+        gmail_msg_id: id.to_string(),
+        thread_id: thread_id.to_string(),
+        from_addr: header("From"),
+        to_addrs: split_address_list(&header("To")),
+        date,
+        subject: header("Subject"),
+        label_ids: label_ids.map(<[String]>::to_vec).unwrap_or_default(),
+        body_text,
+        body_html,
+    }
+}
+
+/// Cacheable form of a fully fetched message
+pub fn message_from_full(message: &FullMessage) -> Message {
+    cached_message(
+        &message.id,
+        &message.thread_id,
+        message.label_ids.as_deref(),
+        message.internal_date.as_deref(),
+        message.payload.as_ref(),
+    )
+}
+
+fn is_unread(message: &FullMessage) -> bool {
+    message.label_ids.as_ref().is_some_and(|labels| labels.iter().any(|l| l == "UNREAD"))
+}
+
+/// Ids of the messages in a thread still carrying `UNREAD`
+pub fn unread_message_ids(thread: &FullThread) -> Vec<String> {
+    thread.messages.iter().filter(|m| is_unread(m)).map(|m| m.id.clone()).collect()
+}
+
+/// Drop `UNREAD` from the given messages' local labels after marking them
+/// read on the server. Returns how many messages remain unread.
+pub fn mark_messages_read_locally(thread: &mut FullThread, message_ids: &[String]) -> usize {
+    for message in thread.messages.iter_mut().filter(|m| message_ids.contains(&m.id)) {
+        if let Some(labels) = message.label_ids.as_mut() {
+            labels.retain(|l| l != "UNREAD");
+        }
+    }
+    thread.messages.iter().filter(|m| is_unread(m)).count()
+}
+
+/// The earliest message still carrying `UNREAD`, so a thread can open
+/// scrolled to the reply the user hasn't seen
+pub fn first_unread_message_id(thread: &FullThread) -> Option<String> {
+    thread
+        .messages
+        .iter()
+        .filter(|m| is_unread(m))
+        .min_by_key(|m| m.internal_date.as_deref().and_then(|d| d.parse::<i64>().ok()).unwrap_or(i64::MAX))
+        .map(|m| m.id.clone())
+}
+
+/// Rebuild a thread from cached messages so it can be shown offline. Bodies
+/// are re-encoded as a multipart/alternative payload, the shape Gmail returns.
+pub fn full_thread_from_cache(thread_id: &str, messages: &[Message]) -> FullThread {
     use base64::Engine;
-    // Gmail uses URL-safe base64 encoding
-    let normalized = data.replace('-', "+").replace('_', "/");
-    base64::engine::general_purpose::STANDARD
-        .decode(&normalized)
+    let encode = |body: &str| base64::engine::general_purpose::URL_SAFE.encode(body);
+    let body_part = |mime_type: &str, body: &str| MessagePart {
+        part_id: None,
+        mime_type: mime_type.to_string(),
+        filename: None,
+        headers: None,
+        body: Some(MessageBody {
+            size: Some(body.len() as i32),
+            data: Some(encode(body)),
+            attachment_id: None,
+        }),
+        parts: None,
+    };
+
+    let messages = messages
+        .iter()
+        .map(|m| {
+            let header = |name: &str, value: &str| Header {
+                name: name.to_string(),
+                value: value.to_string(),
+            };
+            let mut parts = Vec::new();
+            if let Some(text) = &m.body_text {
+                parts.push(body_part("text/plain", text));
+            }
+            if let Some(html) = &m.body_html {
+                parts.push(body_part("text/html", html));
+            }
+            FullMessage {
+                id: m.gmail_msg_id.clone(),
+                thread_id: m.thread_id.clone(),
+                label_ids: Some(m.label_ids.clone()),
+                snippet: None,
+                internal_date: Some(m.date.timestamp_millis().to_string()),
+                payload: Some(MessagePayload {
+                    headers: Some(vec![
+                        header("From", &m.from_addr),
+                        header("To", &m.to_addrs.join(", ")),
+                        header("Subject", &m.subject),
+                    ]),
+                    body: None,
+                    parts: Some(parts),
+                    mime_type: Some("multipart/alternative".to_string()),
+                }),
+            }
+        })
+        .collect();
+
+    FullThread {
+        id: thread_id.to_string(),
+        history_id: None,
+        messages,
+    }
+}
+
+/// Best-effort readable text for a message: plain text, then stripped HTML, then the snippet
+pub fn extract_readable_body(message: &FullMessage) -> String {
+    extract_body_text_from_message(message)
+        .or_else(|| extract_html_from_message(message).map(|html| strip_html_tags(&html)))
+        .unwrap_or_else(|| message.snippet.clone().unwrap_or_default())
+}
+
+/// Metadata for every attachment in a thread (ids, names, sizes, MIME types),
+/// read from the message parts without fetching any content. Files are
+/// downloaded one at a time, when opened, with `download_attachment`.
+pub fn attachment_manifest(thread: &FullThread) -> Vec<Attachment> {
+    thread
+        .messages
+        .iter()
+        .flat_map(|message| {
+            let infos = message
+                .payload
+                .as_ref()
+                .map(|p| extract_attachments_from_parts(&p.parts))
+                .unwrap_or_default();
+            infos.into_iter().map(move |info| Attachment {
+                message_id: message.id.clone(),
+                attachment_id: info.attachment_id,
+                filename: info.filename,
+                mime_type: info.mime_type,
+                size: info.size,
+                inline_data: None,
+                content_id: info.content_id,
+                is_inline: info.is_inline,
+            })
+        })
+        .collect()
+}
+
+/// Attachment IDs and MIME types of inline (Content-ID) images in a message
+pub fn inline_image_attachments(message: &FullMessage) -> Vec<(String, String)> {
+    let Some(payload) = message.payload.as_ref() else {
+        return Vec::new();
+    };
+    extract_attachments_from_parts(&payload.parts)
+        .into_iter()
+        .filter(|a| a.content_id.is_some() && a.mime_type.starts_with("image/"))
+        .map(|a| (a.attachment_id, a.mime_type))
+        .collect()
+}
+
+fn decode_base64_body(data: &str) -> Option<String> {
+    decode_gmail_b64(data)
         .ok()
         .and_then(|bytes| String::from_utf8(bytes).ok())
 }
@@ -1679,22 +3121,169 @@ fn strip_html_tags(html: &str) -> String {
         .replace("&amp;", "&")
 }
 
+/// Headers and body of a text part. ASCII bodies go out as-is; anything else
+/// is quoted-printable so the part stays valid 7-bit MIME.
+fn text_part(content_type: &str, body: &str) -> String {
+    if body.is_ascii() {
+        format!("Content-Type: {}; charset=utf-8\r\n\r\n{}", content_type, body)
+    } else {
+        format!(
+            "Content-Type: {}; charset=utf-8\r\nContent-Transfer-Encoding: quoted-printable\r\n\r\n{}",
+            content_type,
+            encode_quoted_printable(body)
+        )
+    }
+}
+
+/// RFC 2045 quoted-printable: escape non-printable bytes, keep hard line
+/// breaks as CRLF and soft-wrap so no encoded line exceeds 76 characters
+fn encode_quoted_printable(text: &str) -> String {
+    const MAX_LINE: usize = 76;
+
+    let mut out = String::with_capacity(text.len() * 3 / 2);
+    for (i, line) in text.split('\n').enumerate() {
+        if i > 0 {
+            out.push_str("\r\n");
+        }
+        let bytes = line.strip_suffix('\r').unwrap_or(line).as_bytes();
+        let mut width = 0;
+        for (j, &byte) in bytes.iter().enumerate() {
+            let at_line_end = j + 1 == bytes.len();
+            let literal = matches!(byte, b'!'..=b'<' | b'>'..=b'~')
+                || (matches!(byte, b' ' | b'\t') && !at_line_end);
+            let token = if literal {
+                (byte as char).to_string()
+            } else {
+                format!("={:02X}", byte)
+            };
+            // Leave room for the "=" of a soft break unless this ends the line
+            let limit = if at_line_end { MAX_LINE } else { MAX_LINE - 1 };
+            if width + token.len() > limit {
+                out.push_str("=\r\n");
+                width = 0;
+            }
+            out.push_str(&token);
+            width += token.len();
+        }
+    }
+    out
+}
+
+/// "Fwd: " + subject, unless it is already a forward
+fn forward_subject(subject: &str) -> String {
+    let lower = subject.trim_start().to_ascii_lowercase();
+    if lower.starts_with("fwd:") || lower.starts_with("fw:") {
+        subject.to_string()
+    } else {
+        format!("Fwd: {}", subject)
+    }
+}
+
+/// Body of a forward: the note, then the original's From/Date/Subject/To/Cc
+/// and its content. Uses the original HTML when there is one. Returns the
+/// body and whether it is HTML.
+fn build_forward_body(original: &FullMessage, note: &str) -> (String, bool) {
+    let headers: Vec<(&str, &str)> = ["From", "Date", "Subject", "To", "Cc"]
+        .into_iter()
+        .filter_map(|name| Some((name, original.header(name).filter(|v| !v.trim().is_empty())?)))
+        .collect();
+
+    if let Some(html) = extract_html_from_message(original) {
+        let escape = |text: &str| {
+            text.replace('&', "&amp;")
+                .replace('<', "&lt;")
+                .replace('>', "&gt;")
+                .replace('"', "&quot;")
+        };
+        let mut body = String::new();
+        if !note.trim().is_empty() {
+            body.push_str(&format!("<div>{}</div><br>", escape(note).replace('\n', "<br>")));
+        }
+        body.push_str("<div>---------- Forwarded message ---------<br>");
+        for (name, value) in &headers {
+            body.push_str(&format!("{}: {}<br>", name, escape(value)));
+        }
+        body.push_str("</div><br>");
+        body.push_str(&html);
+        return (body, true);
+    }
+
+    let mut body = String::new();
+    if !note.trim().is_empty() {
+        body.push_str(note.trim_end());
+        body.push_str("\n\n");
+    }
+    body.push_str("---------- Forwarded message ---------\n");
+    for (name, value) in &headers {
+        body.push_str(&format!("{}: {}\n", name, value));
+    }
+    body.push('\n');
+    body.push_str(&extract_readable_body(original));
+    (body, false)
+}
+
+/// References header for a reply: the parent's References chain followed by
+/// the parent's own Message-ID, without repeats
+pub fn build_references(parent_references: Option<&str>, parent_message_id: &str) -> String {
+    let mut chain: Vec<String> = extract_message_ids(parent_references.unwrap_or(""));
+    let parent = ensure_angle_brackets(parent_message_id);
+    chain.retain(|id| *id != parent);
+    chain.push(parent);
+    chain.join(" ")
+}
+
 /// Normalize an RFC Message-ID so it is wrapped in exactly one pair of angle brackets
 fn ensure_angle_brackets(id: &str) -> String {
     let trimmed = id.trim().trim_start_matches('<').trim_end_matches('>');
     format!("<{}>", trimmed)
 }
 
-/// RFC 2047 encode a header value when it contains non-ASCII characters
+/// RFC 2047 encode a header value when it contains non-ASCII characters.
+/// Long values become several encoded words on folded lines.
 fn encode_header_value(value: &str) -> String {
+    encode_header_words(value).join("\r\n ")
+}
+
+/// Split `value` into `=?UTF-8?B?...?=` encoded words of at most 75 characters
+/// each, never splitting a UTF-8 character across words. ASCII values pass
+/// through unchanged as a single word.
+fn encode_header_words(value: &str) -> Vec<String> {
+    use base64::Engine;
+    // 75 - len("=?UTF-8?B?" + "?=") leaves 63 base64 chars: 15 quads of 3 bytes
+    const MAX_WORD_BYTES: usize = 45;
+
     if value.is_ascii() {
-        return value.to_string();
+        return vec![value.to_string()];
     }
-    use base64::Engine;
-    format!(
-        "=?UTF-8?B?{}?=",
-        base64::engine::general_purpose::STANDARD.encode(value.as_bytes())
-    )
+
+    let mut words = Vec::new();
+    let mut chunk_start = 0;
+    let mut chunk_end = 0;
+    for (i, c) in value.char_indices() {
+        if i + c.len_utf8() - chunk_start > MAX_WORD_BYTES {
+            words.push(&value[chunk_start..chunk_end]);
+            chunk_start = chunk_end;
+        }
+        chunk_end = i + c.len_utf8();
+    }
+    words.push(&value[chunk_start..chunk_end]);
+
+    words
+        .into_iter()
+        .map(|chunk| {
+            format!(
+                "=?UTF-8?B?{}?=",
+                base64::engine::general_purpose::STANDARD.encode(chunk.as_bytes())
+            )
+        })
+        .collect()
+}
+
+/// Encode an attachment filename for a quoted `name=`/`filename=` parameter.
+/// Encoded words stay on one line, since folding inside a quoted string
+/// confuses some clients.
+fn encode_filename_param(filename: &str) -> String {
+    encode_header_words(&filename.replace(['"', '\\', '\r', '\n'], "_")).join(" ")
 }
 
 /// RFC 2047 encode display names in an address list header, leaving emails untouched
@@ -1746,341 +3335,2614 @@ fn split_address_list(input: &str) -> Vec<String> {
     parts
 }
 
-// ============ Email Reactions ============
+// ============ Signed and encrypted messages ============
 
-/// Reaction data parsed from email
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ParsedReaction {
-    pub emoji: String,
-    pub from_addr: String,
-    pub in_reply_to: String,
-    pub message_id: String,
+/// How a message is protected
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SecurityScheme {
+    Smime,
+    Pgp,
 }
 
-/// Parse reaction from a message if it contains a valid reaction part
-pub fn parse_reaction_from_message(message: &FullMessage) -> Option<ParsedReaction> {
-    let payload = message.payload.as_ref()?;
-
-    // Get headers
-    let headers = payload.headers.as_ref()?;
-    let from = headers.iter()
-        .find(|h| h.name.eq_ignore_ascii_case("From"))
-        .map(|h| extract_email_address(&h.value))?;
-    let in_reply_to = headers.iter()
-        .find(|h| h.name.eq_ignore_ascii_case("In-Reply-To"))
-        .map(|h| h.value.trim().to_string())?;
-
-    // Look for reaction JSON part
-    let emoji = find_reaction_in_payload(payload)?;
+/// Signature and encryption state of a message, so the UI can show a badge
+/// instead of the raw signature or ciphertext parts
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct MessageSecurity {
+    pub is_signed: bool,
+    pub is_encrypted: bool,
+    pub scheme: Option<SecurityScheme>,
+}
 
-    Some(ParsedReaction {
-        emoji,
-        from_addr: from,
-        in_reply_to,
-        message_id: message.id.clone(),
-    })
+/// Detached signatures and the PGP/MIME version part carry no content
+fn is_security_control_part(mime_type: &str) -> bool {
+    matches!(
+        mime_type.to_ascii_lowercase().as_str(),
+        "application/pkcs7-signature"
+            | "application/x-pkcs7-signature"
+            | "application/pgp-signature"
+            | "application/pgp-encrypted"
+    )
 }
 
-/// Recursively search for reaction part in payload
-fn find_reaction_in_payload(payload: &MessagePayload) -> Option<String> {
-    // Check if top-level is reaction
-    if let Some(mime_type) = &payload.mime_type {
-        if mime_type == "text/vnd.google.email-reaction+json" {
-            if let Some(body) = &payload.body {
-                if let Some(data) = &body.data {
-                    return parse_reaction_json(data);
-                }
+/// Detect S/MIME and PGP (PGP/MIME or inline) signing and encryption
+pub fn detect_message_security(payload: &MessagePayload) -> MessageSecurity {
+    let mut security = MessageSecurity::default();
+    let data = payload.body.as_ref().and_then(|b| b.data.as_deref());
+    inspect_security(
+        payload.mime_type.as_deref().unwrap_or(""),
+        payload.headers.as_deref(),
+        data,
+        &mut security,
+    );
+    if let Some(parts) = &payload.parts {
+        inspect_security_parts(parts, &mut security);
+    }
+    security
+}
+
+fn inspect_security_parts(parts: &[MessagePart], security: &mut MessageSecurity) {
+    for part in parts {
+        let data = part.body.as_ref().and_then(|b| b.data.as_deref());
+        inspect_security(&part.mime_type, part.headers.as_deref(), data, security);
+        if let Some(nested) = &part.parts {
+            inspect_security_parts(nested, security);
+        }
+    }
+}
+
+fn inspect_security(mime_type: &str, headers: Option<&[Header]>, data: Option<&str>, security: &mut MessageSecurity) {
+    let mut mark = |signed: bool, scheme: SecurityScheme| {
+        if signed {
+            security.is_signed = true;
+        } else {
+            security.is_encrypted = true;
+        }
+        security.scheme.get_or_insert(scheme);
+    };
+
+    match mime_type.to_ascii_lowercase().as_str() {
+        "application/pkcs7-signature" | "application/x-pkcs7-signature" => mark(true, SecurityScheme::Smime),
+        "application/pgp-signature" => mark(true, SecurityScheme::Pgp),
+        "application/pgp-encrypted" => mark(false, SecurityScheme::Pgp),
+        "application/pkcs7-mime" | "application/x-pkcs7-mime" => {
+            // Opaque-signed S/MIME uses the same type with smime-type=signed-data
+            let content_type = headers
+                .and_then(|h| h.iter().find(|h| h.name.eq_ignore_ascii_case("Content-Type")))
+                .map(|h| h.value.to_ascii_lowercase())
+                .unwrap_or_default();
+            mark(content_type.contains("signed-data"), SecurityScheme::Smime);
+        }
+        "text/plain" => {
+            // Inline PGP keeps the armor in the text body
+            let Some(text) = data.and_then(decode_base64_body) else {
+                return;
+            };
+            if text.contains("-----BEGIN PGP MESSAGE-----") {
+                mark(false, SecurityScheme::Pgp);
+            } else if text.contains("-----BEGIN PGP SIGNED MESSAGE-----") {
+                mark(true, SecurityScheme::Pgp);
             }
         }
+        _ => {}
     }
+}
 
-    // Check parts
-    if let Some(parts) = &payload.parts {
-        for part in parts {
-            if part.mime_type == "text/vnd.google.email-reaction+json" {
-                // Skip if it's an attachment
-                if let Some(headers) = &part.headers {
-                    let is_attachment = headers.iter().any(|h| {
-                        h.name.eq_ignore_ascii_case("Content-Disposition")
-                            && h.value.to_lowercase().contains("attachment")
-                    });
-                    if is_attachment {
-                        continue;
-                    }
-                }
-                if let Some(body) = &part.body {
-                    if let Some(data) = &body.data {
-                        return parse_reaction_json(data);
-                    }
+// ============ Read receipts ============
+
+/// Headers a sender uses to ask for a read receipt (`Disposition-Notification-To`
+/// per RFC 8098, plus the older non-standard ones some clients still send)
+const READ_RECEIPT_HEADERS: &[&str] = &["Disposition-Notification-To", "Return-Receipt-To", "X-Confirm-Reading-To"];
+
+/// The address a received message asks a read receipt to go to, if any. Posta
+/// never sends receipts; this only lets the UI say one was requested.
+pub fn read_receipt_request(payload: &MessagePayload) -> Option<String> {
+    payload
+        .headers
+        .as_deref()?
+        .iter()
+        .find(|h| READ_RECEIPT_HEADERS.iter().any(|name| h.name.eq_ignore_ascii_case(name)))
+        .map(|h| h.value.trim().to_string())
+        .filter(|v| !v.is_empty())
+}
+
+// ============ Card filters ============
+
+/// A Gmail filter that labels matching mail, as sent to
+/// `users.settings.filters.create`
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct FilterSpec {
+    pub criteria: FilterCriteria,
+    pub action: FilterAction,
+}
+
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+pub struct FilterCriteria {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub query: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+pub struct FilterAction {
+    #[serde(rename = "addLabelIds")]
+    pub add_label_ids: Vec<String>,
+    /// `INBOX` here skips the inbox
+    #[serde(rename = "removeLabelIds", skip_serializing_if = "Vec::is_empty")]
+    pub remove_label_ids: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CreatedFilter {
+    id: String,
+}
+
+/// Label and filter mirroring an email card in Gmail, so its query works
+/// there as a server-side "saved search". Calendar cards have no Gmail query.
+pub fn card_filter(card: &crate::models::Card) -> Result<(String, FilterCriteria), String> {
+    if card.card_type == "calendar" {
+        return Err("Calendar cards can't become Gmail filters".to_string());
+    }
+    let name = card.name.trim();
+    let query = card.query.trim();
+    if name.is_empty() || query.is_empty() {
+        return Err("The card needs a name and a query".to_string());
+    }
+    Ok((
+        name.to_string(),
+        FilterCriteria {
+            query: Some(query.to_string()),
+            ..Default::default()
+        },
+    ))
+}
+
+/// Filter labeling all mail from `email` with `label_id`, optionally keeping
+/// it out of the inbox
+pub fn contact_filter(email: &str, label_id: &str, skip_inbox: bool) -> Result<FilterSpec, String> {
+    let address = extract_email_address(email).to_lowercase();
+    let valid = address
+        .split_once('@')
+        .is_some_and(|(local, domain)| !local.is_empty() && domain.contains('.') && !domain.starts_with('.'));
+    if !valid || address.contains(char::is_whitespace) {
+        return Err(format!("Invalid email address: {}", email));
+    }
+
+    Ok(FilterSpec {
+        criteria: FilterCriteria {
+            from: Some(address),
+            ..Default::default()
+        },
+        action: FilterAction {
+            add_label_ids: vec![label_id.to_string()],
+            remove_label_ids: if skip_inbox { vec!["INBOX".to_string()] } else { Vec::new() },
+        },
+    })
+}
+
+// ============ Card label validation ============
+
+/// A `label:` in a card's query that matches no existing label
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct MissingLabel {
+    pub label: String,
+    /// Closest existing label name, if any is close enough to be a likely rename
+    pub suggestion: Option<String>,
+}
+
+/// A card whose query references labels that no longer exist
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct CardLabelIssue {
+    pub card_id: String,
+    pub card_name: String,
+    pub query: String,
+    pub missing: Vec<MissingLabel>,
+    /// The query with every suggestion applied; None without suggestions
+    pub repaired_query: Option<String>,
+}
+
+/// A label name as Gmail's search matches it: `Work/Big Clients` is
+/// `label:work-big-clients`
+fn label_search_name(name: &str) -> String {
+    name.trim()
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_whitespace() || c == '/' { '-' } else { c })
+        .collect()
+}
+
+/// `label:` operands in a query as (byte range of the operand, label name)
+fn query_label_refs(query: &str) -> Vec<(std::ops::Range<usize>, String)> {
+    let lower = query.to_ascii_lowercase();
+    let mut refs = Vec::new();
+    for (at, op) in lower.match_indices("label:") {
+        let starts_term = query[..at]
+            .chars()
+            .next_back()
+            .is_none_or(|c| c.is_whitespace() || matches!(c, '(' | '{' | '-'));
+        if !starts_term {
+            continue;
+        }
+        let start = at + op.len();
+        let rest = &query[start..];
+        let (range, name) = if let Some(quoted) = rest.strip_prefix('"') {
+            let len = quoted.find('"').unwrap_or(quoted.len());
+            let end = start + 1 + len + usize::from(len < quoted.len());
+            (start..end, quoted[..len].to_string())
+        } else {
+            let len = rest
+                .find(|c: char| c.is_whitespace() || matches!(c, ')' | '}'))
+                .unwrap_or(rest.len());
+            (start..start + len, rest[..len].to_string())
+        };
+        if !name.trim().is_empty() {
+            refs.push((range, name));
+        }
+    }
+    refs
+}
+
+/// Levenshtein distance, by character
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Closest existing label to a missing one, within a third of its length
+/// (at least two edits) so unrelated labels aren't suggested
+fn closest_label<'a>(missing: &str, labels: &'a [GmailLabel]) -> Option<&'a GmailLabel> {
+    let target = label_search_name(missing);
+    let max_distance = (target.chars().count() / 3).max(2);
+    labels
+        .iter()
+        .map(|label| (edit_distance(&target, &label_search_name(&label.name)), label))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, label)| label)
+}
+
+/// Labels a card's query references that aren't in `labels`, with a suggested
+/// replacement for each. None when every label exists (or it's a calendar card).
+pub fn card_label_issue(card: &crate::models::Card, labels: &[GmailLabel]) -> Option<CardLabelIssue> {
+    if card.card_type == "calendar" {
+        return None;
+    }
+    let existing: std::collections::HashSet<String> = labels
+        .iter()
+        .flat_map(|l| [label_search_name(&l.name), l.id.to_lowercase()])
+        .collect();
+
+    let mut missing = Vec::new();
+    let mut repaired = card.query.clone();
+    let mut repaired_any = false;
+    // Replace from the end so earlier ranges stay valid
+    for (range, name) in query_label_refs(&card.query).into_iter().rev() {
+        if existing.contains(&label_search_name(&name)) {
+            continue;
+        }
+        let suggestion = closest_label(&name, labels);
+        if let Some(label) = suggestion {
+            repaired.replace_range(range, &label_search_name(&label.name));
+            repaired_any = true;
+        }
+        missing.push(MissingLabel {
+            label: name,
+            suggestion: suggestion.map(|l| l.name.clone()),
+        });
+    }
+    if missing.is_empty() {
+        return None;
+    }
+    missing.reverse();
+
+    Some(CardLabelIssue {
+        card_id: card.id.clone(),
+        card_name: card.name.clone(),
+        query: card.query.clone(),
+        missing,
+        repaired_query: repaired_any.then_some(repaired),
+    })
+}
+
+// ============ Split thread stitching ============
+
+/// Gmail starts a new thread once a conversation reaches this many messages
+const GMAIL_THREAD_SPLIT_SIZE: usize = 100;
+
+/// Upper bound on sibling threads fetched when stitching a split conversation
+const MAX_STITCH_SIBLINGS: usize = 10;
+
+/// A conversation assembled from one or more Gmail threads
+#[derive(Debug, Serialize)]
+pub struct StitchedConversation {
+    /// The requested thread first, then any stitched siblings
+    pub thread_ids: Vec<String>,
+    /// All messages, oldest first
+    pub messages: Vec<FullMessage>,
+    /// Indices in `messages` where the source thread changes
+    pub split_boundaries: Vec<usize>,
+}
+
+/// `<...>` tokens from a Message-ID / References / In-Reply-To header value
+fn extract_message_ids(value: &str) -> Vec<String> {
+    value
+        .split('<')
+        .skip(1)
+        .filter_map(|rest| rest.split_once('>'))
+        .map(|(id, _)| format!("<{}>", id.trim()))
+        .filter(|id| id.len() > 2)
+        .collect()
+}
+
+/// Every Message-ID a thread's messages carry or refer to
+fn conversation_ids(thread: &FullThread) -> Vec<String> {
+    let mut ids: Vec<String> = Vec::new();
+    for message in &thread.messages {
+        for header in ["Message-ID", "In-Reply-To", "References"] {
+            for id in message.header(header).map(extract_message_ids).unwrap_or_default() {
+                if !ids.contains(&id) {
+                    ids.push(id);
                 }
             }
-            // Recurse into nested parts
-            if let Some(nested) = &part.parts {
-                for nested_part in nested {
-                    if nested_part.mime_type == "text/vnd.google.email-reaction+json" {
-                        if let Some(body) = &nested_part.body {
-                            if let Some(data) = &body.data {
-                                return parse_reaction_json(data);
-                            }
-                        }
-                    }
-                }
+        }
+    }
+    ids
+}
+
+/// Subject without any leading "Re:" / "Fwd:" markers
+fn strip_reply_prefixes(subject: &str) -> &str {
+    let mut rest = subject.trim();
+    loop {
+        let lower = rest.to_ascii_lowercase();
+        let prefix_len = ["re:", "fwd:", "fw:"].iter().find(|p| lower.starts_with(*p)).map(|p| p.len());
+        match prefix_len {
+            Some(len) => rest = rest[len..].trim_start(),
+            None => return rest,
+        }
+    }
+}
+
+/// A thread looks split when it hit Gmail's size cap, or when its first
+/// message replies to something that isn't in the thread
+pub fn thread_looks_truncated(thread: &FullThread) -> bool {
+    if thread.messages.len() >= GMAIL_THREAD_SPLIT_SIZE {
+        return true;
+    }
+    let Some(first) = thread.messages.first() else {
+        return false;
+    };
+    let own_ids: Vec<String> = thread
+        .messages
+        .iter()
+        .filter_map(|m| m.header("Message-ID"))
+        .flat_map(extract_message_ids)
+        .collect();
+    first
+        .header("In-Reply-To")
+        .map(extract_message_ids)
+        .unwrap_or_default()
+        .iter()
+        .any(|id| !own_ids.contains(id))
+}
+
+/// Drop duplicate copies of a message from a thread, keeping the first.
+/// Copies are matched by Message-ID; with `match_content` they may also match
+/// on sender, subject and whitespace-normalized body, which can merge a
+/// legitimately repeated message, so it is opt-in. Returns how many were hidden.
+pub fn dedupe_thread_messages(thread: &mut FullThread, match_content: bool) -> usize {
+    let mut seen_ids = std::collections::HashSet::new();
+    let mut seen_content = std::collections::HashSet::new();
+    let before = thread.messages.len();
+
+    thread.messages.retain(|message| {
+        if let Some(id) = message.header("Message-ID").map(|id| ensure_angle_brackets(id).to_lowercase()) {
+            if !seen_ids.insert(id) {
+                return false;
+            }
+        }
+
+        if match_content {
+            let normalize = |text: &str| text.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase();
+            let key = (
+                normalize(message.header("From").unwrap_or("")),
+                normalize(message.header("Subject").unwrap_or("")),
+                normalize(&extract_body_text_from_message(message).unwrap_or_default()),
+            );
+            if !seen_content.insert(key) {
+                return false;
+            }
+        }
+
+        true
+    });
+
+    before - thread.messages.len()
+}
+
+/// Consecutive messages from one sender less than this many minutes apart share a block
+pub const DEFAULT_MESSAGE_GROUP_WINDOW_MINUTES: i64 = 5;
+
+/// A run of consecutive messages from one sender, shown as a single block.
+/// Individual ids are kept so reply/forward still target one message.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct MessageGroup {
+    /// Sender's address, lowercased
+    pub sender: String,
+    /// Gmail ids of the messages in the block, in thread order
+    pub message_ids: Vec<String>,
+}
+
+/// Group consecutive messages from the same sender where each follows the
+/// previous within `window_minutes`. Every message lands in exactly one group.
+pub fn group_consecutive_messages(thread: &FullThread, window_minutes: i64) -> Vec<MessageGroup> {
+    let window_ms = window_minutes.saturating_mul(60_000);
+    let mut groups: Vec<MessageGroup> = Vec::new();
+    let mut last_date: Option<i64> = None;
+
+    for message in &thread.messages {
+        let sender = message.header("From").map(extract_email_address).unwrap_or_default().to_lowercase();
+        let date = message.internal_date.as_deref().and_then(|d| d.parse::<i64>().ok());
+
+        let within_window = matches!((last_date, date), (Some(prev), Some(cur)) if (cur - prev).abs() <= window_ms);
+        match groups.last_mut() {
+            Some(group) if within_window && !sender.is_empty() && group.sender == sender => {
+                group.message_ids.push(message.id.clone());
+            }
+            _ => groups.push(MessageGroup {
+                sender,
+                message_ids: vec![message.id.clone()],
+            }),
+        }
+        last_date = date;
+    }
+
+    groups
+}
+
+/// Merge `siblings` that share a References chain with `primary` into one
+/// date-sorted conversation. Siblings with no shared Message-ID are ignored.
+pub fn stitch_threads(primary: FullThread, siblings: Vec<FullThread>) -> StitchedConversation {
+    let mut chain: std::collections::HashSet<String> = conversation_ids(&primary).into_iter().collect();
+    let mut thread_ids = vec![primary.id.clone()];
+    let mut messages = primary.messages;
+
+    // Linking can be transitive (A-B share ids, B-C share ids), so keep
+    // sweeping until no more siblings join
+    let mut pending = siblings;
+    loop {
+        let (linked, rest): (Vec<FullThread>, Vec<FullThread>) = pending
+            .into_iter()
+            .partition(|t| conversation_ids(t).iter().any(|id| chain.contains(id)));
+        pending = rest;
+        if linked.is_empty() {
+            break;
+        }
+        for thread in linked {
+            if thread_ids.contains(&thread.id) {
+                continue;
+            }
+            chain.extend(conversation_ids(&thread));
+            thread_ids.push(thread.id);
+            messages.extend(thread.messages);
+        }
+    }
+
+    // The same message can surface in more than one split
+    let mut seen = std::collections::HashSet::new();
+    messages.retain(|m| {
+        let key = m
+            .header("Message-ID")
+            .map(|id| id.trim().to_string())
+            .unwrap_or_else(|| m.id.clone());
+        seen.insert(key)
+    });
+
+    messages.sort_by_key(|m| {
+        m.internal_date
+            .as_deref()
+            .and_then(|d| d.parse::<i64>().ok())
+            .unwrap_or(0)
+    });
+
+    let split_boundaries = (1..messages.len())
+        .filter(|&i| messages[i].thread_id != messages[i - 1].thread_id)
+        .collect();
+
+    StitchedConversation {
+        thread_ids,
+        messages,
+        split_boundaries,
+    }
+}
+
+// ============ Awaiting reply ============
+
+/// A file a contact sent, for the per-contact files panel
+#[derive(Debug, Clone, Serialize)]
+pub struct SenderAttachment {
+    pub filename: String,
+    pub mime: String,
+    pub size: i32,
+    pub thread_id: String,
+    pub message_id: String,
+    pub attachment_id: String,
+    /// Unix milliseconds of the message carrying the file
+    pub date: i64,
+}
+
+/// The files `sender` attached to messages in `threads` (from a
+/// `has:attachment` search), newest first. Other participants' files and
+/// inline parts, which belong to a message body, are skipped.
+pub fn sender_attachments(threads: &[FullThread], sender: &str, limit: usize) -> Vec<SenderAttachment> {
+    let mut files: Vec<SenderAttachment> = threads
+        .iter()
+        .flat_map(|thread| &thread.messages)
+        .filter(|message| {
+            message
+                .header("From")
+                .is_some_and(|from| extract_email_address(from).eq_ignore_ascii_case(sender))
+        })
+        .flat_map(|message| {
+            let date = message
+                .internal_date
+                .as_deref()
+                .and_then(|d| d.parse::<i64>().ok())
+                .unwrap_or(0);
+            let infos = message
+                .payload
+                .as_ref()
+                .map(|p| extract_attachments_from_parts(&p.parts))
+                .unwrap_or_default();
+            infos.into_iter().filter(|info| !info.is_inline).map(move |info| SenderAttachment {
+                filename: info.filename,
+                mime: info.mime_type,
+                size: info.size,
+                thread_id: message.thread_id.clone(),
+                message_id: message.id.clone(),
+                attachment_id: info.attachment_id,
+                date,
+            })
+        })
+        .collect();
+
+    files.sort_by(|a, b| b.date.cmp(&a.date));
+    files.truncate(limit);
+    files
+}
+
+/// A thread where the user sent the latest message and nobody has answered
+#[derive(Debug, Clone, Serialize)]
+pub struct AwaitingReply {
+    pub thread_id: String,
+    pub subject: String,
+    /// Recipients of the user's unanswered message
+    pub to: String,
+    /// Unix milliseconds of the unanswered message
+    pub sent_at: i64,
+}
+
+/// If the user is the last sender in `thread` and that message is more than
+/// `days` old, describe the unanswered message. Drafts are ignored.
+pub fn awaiting_reply(thread: &FullThread, account_email: &str, now: DateTime<Utc>, days: i64) -> Option<AwaitingReply> {
+    let latest = thread
+        .messages
+        .iter()
+        .filter(|m| {
+            !m.label_ids
+                .as_ref()
+                .is_some_and(|labels| labels.iter().any(|l| l == "DRAFT"))
+        })
+        .filter_map(|m| Some((m.internal_date.as_deref()?.parse::<i64>().ok()?, m)))
+        .max_by_key(|(date, _)| *date);
+    let (sent_at, message) = latest?;
+
+    let from = extract_email_address(message.header("From")?);
+    if !from.eq_ignore_ascii_case(account_email) {
+        return None;
+    }
+
+    let sent = DateTime::from_timestamp_millis(sent_at)?;
+    if now.signed_duration_since(sent) <= Duration::days(days) {
+        return None;
+    }
+
+    Some(AwaitingReply {
+        thread_id: thread.id.clone(),
+        subject: message.header("Subject").unwrap_or("(No subject)").to_string(),
+        to: message.header("To").unwrap_or_default().to_string(),
+        sent_at,
+    })
+}
+
+/// Whether someone other than the user has written in `thread` after `since`
+/// (Unix milliseconds). Drafts are ignored.
+pub fn reply_received_since(thread: &FullThread, account_email: &str, since: i64) -> bool {
+    thread.messages.iter().any(|m| {
+        let is_draft = m
+            .label_ids
+            .as_ref()
+            .is_some_and(|labels| labels.iter().any(|l| l == "DRAFT"));
+        let after = m
+            .internal_date
+            .as_deref()
+            .and_then(|d| d.parse::<i64>().ok())
+            .is_some_and(|date| date > since);
+        let from_other = m
+            .header("From")
+            .is_some_and(|from| !extract_email_address(from).eq_ignore_ascii_case(account_email));
+        !is_draft && after && from_other
+    })
+}
+
+// ============ Quoting style ============
+
+/// Where a reply put its new text relative to the quoted message
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QuoteStyle {
+    /// New text above the quote
+    TopPosted,
+    /// New text below or between quoted lines
+    Inline,
+    /// Nothing quoted
+    Unquoted,
+}
+
+fn is_quoted_line(line: &str) -> bool {
+    line.trim_start().starts_with('>')
+}
+
+/// "On Mon, Jan 1, 2024 at 10:00 AM Ann <ann@example.com> wrote:"
+fn is_attribution_line(line: &str) -> bool {
+    let line = line.trim();
+    line.starts_with("On ") && line.ends_with("wrote:")
+}
+
+/// Outlook-style separators: everything after them is the quoted message
+fn is_original_message_separator(line: &str) -> bool {
+    let line = line.trim();
+    line.eq_ignore_ascii_case("-----Original Message-----") || line.starts_with("---------- Forwarded message")
+}
+
+/// Classify a plain-text body as top-posted, inline/bottom-posted or unquoted
+pub fn classify_quote_style(text: &str) -> QuoteStyle {
+    let mut seen_quote = false;
+    let mut new_before = false;
+    for line in text.lines() {
+        if is_original_message_separator(line) {
+            return if new_before { QuoteStyle::TopPosted } else { QuoteStyle::Unquoted };
+        }
+        if is_quoted_line(line) || is_attribution_line(line) {
+            seen_quote = true;
+        } else if !line.trim().is_empty() {
+            if seen_quote {
+                return QuoteStyle::Inline;
             }
+            new_before = true;
+        }
+    }
+    if seen_quote {
+        QuoteStyle::TopPosted
+    } else {
+        QuoteStyle::Unquoted
+    }
+}
+
+/// Just the text the sender wrote: quoted lines, attributions and anything
+/// after an "Original Message" separator are dropped
+pub fn new_content(text: &str) -> String {
+    let mut kept = Vec::new();
+    for line in text.lines() {
+        if is_original_message_separator(line) {
+            break;
         }
+        if !is_quoted_line(line) && !is_attribution_line(line) {
+            kept.push(line);
+        }
+    }
+    kept.join("\n").trim().to_string()
+}
+
+// ============ Reply preflight ============
+
+/// Replies to threads idle for longer than this many days are flagged as stale
+pub const DEFAULT_STALE_THREAD_DAYS: i64 = 60;
+
+/// Reply-all to more people than this is flagged for confirmation
+pub const DEFAULT_LARGE_RECIPIENT_THRESHOLD: usize = 15;
+
+/// Non-blocking warnings computed before a reply is sent
+#[derive(Debug, Clone, Serialize)]
+pub struct ReplyPreflight {
+    /// Unix milliseconds of the thread's latest message
+    pub latest_message_date: Option<i64>,
+    /// True when the latest message is older than the stale threshold
+    pub stale_thread: bool,
+    /// Who the reply could go to (sender or mailing list)
+    pub reply_targets: ReplyTargets,
+    /// Who a reply-all would go to
+    pub reply_all: ReplyAllRecipients,
+}
+
+/// Recipients of a reply-all. The warning is advisory; sending is never blocked.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ReplyAllRecipients {
+    /// Reply-To, falling back to From
+    pub to: Option<String>,
+    /// Everyone else on To/Cc, minus the sender and the user
+    pub cc: Vec<String>,
+    /// Distinct addresses the reply would go to
+    pub recipient_count: usize,
+    /// True when `recipient_count` is above the threshold
+    pub large_recipient_warning: bool,
+}
+
+/// Candidate reply recipients for a message
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ReplyTargets {
+    /// Reply-To, falling back to From
+    pub sender: Option<String>,
+    /// Posting address of the mailing list the message came through
+    pub list: Option<String>,
+    /// The list when there is one, otherwise the sender
+    pub recommended: Option<String>,
+}
+
+/// Posting address from a `List-Post` value such as `<mailto:list@x.com?subject=hi>`.
+/// Lists that disallow posting send `List-Post: NO`.
+fn list_post_address(value: &str) -> Option<String> {
+    let start = value.find("mailto:")? + "mailto:".len();
+    let rest = &value[start..];
+    let end = rest.find(['>', '?', ',']).unwrap_or(rest.len());
+    let address = urlencoding::decode(rest[..end].trim()).ok()?.to_string();
+    address.contains('@').then_some(address)
+}
+
+/// Google Groups and similar lists omit List-Post; derive the address from
+/// `List-Id: <name.example.com>` when that address also appears in To/Cc
+fn list_id_address(message: &FullMessage) -> Option<String> {
+    let list_id = message.header("List-Id")?;
+    let id = list_id.rsplit('<').next()?.trim_end_matches('>').trim();
+    let (name, domain) = id.split_once('.')?;
+    let candidate = format!("{}@{}", name, domain).to_lowercase();
+
+    let recipients = [message.header("To"), message.header("Cc")];
+    recipients
+        .iter()
+        .flatten()
+        .flat_map(|value| value.split(','))
+        .map(|addr| extract_email_address(addr).to_lowercase())
+        .find(|addr| *addr == candidate)
+}
+
+/// Work out whether a reply should go to the sender or the mailing list
+pub fn reply_targets(message: &FullMessage) -> ReplyTargets {
+    let sender = message
+        .header("Reply-To")
+        .or_else(|| message.header("From"))
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty());
+
+    // A List-Post header decides on its own, so `List-Post: NO` means no list
+    let list = match message.header("List-Post") {
+        Some(value) => list_post_address(value),
+        None => list_id_address(message),
+    };
+
+    let recommended = list.clone().or_else(|| sender.clone());
+    ReplyTargets {
+        sender,
+        list,
+        recommended,
     }
+}
+
+/// Work out a reply-all's recipients: the sender (Reply-To first) in To and
+/// everyone else from To/Cc in Cc, leaving out the user and duplicates
+pub fn reply_all_recipients(message: &FullMessage, user_email: &str, threshold: usize) -> ReplyAllRecipients {
+    let to = message
+        .header("Reply-To")
+        .or_else(|| message.header("From"))
+        .map(extract_email_address)
+        .filter(|s| !s.is_empty());
+
+    let mut seen: std::collections::HashSet<String> = [Some(user_email.to_lowercase()), to.as_ref().map(|t| t.to_lowercase())]
+        .into_iter()
+        .flatten()
+        .collect();
+    if let Some(from) = message.header("From") {
+        seen.insert(extract_email_address(from).to_lowercase());
+    }
+
+    let cc: Vec<String> = [message.header("To"), message.header("Cc")]
+        .into_iter()
+        .flatten()
+        .flat_map(split_address_list)
+        .map(|addr| extract_email_address(&addr))
+        .filter(|addr| addr.contains('@') && seen.insert(addr.to_lowercase()))
+        .collect();
+
+    let recipient_count = cc.len() + usize::from(to.is_some());
+    ReplyAllRecipients {
+        to,
+        cc,
+        recipient_count,
+        large_recipient_warning: recipient_count > threshold,
+    }
+}
+
+/// Unix milliseconds of the most recent message in a thread
+pub fn latest_message_date(thread: &FullThread) -> Option<i64> {
+    thread
+        .messages
+        .iter()
+        .filter_map(|m| m.internal_date.as_deref()?.parse::<i64>().ok())
+        .max()
+}
+
+/// Whether the thread's latest message is older than `threshold_days`
+pub fn thread_age_warning(thread: &FullThread, now: DateTime<Utc>, threshold_days: i64) -> bool {
+    latest_message_date(thread)
+        .and_then(DateTime::from_timestamp_millis)
+        .is_some_and(|latest| now.signed_duration_since(latest) > Duration::days(threshold_days))
+}
+
+/// Gather reply warnings for a thread. `message_id` (Gmail id) picks the
+/// message being answered; defaults to the latest one.
+pub fn reply_preflight(
+    thread: &FullThread,
+    message_id: Option<&str>,
+    user_email: &str,
+    now: DateTime<Utc>,
+    stale_after_days: i64,
+    large_recipient_threshold: usize,
+) -> ReplyPreflight {
+    let target = message_id
+        .and_then(|id| thread.messages.iter().find(|m| m.id == id))
+        .or_else(|| thread.messages.last());
+
+    ReplyPreflight {
+        latest_message_date: latest_message_date(thread),
+        stale_thread: thread_age_warning(thread, now, stale_after_days),
+        reply_targets: target.map(reply_targets).unwrap_or(ReplyTargets {
+            sender: None,
+            list: None,
+            recommended: None,
+        }),
+        reply_all: target
+            .map(|m| reply_all_recipients(m, user_email, large_recipient_threshold))
+            .unwrap_or(ReplyAllRecipients {
+                to: None,
+                cc: Vec::new(),
+                recipient_count: 0,
+                large_recipient_warning: false,
+            }),
+    }
+}
+
+// ============ Attachment validation ============
+
+/// Result of pre-validating an attachment before send
+#[derive(Debug, Clone, Serialize)]
+pub struct AttachmentValidation {
+    pub size: usize,
+    pub declared_mime_type: String,
+    /// MIME type detected from the file's magic bytes, if recognized
+    pub detected_mime_type: Option<String>,
+    /// True when the detected type contradicts the declared one
+    pub mime_mismatch: bool,
+}
+
+/// Decode attachment data as sent by the frontend.
+/// Accepts standard or URL-safe alphabets, missing padding, embedded
+/// whitespace and a leading `data:<mime>;base64,` prefix.
+fn decode_attachment_data(data: &str) -> Result<Vec<u8>, String> {
+    let data = match data.split_once(";base64,") {
+        Some((prefix, rest)) if prefix.starts_with("data:") => rest,
+        _ => data,
+    };
+    decode_gmail_b64(data)
+}
+
+/// Detect a file's MIME type from its leading magic bytes
+fn sniff_mime_type(bytes: &[u8]) -> Option<&'static str> {
+    if bytes.starts_with(b"%PDF-") {
+        Some("application/pdf")
+    } else if bytes.starts_with(&[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A]) {
+        Some("image/png")
+    } else if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        Some("image/jpeg")
+    } else if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+        Some("image/gif")
+    } else if bytes.len() >= 12 && &bytes[..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+        Some("image/webp")
+    } else if bytes.starts_with(b"PK\x03\x04") {
+        Some("application/zip")
+    } else if bytes.starts_with(&[0x1F, 0x8B]) {
+        Some("application/gzip")
+    } else if bytes.starts_with(b"BEGIN:VCALENDAR") {
+        Some("text/calendar")
+    } else {
+        None
+    }
+}
+
+/// Whether a declared MIME type is consistent with the sniffed one
+fn mime_types_compatible(declared: &str, detected: &str) -> bool {
+    let declared = declared.trim().to_ascii_lowercase();
+    if declared == detected || declared.is_empty() || declared == "application/octet-stream" {
+        return true;
+    }
+    match detected {
+        "image/jpeg" => declared == "image/jpg" || declared == "image/pjpeg",
+        // Office documents, EPUB, JAR etc. are ZIP containers
+        "application/zip" => {
+            declared == "application/x-zip-compressed"
+                || declared.starts_with("application/vnd.")
+                || declared == "application/epub+zip"
+                || declared == "application/java-archive"
+        }
+        "application/gzip" => declared == "application/x-gzip",
+        _ => false,
+    }
+}
+
+/// Decode an attachment the same way `build_mime_message` will and compare
+/// its declared MIME type against the one detected from its contents
+pub fn validate_attachment(attachment: &SendAttachment) -> Result<AttachmentValidation, String> {
+    let bytes = decode_attachment_data(&attachment.data)?;
+    let detected = sniff_mime_type(&bytes);
+
+    Ok(AttachmentValidation {
+        size: bytes.len(),
+        declared_mime_type: attachment.mime_type.clone(),
+        detected_mime_type: detected.map(String::from),
+        mime_mismatch: detected
+            .map(|d| !mime_types_compatible(&attachment.mime_type, d))
+            .unwrap_or(false),
+    })
+}
+
+// ============ Email Reactions ============
+
+/// Reaction data parsed from email
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ParsedReaction {
+    pub emoji: String,
+    pub from_addr: String,
+    pub in_reply_to: String,
+    pub message_id: String,
+}
+
+/// Parse reaction from a message if it contains a valid reaction part
+pub fn parse_reaction_from_message(message: &FullMessage) -> Option<ParsedReaction> {
+    let payload = message.payload.as_ref()?;
+
+    // Get headers
+    let headers = payload.headers.as_ref()?;
+    let from = headers.iter()
+        .find(|h| h.name.eq_ignore_ascii_case("From"))
+        .map(|h| extract_email_address(&h.value))?;
+    let in_reply_to = headers.iter()
+        .find(|h| h.name.eq_ignore_ascii_case("In-Reply-To"))
+        .map(|h| h.value.trim().to_string())?;
+
+    // Look for reaction JSON part
+    let emoji = find_reaction_in_payload(payload)?;
+
+    Some(ParsedReaction {
+        emoji,
+        from_addr: from,
+        in_reply_to,
+        message_id: message.id.clone(),
+    })
+}
+
+/// Recursively search for reaction part in payload
+fn find_reaction_in_payload(payload: &MessagePayload) -> Option<String> {
+    // Check if top-level is reaction
+    if let Some(mime_type) = &payload.mime_type {
+        if mime_type == "text/vnd.google.email-reaction+json" {
+            if let Some(body) = &payload.body {
+                if let Some(data) = &body.data {
+                    return parse_reaction_json(data);
+                }
+            }
+        }
+    }
+
+    // Check parts
+    if let Some(parts) = &payload.parts {
+        for part in parts {
+            if part.mime_type == "text/vnd.google.email-reaction+json" {
+                // Skip if it's an attachment
+                if let Some(headers) = &part.headers {
+                    let is_attachment = headers.iter().any(|h| {
+                        h.name.eq_ignore_ascii_case("Content-Disposition")
+                            && h.value.to_lowercase().contains("attachment")
+                    });
+                    if is_attachment {
+                        continue;
+                    }
+                }
+                if let Some(body) = &part.body {
+                    if let Some(data) = &body.data {
+                        return parse_reaction_json(data);
+                    }
+                }
+            }
+            // Recurse into nested parts
+            if let Some(nested) = &part.parts {
+                for nested_part in nested {
+                    if nested_part.mime_type == "text/vnd.google.email-reaction+json" {
+                        if let Some(body) = &nested_part.body {
+                            if let Some(data) = &body.data {
+                                return parse_reaction_json(data);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    None
+}
+
+/// Parse the reaction JSON and validate it
+fn parse_reaction_json(base64_data: &str) -> Option<String> {
+    let decoded = decode_base64_body(base64_data)?;
+
+    #[derive(Deserialize)]
+    struct ReactionJson {
+        version: i32,
+        emoji: String,
+    }
+
+    let reaction: ReactionJson = serde_json::from_str(&decoded).ok()?;
+
+    // Version must be 1
+    if reaction.version != 1 {
+        return None;
+    }
+
+    // Validate emoji (basic check - must be non-empty)
+    if reaction.emoji.is_empty() {
+        return None;
+    }
+
+    Some(reaction.emoji)
+}
+
+impl GmailClient {
+    /// Send an emoji reaction to a message
+    pub async fn send_reaction(
+        &self,
+        thread_id: &str,
+        message_id: &str,
+        emoji: &str,
+        from_email: &str,
+        to_email: &str,
+    ) -> Result<(), String> {
+        let url = format!("{}/users/me/messages/send", GMAIL_API_BASE);
+
+        let message = self.build_reaction_message(emoji, from_email, to_email, message_id)?;
+
+        use base64::Engine;
+        let encoded = base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(message.as_bytes());
+
+        let request_body = serde_json::json!({
+            "raw": encoded,
+            "threadId": thread_id
+        });
+
+        let resp = send_with_retry(
+            "gmail.messages.send",
+            self.client
+                .post(&url)
+                .bearer_auth(&self.access_token)
+                .json(&request_body),
+        )
+        .await
+        .map_err(|e| format!("Request failed: {}", e))?;
+
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let body = resp.text().await.unwrap_or_default();
+            return Err(format!("API error {}: {}", status, body));
+        }
+
+        Ok(())
+    }
+
+    /// Build a reaction MIME message per Google's spec
+    fn build_reaction_message(
+        &self,
+        emoji: &str,
+        from_email: &str,
+        to_email: &str,
+        reply_to_message_id: &str,
+    ) -> Result<String, String> {
+        let boundary = format!("----=_React_{}", uuid::Uuid::new_v4().to_string().replace("-", ""));
+
+        // Build the reaction JSON
+        let reaction_json = serde_json::json!({
+            "version": 1,
+            "emoji": emoji
+        });
+        let reaction_json_str = serde_json::to_string(&reaction_json)
+            .map_err(|e| format!("Failed to serialize reaction: {}", e))?;
+
+        // Fallback text for clients that don't support reactions
+        let plain_text = format!("Reacted with {}", emoji);
+        let html_text = format!(
+            "<html><body><p>Reacted with <span style=\"font-size: 24px\">{}</span></p></body></html>",
+            emoji
+        );
+
+        let mut message = String::new();
+
+        // Headers
+        message.push_str(&format!("From: {}\r\n", from_email));
+        message.push_str(&format!("To: {}\r\n", to_email));
+        message.push_str(&format!(
+            "Subject: {}\r\n",
+            encode_header_value(&format!("Re: {}", emoji))
+        ));
+        message.push_str("MIME-Version: 1.0\r\n");
+        message.push_str(&format!("In-Reply-To: {}\r\n", reply_to_message_id));
+        message.push_str(&format!("References: {}\r\n", reply_to_message_id));
+        message.push_str(&format!(
+            "Content-Type: multipart/alternative; boundary=\"{}\"\r\n\r\n",
+            boundary
+        ));
+
+        // Plain text part (first, for clients that show first part)
+        message.push_str(&format!("--{}\r\n", boundary));
+        message.push_str("Content-Type: text/plain; charset=utf-8\r\n\r\n");
+        message.push_str(&plain_text);
+        message.push_str("\r\n");
+
+        // Reaction JSON part (between plain and HTML per Google recommendation)
+        message.push_str(&format!("--{}\r\n", boundary));
+        message.push_str("Content-Type: text/vnd.google.email-reaction+json; charset=utf-8\r\n\r\n");
+        message.push_str(&reaction_json_str);
+        message.push_str("\r\n");
+
+        // HTML part (last, for clients that show last part)
+        message.push_str(&format!("--{}\r\n", boundary));
+        message.push_str("Content-Type: text/html; charset=utf-8\r\n\r\n");
+        message.push_str(&html_text);
+        message.push_str("\r\n");
+
+        // Close boundary
+        message.push_str(&format!("--{}--\r\n", boundary));
+
+        Ok(message)
+    }
+}
+
+/// Check if a message should allow reactions based on Google's recommended limits
+pub fn can_react_to_message(
+    to_addrs: &[String],
+    cc_addrs: &[String],
+    user_email: &str,
+    is_mailing_list: bool,
+) -> Result<(), String> {
+    // No reactions on mailing list messages
+    if is_mailing_list {
+        return Err("Cannot react to mailing list messages".to_string());
+    }
+
+    // Max 20 recipients in To + CC
+    let total_recipients = to_addrs.len() + cc_addrs.len();
+    if total_recipients > 20 {
+        return Err("Too many recipients (max 20)".to_string());
+    }
+
+    // User must be in To or CC
+    let user_lower = user_email.to_lowercase();
+    let in_to = to_addrs.iter().any(|a| a.to_lowercase() == user_lower);
+    let in_cc = cc_addrs.iter().any(|a| a.to_lowercase() == user_lower);
+    if !in_to && !in_cc {
+        return Err("You must be a recipient to react".to_string());
+    }
+
+    Ok(())
+}
+
+/// Check if message has mailing list headers
+pub fn is_mailing_list_message(headers: &[Header]) -> bool {
+    headers.iter().any(|h| {
+        h.name.eq_ignore_ascii_case("List-Unsubscribe")
+            || h.name.eq_ignore_ascii_case("List-Id")
+            || h.name.eq_ignore_ascii_case("Precedence") && h.value.to_lowercase() == "list"
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strip_html_converts_breaks_and_paragraphs_to_newlines() {
+        assert_eq!(strip_html_tags("line1<br>line2"), "line1\nline2");
+        assert_eq!(strip_html_tags("line1<br/>line2"), "line1\nline2");
+        assert_eq!(strip_html_tags("line1<br />line2"), "line1\nline2");
+        assert_eq!(strip_html_tags("line1<BR>line2"), "line1\nline2");
+        assert_eq!(strip_html_tags("<p>one</p><p>two</p>"), "\none\n\ntwo\n");
+    }
+
+    #[test]
+    fn strip_html_removes_other_tags() {
+        assert_eq!(strip_html_tags("<b>bold</b> and <i>italic</i>"), "bold and italic");
+        assert_eq!(strip_html_tags("<a href=\"http://x\">link</a>"), "link");
+    }
+
+    #[test]
+    fn strip_html_decodes_entities_without_double_decoding() {
+        assert_eq!(strip_html_tags("a &amp; b"), "a & b");
+        assert_eq!(strip_html_tags("&lt;tag&gt;"), "<tag>");
+        // "&amp;lt;" is the escaped text "&lt;" - it must NOT become "<"
+        assert_eq!(strip_html_tags("&amp;lt;"), "&lt;");
+        assert_eq!(strip_html_tags("&quot;hi&quot; &#39;there&#39;&nbsp;!"), "\"hi\" 'there' !");
+    }
+
+    #[test]
+    fn ensure_angle_brackets_normalizes() {
+        assert_eq!(ensure_angle_brackets("abc@example.com"), "<abc@example.com>");
+        assert_eq!(ensure_angle_brackets("<abc@example.com>"), "<abc@example.com>");
+        assert_eq!(ensure_angle_brackets("  <abc@example.com>  "), "<abc@example.com>");
+    }
+
+    #[test]
+    fn encode_header_value_leaves_ascii_unchanged() {
+        assert_eq!(encode_header_value("Hello world"), "Hello world");
+    }
+
+    #[test]
+    fn encode_header_value_encodes_non_ascii() {
+        let encoded = encode_header_value("Café ☕");
+        assert!(encoded.starts_with("=?UTF-8?B?"));
+        assert!(encoded.ends_with("?="));
+        use base64::Engine;
+        let b64 = &encoded["=?UTF-8?B?".len()..encoded.len() - 2];
+        let decoded = base64::engine::general_purpose::STANDARD.decode(b64).unwrap();
+        assert_eq!(String::from_utf8(decoded).unwrap(), "Café ☕");
+    }
+
+    #[test]
+    fn encode_address_header_encodes_only_display_names() {
+        assert_eq!(
+            encode_address_header("plain@example.com"),
+            "plain@example.com"
+        );
+        assert_eq!(
+            encode_address_header("John Doe <jd@example.com>"),
+            "John Doe <jd@example.com>"
+        );
+        let encoded = encode_address_header("José García <jg@example.com>");
+        assert!(encoded.starts_with("=?UTF-8?B?"));
+        assert!(encoded.ends_with(" <jg@example.com>"));
+    }
+
+    #[test]
+    fn encode_address_header_handles_lists_and_quoted_commas() {
+        assert_eq!(
+            encode_address_header("a@example.com, b@example.com"),
+            "a@example.com, b@example.com"
+        );
+        // Comma inside a quoted display name must not split the address
+        assert_eq!(
+            encode_address_header("\"Doe, John\" <jd@example.com>, x@example.com"),
+            "\"Doe, John\" <jd@example.com>, x@example.com"
+        );
+        let mixed = encode_address_header("Müller <m@example.com>, plain@example.com");
+        assert!(mixed.contains("=?UTF-8?B?"));
+        assert!(mixed.ends_with(", plain@example.com"));
+    }
+
+    fn attachment(mime_type: &str, data: &str) -> SendAttachment {
+        SendAttachment {
+            filename: "file".to_string(),
+            mime_type: mime_type.to_string(),
+            data: data.to_string(),
+            content_id: None,
+        }
+    }
+
+    #[test]
+    fn validate_attachment_accepts_valid_base64() {
+        use base64::Engine;
+        let png = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A, 0, 0, 0, 13];
+        let standard = base64::engine::general_purpose::STANDARD.encode(png);
+        let url_safe = base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(png);
+
+        for data in [standard.clone(), url_safe, format!("data:image/png;base64,{}", standard)] {
+            let result = validate_attachment(&attachment("image/png", &data)).unwrap();
+            assert_eq!(result.size, png.len());
+            assert_eq!(result.detected_mime_type.as_deref(), Some("image/png"));
+            assert!(!result.mime_mismatch);
+        }
+
+        assert_eq!(encode_std_b64_wrapped(&decode_attachment_data("aGVsbG8").unwrap(), 0), "aGVsbG8=");
+        assert_eq!(encode_std_b64_wrapped(&decode_attachment_data("aGVs\r\nbG8=").unwrap(), 0), "aGVsbG8=");
+    }
+
+    #[test]
+    fn validate_attachment_rejects_malformed_base64() {
+        assert!(validate_attachment(&attachment("application/pdf", "not base64!!")).is_err());
+        assert!(validate_attachment(&attachment("application/pdf", "abcde")).is_err());
+    }
+
+    #[test]
+    fn validate_attachment_flags_mime_mismatch() {
+        use base64::Engine;
+        let pdf = base64::engine::general_purpose::STANDARD.encode(b"%PDF-1.7\n...");
+
+        let mismatch = validate_attachment(&attachment("image/png", &pdf)).unwrap();
+        assert_eq!(mismatch.detected_mime_type.as_deref(), Some("application/pdf"));
+        assert!(mismatch.mime_mismatch);
+
+        let matching = validate_attachment(&attachment("application/pdf", &pdf)).unwrap();
+        assert!(!matching.mime_mismatch);
+
+        // ZIP containers such as .docx are not a mismatch
+        let docx = base64::engine::general_purpose::STANDARD.encode(b"PK\x03\x04rest");
+        let office = validate_attachment(&attachment(
+            "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+            &docx,
+        ))
+        .unwrap();
+        assert!(!office.mime_mismatch);
+
+        // Unknown content never counts as a mismatch
+        let text = base64::engine::general_purpose::STANDARD.encode(b"plain text");
+        assert!(!validate_attachment(&attachment("text/plain", &text)).unwrap().mime_mismatch);
+    }
+
+    #[test]
+    fn interaction_query_includes_from_and_to() {
+        assert_eq!(
+            interaction_query("jane@example.com").unwrap(),
+            "from:jane@example.com OR to:jane@example.com"
+        );
+        let query = interaction_query("Jane Doe <jane@example.com>").unwrap();
+        assert!(query.contains("from:jane@example.com"));
+        assert!(query.contains("to:jane@example.com"));
+        assert!(interaction_query("not an email").is_err());
+    }
+
+    fn invite(uid: &str, sequence: i32, start: &str) -> String {
+        format!(
+            "BEGIN:VCALENDAR\r\nMETHOD:REQUEST\r\nBEGIN:VEVENT\r\nUID:{}\r\nSEQUENCE:{}\r\nSUMMARY:Sync\r\nDTSTART:{}\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n",
+            uid, sequence, start
+        )
+    }
+
+    #[test]
+    fn inline_logo_is_not_counted_as_attachment() {
+        let with_disposition = |mime_type: &str, filename: &str, disposition: &str, content_id: Option<&str>| {
+            let mut part = mime_part(mime_type, Some(filename), Some(&format!("att-{}", filename)), None);
+            let mut headers = vec![Header {
+                name: "Content-Disposition".to_string(),
+                value: format!("{}; filename=\"{}\"", disposition, filename),
+            }];
+            if let Some(cid) = content_id {
+                headers.push(Header {
+                    name: "Content-ID".to_string(),
+                    value: format!("<{}>", cid),
+                });
+            }
+            part.headers = Some(headers);
+            part
+        };
+        let parts = Some(vec![
+            mime_part("text/html", None, None, None),
+            with_disposition("image/png", "logo.png", "inline", Some("logo@corp")),
+            with_disposition("application/pdf", "invoice.pdf", "attachment", None),
+            // Disposition wins over a Content-ID
+            with_disposition("image/jpeg", "photo.jpg", "ATTACHMENT", Some("photo@corp")),
+        ]);
+
+        let infos = extract_attachments_from_parts(&parts);
+        let inline: Vec<(&str, bool)> = infos.iter().map(|a| (a.filename.as_str(), a.is_inline)).collect();
+        assert_eq!(inline, vec![("logo.png", true), ("invoice.pdf", false), ("photo.jpg", false)]);
+        // The logo is still listed so it can be fetched for cid: rendering
+        assert_eq!(infos[0].content_id.as_deref(), Some("logo@corp"));
+    }
+
+    #[test]
+    fn filename_less_calendar_attachment_is_an_invite() {
+        let parts = Some(vec![
+            mime_part("text/html", Some(""), None, None),
+            mime_part("TEXT/CALENDAR", None, Some("att-ics"), None),
+        ]);
+
+        let infos = extract_attachments_from_parts(&parts);
+        assert_eq!(infos.len(), 1);
+        assert_eq!(infos[0].filename, "invite.ics");
+
+        let info = infos.into_iter().next().unwrap();
+        let attachment = Attachment {
+            message_id: "m1".to_string(),
+            attachment_id: info.attachment_id,
+            filename: "attachment.bin".to_string(),
+            mime_type: info.mime_type,
+            size: info.size,
+            inline_data: None,
+            content_id: None,
+            is_inline: false,
+        };
+        // The MIME type alone is enough, whatever the file is called
+        assert!(attachment.is_calendar());
+    }
+
+    #[test]
+    fn inline_text_calendar_part_yields_event() {
+        use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+
+        let calendar_part = MessagePart {
+            part_id: Some("1".to_string()),
+            mime_type: "text/calendar".to_string(),
+            filename: None,
+            headers: None,
+            body: Some(MessageBody {
+                size: Some(200),
+                data: Some(URL_SAFE_NO_PAD.encode(invite("inline-uid", 0, "20240115T100000Z"))),
+                attachment_id: None,
+            }),
+            parts: None,
+        };
+        let payload = MessagePayload {
+            headers: None,
+            body: None,
+            parts: Some(vec![MessagePart {
+                part_id: Some("0".to_string()),
+                mime_type: "multipart/alternative".to_string(),
+                filename: Some(String::new()),
+                headers: None,
+                body: None,
+                parts: Some(vec![calendar_part]),
+            }]),
+            mime_type: Some("multipart/mixed".to_string()),
+        };
+
+        // No attachment id, so it isn't listed as an attachment
+        assert!(extract_attachments_from_parts(&payload.parts).is_empty());
+
+        let events = inline_calendar_events(&payload);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].title, "Sync");
+    }
+
+    #[test]
+    fn parse_ics_reads_sequence() {
+        let event = parse_ics_content(&invite("abc", 2, "20240115T100000Z")).unwrap();
+        assert_eq!(event.sequence, 2);
+        assert!(event.is_update);
+
+        let original = parse_ics_content(&invite("abc", 0, "20240115T100000Z")).unwrap();
+        assert_eq!(original.sequence, 0);
+        assert!(!original.is_update);
+    }
+
+    #[test]
+    fn folded_description_is_unfolded() {
+        let ics = "BEGIN:VCALENDAR\r\nMETHOD:REQUEST\r\nBEGIN:VEVENT\r\nUID:abc\r\nSUMMARY:Sync\r\nDTSTART:20240115T100000Z\r\n\
+                   DESCRIPTION:Join with Google Meet: https://meet.google.com/abc-defg-hij\r\n \
+                   ?authuser=0 and bring the quarterly\r\n\t numbers\r\n\
+                   END:VEVENT\r\nEND:VCALENDAR\r\n";
+        let event = parse_ics_content(ics).unwrap();
+        assert_eq!(
+            event.description.as_deref(),
+            Some("Join with Google Meet: https://meet.google.com/abc-defg-hij?authuser=0 and bring the quarterly numbers")
+        );
+    }
+
+    #[test]
+    fn highest_sequence_invite_wins() {
+        let original = parse_ics_content(&invite("abc", 0, "20240115T100000Z")).unwrap();
+        let updated = parse_ics_content(&invite("abc", 1, "20240116T140000Z")).unwrap();
+        let updated_start = updated.start_time;
+
+        // Order of parts must not matter
+        for events in [
+            vec![original.clone(), updated.clone()],
+            vec![updated.clone(), original.clone()],
+        ] {
+            let picked = pick_latest_invite(events).unwrap();
+            assert_eq!(picked.sequence, 1);
+            assert_eq!(picked.start_time, updated_start);
+            assert!(picked.is_update);
+        }
+
+        assert!(pick_latest_invite(Vec::new()).is_none());
+    }
+
+    #[test]
+    fn parse_ics_marks_cancel_method_as_cancelled() {
+        let ics = invite("abc", 3, "20240115T100000Z").replace("METHOD:REQUEST", "METHOD:CANCEL");
+        let event = parse_ics_content(&ics).unwrap();
+        assert!(event.cancelled);
+        assert_eq!(event.method.as_deref(), Some("CANCEL"));
+
+        let status_only = invite("abc", 0, "20240115T100000Z")
+            .replace("SUMMARY:Sync", "SUMMARY:Sync\r\nSTATUS:CANCELLED");
+        assert!(parse_ics_content(&status_only).unwrap().cancelled);
+
+        assert!(!parse_ics_content(&invite("abc", 0, "20240115T100000Z")).unwrap().cancelled);
+    }
+
+    #[test]
+    fn bulk_preview_reports_count_and_sample_only() {
+        let list: ThreadListResponse = serde_json::from_str(
+            r#"{"threads": [{"id": "t1"}, {"id": "t2"}], "resultSizeEstimate": 240}"#,
+        )
+        .unwrap();
+        assert_eq!(list.result_size_estimate, Some(240));
+
+        let sample: Vec<Thread> = (0..8)
+            .map(|i| Thread {
+                subject: format!("Newsletter {}", i),
+                ..thread_with_labels(&["INBOX"])
+            })
+            .collect();
+
+        let preview = bulk_action_preview("trash", 240, &sample).unwrap();
+        assert_eq!(preview.estimated_count, 240);
+        assert_eq!(preview.sample_subjects.len(), BULK_PREVIEW_SAMPLE);
+        assert_eq!(preview.sample_subjects[0], "Newsletter 0");
+        assert!(preview.requires_confirmation);
+
+        assert!(!bulk_action_preview("archive", 3, &sample[..3]).unwrap().requires_confirmation);
+        assert!(bulk_action_preview("explode", 3, &sample).is_err());
+
+        assert!(check_bulk_confirmation(240, false).is_err());
+        assert!(check_bulk_confirmation(240, true).is_ok());
+        assert!(check_bulk_confirmation(3, false).is_ok());
+    }
+
+    #[test]
+    fn card_with_missing_label_is_flagged_with_a_suggestion() {
+        let label = |id: &str, name: &str| GmailLabel {
+            id: id.to_string(),
+            name: name.to_string(),
+            message_list_visibility: None,
+            label_list_visibility: None,
+            label_type: None,
+        };
+        let labels = vec![label("INBOX", "INBOX"), label("Label_1", "Receipts"), label("Label_2", "Work/Clients")];
+
+        let ok = crate::models::Card::new("acc".to_string(), "Clients".to_string(), "label:work-clients is:unread".to_string(), 0);
+        assert_eq!(card_label_issue(&ok, &labels), None);
+
+        let card = crate::models::Card::new(
+            "acc".to_string(),
+            "Money".to_string(),
+            "label:Reciepts OR label:\"Old Stuff\" -label:inbox".to_string(),
+            1,
+        );
+        let issue = card_label_issue(&card, &labels).unwrap();
+        assert_eq!(
+            issue.missing,
+            vec![
+                MissingLabel { label: "Reciepts".to_string(), suggestion: Some("Receipts".to_string()) },
+                MissingLabel { label: "Old Stuff".to_string(), suggestion: None },
+            ]
+        );
+        assert_eq!(
+            issue.repaired_query.as_deref(),
+            Some("label:receipts OR label:\"Old Stuff\" -label:inbox")
+        );
+    }
+
+    #[test]
+    fn card_becomes_label_and_filter_on_its_query() {
+        let card = crate::models::Card::new(
+            "acc".to_string(),
+            " Receipts ".to_string(),
+            "from:(store.com OR shop.com) subject:receipt".to_string(),
+            0,
+        );
+
+        let (label_name, criteria) = card_filter(&card).unwrap();
+        assert_eq!(label_name, "Receipts");
+
+        let filter = FilterSpec {
+            criteria,
+            action: FilterAction {
+                add_label_ids: vec!["Label_7".to_string()],
+                ..Default::default()
+            },
+        };
+        assert_eq!(
+            serde_json::to_value(&filter).unwrap(),
+            serde_json::json!({
+                "criteria": {"query": "from:(store.com OR shop.com) subject:receipt"},
+                "action": {"addLabelIds": ["Label_7"]}
+            })
+        );
+
+        let calendar = crate::models::Card::new_calendar("acc".to_string(), "Week".to_string(), "calendar:week".to_string(), 1);
+        assert!(card_filter(&calendar).is_err());
+    }
+
+    #[test]
+    fn contact_filter_labels_mail_from_the_address() {
+        let filter = contact_filter("Boss <Boss@Example.com>", "Label_3", false).unwrap();
+        assert_eq!(filter.criteria.from.as_deref(), Some("boss@example.com"));
+        assert_eq!(filter.criteria.query, None);
+        assert_eq!(filter.action.add_label_ids, vec!["Label_3"]);
+        assert_eq!(
+            serde_json::to_value(&filter).unwrap(),
+            serde_json::json!({
+                "criteria": {"from": "boss@example.com"},
+                "action": {"addLabelIds": ["Label_3"]}
+            })
+        );
+
+        let skipped = contact_filter("boss@example.com", "Label_3", true).unwrap();
+        assert_eq!(skipped.action.remove_label_ids, vec!["INBOX"]);
+
+        assert!(contact_filter("not an email", "Label_3", false).is_err());
+        assert!(contact_filter("boss@localhost", "Label_3", false).is_err());
+    }
+
+    #[test]
+    fn inline_fetch_budget_caps_a_batch() {
+        let budget = InlineFetchBudget::new(DEFAULT_INLINE_FETCH_BUDGET);
+
+        // A 20-thread card where every thread has 3 inline images
+        let granted: Vec<usize> = (0..20).map(|_| budget.take(MAX_INLINE_IMAGES_PER_THREAD)).collect();
+        assert_eq!(granted.iter().sum::<usize>(), DEFAULT_INLINE_FETCH_BUDGET);
+        assert_eq!(budget.used(), DEFAULT_INLINE_FETCH_BUDGET);
+        assert_eq!(&granted[..7], &[3, 3, 3, 3, 3, 3, 2]);
+        assert!(granted[7..].iter().all(|&g| g == 0));
+
+        assert_eq!(InlineFetchBudget::unlimited().take(3), 3);
+        assert_eq!(InlineFetchBudget::new(0).take(3), 0);
+    }
+
+    fn thread_with_labels(labels: &[&str]) -> Thread {
+        Thread {
+            gmail_thread_id: "t1".to_string(),
+            account_id: String::new(),
+            subject: "Subject".to_string(),
+            snippet: String::new(),
+            last_message_date: Utc::now(),
+            unread_count: 0,
+            labels: labels.iter().map(|l| l.to_string()).collect(),
+            participants: Vec::new(),
+            has_attachment: false,
+            attachments: Vec::new(),
+            calendar_event: None,
+            category: "Primary".to_string(),
+        }
+    }
+
+    #[test]
+    fn query_required_labels_reads_system_label_terms() {
+        assert_eq!(query_required_labels("in:inbox is:unread from:ann"), vec!["INBOX", "UNREAD"]);
+        assert_eq!(query_required_labels("IN:INBOX"), vec!["INBOX"]);
+        assert!(query_required_labels("in:inbox OR is:starred").is_empty());
+        assert!(query_required_labels("{in:inbox is:starred}").is_empty());
+        assert!(query_required_labels("-in:inbox").is_empty());
+    }
+
+    #[test]
+    fn thread_category_maps_gmail_categories() {
+        assert_eq!(thread_category(&thread_with_labels(&["INBOX", "CATEGORY_SOCIAL"])), "Social");
+        assert_eq!(thread_category(&thread_with_labels(&["CATEGORY_PROMOTIONS"])), "Promotions");
+        assert_eq!(thread_category(&thread_with_labels(&["INBOX", "UNREAD"])), "Primary");
+        assert_eq!(thread_category(&thread_with_labels(&[])), "Primary");
+    }
+
+    fn message_at(id: &str, date: DateTime<Utc>) -> FullMessage {
+        FullMessage {
+            id: id.to_string(),
+            thread_id: "t1".to_string(),
+            label_ids: None,
+            snippet: None,
+            internal_date: Some(date.timestamp_millis().to_string()),
+            payload: None,
+        }
+    }
+
+    fn full_thread(messages: Vec<FullMessage>) -> FullThread {
+        FullThread {
+            id: "t1".to_string(),
+            history_id: None,
+            messages,
+        }
+    }
+
+    fn labelled(id: &str, days_ago: i64, labels: &[&str]) -> FullMessage {
+        let mut message = message_at(id, Utc::now() - Duration::days(days_ago));
+        message.label_ids = Some(labels.iter().map(|l| l.to_string()).collect());
+        message
+    }
+
+    #[test]
+    fn finds_first_unread_in_mixed_thread() {
+        let thread = full_thread(vec![
+            labelled("m1", 5, &["INBOX"]),
+            labelled("m2", 4, &["INBOX"]),
+            labelled("m3", 3, &["INBOX", "UNREAD"]),
+            labelled("m4", 2, &["INBOX"]),
+            labelled("m5", 1, &["INBOX", "UNREAD"]),
+        ]);
+        assert_eq!(first_unread_message_id(&thread).as_deref(), Some("m3"));
+
+        let read = full_thread(vec![labelled("m1", 2, &["INBOX"]), message_at("m2", Utc::now())]);
+        assert_eq!(first_unread_message_id(&read), None);
+    }
+
+    #[test]
+    fn opening_marks_only_unread_messages_read() {
+        let mut thread = full_thread(vec![
+            labelled("m1", 3, &["INBOX"]),
+            labelled("m2", 2, &["INBOX", "UNREAD"]),
+            labelled("m3", 1, &["INBOX", "UNREAD", "IMPORTANT"]),
+            message_at("m4", Utc::now()),
+        ]);
+
+        let unread = unread_message_ids(&thread);
+        assert_eq!(unread, vec!["m2", "m3"]);
+
+        // Only the unread messages go to batchModify
+        let remove = vec!["UNREAD".to_string()];
+        let requests = batch_modify_requests(&unread, &[], &remove);
+        assert_eq!(
+            serde_json::to_value(&requests).unwrap(),
+            serde_json::json!([{"ids": ["m2", "m3"], "addLabelIds": [], "removeLabelIds": ["UNREAD"]}])
+        );
+
+        assert_eq!(mark_messages_read_locally(&mut thread, &unread), 0);
+        let labels: Vec<Option<Vec<String>>> = thread.messages.iter().map(|m| m.label_ids.clone()).collect();
+        assert_eq!(labels[1], Some(vec!["INBOX".to_string()]));
+        assert_eq!(labels[2], Some(vec!["INBOX".to_string(), "IMPORTANT".to_string()]));
+        // Read messages were left alone
+        assert_eq!(labels[0], Some(vec!["INBOX".to_string()]));
+        assert_eq!(labels[3], None);
+    }
+
+    #[test]
+    fn old_threads_are_flagged_stale() {
+        let now = Utc::now();
+        let old = full_thread(vec![
+            message_at("m1", now - Duration::days(120)),
+            message_at("m2", now - Duration::days(90)),
+        ]);
+        let preflight = reply_preflight(
+            &old,
+            None,
+            "me@example.com",
+            now,
+            DEFAULT_STALE_THREAD_DAYS,
+            DEFAULT_LARGE_RECIPIENT_THRESHOLD,
+        );
+        assert!(preflight.stale_thread);
+        assert_eq!(
+            preflight.latest_message_date,
+            Some((now - Duration::days(90)).timestamp_millis())
+        );
+
+        let recent = full_thread(vec![
+            message_at("m1", now - Duration::days(120)),
+            message_at("m2", now - Duration::days(2)),
+        ]);
+        assert!(!thread_age_warning(&recent, now, DEFAULT_STALE_THREAD_DAYS));
+
+        // The threshold is configurable
+        assert!(thread_age_warning(&recent, now, 1));
+    }
+
+    fn chained_message(id: &str, thread_id: &str, date_ms: i64, headers: &[(&str, &str)]) -> FullMessage {
+        FullMessage {
+            id: id.to_string(),
+            thread_id: thread_id.to_string(),
+            label_ids: None,
+            snippet: None,
+            internal_date: Some(date_ms.to_string()),
+            payload: Some(MessagePayload {
+                headers: Some(
+                    headers
+                        .iter()
+                        .map(|(name, value)| Header {
+                            name: name.to_string(),
+                            value: value.to_string(),
+                        })
+                        .collect(),
+                ),
+                body: None,
+                parts: None,
+                mime_type: None,
+            }),
+        }
+    }
+
+    #[test]
+    fn retries_only_transient_statuses_with_backoff() {
+        use reqwest::StatusCode;
+        use std::time::Duration;
+
+        assert!(is_retryable_status(StatusCode::TOO_MANY_REQUESTS, true));
+        assert!(is_retryable_status(StatusCode::SERVICE_UNAVAILABLE, true));
+        assert!(is_retryable_status(StatusCode::INTERNAL_SERVER_ERROR, true));
+        assert!(!is_retryable_status(StatusCode::FORBIDDEN, true));
+        assert!(!is_retryable_status(StatusCode::NOT_FOUND, true));
+
+        // A POST such as messages.send may already have taken effect on a 5xx
+        assert!(is_retryable_status(StatusCode::TOO_MANY_REQUESTS, false));
+        assert!(!is_retryable_status(StatusCode::INTERNAL_SERVER_ERROR, false));
+        assert!(!is_retryable_status(StatusCode::SERVICE_UNAVAILABLE, false));
+
+        let jitter = Duration::from_millis(100);
+        assert_eq!(retry_delay(1, None, jitter), Duration::from_millis(600));
+        assert_eq!(retry_delay(2, None, jitter), Duration::from_millis(1_100));
+        // Retry-After wins over backoff, but is capped
+        assert_eq!(retry_delay(1, Some(Duration::from_secs(7)), jitter), Duration::from_secs(7));
+        assert_eq!(retry_delay(1, Some(Duration::from_secs(600)), jitter), MAX_RETRY_DELAY);
+    }
+
+    #[test]
+    fn top_posted_reply_is_classified() {
+        let body = "Sounds good, see you then.\n\nOn Mon, Mar 4, 2024 at 9:00 AM Ann <ann@example.com> wrote:\n> Lunch at noon?\n> Ann";
+        assert_eq!(classify_quote_style(body), QuoteStyle::TopPosted);
+        assert_eq!(new_content(body), "Sounds good, see you then.");
+
+        let outlook = "Approved.\n\n-----Original Message-----\nFrom: Bob\nPlease approve the budget.";
+        assert_eq!(classify_quote_style(outlook), QuoteStyle::TopPosted);
+        assert_eq!(new_content(outlook), "Approved.");
+    }
+
+    #[test]
+    fn bottom_posted_reply_is_classified() {
+        let body = "On Mon, Mar 4, 2024 at 9:00 AM Ann <ann@example.com> wrote:\n> Lunch at noon?\n\nYes, noon works.\n\n> Bring the slides\nWill do.";
+        assert_eq!(classify_quote_style(body), QuoteStyle::Inline);
+        assert_eq!(new_content(body), "Yes, noon works.\n\nWill do.");
+
+        assert_eq!(classify_quote_style("No quotes here."), QuoteStyle::Unquoted);
+    }
+
+    #[test]
+    fn forward_quotes_original_headers_and_body() {
+        use base64::Engine;
+        let mut original = chained_message(
+            "m1",
+            "t1",
+            1_000,
+            &[
+                ("From", "Ann <ann@example.com>"),
+                ("Date", "Mon, 15 Jan 2024 10:00:00 +0000"),
+                ("Subject", "Budget"),
+                ("To", "me@example.com"),
+            ],
+        );
+        if let Some(payload) = original.payload.as_mut() {
+            payload.mime_type = Some("text/plain".to_string());
+            payload.body = Some(MessageBody {
+                size: None,
+                data: Some(base64::engine::general_purpose::URL_SAFE.encode("Numbers attached.")),
+                attachment_id: None,
+            });
+        }
+
+        assert_eq!(forward_subject("Budget"), "Fwd: Budget");
+        assert_eq!(forward_subject("FW: Budget"), "FW: Budget");
+
+        let (body, is_html) = build_forward_body(&original, "See below");
+        assert!(!is_html);
+        assert!(body.starts_with("See below\n\n---------- Forwarded message ---------\n"));
+        assert!(body.contains("From: Ann <ann@example.com>\nDate: Mon, 15 Jan 2024 10:00:00 +0000\nSubject: Budget\nTo: me@example.com\n"));
+        assert!(body.ends_with("\n\nNumbers attached."));
+    }
+
+    #[test]
+    fn references_accumulate_the_full_chain() {
+        // a <- b <- c: each reply's References becomes the next parent's header
+        let to_b = build_references(None, "a@x");
+        assert_eq!(to_b, "<a@x>");
+        let to_c = build_references(Some(&to_b), "<b@x>");
+        let to_d = build_references(Some(&to_c), "c@x");
+        assert_eq!(to_d, "<a@x> <b@x> <c@x>");
+
+        // A parent already listed in its own chain is not repeated
+        assert_eq!(build_references(Some("<a@x> <b@x>"), "<b@x>"), "<a@x> <b@x>");
+    }
+
+    #[test]
+    fn messages_sharing_message_id_collapse() {
+        let mut thread = full_thread(vec![
+            chained_message("m1", "t1", 1_000, &[("Message-ID", "<a@x>"), ("From", "me@x"), ("Subject", "Hi")]),
+            chained_message("m2", "t1", 1_001, &[("Message-ID", "a@x"), ("From", "me@x"), ("Subject", "Hi")]),
+            chained_message("m3", "t1", 2_000, &[("Message-ID", "<b@x>"), ("From", "me@x"), ("Subject", "Hi")]),
+        ]);
+
+        assert_eq!(dedupe_thread_messages(&mut thread, false), 1);
+        let ids: Vec<&str> = thread.messages.iter().map(|m| m.id.as_str()).collect();
+        assert_eq!(ids, vec!["m1", "m3"]);
+
+        // Same sender/subject/(empty) body only collapses when content matching is on
+        assert_eq!(dedupe_thread_messages(&mut thread, false), 0);
+        assert_eq!(dedupe_thread_messages(&mut thread, true), 1);
+    }
+
+    #[test]
+    fn rapid_messages_from_one_sender_form_a_group() {
+        let minute = 60_000;
+        let thread = full_thread(vec![
+            chained_message("m1", "t1", 0, &[("From", "Ann <ann@x.com>")]),
+            chained_message("m2", "t1", minute, &[("From", "ann@x.com")]),
+            chained_message("m3", "t1", 3 * minute, &[("From", "ANN@x.com")]),
+            chained_message("m4", "t1", 4 * minute, &[("From", "bob@x.com")]),
+            // Same sender again, but after someone else spoke
+            chained_message("m5", "t1", 5 * minute, &[("From", "ann@x.com")]),
+            // Too long after m5
+            chained_message("m6", "t1", 30 * minute, &[("From", "ann@x.com")]),
+        ]);
+
+        let groups = group_consecutive_messages(&thread, DEFAULT_MESSAGE_GROUP_WINDOW_MINUTES);
+        let ids: Vec<Vec<&str>> = groups
+            .iter()
+            .map(|g| g.message_ids.iter().map(String::as_str).collect())
+            .collect();
+        assert_eq!(ids, vec![vec!["m1", "m2", "m3"], vec!["m4"], vec!["m5"], vec!["m6"]]);
+        assert_eq!(groups[0].sender, "ann@x.com");
+    }
+
+    #[test]
+    fn stitches_split_threads_in_date_order() {
+        let older = FullThread {
+            id: "old".to_string(),
+            history_id: None,
+            messages: vec![
+                chained_message("m1", "old", 1_000, &[("Message-ID", "<a@x>")]),
+                chained_message("m2", "old", 2_000, &[("Message-ID", "<b@x>"), ("References", "<a@x>")]),
+            ],
+        };
+        let newer = FullThread {
+            id: "new".to_string(),
+            history_id: None,
+            messages: vec![
+                chained_message(
+                    "m3",
+                    "new",
+                    3_000,
+                    &[("Message-ID", "<c@x>"), ("In-Reply-To", "<b@x>"), ("References", "<a@x> <b@x>")],
+                ),
+                chained_message("m4", "new", 4_000, &[("Message-ID", "<d@x>"), ("References", "<a@x> <b@x> <c@x>")]),
+            ],
+        };
+        let unrelated = FullThread {
+            id: "other".to_string(),
+            history_id: None,
+            messages: vec![chained_message("z1", "other", 2_500, &[("Message-ID", "<z@y>")])],
+        };
 
-    None
-}
+        assert!(thread_looks_truncated(&newer));
+        assert!(!thread_looks_truncated(&older));
 
-/// Parse the reaction JSON and validate it
-fn parse_reaction_json(base64_data: &str) -> Option<String> {
-    let decoded = decode_base64_body(base64_data)?;
+        let conversation = stitch_threads(newer, vec![unrelated, older]);
+        assert_eq!(conversation.thread_ids, vec!["new", "old"]);
+        let order: Vec<&str> = conversation.messages.iter().map(|m| m.id.as_str()).collect();
+        assert_eq!(order, vec!["m1", "m2", "m3", "m4"]);
+        assert_eq!(conversation.split_boundaries, vec![2]);
+    }
 
-    #[derive(Deserialize)]
-    struct ReactionJson {
-        version: i32,
-        emoji: String,
+    #[test]
+    fn strips_reply_prefixes() {
+        assert_eq!(strip_reply_prefixes("Re: Fwd: RE: Launch plan"), "Launch plan");
+        assert_eq!(strip_reply_prefixes("Launch plan"), "Launch plan");
     }
 
-    let reaction: ReactionJson = serde_json::from_str(&decoded).ok()?;
+    #[test]
+    fn awaiting_reply_needs_user_as_stale_last_sender() {
+        let now = Utc::now();
+        let old = (now - Duration::days(10)).timestamp_millis();
+        let older = (now - Duration::days(12)).timestamp_millis();
+
+        let unanswered = FullThread {
+            id: "t-unanswered".to_string(),
+            history_id: None,
+            messages: vec![
+                chained_message("m1", "t-unanswered", older, &[("From", "Client <client@example.com>")]),
+                chained_message(
+                    "m2",
+                    "t-unanswered",
+                    old,
+                    &[("From", "Me <Me@Example.com>"), ("To", "client@example.com"), ("Subject", "Proposal")],
+                ),
+            ],
+        };
+        let answered = FullThread {
+            id: "t-answered".to_string(),
+            history_id: None,
+            messages: vec![
+                chained_message("m3", "t-answered", older, &[("From", "me@example.com")]),
+                chained_message("m4", "t-answered", old, &[("From", "client@example.com")]),
+            ],
+        };
 
-    // Version must be 1
-    if reaction.version != 1 {
-        return None;
+        let pending = awaiting_reply(&unanswered, "me@example.com", now, 7).unwrap();
+        assert_eq!(pending.thread_id, "t-unanswered");
+        assert_eq!(pending.subject, "Proposal");
+        assert_eq!(pending.sent_at, old);
+
+        assert!(awaiting_reply(&answered, "me@example.com", now, 7).is_none());
+        // Not stale yet under a longer window
+        assert!(awaiting_reply(&unanswered, "me@example.com", now, 30).is_none());
     }
 
-    // Validate emoji (basic check - must be non-empty)
-    if reaction.emoji.is_empty() {
-        return None;
+    #[test]
+    fn parses_batch_statuses_by_content_id() {
+        let body = "--batch_abc\r\n\
+Content-Type: application/http\r\n\
+Content-ID: <response-item1>\r\n\r\n\
+HTTP/1.1 404 Not Found\r\n\
+Content-Type: application/json\r\n\r\n\
+{\"error\": {\"code\": 404}}\r\n\
+--batch_abc\r\n\
+Content-Type: application/http\r\n\
+Content-ID: <response-item0>\r\n\r\n\
+HTTP/1.1 200 OK\r\n\
+Content-Type: application/json\r\n\r\n\
+{\"id\": \"t0\"}\r\n\
+--batch_abc--\r\n";
+
+        let statuses = parse_batch_statuses(body, "batch_abc");
+        assert_eq!(statuses.len(), 2);
+        assert_eq!(statuses[&0].0, 200);
+        assert_eq!(statuses[&1], (404, "{\"error\": {\"code\": 404}}".to_string()));
     }
 
-    Some(reaction.emoji)
-}
+    #[test]
+    fn list_post_address_is_recommended_reply_target() {
+        let message = chained_message(
+            "m1",
+            "t1",
+            1_000,
+            &[
+                ("From", "Alice <alice@example.com>"),
+                ("To", "list@x.com"),
+                ("List-Id", "Example list <list.x.com>"),
+                ("List-Post", "<mailto:list@x.com>"),
+            ],
+        );
 
-impl GmailClient {
-    /// Send an emoji reaction to a message
-    pub async fn send_reaction(
-        &self,
-        thread_id: &str,
-        message_id: &str,
-        emoji: &str,
-        from_email: &str,
-        to_email: &str,
-    ) -> Result<(), String> {
-        let url = format!("{}/users/me/messages/send", GMAIL_API_BASE);
+        let targets = reply_targets(&message);
+        assert_eq!(targets.sender.as_deref(), Some("Alice <alice@example.com>"));
+        assert_eq!(targets.list.as_deref(), Some("list@x.com"));
+        assert_eq!(targets.recommended.as_deref(), Some("list@x.com"));
+
+        let direct = chained_message("m2", "t1", 2_000, &[("From", "bob@example.com")]);
+        let targets = reply_targets(&direct);
+        assert_eq!(targets.list, None);
+        assert_eq!(targets.recommended.as_deref(), Some("bob@example.com"));
+
+        let announce_only = chained_message("m3", "t1", 3_000, &[("From", "news@x.com"), ("List-Post", "NO")]);
+        assert_eq!(reply_targets(&announce_only).list, None);
+
+        // Posting is disallowed even though List-Id names an address in To
+        let announce_list = chained_message(
+            "m4",
+            "t1",
+            4_000,
+            &[
+                ("From", "news@x.com"),
+                ("To", "announce@x.com"),
+                ("List-Id", "<announce.x.com>"),
+                ("List-Post", "NO"),
+            ],
+        );
+        let targets = reply_targets(&announce_list);
+        assert_eq!(targets.list, None);
+        assert_eq!(targets.recommended.as_deref(), Some("news@x.com"));
+    }
 
-        let message = self.build_reaction_message(emoji, from_email, to_email, message_id)?;
+    #[test]
+    fn large_reply_all_is_flagged() {
+        let everyone: Vec<String> = (0..20).map(|i| format!("person{}@example.com", i)).collect();
+        let to = format!("me@example.com, {}", everyone[..10].join(", "));
+        let cc = everyone[10..].join(", ");
+        let message = chained_message(
+            "m1",
+            "t1",
+            1_000,
+            &[("From", "Alice <alice@example.com>"), ("To", &to), ("Cc", &cc)],
+        );
+
+        let reply_all = reply_all_recipients(&message, "Me@Example.com", DEFAULT_LARGE_RECIPIENT_THRESHOLD);
+        assert_eq!(reply_all.to.as_deref(), Some("alice@example.com"));
+        assert_eq!(reply_all.cc.len(), 20);
+        assert_eq!(reply_all.recipient_count, 21);
+        assert!(reply_all.large_recipient_warning);
+
+        let small = chained_message(
+            "m2",
+            "t1",
+            2_000,
+            &[
+                ("From", "alice@example.com"),
+                ("To", "me@example.com, \"Doe, Bob\" <bob@example.com>"),
+                ("Cc", "carol@example.com, bob@example.com"),
+            ],
+        );
+        let reply_all = reply_all_recipients(&small, "me@example.com", DEFAULT_LARGE_RECIPIENT_THRESHOLD);
+        assert_eq!(reply_all.cc, vec!["bob@example.com", "carol@example.com"]);
+        assert_eq!(reply_all.recipient_count, 3);
+        assert!(!reply_all.large_recipient_warning);
+    }
 
+    #[test]
+    fn html_body_with_attachment_nests_alternative_inside_mixed() {
         use base64::Engine;
-        let encoded = base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(message.as_bytes());
 
-        let request_body = serde_json::json!({
-            "raw": encoded,
-            "threadId": thread_id
-        });
+        let client = GmailClient::new(String::new());
+        let attachment = SendAttachment {
+            filename: "notes.txt".to_string(),
+            mime_type: "text/plain".to_string(),
+            data: base64::engine::general_purpose::STANDARD.encode(b"hello"),
+            content_id: None,
+        };
 
-        let resp = self
-            .client
-            .post(&url)
-            .bearer_auth(&self.access_token)
-            .json(&request_body)
-            .send()
-            .await
-            .map_err(|e| format!("Request failed: {}", e))?;
+        let message = client
+            .build_mime_message("a@example.com", "", "", "Hi", "<p>Hello <b>there</b></p>", &[attachment], None, true)
+            .unwrap();
 
-        if !resp.status().is_success() {
-            let status = resp.status();
-            let body = resp.text().await.unwrap_or_default();
-            return Err(format!("API error {}: {}", status, body));
-        }
+        let mixed = message.find("Content-Type: multipart/mixed").unwrap();
+        let alternative = message.find("Content-Type: multipart/alternative").unwrap();
+        let plain = message.find("Content-Type: text/plain; charset=utf-8").unwrap();
+        let html = message.find("Content-Type: text/html").unwrap();
+        let attachment = message.find("filename=").unwrap();
+        assert!(mixed < alternative && alternative < plain && plain < html && html < attachment);
 
-        Ok(())
+        // The plain alternative is the tag-stripped HTML
+        assert!(message.contains("Hello there"));
     }
 
-    /// Build a reaction MIME message per Google's spec
-    fn build_reaction_message(
-        &self,
-        emoji: &str,
-        from_email: &str,
-        to_email: &str,
-        reply_to_message_id: &str,
-    ) -> Result<String, String> {
-        let boundary = format!("----=_React_{}", uuid::Uuid::new_v4().to_string().replace("-", ""));
+    #[test]
+    fn inline_attachment_keeps_its_content_id() {
+        use base64::Engine;
 
-        // Build the reaction JSON
-        let reaction_json = serde_json::json!({
-            "version": 1,
-            "emoji": emoji
-        });
-        let reaction_json_str = serde_json::to_string(&reaction_json)
-            .map_err(|e| format!("Failed to serialize reaction: {}", e))?;
+        let client = GmailClient::new(String::new());
+        let logo = SendAttachment {
+            filename: "logo.png".to_string(),
+            mime_type: "image/png".to_string(),
+            data: base64::engine::general_purpose::STANDARD.encode(b"png"),
+            content_id: Some("logo@example.com".to_string()),
+        };
+        let message = client
+            .build_mime_message("a@example.com", "", "", "Fwd: Hi", r#"<img src="cid:logo@example.com">"#, &[logo], None, true)
+            .unwrap();
 
-        // Fallback text for clients that don't support reactions
-        let plain_text = format!("Reacted with {}", emoji);
-        let html_text = format!(
-            "<html><body><p>Reacted with <span style=\"font-size: 24px\">{}</span></p></body></html>",
-            emoji
-        );
+        assert!(message.contains("Content-ID: <logo@example.com>\r\n"));
+        assert!(message.contains("Content-Disposition: inline; filename=\"logo.png\""));
+    }
 
-        let mut message = String::new();
+    #[test]
+    fn non_ascii_subject_and_filename_are_encoded_words() {
+        use base64::Engine;
+        let decode_words = |encoded: &str| -> String {
+            encoded
+                .split_whitespace()
+                .map(|word| {
+                    let b64 = word.strip_prefix("=?UTF-8?B?").unwrap().strip_suffix("?=").unwrap();
+                    String::from_utf8(base64::engine::general_purpose::STANDARD.decode(b64).unwrap()).unwrap()
+                })
+                .collect()
+        };
 
-        // Headers
-        message.push_str(&format!("From: {}\r\n", from_email));
-        message.push_str(&format!("To: {}\r\n", to_email));
-        message.push_str(&format!(
-            "Subject: {}\r\n",
-            encode_header_value(&format!("Re: {}", emoji))
-        ));
-        message.push_str("MIME-Version: 1.0\r\n");
-        message.push_str(&format!("In-Reply-To: {}\r\n", reply_to_message_id));
-        message.push_str(&format!("References: {}\r\n", reply_to_message_id));
-        message.push_str(&format!(
-            "Content-Type: multipart/alternative; boundary=\"{}\"\r\n\r\n",
-            boundary
-        ));
+        let subject = "Réunion demain — ordre du jour détaillé pour l'équipe produit 🎉";
+        let encoded = encode_header_value(subject);
+        let words: Vec<&str> = encoded.split("\r\n ").collect();
+        assert!(words.len() > 1);
+        assert!(words.iter().all(|w| w.len() <= 75 && w.starts_with("=?UTF-8?B?")));
+        assert_eq!(decode_words(&encoded), subject);
+
+        let client = GmailClient::new(String::new());
+        let attachment = SendAttachment {
+            filename: "résumé.pdf".to_string(),
+            mime_type: "application/pdf".to_string(),
+            data: base64::engine::general_purpose::STANDARD.encode(b"%PDF"),
+            content_id: None,
+        };
+        let message = client
+            .build_mime_message("a@example.com", "", "", subject, "Hi", &[attachment], None, false)
+            .unwrap();
+        assert!(message.is_ascii());
+        let filename = message.split("filename=\"").nth(1).unwrap().split('"').next().unwrap();
+        assert_eq!(decode_words(filename), "résumé.pdf");
+    }
 
-        // Plain text part (first, for clients that show first part)
-        message.push_str(&format!("--{}\r\n", boundary));
-        message.push_str("Content-Type: text/plain; charset=utf-8\r\n\r\n");
-        message.push_str(&plain_text);
-        message.push_str("\r\n");
+    fn decode_quoted_printable(encoded: &str) -> String {
+        let joined = encoded.replace("=\r\n", "");
+        let mut bytes = Vec::new();
+        let mut input = joined.bytes();
+        while let Some(byte) = input.next() {
+            if byte == b'=' {
+                let hex = [input.next().unwrap(), input.next().unwrap()];
+                bytes.push(u8::from_str_radix(std::str::from_utf8(&hex).unwrap(), 16).unwrap());
+            } else {
+                bytes.push(byte);
+            }
+        }
+        String::from_utf8(bytes).unwrap().replace("\r\n", "\n")
+    }
 
-        // Reaction JSON part (between plain and HTML per Google recommendation)
-        message.push_str(&format!("--{}\r\n", boundary));
-        message.push_str("Content-Type: text/vnd.google.email-reaction+json; charset=utf-8\r\n\r\n");
-        message.push_str(&reaction_json_str);
-        message.push_str("\r\n");
+    #[test]
+    fn non_ascii_body_is_quoted_printable() {
+        let body = format!("Café — déjà vu 🎉\n{}", "Ünïcode line that is long enough to need a soft break. ".repeat(3));
+
+        let client = GmailClient::new(String::new());
+        let message = client
+            .build_mime_message("a@example.com", "", "", "Hi", &body, &[], None, false)
+            .unwrap();
+
+        let (headers, encoded) = message.split_once("\r\n\r\n").unwrap();
+        assert!(headers.contains("Content-Transfer-Encoding: quoted-printable"));
+        assert!(encoded.is_ascii());
+        assert!(encoded.split("\r\n").all(|line| line.len() <= 76));
+        assert_eq!(decode_quoted_printable(encoded), body);
+
+        // Plain ASCII keeps the simple 7-bit form
+        let ascii = client
+            .build_mime_message("a@example.com", "", "", "Hi", "Plain = fine", &[], None, false)
+            .unwrap();
+        assert!(!ascii.contains("Content-Transfer-Encoding"));
+        assert!(ascii.ends_with("\r\n\r\nPlain = fine"));
+    }
 
-        // HTML part (last, for clients that show last part)
-        message.push_str(&format!("--{}\r\n", boundary));
-        message.push_str("Content-Type: text/html; charset=utf-8\r\n\r\n");
-        message.push_str(&html_text);
-        message.push_str("\r\n");
+    #[test]
+    fn bodies_are_found_in_nested_multiparts_and_survive_the_cache() {
+        use base64::Engine;
+        let part = |mime_type: &str, filename: Option<&str>, data: Option<&str>, parts: Option<Vec<MessagePart>>| MessagePart {
+            part_id: None,
+            mime_type: mime_type.to_string(),
+            filename: filename.map(str::to_string),
+            headers: None,
+            body: data.map(|d| MessageBody {
+                size: None,
+                // Gmail omits the padding
+                data: Some(base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(d)),
+                attachment_id: None,
+            }),
+            parts,
+        };
+        // multipart/mixed > multipart/related > multipart/alternative > text parts
+        let parts = vec![
+            part(
+                "multipart/related",
+                None,
+                None,
+                Some(vec![part(
+                    "multipart/alternative",
+                    None,
+                    None,
+                    Some(vec![
+                        part("text/plain", None, Some("Caf\u{e9} at 9?"), None),
+                        part("text/html", None, Some("<p>Caf\u{e9} at 9?</p>"), None),
+                    ]),
+                )]),
+            ),
+            part("text/plain", Some("notes.txt"), Some("attachment, not body"), None),
+        ];
+
+        let (text, html) = extract_bodies_from_parts(&parts);
+        assert_eq!(text.as_deref(), Some("Caf\u{e9} at 9?"));
+        assert_eq!(html.as_deref(), Some("<p>Caf\u{e9} at 9?</p>"));
+
+        let mut message = chained_message("m1", "t1", 1_700_000_000_000, &[("From", "Ann <ann@x.com>"), ("Subject", "Lunch")]);
+        message.payload.as_mut().unwrap().parts = Some(parts);
+        let cached = message_from_full(&message);
+        assert_eq!(cached.subject, "Lunch");
+
+        let rebuilt = full_thread_from_cache("t1", &[cached]);
+        assert_eq!(rebuilt.messages[0].header("From"), Some("Ann <ann@x.com>"));
+        assert_eq!(extract_body_text_from_message(&rebuilt.messages[0]).as_deref(), Some("Caf\u{e9} at 9?"));
+        assert_eq!(extract_html_from_message(&rebuilt.messages[0]).as_deref(), Some("<p>Caf\u{e9} at 9?</p>"));
+    }
 
-        // Close boundary
-        message.push_str(&format!("--{}--\r\n", boundary));
+    #[test]
+    fn unread_threads_are_counted_per_date_bucket() {
+        let now = Utc::now();
+        let thread = |id: &str, date: DateTime<Utc>, unread_count: i32| Thread {
+            gmail_thread_id: id.to_string(),
+            last_message_date: date,
+            unread_count,
+            ..thread_with_labels(&[])
+        };
+        let threads = vec![
+            thread("t1", now, 2),
+            thread("t2", now, 1),
+            thread("t3", now, 0),
+            thread("t4", now - Duration::days(1), 1),
+            thread("t5", now - Duration::days(60), 3),
+            thread("t6", now - Duration::days(60), 0),
+        ];
+
+        let counts = bucket_unread_counts(&threads);
+        assert_eq!(counts.get("Today"), Some(&2));
+        assert_eq!(counts.get("Yesterday"), Some(&1));
+        assert_eq!(counts.get("Older"), Some(&1));
+        assert_eq!(counts.len(), 3);
+    }
 
-        Ok(message)
+    fn mime_part(mime_type: &str, filename: Option<&str>, attachment_id: Option<&str>, parts: Option<Vec<MessagePart>>) -> MessagePart {
+        MessagePart {
+            part_id: None,
+            mime_type: mime_type.to_string(),
+            filename: filename.map(str::to_string),
+            headers: None,
+            body: Some(MessageBody {
+                size: Some(512),
+                data: None,
+                attachment_id: attachment_id.map(str::to_string),
+            }),
+            parts,
+        }
     }
-}
 
-/// Check if a message should allow reactions based on Google's recommended limits
-pub fn can_react_to_message(
-    to_addrs: &[String],
-    cc_addrs: &[String],
-    user_email: &str,
-    is_mailing_list: bool,
-) -> Result<(), String> {
-    // No reactions on mailing list messages
-    if is_mailing_list {
-        return Err("Cannot react to mailing list messages".to_string());
+    fn payload(mime_type: &str, parts: Vec<MessagePart>) -> MessagePayload {
+        MessagePayload {
+            headers: None,
+            body: None,
+            parts: Some(parts),
+            mime_type: Some(mime_type.to_string()),
+        }
     }
 
-    // Max 20 recipients in To + CC
-    let total_recipients = to_addrs.len() + cc_addrs.len();
-    if total_recipients > 20 {
-        return Err("Too many recipients (max 20)".to_string());
+    #[test]
+    fn sender_files_come_from_their_own_messages() {
+        let with_parts = |id: &str, date_ms: i64, from: &str, parts: Vec<MessagePart>| {
+            let mut message = chained_message(id, "t1", date_ms, &[("From", from)]);
+            message.payload.as_mut().unwrap().parts = Some(parts);
+            message
+        };
+        let mut logo = mime_part("image/png", Some("logo.png"), Some("att-logo"), None);
+        logo.headers = Some(vec![Header {
+            name: "Content-Disposition".to_string(),
+            value: "inline".to_string(),
+        }]);
+        let thread = full_thread(vec![
+            with_parts(
+                "m1",
+                1_000,
+                "Ann <Ann@x.com>",
+                vec![mime_part("application/pdf", Some("contract.pdf"), Some("att-contract"), None), logo],
+            ),
+            with_parts(
+                "m2",
+                2_000,
+                "me@x.com",
+                vec![mime_part("application/pdf", Some("signed.pdf"), Some("att-signed"), None)],
+            ),
+        ]);
+
+        let files = sender_attachments(&[thread], "ann@x.com", 10);
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].filename, "contract.pdf");
+        assert_eq!(files[0].message_id, "m1");
+        assert_eq!(files[0].date, 1_000);
     }
 
-    // User must be in To or CC
-    let user_lower = user_email.to_lowercase();
-    let in_to = to_addrs.iter().any(|a| a.to_lowercase() == user_lower);
-    let in_cc = cc_addrs.iter().any(|a| a.to_lowercase() == user_lower);
-    if !in_to && !in_cc {
-        return Err("You must be a recipient to react".to_string());
+    #[test]
+    fn thread_manifest_lists_attachments_without_content() {
+        let message = |id: &str, payload: Option<MessagePayload>| FullMessage {
+            id: id.to_string(),
+            thread_id: "t1".to_string(),
+            label_ids: None,
+            snippet: None,
+            internal_date: None,
+            payload,
+        };
+        let thread = full_thread(vec![
+            message(
+                "m1",
+                Some(payload(
+                    "multipart/mixed",
+                    vec![
+                        mime_part("text/plain", None, None, None),
+                        mime_part("application/pdf", Some("report.pdf"), Some("att1"), None),
+                        mime_part("image/png", Some("chart.png"), Some("att2"), None),
+                    ],
+                )),
+            ),
+            message("m2", None),
+        ]);
+
+        let manifest = attachment_manifest(&thread);
+        let listed: Vec<(&str, &str, &str, &str, i32)> = manifest
+            .iter()
+            .map(|a| (a.message_id.as_str(), a.attachment_id.as_str(), a.filename.as_str(), a.mime_type.as_str(), a.size))
+            .collect();
+        assert_eq!(
+            listed,
+            vec![
+                ("m1", "att1", "report.pdf", "application/pdf", 512),
+                ("m1", "att2", "chart.png", "image/png", 512),
+            ]
+        );
+        // Nothing is downloaded, not even small images
+        assert!(manifest.iter().all(|a| a.inline_data.is_none()));
     }
 
-    Ok(())
-}
+    #[test]
+    fn best_body_prefers_nested_html_over_plain_text() {
+        use base64::Engine;
 
-/// Check if message has mailing list headers
-pub fn is_mailing_list_message(headers: &[Header]) -> bool {
-    headers.iter().any(|h| {
-        h.name.eq_ignore_ascii_case("List-Unsubscribe")
-            || h.name.eq_ignore_ascii_case("List-Id")
-            || h.name.eq_ignore_ascii_case("Precedence") && h.value.to_lowercase() == "list"
-    })
-}
+        let data_part = |mime_type: &str, content: &str| MessagePart {
+            body: Some(MessageBody {
+                size: Some(content.len() as i32),
+                data: Some(base64::engine::general_purpose::URL_SAFE.encode(content)),
+                attachment_id: None,
+            }),
+            ..mime_part(mime_type, None, None, None)
+        };
+        let message = |payload: MessagePayload| FullMessage {
+            id: "m1".to_string(),
+            thread_id: "t1".to_string(),
+            label_ids: None,
+            snippet: None,
+            internal_date: None,
+            payload: Some(payload),
+        };
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+        let mixed = payload(
+            "multipart/mixed",
+            vec![
+                mime_part(
+                    "multipart/alternative",
+                    None,
+                    None,
+                    Some(vec![data_part("text/plain", "Hello"), data_part("text/html", "<p>Hello</p>")]),
+                ),
+                mime_part("application/pdf", Some("a.pdf"), Some("att1"), None),
+            ],
+        );
+        let body = best_body(&message(mixed)).unwrap();
+        assert_eq!(body.mime_type, "text/html");
+        assert_eq!(body.content, "<p>Hello</p>");
 
-    #[test]
-    fn strip_html_converts_breaks_and_paragraphs_to_newlines() {
-        assert_eq!(strip_html_tags("line1<br>line2"), "line1\nline2");
-        assert_eq!(strip_html_tags("line1<br/>line2"), "line1\nline2");
-        assert_eq!(strip_html_tags("line1<br />line2"), "line1\nline2");
-        assert_eq!(strip_html_tags("line1<BR>line2"), "line1\nline2");
-        assert_eq!(strip_html_tags("<p>one</p><p>two</p>"), "\none\n\ntwo\n");
+        let plain_only = payload(
+            "multipart/mixed",
+            vec![mime_part("multipart/alternative", None, None, Some(vec![data_part("text/plain", "Just text")]))],
+        );
+        let body = best_body(&message(plain_only)).unwrap();
+        assert_eq!(body.mime_type, "text/plain");
+        assert_eq!(body.content, "Just text");
     }
 
     #[test]
-    fn strip_html_removes_other_tags() {
-        assert_eq!(strip_html_tags("<b>bold</b> and <i>italic</i>"), "bold and italic");
-        assert_eq!(strip_html_tags("<a href=\"http://x\">link</a>"), "link");
+    fn smime_signed_message_is_flagged_and_signature_hidden() {
+        let signed = payload(
+            "multipart/signed",
+            vec![
+                mime_part(
+                    "multipart/alternative",
+                    None,
+                    None,
+                    Some(vec![mime_part("text/plain", None, None, None), mime_part("text/html", None, None, None)]),
+                ),
+                mime_part("application/pkcs7-signature", Some("smime.p7s"), Some("sig"), None),
+            ],
+        );
+
+        let security = detect_message_security(&signed);
+        assert!(security.is_signed);
+        assert!(!security.is_encrypted);
+        assert_eq!(security.scheme, Some(SecurityScheme::Smime));
+        assert!(extract_attachments_from_parts(&signed.parts).is_empty());
     }
 
     #[test]
-    fn strip_html_decodes_entities_without_double_decoding() {
-        assert_eq!(strip_html_tags("a &amp; b"), "a & b");
-        assert_eq!(strip_html_tags("&lt;tag&gt;"), "<tag>");
-        // "&amp;lt;" is the escaped text "&lt;" - it must NOT become "<"
-        assert_eq!(strip_html_tags("&amp;lt;"), "&lt;");
-        assert_eq!(strip_html_tags("&quot;hi&quot; &#39;there&#39;&nbsp;!"), "\"hi\" 'there' !");
+    fn pgp_encrypted_message_is_flagged() {
+        let encrypted = payload(
+            "multipart/encrypted",
+            vec![
+                mime_part("application/pgp-encrypted", None, Some("version"), None),
+                mime_part("application/octet-stream", Some("encrypted.asc"), Some("cipher"), None),
+            ],
+        );
+
+        let security = detect_message_security(&encrypted);
+        assert!(security.is_encrypted);
+        assert!(!security.is_signed);
+        assert_eq!(security.scheme, Some(SecurityScheme::Pgp));
+        // The ciphertext stays downloadable; the version part does not show up
+        let attachments = extract_attachments_from_parts(&encrypted.parts);
+        assert_eq!(attachments.len(), 1);
+        assert_eq!(attachments[0].filename, "encrypted.asc");
+
+        assert_eq!(detect_message_security(&payload("multipart/mixed", Vec::new())), MessageSecurity::default());
     }
 
     #[test]
-    fn ensure_angle_brackets_normalizes() {
-        assert_eq!(ensure_angle_brackets("abc@example.com"), "<abc@example.com>");
-        assert_eq!(ensure_angle_brackets("<abc@example.com>"), "<abc@example.com>");
-        assert_eq!(ensure_angle_brackets("  <abc@example.com>  "), "<abc@example.com>");
+    fn detects_read_receipt_request() {
+        let mut incoming = payload("text/plain", Vec::new());
+        assert_eq!(read_receipt_request(&incoming), None);
+
+        incoming.headers = Some(vec![
+            Header { name: "From".to_string(), value: "Ann <ann@example.com>".to_string() },
+            Header { name: "disposition-notification-to".to_string(), value: " ann@example.com ".to_string() },
+        ]);
+        assert_eq!(read_receipt_request(&incoming).as_deref(), Some("ann@example.com"));
     }
 
-    #[test]
-    fn encode_header_value_leaves_ascii_unchanged() {
-        assert_eq!(encode_header_value("Hello world"), "Hello world");
+    fn utc_ms(s: &str) -> i64 {
+        parse_ics_datetime(s, "").unwrap().0
     }
 
-    #[test]
-    fn encode_header_value_encodes_non_ascii() {
-        let encoded = encode_header_value("Café ☕");
-        assert!(encoded.starts_with("=?UTF-8?B?"));
-        assert!(encoded.ends_with("?="));
-        use base64::Engine;
-        let b64 = &encoded["=?UTF-8?B?".len()..encoded.len() - 2];
-        let decoded = base64::engine::general_purpose::STANDARD.decode(b64).unwrap();
-        assert_eq!(String::from_utf8(decoded).unwrap(), "Café ☕");
+    fn expand(rrule: &str, start: &str, exdates: &[i64]) -> Vec<i64> {
+        expand_rrule(rrule, utc_ms(start), &IcsZone::Utc, exdates, 0, i64::MAX / 2)
     }
 
     #[test]
-    fn encode_address_header_encodes_only_display_names() {
+    fn weekly_byday_rule_expands_with_count() {
+        // Monday 2024-01-15 10:00 UTC
+        let instances = expand("FREQ=WEEKLY;BYDAY=MO,WE;COUNT=5", "20240115T100000Z", &[utc_ms("20240117T100000Z")]);
         assert_eq!(
-            encode_address_header("plain@example.com"),
-            "plain@example.com"
-        );
-        assert_eq!(
-            encode_address_header("John Doe <jd@example.com>"),
-            "John Doe <jd@example.com>"
+            instances,
+            vec![
+                utc_ms("20240115T100000Z"),
+                // The 17th is excluded but still counts toward COUNT
+                utc_ms("20240122T100000Z"),
+                utc_ms("20240124T100000Z"),
+                utc_ms("20240129T100000Z"),
+            ]
         );
-        let encoded = encode_address_header("José García <jg@example.com>");
-        assert!(encoded.starts_with("=?UTF-8?B?"));
-        assert!(encoded.ends_with(" <jg@example.com>"));
     }
 
     #[test]
-    fn encode_address_header_handles_lists_and_quoted_commas() {
+    fn daily_and_monthly_rules_honor_interval_and_until() {
         assert_eq!(
-            encode_address_header("a@example.com, b@example.com"),
-            "a@example.com, b@example.com"
+            expand("FREQ=DAILY;INTERVAL=2;UNTIL=20240107T235959Z", "20240101T090000Z", &[]),
+            vec![
+                utc_ms("20240101T090000Z"),
+                utc_ms("20240103T090000Z"),
+                utc_ms("20240105T090000Z"),
+                utc_ms("20240107T090000Z"),
+            ]
         );
-        // Comma inside a quoted display name must not split the address
+
+        // Months without a 31st are skipped
         assert_eq!(
-            encode_address_header("\"Doe, John\" <jd@example.com>, x@example.com"),
-            "\"Doe, John\" <jd@example.com>, x@example.com"
+            expand("FREQ=MONTHLY;COUNT=3", "20240131T120000Z", &[]),
+            vec![utc_ms("20240131T120000Z"), utc_ms("20240331T120000Z"), utc_ms("20240531T120000Z")]
         );
-        let mixed = encode_address_header("Müller <m@example.com>, plain@example.com");
-        assert!(mixed.contains("=?UTF-8?B?"));
-        assert!(mixed.ends_with(", plain@example.com"));
+
+        // Instances keep their wall-clock time across DST
+        let zone = IcsZone::Named(chrono_tz::America::New_York);
+        let start = parse_ics_datetime("20240308T090000", "TZID=America/New_York").unwrap().0;
+        let instances = expand_rrule("FREQ=DAILY;COUNT=3", start, &zone, &[], 0, i64::MAX / 2);
+        assert_eq!(instances[2] - instances[1], 23 * 3_600_000);
+
+        // Unsupported rules expand to nothing rather than guessing
+        assert!(expand("FREQ=YEARLY", "20240101T090000Z", &[]).is_empty());
+        assert!(expand("FREQ=MONTHLY;BYDAY=2TU", "20240101T090000Z", &[]).is_empty());
+    }
+
+    #[test]
+    fn open_ended_rules_are_capped() {
+        assert_eq!(expand("FREQ=DAILY", "20240101T090000Z", &[]).len(), MAX_RECURRENCE_INSTANCES);
+
+        let ics = "BEGIN:VCALENDAR\r\nBEGIN:VEVENT\r\nUID:standup\r\nSUMMARY:Standup\r\n\
+                   DTSTART:20240101T090000Z\r\nRRULE:FREQ=WEEKLY;BYDAY=MO\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n";
+        let event = parse_ics_content(ics).unwrap();
+        let now = Utc::now().timestamp_millis();
+        assert_eq!(event.recurrence.as_deref(), Some("FREQ=WEEKLY;BYDAY=MO"));
+        assert!(!event.occurrences.is_empty() && event.occurrences.len() <= MAX_RECURRENCE_INSTANCES);
+        assert!(event.occurrences.iter().all(|&start| start >= now));
     }
 }
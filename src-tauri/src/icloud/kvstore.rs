@@ -1,6 +1,6 @@
 // iOS iCloud Key-Value Store wrapper
 
-use crate::models::Card;
+use crate::models::{AccountAppearance, Card};
 use objc2::rc::Retained;
 use objc2::runtime::AnyObject;
 use objc2::{class, msg_send};
@@ -112,6 +112,41 @@ impl ICloudKVStore {
             }
         }
     }
+
+    /// Sync account nicknames and colors (lowercased email -> appearance) to iCloud
+    pub fn sync_account_appearances(&self, appearances: &HashMap<String, AccountAppearance>) -> Result<(), String> {
+        let json = serde_json::to_string(appearances).map_err(|e| e.to_string())?;
+        let key = NSString::from_str("posta_account_appearances");
+        let value = NSString::from_str(&json);
+
+        unsafe {
+            let _: () = msg_send![self.store_ptr, setString: &*value, forKey: &*key];
+            let _: bool = msg_send![self.store_ptr, synchronize];
+        }
+        Ok(())
+    }
+
+    /// Load account nicknames and colors (lowercased email -> appearance) from iCloud
+    pub fn load_account_appearances(&self) -> Result<Option<HashMap<String, AccountAppearance>>, String> {
+        let key = NSString::from_str("posta_account_appearances");
+
+        unsafe {
+            let value: Option<Retained<NSString>> = msg_send![self.store_ptr, stringForKey: &*key];
+
+            match value {
+                Some(s) => {
+                    let json = s.to_string();
+                    if json.is_empty() {
+                        return Ok(None);
+                    }
+                    let appearances: HashMap<String, AccountAppearance> =
+                        serde_json::from_str(&json).map_err(|e| e.to_string())?;
+                    Ok(Some(appearances))
+                }
+                None => Ok(None),
+            }
+        }
+    }
 }
 
 impl Default for ICloudKVStore {
@@ -9,7 +9,7 @@ pub use kvstore::ICloudKVStore;
 // No-op stub for non-Apple platforms
 #[cfg(not(any(target_os = "ios", target_os = "macos")))]
 mod stub {
-    use crate::models::Card;
+    use crate::models::{AccountAppearance, Card};
     use std::collections::HashMap;
 
     pub struct ICloudKVStore;
@@ -34,6 +34,14 @@ mod stub {
         pub fn load_account_mappings(&self) -> Result<Option<HashMap<String, String>>, String> {
             Ok(None)
         }
+
+        pub fn sync_account_appearances(&self, _appearances: &HashMap<String, AccountAppearance>) -> Result<(), String> {
+            Ok(())
+        }
+
+        pub fn load_account_appearances(&self) -> Result<Option<HashMap<String, AccountAppearance>>, String> {
+            Ok(None)
+        }
     }
 }
 
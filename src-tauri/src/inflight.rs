@@ -0,0 +1,164 @@
+// Cancellable in-flight requests
+//
+// Long fetches can be tagged with a caller-chosen request id. The frontend
+// cancels stale ids (e.g. after switching accounts) so late responses never
+// reach the UI or any cache written at the end of the request.
+
+use futures::future::{AbortHandle, AbortRegistration, Abortable};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Mutex;
+
+/// Error returned by a request that was cancelled before it finished
+pub const CANCELLED_ERROR: &str = "Request cancelled";
+
+#[derive(Default)]
+pub struct InflightRequests {
+    /// Abort handle per request id, with the generation of the task holding it
+    handles: HashMap<String, (u64, AbortHandle)>,
+    next_generation: u64,
+}
+
+impl InflightRequests {
+    /// Track a new request, aborting any earlier one that reused the id.
+    /// Returns the generation the task must pass back to `finish`.
+    fn register(&mut self, request_id: &str) -> (u64, AbortRegistration) {
+        let (handle, registration) = AbortHandle::new_pair();
+        let generation = self.next_generation;
+        self.next_generation += 1;
+        if let Some((_, previous)) = self.handles.insert(request_id.to_string(), (generation, handle)) {
+            previous.abort();
+        }
+        (generation, registration)
+    }
+
+    /// Abort a request. Returns false if it already finished or never existed.
+    pub fn cancel(&mut self, request_id: &str) -> bool {
+        match self.handles.remove(request_id) {
+            Some((_, handle)) => {
+                handle.abort();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Stop tracking a finished task, unless a newer task has taken over its id
+    fn finish(&mut self, request_id: &str, generation: u64) {
+        if self.handles.get(request_id).is_some_and(|(current, _)| *current == generation) {
+            self.handles.remove(request_id);
+        }
+    }
+}
+
+/// Run `task`, making it abortable via `request_id` when one is given.
+/// A cancelled task stops at its next await point, so work after that
+/// point (such as cache writes) never happens.
+pub async fn run_cancellable<T, F>(
+    inflight: &Mutex<InflightRequests>,
+    request_id: Option<&str>,
+    task: F,
+) -> Result<T, String>
+where
+    F: Future<Output = Result<T, String>>,
+{
+    let Some(request_id) = request_id else {
+        return task.await;
+    };
+
+    let (generation, registration) = inflight.lock().map_err(|_| "Lock error")?.register(request_id);
+    let result = Abortable::new(task, registration).await;
+
+    if let Ok(mut inflight) = inflight.lock() {
+        inflight.finish(request_id, generation);
+    }
+
+    result.map_err(|_| CANCELLED_ERROR.to_string())?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use tokio::sync::oneshot;
+
+    #[tokio::test]
+    async fn cancelled_fetch_does_not_persist() {
+        let inflight = Arc::new(Mutex::new(InflightRequests::default()));
+        let cache: Arc<Mutex<Vec<String>>> = Arc::default();
+        let (respond, response) = oneshot::channel::<String>();
+
+        let task = {
+            let inflight = inflight.clone();
+            let cache = cache.clone();
+            tokio::spawn(async move {
+                run_cancellable(&inflight, Some("card-1"), async {
+                    let page = response.await.map_err(|e| e.to_string())?;
+                    cache.lock().unwrap().push(page);
+                    Ok(())
+                })
+                .await
+            })
+        };
+
+        // Let the fetch register and start waiting on its "network" response
+        tokio::task::yield_now().await;
+        while !inflight.lock().unwrap().handles.contains_key("card-1") {
+            tokio::task::yield_now().await;
+        }
+
+        assert!(inflight.lock().unwrap().cancel("card-1"));
+        let _ = respond.send("page 1".to_string());
+
+        let result = task.await.unwrap();
+        assert_eq!(result, Err(CANCELLED_ERROR.to_string()));
+        assert!(cache.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn reused_id_stays_cancellable_after_the_older_task_ends() {
+        let inflight = Arc::new(Mutex::new(InflightRequests::default()));
+        let spawn_waiting = |inflight: Arc<Mutex<InflightRequests>>| {
+            let (respond, response) = oneshot::channel::<()>();
+            let task = tokio::spawn(async move {
+                run_cancellable(&inflight, Some("card-1"), async {
+                    response.await.map_err(|e| e.to_string())
+                })
+                .await
+            });
+            (respond, task)
+        };
+
+        let (_respond_first, first) = spawn_waiting(inflight.clone());
+        while !inflight.lock().unwrap().handles.contains_key("card-1") {
+            tokio::task::yield_now().await;
+        }
+
+        // The second request reuses the id, aborting the first
+        let (_respond_second, second) = spawn_waiting(inflight.clone());
+        while inflight.lock().unwrap().handles["card-1"].0 == 0 {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(first.await.unwrap(), Err(CANCELLED_ERROR.to_string()));
+
+        // The first task's cleanup left the second one's handle in place
+        assert!(inflight.lock().unwrap().cancel("card-1"));
+        assert_eq!(second.await.unwrap(), Err(CANCELLED_ERROR.to_string()));
+    }
+
+    #[tokio::test]
+    async fn untagged_requests_run_normally() {
+        let inflight = Mutex::new(InflightRequests::default());
+        let result = run_cancellable(&inflight, None, async { Ok::<_, String>(42) }).await;
+        assert_eq!(result, Ok(42));
+    }
+
+    #[tokio::test]
+    async fn finished_requests_are_untracked() {
+        let inflight = Mutex::new(InflightRequests::default());
+        run_cancellable(&inflight, Some("r1"), async { Ok::<_, String>(()) })
+            .await
+            .unwrap();
+        assert!(!inflight.lock().unwrap().cancel("r1"));
+    }
+}
@@ -1,13 +1,21 @@
 // Gmail client - Posta
 
 pub mod auth;
+pub mod background;
 pub mod cache;
 pub mod calendar;
+pub mod card_bundle;
 pub mod commands;
+pub mod debounce;
+pub mod diagnostics;
+pub mod drive;
 pub mod gmail;
 pub mod icloud;
+pub mod inflight;
 pub mod models;
+pub mod pdf;
 pub mod people;
+pub mod rate_limit;
 pub mod ai;
 
 use commands::AppState;
@@ -23,8 +31,38 @@ pub struct MailtoData {
     pub body: String,
 }
 
+impl MailtoData {
+    /// Whether the link has something to compose: at least one recipient,
+    /// subject or body, and every recipient looks like an address
+    pub fn is_valid(&self) -> bool {
+        let recipients: Vec<&str> = [&self.to, &self.cc, &self.bcc]
+            .iter()
+            .flat_map(|field| field.split(','))
+            .map(str::trim)
+            .filter(|r| !r.is_empty())
+            .collect();
+
+        if !recipients.iter().all(|r| looks_like_address(r)) {
+            return false;
+        }
+        !recipients.is_empty() || !self.subject.trim().is_empty() || !self.body.trim().is_empty()
+    }
+}
+
+fn looks_like_address(recipient: &str) -> bool {
+    match recipient.split_once('@') {
+        Some((local, domain)) => {
+            !local.is_empty()
+                && !domain.is_empty()
+                && !domain.contains('@')
+                && !recipient.chars().any(|c| c.is_whitespace() || c.is_control())
+        }
+        None => false,
+    }
+}
+
 /// Parse a mailto: URL into structured data
-fn parse_mailto(url: &str) -> MailtoData {
+pub(crate) fn parse_mailto(url: &str) -> MailtoData {
     let mut data = MailtoData {
         to: String::new(),
         cc: String::new(),
@@ -33,8 +71,11 @@ fn parse_mailto(url: &str) -> MailtoData {
         body: String::new(),
     };
 
-    // Remove "mailto:" prefix
-    let url = url.strip_prefix("mailto:").unwrap_or(url);
+    // Remove the "mailto:" prefix; URL schemes are case-insensitive
+    let url = match url.get(..7) {
+        Some(scheme) if scheme.eq_ignore_ascii_case("mailto:") => &url[7..],
+        _ => url,
+    };
 
     // Split by ? to get the email and query params
     let (email_part, query_part) = match url.split_once('?') {
@@ -90,8 +131,12 @@ pub fn run() {
                     // The payload is a JSON array of URLs
                     if let Ok(url_list) = serde_json::from_str::<Vec<String>>(urls) {
                         for url in url_list {
-                            if url.starts_with("mailto:") {
+                            if url.get(..7).is_some_and(|s| s.eq_ignore_ascii_case("mailto:")) {
                                 let mailto_data = parse_mailto(&url);
+                                if !mailto_data.is_valid() {
+                                    tracing::warn!("Ignoring malformed mailto link: {}", url);
+                                    continue;
+                                }
                                 tracing::info!("Received mailto: to={}", mailto_data.to);
 
                                 // Emit to frontend
@@ -107,56 +152,118 @@ pub fn run() {
                     }
                 });
             }
+
+            tauri::async_runtime::spawn(background::run_followup_checker(app.handle().clone()));
+            tauri::async_runtime::spawn(background::run_snooze_waker(app.handle().clone()));
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
             commands::init_app,
+            commands::parse_mailto_command,
             commands::configure_auth,
             commands::get_stored_credentials,
             commands::start_oauth_flow,
+            commands::get_requested_scopes,
+            commands::set_requested_scopes,
             commands::complete_oauth_flow,
             commands::run_oauth_flow,
             commands::get_accounts,
             commands::delete_account,
+            commands::account_health,
+            commands::merge_accounts_by_email,
+            commands::set_account_appearance,
             commands::update_account_signature,
             commands::get_cards,
             commands::create_card,
+            commands::import_cards_from_url,
             commands::update_card,
             commands::delete_card,
             commands::reorder_cards,
+            commands::move_card_to_account,
             commands::fetch_threads,
             commands::fetch_threads_paginated,
+            commands::prefetch_next_page,
+            commands::cancel_request,
             commands::sync_threads_incremental,
             commands::search_threads_preview,
+            commands::interaction_history,
             commands::modify_threads,
+            commands::trash_threads,
+            commands::untrash_threads,
+            commands::delete_threads_permanently,
+            commands::preview_bulk_action,
+            commands::snooze_card,
+            commands::create_label_and_filter_from_card,
+            commands::create_contact_filter,
             commands::get_thread_details,
+            commands::get_cached_thread_details,
+            commands::get_message_html,
+            commands::open_thread,
+            commands::get_message,
+            commands::get_read_receipt_request,
+            commands::get_full_conversation,
+            commands::validate_attachment,
             commands::send_email,
+            commands::recent_sent,
             commands::reply_to_thread,
+            commands::forward_email,
+            commands::prepare_reply,
+            commands::awaiting_reply,
+            commands::sender_attachments,
+            commands::set_followup_reminder,
+            commands::list_followups,
+            commands::cancel_followup,
             commands::send_reaction,
             commands::get_cached_card_threads,
             commands::save_cached_card_threads,
+            commands::card_bucket_unread,
+            commands::card_view,
             commands::get_cached_card_events,
             commands::save_cached_card_events,
             commands::clear_card_cache,
             commands::download_attachment,
             commands::open_attachment,
             commands::save_attachment,
+            commands::export_thread_pdf,
             commands::list_labels,
+            commands::validate_cards,
+            commands::repair_card_labels,
             commands::save_draft,
             commands::delete_draft,
             commands::rsvp_calendar_event,
             commands::get_calendar_rsvp_status,
             commands::pull_from_icloud,
             commands::force_icloud_sync,
+            commands::pause_background,
+            commands::resume_background,
+            commands::is_background_paused,
+            commands::get_inline_fetch_budget,
+            commands::set_inline_fetch_budget,
+            commands::storage_usage,
             commands::fetch_contacts,
             commands::search_contacts,
             commands::list_calendars,
             commands::fetch_calendar_events,
+            commands::search_all_upcoming_events,
             commands::create_calendar_event,
+            commands::check_freebusy,
+            commands::export_availability_text,
+            commands::import_agenda,
+            commands::export_calendar_ics,
+            commands::set_event_color,
+            commands::auto_color_events,
             commands::move_calendar_event,
             commands::delete_calendar_event,
+            commands::remove_cancelled_event,
             commands::update_calendar_event,
             commands::suggest_replies,
+            commands::summarize_thread,
+            commands::compose_draft,
+            commands::weekly_digest,
+            commands::set_ai_provider,
+            commands::get_ai_provider,
+            commands::get_api_diagnostics,
+            commands::get_quota_status,
         ])
         .build(tauri::generate_context!())
         .expect("error while building tauri application");
@@ -194,3 +301,40 @@ pub fn run() {
         }
     });
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn valid_mailto_parses() {
+        let data = parse_mailto("mailto:ann@example.com?cc=bob@example.com&subject=Hello%20there");
+        assert_eq!(data.to, "ann@example.com");
+        assert_eq!(data.subject, "Hello there");
+        assert!(data.is_valid());
+
+        // A subject alone is enough to open compose
+        assert!(parse_mailto("mailto:?subject=Feedback").is_valid());
+    }
+
+    #[test]
+    fn uppercase_scheme_is_stripped() {
+        let data = parse_mailto("MAILTO:ann@x.com?Subject=Hi");
+        assert_eq!(data.to, "ann@x.com");
+        assert_eq!(data.subject, "Hi");
+        assert!(data.is_valid());
+        assert_eq!(parse_mailto("MailTo:ann@x.com").to, "ann@x.com");
+    }
+
+    #[test]
+    fn empty_mailto_is_invalid() {
+        assert!(!parse_mailto("mailto:").is_valid());
+        assert!(!parse_mailto("mailto:?foo=bar").is_valid());
+    }
+
+    #[test]
+    fn malformed_recipient_is_invalid() {
+        assert!(!parse_mailto("mailto:not-an-address?subject=Hi").is_valid());
+        assert!(!parse_mailto("mailto:ann@example.com,bob%20smith@").is_valid());
+    }
+}
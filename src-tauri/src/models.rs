@@ -14,10 +14,23 @@ pub struct Account {
     pub picture: Option<String>,
     #[serde(default)]
     pub signature: Option<String>,
+    /// Shown instead of the email address in the account switcher
+    #[serde(default)]
+    pub nickname: Option<String>,
+    /// Tint for the account's cards
+    #[serde(default)]
+    pub color: Option<String>,
     #[serde(skip_serializing)]
     pub refresh_token_ref: Option<String>,
 }
 
+/// An account's nickname and color, synced across devices by email
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AccountAppearance {
+    pub nickname: Option<String>,
+    pub color: Option<String>,
+}
+
 impl Account {
     pub fn new(email: String, picture: Option<String>) -> Self {
         Self {
@@ -25,6 +38,8 @@ impl Account {
             email,
             picture,
             signature: None,
+            nickname: None,
+            color: None,
             refresh_token_ref: None,
         }
     }
@@ -64,8 +79,8 @@ impl Card {
             position,
             collapsed: false,
             color: None,
-            group_by: "date".to_string(),
-            card_type: "email".to_string(),
+            group_by: default_group_by(),
+            card_type: default_card_type(),
         }
     }
 
@@ -78,7 +93,7 @@ impl Card {
             position,
             collapsed: false,
             color: None,
-            group_by: "date".to_string(),
+            group_by: default_group_by(),
             card_type: "calendar".to_string(),
         }
     }
@@ -95,6 +110,10 @@ pub struct Attachment {
     pub inline_data: Option<String>,
     /// Content-ID for inline images (used for cid: references in HTML)
     pub content_id: Option<String>,
+    /// Shown inside the message body (signature logos, embedded images)
+    /// rather than offered as a file; not counted by `has_attachment`
+    #[serde(default)]
+    pub is_inline: bool,
 }
 
 impl Attachment {
@@ -102,10 +121,12 @@ impl Attachment {
         self.mime_type.starts_with("image/")
     }
 
+    /// Invites are recognised by MIME type first, since some senders attach
+    /// the text/calendar part without a filename
     pub fn is_calendar(&self) -> bool {
-        self.mime_type == "text/calendar"
-            || self.mime_type == "application/ics"
-            || self.filename.ends_with(".ics")
+        self.mime_type.eq_ignore_ascii_case("text/calendar")
+            || self.mime_type.eq_ignore_ascii_case("application/ics")
+            || self.filename.to_ascii_lowercase().ends_with(".ics")
     }
 }
 
@@ -136,6 +157,21 @@ pub struct CalendarEvent {
     pub status: Option<String>,
     /// User's response status: accepted, tentative, declined, needsAction
     pub response_status: Option<String>,
+    /// Revision number (SEQUENCE); organizers bump it on every change
+    #[serde(default)]
+    pub sequence: i32,
+    /// True when this invite revises an earlier version of the event
+    #[serde(default)]
+    pub is_update: bool,
+    /// True for METHOD:CANCEL or STATUS:CANCELLED invites
+    #[serde(default)]
+    pub cancelled: bool,
+    /// Raw RRULE for recurring events
+    #[serde(default)]
+    pub recurrence: Option<String>,
+    /// Upcoming instance start times (Unix ms) expanded from the RRULE
+    #[serde(default)]
+    pub occurrences: Vec<i64>,
 }
 
 /// Attachment for outgoing emails (compose/reply)
@@ -145,6 +181,10 @@ pub struct SendAttachment {
     pub mime_type: String,
     /// Base64-encoded file data
     pub data: String,
+    /// Content-ID (without angle brackets) of an inline part the HTML body
+    /// refers to as `cid:`; such parts are sent inline rather than as files
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_id: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -162,6 +202,13 @@ pub struct Thread {
     pub attachments: Vec<Attachment>,
     /// Calendar event if this thread contains a calendar invite
     pub calendar_event: Option<CalendarEvent>,
+    /// Gmail inbox category: Primary, Social, Promotions, Updates or Forums
+    #[serde(default = "default_category")]
+    pub category: String,
+}
+
+fn default_category() -> String {
+    "Primary".to_string()
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -171,6 +218,10 @@ pub struct Message {
     pub from_addr: String,
     pub to_addrs: Vec<String>,
     pub date: DateTime<Utc>,
+    #[serde(default)]
+    pub subject: String,
+    #[serde(default)]
+    pub label_ids: Vec<String>,
     pub body_text: Option<String>,
     pub body_html: Option<String>,
 }
@@ -198,6 +249,32 @@ pub struct FetchResult {
     pub next_page_token: Option<String>,
 }
 
+/// Local reminder to follow up on a sent thread if nobody replies
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FollowupReminder {
+    pub account_id: String,
+    pub thread_id: String,
+    /// Unix milliseconds when the reminder fires
+    pub remind_at: i64,
+    /// Unix milliseconds when it was set; replies after this cancel it
+    pub created_at: i64,
+    /// Who the user is waiting on (To of their last message)
+    pub recipient: String,
+    pub subject: String,
+}
+
+/// A message sent from Posta, mirrored locally for the "recently sent" view
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SentLogEntry {
+    pub account_id: String,
+    /// Thread the message was sent into; None for new messages
+    pub thread_id: Option<String>,
+    pub recipient: String,
+    pub subject: String,
+    /// Unix milliseconds
+    pub sent_at: i64,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum DateBucket {
     Today,
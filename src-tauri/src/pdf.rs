@@ -0,0 +1,387 @@
+// Minimal PDF writer for exporting threads
+//
+// Produces paginated US Letter documents using the built-in Helvetica fonts
+// (WinAnsi encoding) and embeds JPEG images directly via DCTDecode.
+
+const PAGE_WIDTH: f32 = 612.0;
+const PAGE_HEIGHT: f32 = 792.0;
+const MARGIN: f32 = 54.0;
+const CONTENT_WIDTH: f32 = PAGE_WIDTH - 2.0 * MARGIN;
+const MAX_IMAGE_HEIGHT: f32 = 320.0;
+/// Average Helvetica glyph width as a fraction of the font size
+const AVG_CHAR_WIDTH: f32 = 0.52;
+
+/// A unit of content laid out top to bottom
+pub enum Block {
+    Heading(String),
+    Meta(String),
+    Paragraph(String),
+    Rule,
+    Image(JpegImage),
+}
+
+pub struct JpegImage {
+    data: Vec<u8>,
+    width: u32,
+    height: u32,
+    components: u8,
+}
+
+impl JpegImage {
+    /// Parse the frame header of a baseline or progressive JPEG
+    pub fn from_bytes(data: Vec<u8>) -> Option<Self> {
+        if !data.starts_with(&[0xFF, 0xD8]) {
+            return None;
+        }
+        let mut i = 2;
+        while i + 4 <= data.len() {
+            if data[i] != 0xFF {
+                return None;
+            }
+            let marker = data[i + 1];
+            let len = u16::from_be_bytes([data[i + 2], data[i + 3]]) as usize;
+            // SOF0..SOF15, excluding DHT (C4), JPG (C8) and DAC (CC)
+            if (0xC0..=0xCF).contains(&marker) && ![0xC4, 0xC8, 0xCC].contains(&marker) {
+                if i + 10 > data.len() {
+                    return None;
+                }
+                let height = u16::from_be_bytes([data[i + 5], data[i + 6]]) as u32;
+                let width = u16::from_be_bytes([data[i + 7], data[i + 8]]) as u32;
+                let components = data[i + 9];
+                if width == 0 || height == 0 {
+                    return None;
+                }
+                return Some(Self { data, width, height, components });
+            }
+            i += 2 + len;
+        }
+        None
+    }
+}
+
+struct PageContent {
+    ops: Vec<u8>,
+}
+
+struct Layout {
+    pages: Vec<PageContent>,
+    y: f32,
+    images: Vec<JpegImage>,
+}
+
+impl Layout {
+    fn new() -> Self {
+        Self {
+            pages: vec![PageContent { ops: Vec::new() }],
+            y: PAGE_HEIGHT - MARGIN,
+            images: Vec::new(),
+        }
+    }
+
+    /// Ensure `height` points fit on the current page, starting a new one if not
+    fn reserve(&mut self, height: f32) {
+        if self.y - height < MARGIN {
+            self.pages.push(PageContent { ops: Vec::new() });
+            self.y = PAGE_HEIGHT - MARGIN;
+        }
+    }
+
+    fn ops(&mut self) -> &mut Vec<u8> {
+        &mut self.pages.last_mut().expect("layout always has a page").ops
+    }
+
+    fn text(&mut self, text: &str, font: &str, size: f32, gray: f32) {
+        let line_height = size * 1.4;
+        let max_chars = (CONTENT_WIDTH / (size * AVG_CHAR_WIDTH)) as usize;
+        for line in wrap_text(text, max_chars) {
+            self.reserve(line_height);
+            self.y -= line_height;
+            let y = self.y;
+            let ops = self.ops();
+            ops.extend_from_slice(
+                format!("BT {} g /{} {} Tf {} {} Td ", gray, font, size, MARGIN, y).as_bytes(),
+            );
+            ops.extend_from_slice(&pdf_string(&line));
+            ops.extend_from_slice(b" Tj ET\n");
+        }
+    }
+
+    fn gap(&mut self, height: f32) {
+        self.y -= height;
+    }
+
+    fn rule(&mut self) {
+        self.reserve(12.0);
+        self.y -= 6.0;
+        let y = self.y;
+        self.ops().extend_from_slice(
+            format!("0.8 G 0.5 w {} {} m {} {} l S\n", MARGIN, y, PAGE_WIDTH - MARGIN, y).as_bytes(),
+        );
+        self.y -= 6.0;
+    }
+
+    fn image(&mut self, image: JpegImage) {
+        let scale = (CONTENT_WIDTH / image.width as f32)
+            .min(MAX_IMAGE_HEIGHT / image.height as f32)
+            .min(1.0);
+        let width = image.width as f32 * scale;
+        let height = image.height as f32 * scale;
+
+        self.reserve(height + 6.0);
+        self.y -= height + 6.0;
+        let name = format!("Im{}", self.images.len());
+        let y = self.y;
+        self.ops().extend_from_slice(
+            format!("q {} 0 0 {} {} {} cm /{} Do Q\n", width, height, MARGIN, y, name).as_bytes(),
+        );
+        self.images.push(image);
+    }
+}
+
+/// Word-wrap text to at most `max_chars` per line, preserving explicit line breaks
+fn wrap_text(text: &str, max_chars: usize) -> Vec<String> {
+    let max_chars = max_chars.max(1);
+    let mut lines = Vec::new();
+
+    for raw_line in text.lines() {
+        let mut current = String::new();
+        for word in raw_line.split_whitespace() {
+            let mut word = word.to_string();
+            // Hard-break words that can never fit on a line
+            while word.chars().count() > max_chars {
+                if !current.is_empty() {
+                    lines.push(std::mem::take(&mut current));
+                }
+                let split: String = word.chars().take(max_chars).collect();
+                word = word.chars().skip(max_chars).collect();
+                lines.push(split);
+            }
+            let needed = if current.is_empty() { 0 } else { 1 } + word.chars().count();
+            if current.chars().count() + needed > max_chars {
+                lines.push(std::mem::take(&mut current));
+            }
+            if !current.is_empty() {
+                current.push(' ');
+            }
+            current.push_str(&word);
+        }
+        lines.push(current);
+    }
+
+    lines
+}
+
+/// Encode text as a PDF literal string in WinAnsi, replacing unsupported characters
+fn pdf_string(text: &str) -> Vec<u8> {
+    let mut out = vec![b'('];
+    for c in text.chars() {
+        let byte = match c {
+            '(' | ')' | '\\' => {
+                out.push(b'\\');
+                c as u8
+            }
+            '\u{2018}' | '\u{2019}' => b'\'',
+            '\u{201C}' | '\u{201D}' => b'"',
+            '\u{2013}' | '\u{2014}' => b'-',
+            '\u{2026}' => 0x85,
+            '\u{20AC}' => 0x80,
+            '\t' => b' ',
+            ' '..='~' => c as u8,
+            '\u{A0}'..='\u{FF}' => c as u32 as u8,
+            _ => b'?',
+        };
+        out.push(byte);
+    }
+    out.push(b')');
+    out
+}
+
+/// Lay out blocks and serialize them as a complete PDF document
+pub fn render(blocks: Vec<Block>) -> Vec<u8> {
+    let mut layout = Layout::new();
+
+    for block in blocks {
+        match block {
+            Block::Heading(text) => {
+                layout.text(&text, "F2", 14.0, 0.0);
+                layout.gap(4.0);
+            }
+            Block::Meta(text) => layout.text(&text, "F1", 9.0, 0.4),
+            Block::Paragraph(text) => {
+                layout.gap(6.0);
+                layout.text(&text, "F1", 10.0, 0.0);
+            }
+            Block::Rule => layout.rule(),
+            Block::Image(image) => layout.image(image),
+        }
+    }
+
+    serialize(layout)
+}
+
+fn serialize(layout: Layout) -> Vec<u8> {
+    // Object layout: 1 catalog, 2 page tree, 3-4 fonts, then images, then (content, page) pairs
+    let first_image = 5;
+    let first_page = first_image + layout.images.len();
+    let object_count = first_page + layout.pages.len() * 2 - 1;
+
+    let mut out: Vec<u8> = b"%PDF-1.4\n%\xE2\xE3\xCF\xD3\n".to_vec();
+    let mut offsets = vec![0usize; object_count + 1];
+
+    let mut write_object = |out: &mut Vec<u8>, id: usize, body: &[u8]| {
+        offsets[id] = out.len();
+        out.extend_from_slice(format!("{} 0 obj\n", id).as_bytes());
+        out.extend_from_slice(body);
+        out.extend_from_slice(b"\nendobj\n");
+    };
+
+    let page_ids: Vec<usize> = (0..layout.pages.len()).map(|i| first_page + i * 2 + 1).collect();
+
+    write_object(&mut out, 1, b"<< /Type /Catalog /Pages 2 0 R >>");
+    let kids: Vec<String> = page_ids.iter().map(|id| format!("{} 0 R", id)).collect();
+    write_object(
+        &mut out,
+        2,
+        format!("<< /Type /Pages /Kids [{}] /Count {} >>", kids.join(" "), page_ids.len()).as_bytes(),
+    );
+    write_object(
+        &mut out,
+        3,
+        b"<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>",
+    );
+    write_object(
+        &mut out,
+        4,
+        b"<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica-Bold /Encoding /WinAnsiEncoding >>",
+    );
+
+    for (i, image) in layout.images.iter().enumerate() {
+        let color_space = match image.components {
+            1 => "/DeviceGray",
+            4 => "/DeviceCMYK",
+            _ => "/DeviceRGB",
+        };
+        let mut body = format!(
+            "<< /Type /XObject /Subtype /Image /Width {} /Height {} /ColorSpace {} /BitsPerComponent 8 /Filter /DCTDecode /Length {} >>\nstream\n",
+            image.width,
+            image.height,
+            color_space,
+            image.data.len()
+        )
+        .into_bytes();
+        body.extend_from_slice(&image.data);
+        body.extend_from_slice(b"\nendstream");
+        write_object(&mut out, first_image + i, &body);
+    }
+
+    let xobjects: String = (0..layout.images.len())
+        .map(|i| format!("/Im{} {} 0 R", i, first_image + i))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    for (i, page) in layout.pages.iter().enumerate() {
+        let content_id = first_page + i * 2;
+        let mut content = format!("<< /Length {} >>\nstream\n", page.ops.len()).into_bytes();
+        content.extend_from_slice(&page.ops);
+        content.extend_from_slice(b"\nendstream");
+        write_object(&mut out, content_id, &content);
+
+        write_object(
+            &mut out,
+            content_id + 1,
+            format!(
+                "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] /Resources << /Font << /F1 3 0 R /F2 4 0 R >> /XObject << {} >> >> /Contents {} 0 R >>",
+                PAGE_WIDTH, PAGE_HEIGHT, xobjects, content_id
+            )
+            .as_bytes(),
+        );
+    }
+
+    let xref_offset = out.len();
+    out.extend_from_slice(format!("xref\n0 {}\n0000000000 65535 f \n", object_count + 1).as_bytes());
+    for offset in &offsets[1..] {
+        out.extend_from_slice(format!("{:010} 00000 n \n", offset).as_bytes());
+    }
+    out.extend_from_slice(
+        format!(
+            "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+            object_count + 1,
+            xref_offset
+        )
+        .as_bytes(),
+    );
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn count(haystack: &[u8], needle: &[u8]) -> usize {
+        haystack.windows(needle.len()).filter(|w| *w == needle).count()
+    }
+
+    #[test]
+    fn renders_valid_pdf() {
+        let pdf = render(vec![
+            Block::Heading("Quarterly planning".to_string()),
+            Block::Rule,
+            Block::Meta("From: Jane <jane@example.com>".to_string()),
+            Block::Paragraph("Hello (world) \\ café".to_string()),
+        ]);
+
+        assert!(!pdf.is_empty());
+        assert!(pdf.starts_with(b"%PDF-1.4"));
+        assert!(pdf.ends_with(b"%%EOF\n"));
+        assert_eq!(count(&pdf, b"/Type /Page "), 1);
+    }
+
+    #[test]
+    fn paginates_long_threads() {
+        let long_body = "A line of message text.\n".repeat(400);
+        let pdf = render(vec![Block::Paragraph(long_body)]);
+        assert!(count(&pdf, b"/Type /Page ") > 1);
+    }
+
+    #[test]
+    fn xref_offsets_point_at_objects() {
+        let pdf = render(vec![Block::Paragraph("Body".to_string())]);
+        let text = String::from_utf8_lossy(&pdf);
+        let xref_start = text.find("xref\n").unwrap();
+        let entries: Vec<usize> = text[xref_start..]
+            .lines()
+            .skip(3)
+            .take_while(|l| l.ends_with(" n "))
+            .map(|l| l[..10].parse().unwrap())
+            .collect();
+        assert!(!entries.is_empty());
+        for (i, offset) in entries.iter().enumerate() {
+            assert!(pdf[*offset..].starts_with(format!("{} 0 obj", i + 1).as_bytes()));
+        }
+    }
+
+    #[test]
+    fn escapes_and_replaces_unsupported_characters() {
+        assert_eq!(pdf_string("a(b)c\\"), b"(a\\(b\\)c\\\\)".to_vec());
+        assert_eq!(pdf_string("é☕"), vec![b'(', 0xE9, b'?', b')']);
+    }
+
+    #[test]
+    fn wraps_long_lines() {
+        let lines = wrap_text("one two three four", 9);
+        assert_eq!(lines, vec!["one two", "three", "four"]);
+        assert_eq!(wrap_text("abcdefghij", 4), vec!["abcd", "efgh", "ij"]);
+    }
+
+    #[test]
+    fn reads_jpeg_dimensions() {
+        let mut jpeg = vec![0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x04, 0x00, 0x00];
+        jpeg.extend_from_slice(&[0xFF, 0xC0, 0x00, 0x11, 0x08, 0x00, 0x10, 0x00, 0x20, 0x03]);
+        jpeg.extend_from_slice(&[0; 9]);
+        let image = JpegImage::from_bytes(jpeg).unwrap();
+        assert_eq!((image.width, image.height, image.components), (32, 16, 3));
+
+        assert!(JpegImage::from_bytes(b"\x89PNG".to_vec()).is_none());
+    }
+}
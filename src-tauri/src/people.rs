@@ -1,6 +1,8 @@
 // Google People API client for contacts
 
+use crate::models::{Thread, ThreadGroup};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 const PEOPLE_API_BASE: &str = "https://people.googleapis.com/v1";
 
@@ -164,7 +166,7 @@ impl PeopleClient {
             let body = resp.text().await.unwrap_or_default();
             // If 403, the user might not have granted contacts scope
             if status.as_u16() == 403 {
-                return Err("Contacts permission not granted. Please re-authenticate to enable contact search.".to_string());
+                return Err("Contacts scope not granted. Enable contacts access in Settings and sign in again.".to_string());
             }
             return Err(format!("People search error ({}): {}", status, body));
         }
@@ -221,3 +223,173 @@ impl PeopleClient {
         })
     }
 }
+
+// ============ Thread list participants ============
+
+/// The person a thread row shows: name and photo from contacts when known,
+/// plus a monogram for when there's no photo
+#[derive(Debug, Clone, Serialize)]
+pub struct ThreadParticipant {
+    pub email: String,
+    pub name: Option<String>,
+    pub photo_url: Option<String>,
+    pub monogram: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CardViewThread {
+    #[serde(flatten)]
+    pub thread: Thread,
+    pub participant: Option<ThreadParticipant>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CardViewGroup {
+    pub label: String,
+    pub threads: Vec<CardViewThread>,
+}
+
+/// The most recent participant other than the account owner; the owner when
+/// they are the only one (e.g. notes to self)
+pub fn primary_participant<'a>(thread: &'a Thread, account_email: &str) -> Option<&'a str> {
+    thread
+        .participants
+        .iter()
+        .rev()
+        .find(|p| !p.eq_ignore_ascii_case(account_email))
+        .or_else(|| thread.participants.first())
+        .map(String::as_str)
+}
+
+/// Up to two initials from a name, or the first letter of the email
+pub fn monogram(name: Option<&str>, email: &str) -> String {
+    let initials: String = name
+        .unwrap_or("")
+        .split_whitespace()
+        .filter_map(|word| word.chars().find(|c| c.is_alphanumeric()))
+        .take(2)
+        .flat_map(char::to_uppercase)
+        .collect();
+    if !initials.is_empty() {
+        return initials;
+    }
+    email
+        .chars()
+        .find(|c| c.is_alphanumeric())
+        .map(|c| c.to_uppercase().collect())
+        .unwrap_or_else(|| "?".to_string())
+}
+
+/// Contacts keyed by lowercased email, as returned by the contact cache
+pub fn resolve_participant(email: &str, contacts: &HashMap<String, Contact>) -> ThreadParticipant {
+    let contact = contacts.get(&email.to_lowercase());
+    let name = contact.and_then(|c| c.display_name.clone()).filter(|n| !n.trim().is_empty());
+    ThreadParticipant {
+        email: email.to_string(),
+        monogram: monogram(name.as_deref(), email),
+        photo_url: contact.and_then(|c| c.photo_url.clone()),
+        name,
+    }
+}
+
+/// Emails that need resolving to render `groups`, without duplicates
+pub fn primary_participant_emails(groups: &[ThreadGroup], account_email: &str) -> Vec<String> {
+    let mut emails: Vec<String> = Vec::new();
+    for thread in groups.iter().flat_map(|g| &g.threads) {
+        if let Some(email) = primary_participant(thread, account_email) {
+            let email = email.to_lowercase();
+            if !emails.contains(&email) {
+                emails.push(email);
+            }
+        }
+    }
+    emails
+}
+
+/// Attach each thread's primary participant to the card's thread groups
+pub fn build_card_view(
+    groups: Vec<ThreadGroup>,
+    account_email: &str,
+    contacts: &HashMap<String, Contact>,
+) -> Vec<CardViewGroup> {
+    groups
+        .into_iter()
+        .map(|group| CardViewGroup {
+            label: group.label,
+            threads: group
+                .threads
+                .into_iter()
+                .map(|thread| CardViewThread {
+                    participant: primary_participant(&thread, account_email)
+                        .map(|email| resolve_participant(email, contacts)),
+                    thread,
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn thread(id: &str, participants: &[&str]) -> Thread {
+        Thread {
+            gmail_thread_id: id.to_string(),
+            account_id: "acc".to_string(),
+            subject: "Subject".to_string(),
+            snippet: String::new(),
+            last_message_date: Utc::now(),
+            unread_count: 0,
+            labels: Vec::new(),
+            participants: participants.iter().map(|p| p.to_string()).collect(),
+            has_attachment: false,
+            attachments: Vec::new(),
+            calendar_event: None,
+            category: "Primary".to_string(),
+        }
+    }
+
+    #[test]
+    fn threads_resolve_display_names_from_contacts() {
+        let groups = vec![ThreadGroup {
+            label: "Today".to_string(),
+            threads: vec![
+                thread("t1", &["ann@x.com", "me@x.com"]),
+                thread("t2", &["bob.stone@y.com"]),
+                thread("t3", &["me@x.com"]),
+            ],
+        }];
+        let mut contacts = HashMap::new();
+        contacts.insert(
+            "ann@x.com".to_string(),
+            Contact {
+                resource_name: "people/1".to_string(),
+                display_name: Some("Ann Lee".to_string()),
+                email_addresses: vec!["Ann@x.com".to_string()],
+                photo_url: Some("https://photos/ann.jpg".to_string()),
+            },
+        );
+
+        assert_eq!(
+            primary_participant_emails(&groups, "me@x.com"),
+            vec!["ann@x.com", "bob.stone@y.com", "me@x.com"]
+        );
+
+        let view = build_card_view(groups, "me@x.com", &contacts);
+        let people: Vec<_> = view[0].threads.iter().map(|t| t.participant.clone().unwrap()).collect();
+
+        assert_eq!(people[0].email, "ann@x.com");
+        assert_eq!(people[0].name.as_deref(), Some("Ann Lee"));
+        assert_eq!(people[0].photo_url.as_deref(), Some("https://photos/ann.jpg"));
+        assert_eq!(people[0].monogram, "AL");
+
+        // Unknown senders fall back to a monogram
+        assert_eq!(people[1].name, None);
+        assert_eq!(people[1].monogram, "B");
+
+        // Notes to self show the owner
+        assert_eq!(people[2].email, "me@x.com");
+    }
+}
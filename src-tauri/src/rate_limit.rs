@@ -0,0 +1,209 @@
+// Shared Gmail rate limiter
+//
+// A token bucket paces outgoing Gmail requests, and quota signals from
+// responses (429s, Retry-After, rate-limit headers) push a backoff onto it so
+// later requests slow down before Google starts rejecting them.
+
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Requests that can go out back to back after an idle period
+const BUCKET_CAPACITY: f64 = 25.0;
+/// Sustained requests per second
+const REFILL_PER_SEC: f64 = 10.0;
+/// Backoff after a 429 that carries no Retry-After
+const DEFAULT_RATE_LIMIT_BACKOFF: Duration = Duration::from_secs(2);
+/// Longest backoff honored from a response
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+static LIMITER: Lazy<Mutex<RateLimiter>> = Lazy::new(|| Mutex::new(RateLimiter::new(Instant::now())));
+
+/// Current pacing state, for the diagnostics panel
+#[derive(Debug, Clone, Serialize)]
+pub struct QuotaStatus {
+    /// How long the next request would wait, in milliseconds
+    pub delay_ms: u64,
+    /// Remaining server-requested backoff, in milliseconds
+    pub backoff_ms: u64,
+    pub tokens_available: f64,
+    pub capacity: f64,
+    pub refill_per_sec: f64,
+    /// Retry-After from the most recent rate-limited response, in seconds
+    pub last_retry_after_secs: Option<u64>,
+    /// Rate-limited responses seen since launch
+    pub rate_limited_count: u64,
+    /// Latest quota-related response headers, by lowercased name
+    pub quota_headers: HashMap<String, String>,
+}
+
+#[derive(Debug)]
+pub struct RateLimiter {
+    tokens: f64,
+    last_refill: Instant,
+    backoff_until: Option<Instant>,
+    last_retry_after: Option<Duration>,
+    rate_limited_count: u64,
+    quota_headers: HashMap<String, String>,
+}
+
+impl RateLimiter {
+    pub fn new(now: Instant) -> Self {
+        Self {
+            tokens: BUCKET_CAPACITY,
+            last_refill: now,
+            backoff_until: None,
+            last_retry_after: None,
+            rate_limited_count: 0,
+            quota_headers: HashMap::new(),
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * REFILL_PER_SEC).min(BUCKET_CAPACITY);
+        self.last_refill = now;
+    }
+
+    fn backoff_remaining(&self, now: Instant) -> Duration {
+        self.backoff_until.map(|until| until.saturating_duration_since(now)).unwrap_or_default()
+    }
+
+    /// How long a request made at `now` would have to wait
+    pub fn delay(&mut self, now: Instant) -> Duration {
+        self.refill(now);
+        let bucket_wait = if self.tokens >= 1.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64((1.0 - self.tokens) / REFILL_PER_SEC)
+        };
+        bucket_wait.max(self.backoff_remaining(now))
+    }
+
+    /// Reserve a slot for one request, returning how long to wait before sending it
+    pub fn acquire(&mut self, now: Instant) -> Duration {
+        let delay = self.delay(now);
+        // May go negative: later callers queue behind this one
+        self.tokens -= 1.0;
+        delay
+    }
+
+    /// Apply a response's quota signals. `retry_after` is its parsed Retry-After.
+    pub fn observe(&mut self, now: Instant, status: u16, retry_after: Option<Duration>, quota_headers: HashMap<String, String>) {
+        if !quota_headers.is_empty() {
+            self.quota_headers = quota_headers;
+        }
+
+        let backoff = match (status, retry_after) {
+            (_, Some(delay)) => {
+                self.last_retry_after = Some(delay);
+                Some(delay)
+            }
+            (429, None) => Some(DEFAULT_RATE_LIMIT_BACKOFF),
+            _ => None,
+        };
+        if status == 429 {
+            self.rate_limited_count += 1;
+        }
+
+        if let Some(backoff) = backoff {
+            let until = now + backoff.min(MAX_BACKOFF);
+            // None sorts first, so this keeps whichever backoff ends later
+            if self.backoff_until < Some(until) {
+                self.backoff_until = Some(until);
+            }
+        }
+    }
+
+    pub fn status(&mut self, now: Instant) -> QuotaStatus {
+        let delay = self.delay(now);
+        QuotaStatus {
+            delay_ms: delay.as_millis() as u64,
+            backoff_ms: self.backoff_remaining(now).as_millis() as u64,
+            tokens_available: self.tokens.max(0.0),
+            capacity: BUCKET_CAPACITY,
+            refill_per_sec: REFILL_PER_SEC,
+            last_retry_after_secs: self.last_retry_after.map(|d| d.as_secs()),
+            rate_limited_count: self.rate_limited_count,
+            quota_headers: self.quota_headers.clone(),
+        }
+    }
+}
+
+/// Headers that carry quota information
+fn is_quota_header(name: &str) -> bool {
+    name == "retry-after" || name.contains("ratelimit") || name.contains("rate-limit") || name.contains("quota")
+}
+
+/// Retry-After in its delay-seconds form (HTTP dates are ignored)
+pub fn parse_retry_after(resp: &reqwest::Response) -> Option<Duration> {
+    let value = resp.headers().get(reqwest::header::RETRY_AFTER)?.to_str().ok()?;
+    value.trim().parse::<u64>().ok().map(Duration::from_secs)
+}
+
+/// Wait for the shared limiter before sending a request
+pub async fn throttle() {
+    let delay = LIMITER.lock().acquire(Instant::now());
+    if !delay.is_zero() {
+        tracing::debug!("Rate limiter delaying request by {:?}", delay);
+        tokio::time::sleep(delay).await;
+    }
+}
+
+/// Feed a response's status and quota headers into the shared limiter
+pub fn observe_response(resp: &reqwest::Response) {
+    let quota_headers = resp
+        .headers()
+        .iter()
+        .filter(|(name, _)| is_quota_header(name.as_str()))
+        .filter_map(|(name, value)| Some((name.as_str().to_string(), value.to_str().ok()?.to_string())))
+        .collect();
+    LIMITER
+        .lock()
+        .observe(Instant::now(), resp.status().as_u16(), parse_retry_after(resp), quota_headers);
+}
+
+/// Current backoff and bucket state
+pub fn status() -> QuotaStatus {
+    LIMITER.lock().status(Instant::now())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retry_after_lengthens_the_delay() {
+        let now = Instant::now();
+        let mut limiter = RateLimiter::new(now);
+        assert_eq!(limiter.delay(now), Duration::ZERO);
+
+        limiter.observe(now, 429, Some(Duration::from_secs(7)), HashMap::new());
+        assert_eq!(limiter.delay(now), Duration::from_secs(7));
+        assert_eq!(limiter.delay(now + Duration::from_secs(5)), Duration::from_secs(2));
+        assert_eq!(limiter.delay(now + Duration::from_secs(8)), Duration::ZERO);
+
+        let status = limiter.status(now);
+        assert_eq!(status.rate_limited_count, 1);
+        assert_eq!(status.last_retry_after_secs, Some(7));
+
+        // A shorter later Retry-After doesn't cut an existing backoff short
+        limiter.observe(now, 429, Some(Duration::from_secs(1)), HashMap::new());
+        assert_eq!(limiter.delay(now), Duration::from_secs(7));
+    }
+
+    #[test]
+    fn bucket_paces_bursts() {
+        let now = Instant::now();
+        let mut limiter = RateLimiter::new(now);
+        for _ in 0..BUCKET_CAPACITY as usize {
+            assert_eq!(limiter.acquire(now), Duration::ZERO);
+        }
+        assert!(limiter.acquire(now) > Duration::ZERO);
+
+        // Refills over time
+        assert_eq!(limiter.delay(now + Duration::from_secs(5)), Duration::ZERO);
+    }
+}
@@ -28,6 +28,11 @@ import {
   reorderCards,
   deleteAccount,
   fetchThreadsPaginated,
+  prefetchNextPage,
+  cancelRequest,
+  setAiProvider,
+  isCancelledError,
+  isReauthError,
   searchThreadsPreview,
   modifyThreads,
   type Account,
@@ -45,9 +50,13 @@ import {
   downloadAttachment as downloadAttachmentApi,
   saveAttachment as saveAttachmentApi,
   type SendAttachment,
+  validateAttachment,
   listLabels,
   type GmailLabel,
   rsvpCalendarEvent,
+  prepareReply,
+  storageUsage,
+  removeCancelledEvent,
   getCalendarRsvpStatus,
   syncThreadsIncremental,
   fetchContacts,
@@ -63,7 +72,13 @@ import {
   saveCachedCardEvents,
   createCalendarEvent,
   type EventInput,
+  type EventPatch,
   sendReaction,
+  type FollowupReminder,
+  accountHealth,
+  type AccountHealth,
+  previewBulkAction,
+  BULK_CONFIRM_THRESHOLD,
 } from "./api/tauri";
 import { Menu, MenuItem, PredefinedMenuItem } from "@tauri-apps/api/menu";
 import {
@@ -85,6 +100,7 @@ import {
   decodeHtmlEntities,
   getResponseStatusLabel,
   normalizeBase64Url,
+  visibleAttachments,
   addReplyPrefix,
   toDateInputString,
 } from "./utils";
@@ -211,6 +227,18 @@ function App() {
     }
   };
 
+  const handleRemoveCancelledEvent = async (eventUid: string) => {
+    const account = selectedAccount();
+    if (!account) return;
+    try {
+      const removed = await removeCancelledEvent(account.id, eventUid);
+      showToast(removed ? "Removed from calendar" : "Event is not on your calendar");
+    } catch (e) {
+      console.error("Failed to remove cancelled event:", e);
+      showToast(`Failed to remove event: ${e}`);
+    }
+  };
+
   // Undo/toast state
   interface UndoableAction {
     action: string;
@@ -255,6 +283,7 @@ function App() {
   const [settingsOpen, setSettingsOpen] = createSignal(false);
   const [shortcutsHelpOpen, setShortcutsHelpOpen] = createSignal(false);
   const [accountChooserOpen, setAccountChooserOpen] = createSignal(false);
+  const [storageLabel, setStorageLabel] = createSignal<string | null>(null);
   const [resizing, setResizing] = createSignal(false);
   const MIN_CARD_WIDTH = 250;
   const MAX_CARD_WIDTH = 600;
@@ -342,6 +371,23 @@ function App() {
     }
   });
 
+  // Load storage usage for the selected account whenever the chooser opens
+  createEffect(() => {
+    const account = selectedAccount();
+    if (!accountChooserOpen() || !account) return;
+    storageUsage(account.id)
+      .then((usage) => {
+        if (usage.percent_used !== null) {
+          setStorageLabel(`${Math.round(usage.percent_used)}% full`);
+        } else if (usage.drive_scope_missing) {
+          setStorageLabel("Re-login to see storage");
+        } else {
+          setStorageLabel(null);
+        }
+      })
+      .catch(() => setStorageLabel(null));
+  });
+
   // Edit card state
   const [editingCardId, setEditingCardId] = createSignal<string | null>(null);
   const [editCardName, setEditCardName] = createSignal("");
@@ -563,7 +609,8 @@ function App() {
     recurrence: string | null;
     saving: boolean;
     error: string | null;
-    editing: { id: string; calendarId: string } | null;
+    // The event being edited and its values when the form opened, so only changes are sent
+    editing: { id: string; calendarId: string; original: EventInput } | null;
     closing: boolean;
   }
 
@@ -693,6 +740,7 @@ function App() {
         subject: draft.subject,
         body: draft.body,
         threadId: draft.threadId || null,
+        isHtml: composeIsHtml(),
       });
       if (epoch !== draftEpoch) {
         // Draft was cleared (send/close) while the save was in flight;
@@ -800,7 +848,29 @@ function App() {
   const [clientId, setClientId] = createSignal("");
   const [clientSecret, setClientSecret] = createSignal("");
   const [geminiApiKey, setGeminiApiKey] = createSignal(safeGetItem("gemini_api_key") || "");
+  const [aiProvider, setAiProviderChoice] = createSignal(safeGetItem("ai_provider") || "gemini");
+  const [customAiKey, setCustomAiKey] = createSignal("");
+  const [customAiBaseUrl, setCustomAiBaseUrl] = createSignal(safeGetItem("ai_base_url") || "");
+  const [customAiModel, setCustomAiModel] = createSignal(safeGetItem("ai_model") || "");
   const [smartRepliesOpen, setSmartRepliesOpen] = createSignal(false);
+  const [accountHealthList, setAccountHealthList] = createSignal<AccountHealth[]>([]);
+
+  // Refresh token health whenever settings open
+  createEffect(() => {
+    if (!settingsOpen()) return;
+    accountHealth()
+      .then(setAccountHealthList)
+      .catch(() => setAccountHealthList([]));
+  });
+
+  function healthTitle(health: AccountHealth | undefined): string {
+    if (!health || health.status === "unknown") return "Not refreshed yet";
+    if (health.status === "ok") {
+      return `Last refreshed ${new Date(health.last_successful_refresh!).toLocaleString()}`;
+    }
+    const label = health.status === "token_expired" ? "Sign-in expired, re-login needed" : "Network error";
+    return `${label} (${new Date(health.last_error_at!).toLocaleString()}): ${health.last_refresh_error}`;
+  }
 
   // Preset selection for new accounts
   const [showPresetSelection, setShowPresetSelection] = createSignal(false);
@@ -1012,8 +1082,7 @@ function App() {
         setCards(reorderedCards);
 
         try {
-          const orders: [string, number][] = reorderedCards.map(c => [c.id, c.position]);
-          await reorderCards(orders);
+          await reorderCards(movedCard.account_id, reorderedCards.map(c => c.id));
         } catch (err) {
           console.error("Failed to persist card order:", err);
           setCards(previousCards);
@@ -1045,6 +1114,8 @@ function App() {
   });
 
   let unlistenMailto: (() => void) | undefined;
+  let unlistenFollowup: (() => void) | undefined;
+  let unlistenSnoozeWoke: (() => void) | undefined;
 
   onMount(async () => {
     // Apply saved card width
@@ -1154,6 +1225,18 @@ function App() {
         setForwardingThread(null);
         setComposing(true);
       });
+
+      // Follow-up reminders that came due without a reply
+      unlistenFollowup = await listen<FollowupReminder>("followup-due", (event) => {
+        const { recipient, subject } = event.payload;
+        const name = recipient.split(",")[0].replace(/<.*>/, "").trim() || recipient;
+        showToast(`No reply yet: follow up with ${name} about "${subject}"`);
+      });
+
+      // Snoozed threads are back in the inbox; pick them up right away
+      unlistenSnoozeWoke = await listen<string>("snoozed-threads-woke", () => {
+        performIncrementalSync();
+      });
     } catch (e) {
       setError(String(e));
     } finally {
@@ -1180,6 +1263,8 @@ function App() {
     clearInterval(timeUpdateInterval);
     window.removeEventListener("focus", handleWindowFocus);
     unlistenMailto?.();
+    unlistenFollowup?.();
+    unlistenSnoozeWoke?.();
   });
 
   // Helper to get all threads from a card as a flat array
@@ -1900,6 +1985,7 @@ function App() {
 
     const newAttachments: SendAttachment[] = [];
     const skippedFiles: string[] = [];
+    const unreadableFiles: string[] = [];
 
     for (const file of Array.from(input.files)) {
       // Check file size
@@ -1917,15 +2003,27 @@ function App() {
         };
         reader.readAsDataURL(file);
       });
-      newAttachments.push({
+      const attachment: SendAttachment = {
         filename: file.name,
         mime_type: file.type || 'application/octet-stream',
         data,
-      });
+      };
+      try {
+        const validation = await validateAttachment(attachment);
+        if (validation.mime_mismatch && validation.detected_mime_type) {
+          attachment.mime_type = validation.detected_mime_type;
+        }
+      } catch (err) {
+        unreadableFiles.push(`${file.name} (${err})`);
+        continue;
+      }
+      newAttachments.push(attachment);
     }
 
     if (skippedFiles.length > 0) {
       setComposeEmailError(`Files too large: ${skippedFiles.join(', ')}`);
+    } else if (unreadableFiles.length > 0) {
+      setComposeEmailError(`Could not attach: ${unreadableFiles.join(', ')}`);
     }
 
     if (newAttachments.length > 0) {
@@ -1938,6 +2036,54 @@ function App() {
     setComposeAttachments(composeAttachments().filter((_, i) => i !== index));
   }
 
+  function eventInputFromForm(form: EventFormState): EventInput {
+    let start: number, end: number;
+    if (form.allDay) {
+      const sParts = form.startDate.split('-');
+      start = Date.UTC(parseInt(sParts[0]), parseInt(sParts[1]) - 1, parseInt(sParts[2]), 12, 0, 0);
+
+      const eParts = form.endDate.split('-');
+      end = Date.UTC(parseInt(eParts[0]), parseInt(eParts[1]) - 1, parseInt(eParts[2]), 12, 0, 0);
+    } else {
+      const s = new Date(`${form.startDate}T${form.startTime}`);
+      start = s.getTime();
+      const e = new Date(`${form.endDate}T${form.endTime}`);
+      end = e.getTime();
+    }
+
+    const attendeesList = form.attendees
+      .split(',')
+      .map(s => s.trim())
+      .filter(s => s.length > 0);
+
+    return {
+      summary: form.summary,
+      description: form.description || null,
+      location: form.location || null,
+      startTime: start,
+      endTime: end,
+      allDay: form.allDay,
+      attendees: attendeesList.length > 0 ? attendeesList : null,
+      recurrence: form.recurrence ? [form.recurrence] : null,
+    };
+  }
+
+  // Only the fields that differ; a field emptied in the form is sent as "" or [] to clear it
+  function eventChanges(original: EventInput, edited: EventInput): EventPatch {
+    const patch: EventPatch = {};
+    if (edited.summary !== original.summary) patch.summary = edited.summary;
+    if (edited.description !== original.description) patch.description = edited.description ?? "";
+    if (edited.location !== original.location) patch.location = edited.location ?? "";
+    if (edited.startTime !== original.startTime || edited.endTime !== original.endTime || edited.allDay !== original.allDay) {
+      patch.startTime = edited.startTime;
+      patch.endTime = edited.endTime;
+      patch.allDay = edited.allDay;
+    }
+    if ((edited.attendees ?? []).join(',') !== (original.attendees ?? []).join(',')) patch.attendees = edited.attendees ?? [];
+    if ((edited.recurrence ?? []).join(',') !== (original.recurrence ?? []).join(',')) patch.recurrence = edited.recurrence ?? [];
+    return patch;
+  }
+
   async function handleCreateEvent() {
     const account = selectedAccount();
     if (!account) return;
@@ -1954,43 +2100,15 @@ function App() {
     const editing = form.editing;
 
     try {
-      let start: number, end: number;
-      if (form.allDay) {
-        const sParts = form.startDate.split('-');
-        start = Date.UTC(parseInt(sParts[0]), parseInt(sParts[1]) - 1, parseInt(sParts[2]), 12, 0, 0);
-
-        const eParts = form.endDate.split('-');
-        end = Date.UTC(parseInt(eParts[0]), parseInt(eParts[1]) - 1, parseInt(eParts[2]), 12, 0, 0);
-      } else {
-        const s = new Date(`${form.startDate}T${form.startTime}`);
-        start = s.getTime();
-        const e = new Date(`${form.endDate}T${form.endTime}`);
-        end = e.getTime();
-      }
-
-      const attendeesList = form.attendees
-        .split(',')
-        .map(s => s.trim())
-        .filter(s => s.length > 0);
-
-      const eventInput: EventInput = {
-        summary: form.summary,
-        description: form.description || null,
-        location: form.location || null,
-        startTime: start,
-        endTime: end,
-        allDay: form.allDay,
-        attendees: attendeesList.length > 0 ? attendeesList : null,
-        recurrence: form.recurrence ? [form.recurrence] : null,
-      };
+      const eventInput = eventInputFromForm(form);
 
       if (editing) {
-        // Update existing event
+        // Update existing event, sending only what changed
         await updateCalendarEvent(
           account.id,
           editing.calendarId,
           editing.id,
-          eventInput
+          eventChanges(editing.original, eventInput)
         );
       } else {
         // Create new event
@@ -2021,7 +2139,7 @@ function App() {
     }
   }
 
-  function handleSendEmail() {
+  async function handleSendEmail() {
     const account = selectedAccount();
     if (!account || !composeTo().trim()) return;
 
@@ -2043,6 +2161,26 @@ function App() {
 
     setComposeEmailError(null);
 
+    // Replying to a long-dormant thread is usually a mistake; confirm first
+    const replyTarget = replyingToThread();
+    if (replyTarget) {
+      try {
+        const preflight = await prepareReply(account.id, replyTarget.threadId);
+        if (preflight.stale_thread && preflight.latest_message_date) {
+          const when = new Date(preflight.latest_message_date).toLocaleDateString();
+          if (!confirm(`The last message in this thread is from ${when}. Reply anyway?`)) return;
+        }
+        // Reply-all to a big thread is easy to do by accident
+        if (composeCc().trim() && preflight.reply_all.large_recipient_warning) {
+          const count = preflight.reply_all.recipient_count;
+          if (!confirm(`This reply will go to ${count} people. Reply to all of them?`)) return;
+        }
+      } catch (e) {
+        // The check is advisory; never block sending on it
+        console.error("Reply preflight failed:", e);
+      }
+    }
+
     // Queue the send with undo capability. Capture the account now so
     // switching accounts during the undo window can't change the sender.
     const pending: PendingSend = {
@@ -2298,13 +2436,33 @@ function App() {
     setComposing(true);
 
     const thread = activeThread();
+    let gmailMessageId: string | undefined;
     if (thread && messageId) {
       // ThreadView may report either the Gmail API id or the RFC Message-ID
       const messageIndex = thread.messages.findIndex(m =>
         m.id === messageId ||
         m.payload?.headers?.find(h => h.name === 'Message-ID')?.value === messageId
       );
-      if (messageIndex >= 0) setFocusedMessageIndex(messageIndex);
+      if (messageIndex >= 0) {
+        setFocusedMessageIndex(messageIndex);
+        gmailMessageId = thread.messages[messageIndex].id;
+      }
+    }
+
+    // Messages from a mailing list are usually best answered on the list
+    const account = selectedAccount();
+    if (account) {
+      prepareReply(account.id, threadId, gmailMessageId)
+        .then(({ reply_targets }) => {
+          const list = reply_targets.list;
+          if (!list || reply_targets.recommended !== list) return;
+          // Leave it alone if the user already edited To or the list is on it
+          if (composeTo() !== to || extractEmail(to).toLowerCase() === list.toLowerCase()) return;
+          if (cc.toLowerCase().includes(list.toLowerCase())) return;
+          setComposeTo(list);
+          showToast(`Replying to list ${list}`);
+        })
+        .catch((e) => console.debug("Reply targets unavailable:", e));
     }
   }
 
@@ -2781,6 +2939,8 @@ function App() {
   async function switchAccount(account: Account) {
     if (selectedAccount()?.id === account.id) return;
 
+    // Responses for the previous account's cards must not land in this one
+    cancelAllCardRequests();
     setSelectedAccount(account);
     setCardThreads(reconcile({}));
     setCardCalendarEvents(reconcile({}));
@@ -2852,7 +3012,9 @@ function App() {
       }
 
       const pageToken = append ? cardPageTokens[cardId] : null;
-      const result = await fetchThreadsPaginated(account.id, cardId, pageToken);
+      const requestId = beginCardRequest(cardId);
+      const result = await fetchThreadsPaginated(account.id, cardId, pageToken, requestId);
+      endCardRequest(cardId, requestId);
 
       if (append) {
         // Merge new threads into existing groups
@@ -2871,14 +3033,16 @@ function App() {
       setCardHasMore(cardId, result.has_more);
       setLastSyncTimes(cardId, Date.now());
       setSyncErrors(cardId, null);
+      if (result.has_more) prefetchCardPage(account.id, cardId);
     } catch (e) {
+      // Superseded by a newer load or an account switch; leave state alone
+      if (isCancelledError(e)) return;
       const errorMsg = String(e);
       console.error("loadCardThreads error:", errorMsg);
-      // Check for session expiry (token revoked, keyring issues, refresh failures)
-      if (errorMsg.includes("Keyring error") ||
+      // Check for session expiry (token revoked or missing); other refresh
+      // failures are usually transient network errors
+      if (isReauthError(e) ||
           errorMsg.includes("No auth token") ||
-          errorMsg.includes("Token refresh failed") ||
-          errorMsg.includes("invalid_grant") ||
           errorMsg.includes("unauthorized")) {
         // Only trigger sign out once (prevent race conditions from multiple card loads)
         if (!error()?.includes("Session expired")) {
@@ -2937,10 +3101,8 @@ function App() {
     } catch (e) {
       const errorMsg = String(e);
       console.error("loadCalendarEvents error:", errorMsg);
-      if (errorMsg.includes("Keyring error") ||
+      if (isReauthError(e) ||
           errorMsg.includes("No auth token") ||
-          errorMsg.includes("Token refresh failed") ||
-          errorMsg.includes("invalid_grant") ||
           errorMsg.includes("unauthorized")) {
         if (!error()?.includes("Session expired")) {
           setError("Session expired - please sign in again");
@@ -2969,12 +3131,15 @@ function App() {
 
   async function fetchAndCacheCalendarEvents(accountId: string, cardId: string, query: string) {
     try {
-      const events = await fetchCalendarEvents(accountId, query);
+      const requestId = beginCardRequest(cardId);
+      const events = await fetchCalendarEvents(accountId, query, requestId);
+      endCardRequest(cardId, requestId);
       setCardCalendarEvents(cardId, events);
       await saveCachedCardEvents(cardId, events);
       setLastSyncTimes(cardId, Date.now());
       setSyncErrors(cardId, null);
     } catch (e) {
+      if (isCancelledError(e)) return;
       console.error("Failed to fetch calendar events:", e);
       setSyncErrors(cardId, String(e));
       // If foreground load failed, rethrow to be caught by loadCalendarEvents
@@ -2984,6 +3149,37 @@ function App() {
     }
   }
 
+  // In-flight fetch per card, so a newer load or an account switch can
+  // cancel it before its response lands in the UI or the cache
+  const inflightCardRequests = new Map<string, string>();
+
+  function beginCardRequest(cardId: string): string {
+    const previous = inflightCardRequests.get(cardId);
+    if (previous) cancelRequest(previous).catch(() => {});
+    const requestId = crypto.randomUUID();
+    inflightCardRequests.set(cardId, requestId);
+    return requestId;
+  }
+
+  function endCardRequest(cardId: string, requestId: string) {
+    if (inflightCardRequests.get(cardId) === requestId) {
+      inflightCardRequests.delete(cardId);
+    }
+  }
+
+  function cancelAllCardRequests() {
+    for (const requestId of inflightCardRequests.values()) {
+      cancelRequest(requestId).catch(() => {});
+    }
+    inflightCardRequests.clear();
+  }
+
+  // Warm the next page so "load more" doesn't wait on the network
+  function prefetchCardPage(accountId: string, cardId: string) {
+    if (!navigator.onLine) return;
+    prefetchNextPage(accountId, cardId).catch((e) => console.debug("Prefetch skipped:", e));
+  }
+
   // Background fetch and cache update (no loading state shown)
   async function fetchAndCacheThreads(accountId: string, cardId: string) {
     // Skip for calendar cards (they don't use thread caching)
@@ -2993,7 +3189,9 @@ function App() {
     // user paginated doesn't wipe appended pages or rewind the page token
     const tokenBeforeFetch = cardPageTokens[cardId];
     try {
-      const result = await fetchThreadsPaginated(accountId, cardId, null);
+      const requestId = beginCardRequest(cardId);
+      const result = await fetchThreadsPaginated(accountId, cardId, null, requestId);
+      endCardRequest(cardId, requestId);
       // Skip update if a recent action happened (prevents overwriting optimistic updates)
       const recent = lastAction();
       if (recent && Date.now() - recent.timestamp < 3000) {
@@ -3013,7 +3211,9 @@ function App() {
       await saveCachedCardThreads(cardId, result.groups, result.next_page_token);
       setLastSyncTimes(cardId, Date.now());
       setSyncErrors(cardId, null);
+      if (result.has_more) prefetchCardPage(accountId, cardId);
     } catch (e) {
+      if (isCancelledError(e)) return;
       // Background refresh failed - set sync error but keep cached data shown
       setSyncErrors(cardId, String(e));
     }
@@ -3759,9 +3959,10 @@ function App() {
 
     hideToast();
 
-    // Reverse the labels: add what was removed, remove what was added
+    // Reverse the labels: add what was removed, remove what was added. The
+    // original action was already confirmed, so its undo is too.
     try {
-      await modifyThreads(account.id, action.threadIds, action.removedLabels, action.addedLabels);
+      await modifyThreads(account.id, action.threadIds, action.removedLabels, action.addedLabels, true);
       // Refresh every card the optimistic update touched, not just the
       // one the action originated from
       const cardsToRefresh = action.cardIds.length > 0 ? action.cardIds : [action.cardId];
@@ -3796,12 +3997,28 @@ function App() {
     const account = selectedAccount();
     if (!account) return;
 
-    // Confirm destructive bulk actions
+    // Confirm destructive bulk actions. Large ones are previewed first so
+    // the prompt shows what the card's search would touch, and the backend
+    // only runs them once they are sent as confirmed.
+    let confirmed = false;
     if (threadIds.length > 1 && (action === 'archive' || action === 'trash' || action === 'spam')) {
       const actionText = action === 'trash' ? 'delete' : action === 'spam' ? 'move to spam' : 'archive';
-      if (!confirm(`${actionText.charAt(0).toUpperCase() + actionText.slice(1)} ${threadIds.length} threads?`)) {
+      let prompt = `${actionText.charAt(0).toUpperCase() + actionText.slice(1)} ${threadIds.length} threads?`;
+      const card = cards().find(c => c.id === cardId);
+      if (threadIds.length >= BULK_CONFIRM_THRESHOLD && card) {
+        try {
+          const preview = await previewBulkAction(account.id, card.query, action === 'trash' ? 'trash' : 'archive');
+          if (preview.sample_subjects.length > 0) {
+            prompt += `\n\nIncluding:\n${preview.sample_subjects.map(s => `• ${s}`).join("\n")}`;
+          }
+        } catch (e) {
+          console.error("Failed to preview bulk action", e);
+        }
+      }
+      if (!confirm(prompt)) {
         return;
       }
+      confirmed = true;
     }
 
     let addLabels: string[] = [];
@@ -3887,7 +4104,17 @@ function App() {
     }
 
     try {
-      await modifyThreads(account.id, threadIds, addLabels, removeLabels);
+      const outcomes = await modifyThreads(account.id, threadIds, addLabels, removeLabels, confirmed);
+      const failed = threadIds.filter(id => outcomes[id] && !outcomes[id].ok);
+      if (failed.length > 0) {
+        // Partial failure: resync the touched cards from the server instead
+        // of persisting an optimistic state that is only partly true
+        setError(`Couldn't update ${failed.length} of ${threadIds.length} threads`);
+        for (const cId of affectedCardIds) {
+          fetchAndCacheThreads(account.id, cId);
+        }
+        return;
+      }
       // Persist the optimistic changes only after the server accepted them
       for (const cId of affectedCardIds) {
         saveCachedCardThreads(cId, updatedCardThreads[cId], cardPageTokens[cId] || null);
@@ -4178,6 +4405,9 @@ function App() {
                               </span>
                             )}
                             <span class="account-chooser-email">{account.email}</span>
+                            {account.id === selectedAccount()?.id && storageLabel() && (
+                              <span class="account-chooser-storage">{storageLabel()}</span>
+                            )}
                             {account.id === selectedAccount()?.id && (
                               <span class="account-chooser-check">✓</span>
                             )}
@@ -4442,9 +4672,9 @@ function App() {
                                           </div>
                                           <div class="thread-snippet">{decodeHtmlEntities(thread.snippet)}</div>
                                           {/* Attachment previews */}
-                                          <Show when={thread.attachments?.length > 0 && !thread.calendar_event}>
+                                          <Show when={visibleAttachments(thread.attachments).length > 0 && !thread.calendar_event}>
                                             <div class="thread-attachments">
-                                              <For each={thread.attachments?.filter(a => a.inline_data && a.mime_type.startsWith("image/")).slice(0, 3)}>
+                                              <For each={visibleAttachments(thread.attachments).filter(a => a.inline_data && a.mime_type.startsWith("image/")).slice(0, 3)}>
                                                 {(attachment) => (
                                                   <img
                                                     class="thread-image-thumb clickable"
@@ -4455,7 +4685,7 @@ function App() {
                                                   />
                                                 )}
                                               </For>
-                                              <For each={thread.attachments?.filter(a => !a.inline_data || !a.mime_type.startsWith("image/")).slice(0, 2)}>
+                                              <For each={visibleAttachments(thread.attachments).filter(a => !a.inline_data || !a.mime_type.startsWith("image/")).slice(0, 2)}>
                                                 {(attachment) => (
                                                   <div
                                                     class="thread-file-item clickable"
@@ -4647,6 +4877,9 @@ function App() {
                                                 <div class="unread-dot"></div>
                                               </Show>
                                               <span class="thread-subject">{thread.subject}</span>
+                                              <Show when={thread.category && thread.category !== "Primary"}>
+                                                <span class="thread-category-chip">{thread.category}</span>
+                                              </Show>
                                               <Show when={thread.calendar_event}>
                                                 <span class="thread-indicator" title="Calendar invite">
                                                   <CalendarIcon />
@@ -4662,7 +4895,23 @@ function App() {
                                             {/* Calendar event preview */}
                                             <Show when={thread.calendar_event}>
                                               <div class="calendar-event-preview">
-                                                <div class="calendar-event-time">
+                                                <Show when={thread.calendar_event!.cancelled}>
+                                                  <div class="calendar-event-cancelled">
+                                                    Cancelled
+                                                    <Show when={thread.calendar_event!.uid}>
+                                                      <button
+                                                        onClick={(e) => {
+                                                          e.stopPropagation();
+                                                          handleRemoveCancelledEvent(thread.calendar_event!.uid!);
+                                                        }}
+                                                      >Remove from calendar</button>
+                                                    </Show>
+                                                  </div>
+                                                </Show>
+                                                <Show when={thread.calendar_event!.is_update && !thread.calendar_event!.cancelled}>
+                                                  <div class="calendar-event-updated">Meeting time changed</div>
+                                                </Show>
+                                                <div class="calendar-event-time" classList={{ "calendar-event-struck": thread.calendar_event!.cancelled }}>
                                                   <ClockIcon />
                                                   <span>{formatCalendarEventDate(thread.calendar_event!.start_time, thread.calendar_event!.end_time, thread.calendar_event!.all_day)}</span>
                                                 </div>
@@ -4705,8 +4954,8 @@ function App() {
                                               const isCalendarFile = (a: { mime_type: string; filename: string }) =>
                                                 a.mime_type === "text/calendar" || a.mime_type === "application/ics" || a.filename.endsWith(".ics");
                                               const attachments = thread.calendar_event
-                                                ? thread.attachments?.filter(a => !isCalendarFile(a))
-                                                : thread.attachments;
+                                                ? visibleAttachments(thread.attachments).filter(a => !isCalendarFile(a))
+                                                : visibleAttachments(thread.attachments);
                                               return (
                                                 <Show when={attachments && attachments.length > 0}>
                                                   <div class="thread-attachments" onClick={(e) => e.stopPropagation()}>
@@ -4963,9 +5212,9 @@ function App() {
                                     <span class="thread-time">{formatTime(thread.last_message_date)}</span>
                                   </div>
                                   <div class="thread-snippet">{decodeHtmlEntities(thread.snippet)}</div>
-                                  <Show when={thread.attachments?.length > 0}>
+                                  <Show when={visibleAttachments(thread.attachments).length > 0}>
                                     <div class="thread-attachments">
-                                      <For each={thread.attachments?.filter(a => a.inline_data && a.mime_type.startsWith("image/")).slice(0, 3)}>
+                                      <For each={visibleAttachments(thread.attachments).filter(a => a.inline_data && a.mime_type.startsWith("image/")).slice(0, 3)}>
                                         {(attachment) => (
                                           <img
                                             class="thread-image-thumb"
@@ -4975,7 +5224,7 @@ function App() {
                                           />
                                         )}
                                       </For>
-                                      <For each={thread.attachments?.filter(a => !a.inline_data || !a.mime_type.startsWith("image/")).slice(0, 2)}>
+                                      <For each={visibleAttachments(thread.attachments).filter(a => !a.inline_data || !a.mime_type.startsWith("image/")).slice(0, 2)}>
                                         {(attachment) => (
                                           <div class="thread-file-item" title={`${attachment.filename} (${formatFileSize(attachment.size)})`}>
                                             <span class="file-name">{truncateMiddle(attachment.filename, 14)}</span>
@@ -5382,20 +5631,23 @@ function App() {
             if (event.all_day && event.end_time) {
               endDateVal = new Date(endDateVal.getTime() - 86400000);
             }
-            setEventForm(f => ({
-              ...f,
-              summary: event.title || '',
-              description: event.description || '',
-              location: event.location || '',
-              startDate: toDateInputString(startDate, event.all_day),
-              startTime: startDate.toTimeString().slice(0, 5),
-              endDate: toDateInputString(endDateVal, event.all_day),
-              endTime: endDateVal.toTimeString().slice(0, 5),
-              allDay: event.all_day,
-              attendees: event.attendees.map(a => a.email).join(', '),
-              recurrence: null, // Recurrence editing not supported yet
-              editing: { id: event.id, calendarId: event.calendar_id },
-            }));
+            setEventForm(f => {
+              const form: EventFormState = {
+                ...f,
+                summary: event.title || '',
+                description: event.description || '',
+                location: event.location || '',
+                startDate: toDateInputString(startDate, event.all_day),
+                startTime: startDate.toTimeString().slice(0, 5),
+                endDate: toDateInputString(endDateVal, event.all_day),
+                endTime: endDateVal.toTimeString().slice(0, 5),
+                allDay: event.all_day,
+                attendees: event.attendees.map(a => a.email).join(', '),
+                recurrence: null, // Recurrence editing not supported yet
+                editing: null,
+              };
+              return { ...form, editing: { id: event.id, calendarId: event.calendar_id, original: eventInputFromForm(form) } };
+            });
           }}
           onDelete={async () => {
             const event = activeEvent();
@@ -5640,6 +5892,14 @@ function App() {
                   <code>calendar:month</code>
                   <span>This month</span>
                 </div>
+                <div class="query-help-row">
+                  <code>calendar:nextweek</code>
+                  <span>Next Monday to Sunday</span>
+                </div>
+                <div class="query-help-row">
+                  <code>calendar:lastweek</code>
+                  <span>Last Monday to Sunday</span>
+                </div>
                 <div class="query-help-row">
                   <code>with:name</code>
                   <span>Attendee name/email</span>
@@ -5729,6 +5989,22 @@ function App() {
               Connect <span class="shortcut-hint">↵</span>
             </button>
           </div>
+          <Show when={accounts().length > 0}>
+            <div class="settings-section">
+              <div class="settings-section-title">Accounts</div>
+              <For each={accounts()}>
+                {(account) => {
+                  const health = () => accountHealthList().find(h => h.account_id === account.id);
+                  return (
+                    <div class="settings-account-row" title={healthTitle(health())}>
+                      <span class={`account-health-dot ${health()?.status ?? 'unknown'}`}></span>
+                      <span class="settings-account-email">{account.email}</span>
+                    </div>
+                  );
+                }}
+              </For>
+            </div>
+          </Show>
           <div class={`settings-section collapsible ${smartRepliesOpen() ? 'open' : ''}`}>
             <div class="settings-section-title" onClick={() => setSmartRepliesOpen(!smartRepliesOpen())}>
               <span>Smart Replies</span>
@@ -5736,20 +6012,87 @@ function App() {
             </div>
             <Show when={smartRepliesOpen()}>
               <p class="settings-hint" style="margin-bottom: 12px;">
-                AI-powered reply suggestions via Gemini.
+                AI-powered reply suggestions via Gemini or any OpenAI-compatible API.
               </p>
               <div class="settings-form-group">
-                <label>API Key</label>
-                <input
-                  type="password"
-                  value={geminiApiKey()}
-                  onInput={(e) => {
-                    setGeminiApiKey(e.currentTarget.value);
-                    safeSetItem("gemini_api_key", e.currentTarget.value);
+                <label>Provider</label>
+                <select
+                  value={aiProvider()}
+                  onChange={(e) => {
+                    const provider = e.currentTarget.value;
+                    setAiProviderChoice(provider);
+                    if (provider === "gemini") {
+                      // Back to the default: Gemini with the key below
+                      safeSetItem("ai_provider", "gemini");
+                      setAiProvider("gemini", "").catch((err) => showToast(String(err)));
+                    }
                   }}
-                  placeholder="AIza..."
-                />
+                >
+                  <option value="gemini">Gemini</option>
+                  <option value="openai">OpenAI-compatible</option>
+                </select>
               </div>
+              <Show when={aiProvider() === "gemini"}>
+                <div class="settings-form-group">
+                  <label>API Key</label>
+                  <input
+                    type="password"
+                    value={geminiApiKey()}
+                    onInput={(e) => {
+                      setGeminiApiKey(e.currentTarget.value);
+                      safeSetItem("gemini_api_key", e.currentTarget.value);
+                    }}
+                    placeholder="AIza..."
+                  />
+                </div>
+              </Show>
+              <Show when={aiProvider() === "openai"}>
+                <div class="settings-form-group">
+                  <label>Base URL</label>
+                  <input
+                    type="text"
+                    value={customAiBaseUrl()}
+                    onInput={(e) => setCustomAiBaseUrl(e.currentTarget.value)}
+                    placeholder="https://api.openai.com/v1"
+                  />
+                </div>
+                <div class="settings-form-group">
+                  <label>Model</label>
+                  <input
+                    type="text"
+                    value={customAiModel()}
+                    onInput={(e) => setCustomAiModel(e.currentTarget.value)}
+                    placeholder="gpt-4o-mini"
+                  />
+                </div>
+                <div class="settings-form-group">
+                  <label>API Key</label>
+                  <input
+                    type="password"
+                    value={customAiKey()}
+                    onInput={(e) => setCustomAiKey(e.currentTarget.value)}
+                    placeholder="Stored in your keychain"
+                  />
+                </div>
+                <button
+                  class="settings-btn"
+                  disabled={!customAiKey().trim()}
+                  onClick={async () => {
+                    try {
+                      await setAiProvider("openai", customAiKey(), customAiBaseUrl() || undefined, customAiModel() || undefined);
+                      safeSetItem("ai_provider", "openai");
+                      safeSetItem("ai_base_url", customAiBaseUrl());
+                      safeSetItem("ai_model", customAiModel());
+                      setCustomAiKey("");
+                      showToast("AI provider saved");
+                    } catch (err) {
+                      showToast(String(err));
+                    }
+                  }}
+                >
+                  Save
+                </button>
+              </Show>
             </Show>
           </div>
         </div>
@@ -7,6 +7,8 @@ export interface Account {
   email: string;
   picture: string | null;
   signature: string | null;
+  nickname: string | null; // shown instead of the email when set
+  color: string | null; // tint for the account's cards
 }
 
 export interface Card {
@@ -35,6 +37,19 @@ export interface EventInput {
   allDay: boolean;
   attendees: string[] | null;
   recurrence: string[] | null;
+  withMeet?: boolean; // create a Google Meet call (new events only)
+}
+
+// Changes to an existing event; undefined fields keep their current value
+export interface EventPatch {
+  summary?: string;
+  description?: string; // "" clears it
+  location?: string; // "" clears it
+  startTime?: number; // start, end and allDay are sent together
+  endTime?: number;
+  allDay?: boolean;
+  attendees?: string[]; // [] removes everyone
+  recurrence?: string[];
 }
 
 export interface Attachment {
@@ -45,6 +60,7 @@ export interface Attachment {
   size: number;
   inline_data: string | null; // Base64-encoded data for small images
   content_id: string | null; // Content-ID for inline images (cid: references)
+  is_inline: boolean; // Rendered in the body, not offered as a file
 }
 
 export interface CalendarEvent {
@@ -60,6 +76,11 @@ export interface CalendarEvent {
   method: string | null; // REQUEST, REPLY, CANCEL
   status: string | null; // CONFIRMED, TENTATIVE, CANCELLED
   response_status: string | null; // accepted, tentative, declined, needsAction
+  sequence: number; // ICS SEQUENCE revision number
+  is_update: boolean; // true when this invite revises an earlier version
+  cancelled: boolean; // METHOD:CANCEL or STATUS:CANCELLED
+  recurrence: string | null; // raw RRULE for recurring events
+  occurrences: number[]; // upcoming instance starts (ms), at most 50
 }
 
 export interface Thread {
@@ -74,6 +95,7 @@ export interface Thread {
   has_attachment: boolean;
   attachments: Attachment[];
   calendar_event: CalendarEvent | null;
+  category: string; // Primary, Social, Promotions, Updates, Forums
 }
 
 export interface ThreadGroup {
@@ -91,6 +113,19 @@ export async function initApp(): Promise<void> {
   return invoke("init_app");
 }
 
+export interface MailtoData {
+  to: string;
+  cc: string;
+  bcc: string;
+  subject: string;
+  body: string;
+}
+
+// Rejects links with nothing to compose or malformed recipients
+export async function parseMailto(url: string): Promise<MailtoData> {
+  return invoke("parse_mailto_command", { url });
+}
+
 export async function configureAuth(config: AuthConfig): Promise<void> {
   return invoke("configure_auth", { config });
 }
@@ -104,10 +139,32 @@ export async function getStoredCredentials(): Promise<StoredCredentials | null>
   return invoke("get_stored_credentials");
 }
 
+// Prefix on errors that mean the account's sign-in was revoked or expired
+export const REAUTH_ERROR_PREFIX = "REAUTH:";
+
+export function isReauthError(e: unknown): boolean {
+  return String(e).includes(REAUTH_ERROR_PREFIX);
+}
+
 export async function runOAuthFlow(): Promise<Account> {
   return await invoke('run_oauth_flow');
 }
 
+// Optional Google scopes (calendar, contacts, filters); mail access is always requested
+export interface ScopeSelection {
+  supported: string[];
+  requested: string[];
+}
+
+export async function getRequestedScopes(): Promise<ScopeSelection> {
+  return invoke("get_requested_scopes");
+}
+
+// Takes effect at the next sign-in
+export async function setRequestedScopes(scopes: string[]): Promise<void> {
+  return invoke("set_requested_scopes", { scopes });
+}
+
 export async function getAccounts(): Promise<Account[]> {
   return invoke("get_accounts");
 }
@@ -116,6 +173,33 @@ export async function deleteAccount(id: string): Promise<void> {
   return invoke("delete_account", { accountId: id });
 }
 
+export type AccountHealthStatus = "ok" | "token_expired" | "network_error" | "unknown";
+
+export interface AccountHealth {
+  account_id: string;
+  status: AccountHealthStatus;
+  last_successful_refresh: number | null;
+  last_refresh_error: string | null;
+  last_error_at: number | null;
+}
+
+// Token refresh status per account, from refreshes since launch
+export async function accountHealth(): Promise<AccountHealth[]> {
+  return invoke("account_health");
+}
+
+export async function mergeAccountsByEmail(oldEmail: string, newAccountId: string): Promise<number> {
+  return invoke("merge_accounts_by_email", { oldEmail, newAccountId });
+}
+
+export async function setAccountAppearance(
+  accountId: string,
+  nickname: string | null,
+  color: string | null
+): Promise<void> {
+  return invoke("set_account_appearance", { accountId, nickname, color });
+}
+
 export async function updateAccountSignature(accountId: string, signature: string | null): Promise<void> {
   return invoke("update_account_signature", { accountId, signature });
 }
@@ -135,6 +219,16 @@ export async function createCard(
   return invoke("create_card", { accountId, name, query, color, groupBy, cardType });
 }
 
+export interface ImportCardsResult {
+  created: number;
+  skipped: number;
+  invalid: string[];
+}
+
+export async function importCardsFromUrl(accountId: string, url: string): Promise<ImportCardsResult> {
+  return invoke("import_cards_from_url", { accountId, url });
+}
+
 export async function updateCard(card: Card): Promise<void> {
   return invoke("update_card", { card });
 }
@@ -143,16 +237,36 @@ export async function deleteCard(id: string): Promise<void> {
   return invoke("delete_card", { id });
 }
 
-export async function reorderCards(orders: [string, number][]): Promise<void> {
-  return invoke("reorder_cards", { orders });
+export async function reorderCards(accountId: string, orderedIds: string[]): Promise<void> {
+  return invoke("reorder_cards", { accountId, orderedIds });
+}
+
+export async function moveCardToAccount(cardId: string, targetAccountId: string): Promise<void> {
+  return invoke("move_card_to_account", { cardId, targetAccountId });
 }
 
 export async function fetchThreadsPaginated(
   accountId: string,
   cardId: string,
-  pageToken?: string | null
+  pageToken?: string | null,
+  requestId?: string
 ): Promise<SearchResult> {
-  return invoke("fetch_threads_paginated", { accountId, cardId, pageToken });
+  return invoke("fetch_threads_paginated", { accountId, cardId, pageToken, requestId });
+}
+
+export async function prefetchNextPage(accountId: string, cardId: string, requestId?: string): Promise<boolean> {
+  return invoke("prefetch_next_page", { accountId, cardId, requestId });
+}
+
+// Error message of a fetch aborted via cancelRequest
+export const REQUEST_CANCELLED = "Request cancelled";
+
+export function isCancelledError(e: unknown): boolean {
+  return String(e).includes(REQUEST_CANCELLED);
+}
+
+export async function cancelRequest(requestId: string): Promise<boolean> {
+  return invoke("cancel_request", { requestId });
 }
 
 export interface IncrementalSyncResult {
@@ -174,20 +288,117 @@ export async function searchThreadsPreview(
 ): Promise<ThreadGroup[]> {
   return invoke("search_threads_preview", { accountId, query });
 }
+
+export async function interactionHistory(
+  accountId: string,
+  email: string,
+  limit?: number
+): Promise<Thread[]> {
+  return invoke("interaction_history", { accountId, email, limit });
+}
+
+export async function snoozeCard(
+  accountId: string,
+  cardId: string,
+  until: number
+): Promise<number> {
+  return invoke("snooze_card", { accountId, cardId, until: Math.round(until) });
+}
+
+export interface CardFilterResult {
+  label_id: string;
+  label_name: string;
+  filter_id: string;
+}
+
+// Create a Gmail label named after the card and a filter applying it to the
+// card's query, so the card works as a saved search in Gmail web
+export async function createLabelAndFilterFromCard(
+  accountId: string,
+  cardId: string
+): Promise<CardFilterResult> {
+  return invoke("create_label_and_filter_from_card", { accountId, cardId });
+}
+
+// Label all mail from one address (label created if needed); skipInbox also archives it
+export async function createContactFilter(
+  accountId: string,
+  email: string,
+  labelName: string,
+  skipInbox = false
+): Promise<CardFilterResult> {
+  return invoke("create_contact_filter", { accountId, email, labelName, skipInbox });
+}
+
+export interface ModifyOutcome {
+  ok: boolean;
+  error: string | null;
+}
+
+// Resolves with a per-thread outcome; rejects only when every thread failed
 export async function modifyThreads(
   accountId: string,
   threadIds: string[],
   addLabels: string[],
-  removeLabels: string[]
-): Promise<void> {
+  removeLabels: string[],
+  confirmed?: boolean
+): Promise<Record<string, ModifyOutcome>> {
   return invoke("modify_threads", {
     accountId,
     threadIds,
     addLabels,
     removeLabels,
+    confirmed,
   });
 }
 
+// Move threads to Trash (recoverable for 30 days)
+export async function trashThreads(
+  accountId: string,
+  threadIds: string[],
+  confirmed?: boolean
+): Promise<Record<string, ModifyOutcome>> {
+  return invoke("trash_threads", { accountId, threadIds, confirmed });
+}
+
+export async function untrashThreads(
+  accountId: string,
+  threadIds: string[]
+): Promise<Record<string, ModifyOutcome>> {
+  return invoke("untrash_threads", { accountId, threadIds });
+}
+
+// Delete threads for good, bypassing Trash
+export async function deleteThreadsPermanently(
+  accountId: string,
+  threadIds: string[],
+  confirmed?: boolean
+): Promise<Record<string, ModifyOutcome>> {
+  return invoke("delete_threads_permanently", { accountId, threadIds, confirmed });
+}
+
+export type BulkAction = "trash" | "delete" | "archive";
+
+// Thread count at which destructive bulk actions must be sent as confirmed
+export const BULK_CONFIRM_THRESHOLD = 100;
+
+export interface BulkActionPreview {
+  action: BulkAction;
+  estimated_count: number;
+  sample_subjects: string[];
+  requires_confirmation: boolean;
+}
+
+// Estimate what a bulk action over a search would touch, without running it.
+// Large actions must then be sent with `confirmed: true`.
+export async function previewBulkAction(
+  accountId: string,
+  query: string,
+  action: BulkAction
+): Promise<BulkActionPreview> {
+  return invoke("preview_bulk_action", { accountId, query, action });
+}
+
 export interface Header {
   name: string;
   value: string;
@@ -229,17 +440,129 @@ export interface FullThread {
   messages: FullMessage[];
 }
 
+export interface MessageHtml {
+  raw: string;
+  dark: string | null; // set when requested with darkMode
+}
+
+// HTML body of a message; with darkMode, also a copy whose hardcoded
+// white backgrounds and black text inherit the theme
+export async function getMessageHtml(
+  accountId: string,
+  threadId: string,
+  messageId: string,
+  darkMode = false
+): Promise<MessageHtml | null> {
+  return invoke("get_message_html", { accountId, threadId, messageId, darkMode });
+}
+
+export interface MessageSecurity {
+  is_signed: boolean;
+  is_encrypted: boolean;
+  scheme: "smime" | "pgp" | null;
+}
+
+export interface DecodedBody {
+  mime_type: "text/html" | "text/plain";
+  content: string;
+}
+
+export interface SingleMessage extends FullMessage {
+  body: DecodedBody | null; // HTML when present, otherwise plain text
+}
+
+// Refetch one message without reloading its thread
+export async function getMessage(accountId: string, messageId: string): Promise<SingleMessage> {
+  return invoke("get_message", { accountId, messageId });
+}
+
+// Address the sender asked a read receipt to go to; Posta never sends one
+export async function getReadReceiptRequest(accountId: string, messageId: string): Promise<string | null> {
+  return invoke("get_read_receipt_request", { accountId, messageId });
+}
+
+export interface ThreadDetailsOptions {
+  // Hide duplicate copies of a message that share a Message-ID
+  dedupeThreadMessages?: boolean;
+  // Also hide copies with identical sender, subject and body
+  dedupeByContent?: boolean;
+  // Mark only the first unread message read, leaving later ones unread
+  markFirstUnreadRead?: boolean;
+  // Return blocks of rapid consecutive messages from one sender
+  groupConsecutive?: boolean;
+  groupWindowMinutes?: number; // default 5
+}
+
+export type QuoteStyle = "top_posted" | "inline" | "unquoted";
+
+export interface ThreadDetails extends FullThread {
+  first_unread_message_id: string | null; // scroll here on open
+  quote_styles: Record<string, QuoteStyle>; // by message id, plain-text bodies only
+  security: Record<string, MessageSecurity>; // by message id, signed or encrypted messages only
+  language: string; // detected conversation language, e.g. "French"
+  grouped_messages: MessageGroup[] | null; // set when groupConsecutive is on
+  attachments: Attachment[]; // metadata only; fetch content with downloadAttachment
+}
+
+export interface MessageGroup {
+  sender: string;
+  message_ids: string[]; // in thread order; act on these individually
+}
+
 export async function getThreadDetails(
+  accountId: string,
+  threadId: string,
+  options: ThreadDetailsOptions = { dedupeThreadMessages: true }
+): Promise<ThreadDetails> {
+  return invoke("get_thread_details", { accountId, threadId, ...options });
+}
+
+export interface OpenedThread extends FullThread {
+  marked_read_message_ids: string[];
+  unread_count: number; // messages still unread after opening
+}
+
+// With markRead, only the messages that were unread are marked read
+export async function openThread(accountId: string, threadId: string, markRead: boolean): Promise<OpenedThread> {
+  return invoke("open_thread", { accountId, threadId, markRead });
+}
+
+// Thread rebuilt from cached bodies for offline reading; null when not cached
+export async function getCachedThreadDetails(accountId: string, threadId: string): Promise<FullThread | null> {
+  return invoke("get_cached_thread_details", { accountId, threadId });
+}
+
+export interface StitchedConversation {
+  thread_ids: string[]; // requested thread first, then stitched siblings
+  messages: FullMessage[]; // oldest first
+  split_boundaries: number[]; // indices where the source thread changes
+}
+
+export async function getFullConversation(
   accountId: string,
   threadId: string
-): Promise<FullThread> {
-  return invoke("get_thread_details", { accountId, threadId });
+): Promise<StitchedConversation> {
+  return invoke("get_full_conversation", { accountId, threadId });
 }
 
 export interface SendAttachment {
   filename: string;
   mime_type: string;
   data: string; // Base64-encoded file data
+  content_id?: string; // inline part referenced as cid: from the HTML body
+}
+
+export interface AttachmentValidation {
+  size: number;
+  declared_mime_type: string;
+  detected_mime_type: string | null;
+  mime_mismatch: boolean;
+}
+
+export async function validateAttachment(
+  attachment: SendAttachment
+): Promise<AttachmentValidation> {
+  return invoke("validate_attachment", { attachment });
 }
 
 export async function sendEmail(
@@ -255,6 +578,19 @@ export async function sendEmail(
   return invoke("send_email", { accountId, to, cc, bcc, subject, body, attachments, isHtml });
 }
 
+export interface SentLogEntry {
+  account_id: string;
+  thread_id: string | null;
+  recipient: string;
+  subject: string;
+  sent_at: number;
+}
+
+// Messages sent from Posta, newest first, from the local log (works offline)
+export async function recentSent(accountId?: string, limit?: number): Promise<SentLogEntry[]> {
+  return invoke("recent_sent", { accountId, limit });
+}
+
 export async function replyToThread(
   accountId: string,
   threadId: string,
@@ -265,9 +601,111 @@ export async function replyToThread(
   body: string,
   messageId?: string,
   attachments: SendAttachment[] = [],
-  isHtml?: boolean
+  isHtml?: boolean,
+  references?: string // parent's References header, if already known
+): Promise<void> {
+  return invoke("reply_to_thread", { accountId, threadId, to, cc, bcc, subject, body, messageId, attachments, isHtml, references });
+}
+
+// Forward a message with its attachments; note is added above the original
+export async function forwardEmail(
+  accountId: string,
+  messageId: string,
+  to: string,
+  cc: string,
+  bcc: string,
+  note?: string
 ): Promise<void> {
-  return invoke("reply_to_thread", { accountId, threadId, to, cc, bcc, subject, body, messageId, attachments, isHtml });
+  return invoke("forward_email", { accountId, messageId, to, cc, bcc, note });
+}
+
+export interface AwaitingReply {
+  thread_id: string;
+  subject: string;
+  to: string; // recipients of the unanswered message
+  sent_at: number; // Unix milliseconds
+}
+
+// Sent threads older than `days` with no reply since the user's last message
+export async function awaitingReply(accountId: string, days: number): Promise<AwaitingReply[]> {
+  return invoke("awaiting_reply", { accountId, days });
+}
+
+export interface SenderAttachment {
+  filename: string;
+  mime: string;
+  size: number;
+  thread_id: string;
+  message_id: string;
+  attachment_id: string;
+  date: number; // Unix milliseconds of the message carrying the file
+}
+
+// Files a contact has sent, newest first
+export async function senderAttachments(
+  accountId: string,
+  email: string,
+  limit?: number
+): Promise<SenderAttachment[]> {
+  return invoke("sender_attachments", { accountId, email, limit });
+}
+
+export interface FollowupReminder {
+  account_id: string;
+  thread_id: string;
+  remind_at: number; // Unix milliseconds
+  created_at: number; // Unix milliseconds
+  recipient: string;
+  subject: string;
+}
+
+// Nudge the user if nobody replies within afterDays; a reply cancels it.
+// Due reminders arrive as "followup-due" events.
+export async function setFollowupReminder(
+  accountId: string,
+  threadId: string,
+  afterDays: number
+): Promise<FollowupReminder> {
+  return invoke("set_followup_reminder", { accountId, threadId, afterDays });
+}
+
+export async function listFollowups(accountId: string): Promise<FollowupReminder[]> {
+  return invoke("list_followups", { accountId });
+}
+
+export async function cancelFollowup(accountId: string, threadId: string): Promise<boolean> {
+  return invoke("cancel_followup", { accountId, threadId });
+}
+
+export interface ReplyTargets {
+  sender: string | null; // Reply-To, falling back to From
+  list: string | null; // mailing list posting address
+  recommended: string | null;
+}
+
+export interface ReplyAllRecipients {
+  to: string | null; // Reply-To, falling back to From
+  cc: string[];
+  recipient_count: number;
+  large_recipient_warning: boolean; // advisory: confirm before sending
+}
+
+export interface ReplyPreflight {
+  latest_message_date: number | null;
+  stale_thread: boolean;
+  reply_targets: ReplyTargets;
+  reply_all: ReplyAllRecipients;
+}
+
+// messageId is the Gmail id of the message being answered (defaults to the latest)
+export async function prepareReply(
+  accountId: string,
+  threadId: string,
+  messageId?: string,
+  staleAfterDays?: number,
+  largeRecipientThreshold?: number
+): Promise<ReplyPreflight> {
+  return invoke("prepare_reply", { accountId, threadId, messageId, staleAfterDays, largeRecipientThreshold });
 }
 
 // Cache operations
@@ -290,6 +728,32 @@ export async function saveCachedCardThreads(
   return invoke("save_cached_card_threads", { cardId, groups, nextPageToken });
 }
 
+export interface ThreadParticipant {
+  email: string;
+  name: string | null; // from contacts
+  photo_url: string | null;
+  monogram: string; // initials for when there's no photo
+}
+
+export interface CardViewThread extends Thread {
+  participant: ThreadParticipant | null;
+}
+
+export interface CardViewGroup {
+  label: string;
+  threads: CardViewThread[];
+}
+
+// A card's threads with participant names and photos resolved from cached contacts
+export async function cardView(accountId: string, cardId: string): Promise<CardViewGroup[]> {
+  return invoke("card_view", { accountId, cardId });
+}
+
+// Unread thread counts keyed by date bucket label ("Today", "This week", ...)
+export async function cardBucketUnread(accountId: string, cardId: string): Promise<Record<string, number>> {
+  return invoke("card_bucket_unread", { accountId, cardId });
+}
+
 export async function clearCardCache(cardId: string): Promise<void> {
   return invoke("clear_card_cache", { cardId });
 }
@@ -341,6 +805,14 @@ export async function saveAttachment(
   return invoke("save_attachment", { accountId, messageId, attachmentId, filename, mimeType, inlineData });
 }
 
+export async function exportThreadPdf(
+  accountId: string,
+  threadId: string,
+  dest: string
+): Promise<string> {
+  return invoke("export_thread_pdf", { accountId, threadId, dest });
+}
+
 // Gmail Labels
 
 export interface GmailLabel {
@@ -355,11 +827,33 @@ export async function listLabels(accountId: string): Promise<GmailLabel[]> {
   return invoke("list_labels", { accountId });
 }
 
+export interface MissingLabel {
+  label: string;
+  suggestion: string | null; // closest existing label name
+}
+
+export interface CardLabelIssue {
+  card_id: string;
+  card_name: string;
+  query: string;
+  missing: MissingLabel[];
+  repaired_query: string | null; // query with all suggestions applied
+}
+
+export async function validateCards(accountId: string): Promise<CardLabelIssue[]> {
+  return invoke("validate_cards", { accountId });
+}
+
+export async function repairCardLabels(accountId: string, cardId: string): Promise<Card> {
+  return invoke("repair_card_labels", { accountId, cardId });
+}
+
 export async function rsvpCalendarEvent(
   accountId: string,
   eventUid: string,
   status: "accepted" | "tentative" | "declined"
-): Promise<void> {
+): Promise<"updated" | "already_set"> {
+  // "already_set" means the response was unchanged and no one was notified
   return invoke("rsvp_calendar_event", { accountId, eventUid, status });
 }
 
@@ -380,6 +874,44 @@ export async function forceICloudSync(): Promise<void> {
   return invoke("force_icloud_sync");
 }
 
+// Battery saver: background polling and reminder checks stop until resumed.
+// Emits "background-paused" with the new state.
+export async function pauseBackground(): Promise<void> {
+  return invoke("pause_background");
+}
+
+export async function resumeBackground(): Promise<void> {
+  return invoke("resume_background");
+}
+
+export async function isBackgroundPaused(): Promise<boolean> {
+  return invoke("is_background_paused");
+}
+
+// Inline images downloaded per card load; the rest load when a thread is opened.
+export async function getInlineFetchBudget(): Promise<number> {
+  return invoke("get_inline_fetch_budget");
+}
+
+export async function setInlineFetchBudget(budget: number): Promise<void> {
+  return invoke("set_inline_fetch_budget", { budget });
+}
+
+// Storage
+
+export interface StorageUsage {
+  messages_total: number;
+  threads_total: number;
+  used_bytes: number | null;
+  total_bytes: number | null;
+  percent_used: number | null;
+  drive_scope_missing: boolean;
+}
+
+export async function storageUsage(accountId: string): Promise<StorageUsage> {
+  return invoke("storage_usage", { accountId });
+}
+
 // People API (Contacts)
 
 export interface Contact {
@@ -424,6 +956,8 @@ export interface GoogleCalendarEvent {
   hangout_link: string | null;
   response_status: string | null; // accepted, declined, tentative, needsAction
   can_edit: boolean; // whether the current user can edit this event
+  color_id: string | null; // Google event palette id ("1".."11"), null for calendar default
+  time_zone: string | null; // event's own IANA zone, set only when it differs from ours
 }
 
 export interface CalendarInfo {
@@ -438,9 +972,74 @@ export async function listCalendars(accountId: string): Promise<CalendarInfo[]>
 
 export async function fetchCalendarEvents(
   accountId: string,
-  query: string
+  query: string,
+  requestId?: string,
+  hideDeclined = true
 ): Promise<GoogleCalendarEvent[]> {
-  return invoke("fetch_calendar_events", { accountId, query });
+  return invoke("fetch_calendar_events", { accountId, query, requestId, hideDeclined });
+}
+
+// Keyword search over upcoming events (default: the next 365 days), sorted by date
+export async function searchAllUpcomingEvents(
+  accountId: string,
+  text: string,
+  horizonDays?: number
+): Promise<GoogleCalendarEvent[]> {
+  return invoke("search_all_upcoming_events", { accountId, text, horizonDays });
+}
+
+export interface AgendaImportResult {
+  created_ids: string[];
+  unparsed_lines: string[]; // lines not matching "HH:MM[-HH:MM] Title"
+  failed_lines: string[]; // titles whose event could not be created
+}
+
+// Create events from a typed day plan; baseDate is YYYY-MM-DD
+export async function importAgenda(
+  accountId: string,
+  calendarId: string,
+  text: string,
+  baseDate: string
+): Promise<AgendaImportResult> {
+  return invoke("import_agenda", { accountId, calendarId, text, baseDate });
+}
+
+// Export events in [timeMin, timeMax] (ms) as ICS. With dest, writes the
+// file and returns its path; otherwise returns the ICS text.
+export async function exportCalendarIcs(
+  accountId: string,
+  calendarId: string,
+  timeMin: number,
+  timeMax: number,
+  dest?: string
+): Promise<string> {
+  return invoke("export_calendar_ics", { accountId, calendarId, timeMin, timeMax, dest });
+}
+
+export interface AttendeeAvailability {
+  email: string;
+  status: "known" | "unknown"; // unknown: calendar not shared with this account
+  busy: [number, number][]; // [start, end] in ms
+}
+
+export async function checkFreeBusy(
+  accountId: string,
+  emails: string[],
+  timeMin: number,
+  timeMax: number
+): Promise<AttendeeAvailability[]> {
+  return invoke("check_freebusy", { accountId, emails, timeMin, timeMax });
+}
+
+// Free time in working hours as paste-ready text, e.g. for replying to a scheduling email
+export async function exportAvailabilityText(
+  accountId: string,
+  start: number,
+  end: number,
+  tz: string,
+  slotMinutes: number
+): Promise<string> {
+  return invoke("export_availability_text", { accountId, start, end, tz, slotMinutes });
 }
 
 export async function createCalendarEvent(
@@ -459,9 +1058,35 @@ export async function createCalendarEvent(
     allDay: event.allDay,
     attendees: event.attendees,
     recurrence: event.recurrence,
+    withMeet: event.withMeet ?? false,
   });
 }
 
+export async function removeCancelledEvent(
+  accountId: string,
+  eventUid: string
+): Promise<boolean> {
+  return invoke("remove_cancelled_event", { accountId, eventUid });
+}
+
+export interface ColorRule {
+  keyword: string;
+  color: string; // palette id ("10"), palette name ("basil") or plain color ("green")
+}
+
+export async function setEventColor(
+  accountId: string,
+  calendarId: string,
+  eventId: string,
+  colorId: string
+): Promise<void> {
+  return invoke("set_event_color", { accountId, calendarId, eventId, colorId });
+}
+
+export async function autoColorEvents(accountId: string, rules: ColorRule[], days?: number): Promise<number> {
+  return invoke("auto_color_events", { accountId, rules, days });
+}
+
 export async function moveCalendarEvent(
   accountId: string,
   sourceCalendarId: string,
@@ -492,29 +1117,98 @@ export async function updateCalendarEvent(
   accountId: string,
   calendarId: string,
   eventId: string,
-  event: EventInput
+  patch: EventPatch
 ): Promise<GoogleCalendarEvent> {
   return invoke("update_calendar_event", {
     accountId,
     calendarId,
     eventId,
-    summary: event.summary,
-    description: event.description,
-    location: event.location,
-    startTime: Math.round(event.startTime),
-    endTime: Math.round(event.endTime),
-    allDay: event.allDay,
-    attendees: event.attendees,
-    recurrence: event.recurrence,
+    summary: patch.summary,
+    description: patch.description,
+    location: patch.location,
+    startTime: patch.startTime === undefined ? undefined : Math.round(patch.startTime),
+    endTime: patch.endTime === undefined ? undefined : Math.round(patch.endTime),
+    allDay: patch.allDay,
+    attendees: patch.attendees,
+    recurrence: patch.recurrence,
   });
 }
 
+// Overrides for a single AI request; unset fields use the provider default
+export interface AiSettings {
+  model?: string;
+}
+
 export async function suggestReplies(
   accountId: string,
   threadId: string,
-  apiKey: string
+  apiKey: string,
+  aiSettings?: AiSettings
 ): Promise<string[]> {
-  return invoke("suggest_replies", { accountId, threadId, apiKey });
+  return invoke("suggest_replies", { accountId, threadId, apiKey, aiSettings });
+}
+
+export type DraftTone = "formal" | "casual" | "friendly";
+
+// Draft a full email body from a short instruction; pass threadId to reply
+export async function composeDraft(
+  accountId: string,
+  instruction: string,
+  apiKey: string,
+  options: { threadId?: string; tone?: DraftTone; aiSettings?: AiSettings } = {}
+): Promise<string> {
+  return invoke("compose_draft", {
+    accountId,
+    instruction,
+    apiKey,
+    threadId: options.threadId,
+    tone: options.tone,
+    aiSettings: options.aiSettings,
+  });
+}
+
+export interface ThreadSummary {
+  summary: string;
+  action_items: string[];
+}
+
+export async function summarizeThread(
+  accountId: string,
+  threadId: string,
+  apiKey: string,
+  aiSettings?: AiSettings
+): Promise<ThreadSummary> {
+  return invoke("summarize_thread", { accountId, threadId, apiKey, aiSettings });
+}
+
+export interface DigestTopic {
+  topic: string;
+  summary: string;
+  thread_ids: string[];
+}
+
+export interface WeeklyDigest {
+  topics: DigestTopic[];
+}
+
+// "Catch up on the week": the past week's unread and important threads grouped by topic
+export async function weeklyDigest(accountId: string, apiKey: string): Promise<WeeklyDigest> {
+  return invoke("weekly_digest", { accountId, apiKey });
+}
+
+// Pick the AI backend. provider is "gemini" or "openai" (any OpenAI-compatible
+// endpoint); "gemini" with an empty key reverts to the settings key.
+export async function setAiProvider(
+  provider: string,
+  apiKey: string,
+  baseUrl?: string,
+  model?: string
+): Promise<void> {
+  return invoke("set_ai_provider", { provider, apiKey, baseUrl, model });
+}
+
+export async function getAiProvider(): Promise<string | null> {
+  return invoke("get_ai_provider");
 }
 
 // Email Reactions
@@ -528,3 +1222,32 @@ export async function sendReaction(
 ): Promise<void> {
   return invoke("send_reaction", { accountId, threadId, messageId, emoji, toEmail });
 }
+
+// Diagnostics
+
+export interface EndpointStats {
+  endpoint: string;
+  count: number;
+  p50_ms: number;
+  p95_ms: number;
+  error_rate: number;
+}
+
+export async function getApiDiagnostics(): Promise<EndpointStats[]> {
+  return invoke("get_api_diagnostics");
+}
+
+export interface QuotaStatus {
+  delay_ms: number; // wait before the next Gmail request
+  backoff_ms: number; // remaining server-requested backoff
+  tokens_available: number;
+  capacity: number;
+  refill_per_sec: number;
+  last_retry_after_secs: number | null;
+  rate_limited_count: number;
+  quota_headers: Record<string, string>;
+}
+
+export async function getQuotaStatus(): Promise<QuotaStatus> {
+  return invoke("get_quota_status");
+}
@@ -13,9 +13,11 @@ export const SmartReplies = (props: SmartRepliesProps) => {
     const [error, setError] = createSignal<string | null>(null);
 
     const apiKey = () => localStorage.getItem("gemini_api_key") || "";
+    // A custom provider keeps its key in the keychain, not localStorage
+    const hasProvider = () => !!apiKey() || localStorage.getItem("ai_provider") === "openai";
 
     const fetchSuggestions = async () => {
-        if (!props.threadId || !props.accountId || !apiKey()) return;
+        if (!props.threadId || !props.accountId || !hasProvider()) return;
 
         setLoading(true);
         setError(null);
@@ -30,13 +32,13 @@ export const SmartReplies = (props: SmartRepliesProps) => {
     };
 
     onMount(() => {
-        if (apiKey()) {
+        if (hasProvider()) {
             fetchSuggestions();
         }
     });
 
-    // Don't render if no API key configured
-    if (!apiKey()) return null;
+    // Don't render if no AI provider configured
+    if (!hasProvider()) return null;
 
     return (
         <div class="smart-replies-container">
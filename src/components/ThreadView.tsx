@@ -318,6 +318,11 @@ export const ThreadView = (props: {
                         const threadAtt = props.threadAttachments?.find(
                           a => a.message_id === msg.id && (a.attachment_id === attachmentId || a.filename === part.filename)
                         );
+                        // Inline images are already rendered in the body
+                        if (threadAtt?.is_inline) {
+                          if (part.parts) findAttachments(part.parts);
+                          return;
+                        }
                         attachments.push({
                           filename: part.filename,
                           mimeType: part.mimeType || 'application/octet-stream',
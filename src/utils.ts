@@ -174,6 +174,14 @@ export function formatSyncTime(timestamp: number | undefined, now?: number): str
   return new Date(timestamp).toLocaleDateString([], { month: "short", day: "numeric" });
 }
 
+/**
+ * Attachments to show as files: inline images (signature logos, embedded
+ * pictures) are only rendered in the message body
+ */
+export function visibleAttachments<T extends { is_inline?: boolean }>(attachments: T[] | undefined): T[] {
+  return (attachments || []).filter(a => !a.is_inline);
+}
+
 /**
  * Truncate filename in the middle, preserving extension
  */